@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use uuid::Uuid;
+
+use crate::daemon_client::DaemonClient;
+use crate::models::{AppState, Daemon};
+use crate::notifications;
+
+/// Name prefix of scheduled backups; retention only ever prunes these, never manual ones.
+pub const SCHEDULED_PREFIX: &str = "scheduled";
+
+pub const BACKUP_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+const BACKUP_CONCURRENCY: usize = 4;
+
+#[derive(Debug, sqlx::FromRow)]
+struct DueBackup {
+    container_id: Uuid,
+    keep_last: i32,
+    name: String,
+    user_id: Uuid,
+    daemon_id: Uuid,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct DaemonBackup {
+    pub name: String,
+}
+
+pub fn spawn(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = run_due_backups(&state).await {
+                tracing::error!("Backup scheduler failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn run_due_backups(state: &AppState) -> anyhow::Result<()> {
+    // Claiming rows in the UPDATE keeps a second API instance from running the same backup.
+    // A schedule is due once today's hh:mm in the container's timezone has passed since it last changed.
+    let due: Vec<DueBackup> = sqlx::query_as(
+        r#"
+        UPDATE backup_schedules s
+        SET last_run_at = NOW(), last_status = 'running', updated_at = NOW()
+        FROM containers c
+        WHERE c.id = s.container_id
+        AND s.enabled
+        AND c.deleted_at IS NULL
+        AND (date_trunc('day', NOW() AT TIME ZONE c.timezone) + make_interval(hours => s.hour, mins => s.minute)) AT TIME ZONE c.timezone <= NOW()
+        AND s.updated_at < (date_trunc('day', NOW() AT TIME ZONE c.timezone) + make_interval(hours => s.hour, mins => s.minute)) AT TIME ZONE c.timezone
+        RETURNING s.container_id, s.keep_last, c.name, c.user_id, c.daemon_id
+        "#
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    futures_util::stream::iter(due)
+        .for_each_concurrent(BACKUP_CONCURRENCY, |backup| async move {
+            let result = run_scheduled_backup(state, &backup).await;
+
+            let (status, error) = match &result {
+                Ok(()) => ("success", None),
+                Err(e) => ("failed", Some(e.to_string())),
+            };
+
+            if let Err(e) = sqlx::query(
+                "UPDATE backup_schedules SET last_status = $1, last_error = $2, updated_at = NOW() WHERE container_id = $3"
+            )
+            .bind(status)
+            .bind(&error)
+            .bind(backup.container_id)
+            .execute(&state.db)
+            .await
+            {
+                tracing::error!("Failed to record backup result for {}: {}", backup.container_id, e);
+            }
+
+            if let Some(error) = error {
+                tracing::warn!("Scheduled backup of {} ({}) failed: {}", backup.name, backup.container_id, error);
+                notify_owner(state, &backup, &error).await;
+            }
+        })
+        .await;
+
+    Ok(())
+}
+
+async fn run_scheduled_backup(state: &AppState, backup: &DueBackup) -> anyhow::Result<()> {
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(backup.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Daemon {} not found", backup.daemon_id))?;
+
+    let created = create_backup(&daemon, backup.container_id, SCHEDULED_PREFIX).await?;
+    tracing::info!("Created scheduled backup {} for {}", created.name, backup.name);
+
+    prune_backups(&daemon, backup.container_id, backup.keep_last.max(1) as usize).await
+}
+
+pub async fn create_backup(daemon: &Daemon, container_id: Uuid, prefix: &str) -> anyhow::Result<DaemonBackup> {
+    let url = format!("{}/containers/{}/backups", daemon.base_url(), container_id);
+
+    let res = DaemonClient::with_timeout(BACKUP_TIMEOUT)
+        .post(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .json(&serde_json::json!({ "prefix": prefix }))
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        anyhow::bail!("Daemon failed to create backup: {}", error_text);
+    }
+
+    Ok(res.json().await?)
+}
+
+/// Deletes scheduled backups beyond the newest `keep_last`.
+async fn prune_backups(daemon: &Daemon, container_id: Uuid, keep_last: usize) -> anyhow::Result<()> {
+    let client = crate::handlers::containers::daemon_client();
+    let url = format!("{}/containers/{}/backups", daemon.base_url(), container_id);
+
+    let res = client
+        .get(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        anyhow::bail!("Daemon failed to list backups for pruning: {}", res.status());
+    }
+
+    // The daemon lists newest first
+    let backups: Vec<DaemonBackup> = res.json().await?;
+    let prefix = format!("{}-", SCHEDULED_PREFIX);
+
+    for old in backups.iter().filter(|b| b.name.starts_with(&prefix)).skip(keep_last) {
+        let delete_url = format!("{}/{}", url, old.name);
+        let res = client
+            .delete(&delete_url)
+            .header("X-API-Key", &daemon.api_key)
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            anyhow::bail!("Daemon failed to delete old backup {}: {}", old.name, res.status());
+        }
+
+        tracing::info!("Pruned backup {} of container {}", old.name, container_id);
+    }
+
+    Ok(())
+}
+
+async fn notify_owner(state: &AppState, backup: &DueBackup, error: &str) {
+    let message = format!(
+        "The scheduled backup of your server \"{}\" failed: {}. It will be retried at the next scheduled time.",
+        backup.name, error
+    );
+
+    notifications::send_to_user(state, backup.user_id, "Scheduled backup failed", &message).await;
+}
@@ -2,15 +2,47 @@
 #[derive(Debug, Clone)]
 pub struct Config {
     pub database_url: String,
+    pub db_pool: DbPoolConfig,
     pub jwt_secret: String,
+    /// Encrypts secret variable values at rest; derived from APP_KEY.
+    pub secret_key: [u8; 32],
     pub jwt_expiry_days: i64,
     pub api_addr: String,
     pub app_url: String,
+    /// Origins allowed to call the API with credentials; defaults to `app_url`.
+    pub cors_allowed_origins: Vec<String>,
+    /// Any origin, without credentials. Only for development.
+    pub cors_allow_all: bool,
     pub bcrypt_cost: u32,
+    pub reconcile_interval_secs: u64,
+    pub status_stuck_threshold_secs: i64,
+    pub reconcile_daemon_concurrency: usize,
+    pub reconcile_batch_size: i64,
+    pub billing_webhook_url: Option<String>,
+    pub outbox_poll_interval_secs: u64,
+    /// Bearer token for `/metrics`; the endpoint is off without one.
+    pub metrics_token: Option<String>,
+    /// How long deleted containers stay restorable; 0 deletes immediately.
+    pub container_trash_retention_hours: i64,
+    /// Browser websockets without input for this long are closed; 0 keeps them open.
+    pub ws_idle_timeout_secs: u64,
+    /// Largest accepted upload chunk in bytes (UPLOAD_CHUNK_SIZE_MB), shared with the daemons.
+    pub upload_chunk_size: usize,
     pub smtp: Option<SmtpConfig>,
     pub admin: AdminConfig,
 }
 
+/// Connections are per API instance: all instances together, plus anything else using the
+/// database, must stay below Postgres' own `max_connections` (100 by default).
+#[derive(Debug, Clone)]
+pub struct DbPoolConfig {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    /// Idle connections above the minimum are closed after this long; 0 keeps them.
+    pub idle_timeout_secs: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct SmtpConfig {
     pub host: String,
@@ -34,21 +66,60 @@ impl Config {
             .expect("APP_KEY must be set - generate with: openssl rand -base64 32");
 
         let smtp = Self::load_smtp_config();
+        let app_url = std::env::var("APP_URL").unwrap_or_else(|_| "http://localhost:5173".into());
 
         Self {
             database_url: std::env::var("DATABASE_URL")
                 .expect("DATABASE_URL must be set"),
+            db_pool: Self::load_db_pool_config(),
+            secret_key: crate::secrets::derive_key(&app_key),
             jwt_secret: std::env::var("JWT_SECRET").unwrap_or(app_key),
             jwt_expiry_days: std::env::var("JWT_EXPIRY_DAYS")
                 .unwrap_or_else(|_| "7".into())
                 .parse()
                 .unwrap_or(7),
             api_addr: std::env::var("API_ADDR").unwrap_or_else(|_| "0.0.0.0:3000".into()),
-            app_url: std::env::var("APP_URL").unwrap_or_else(|_| "http://localhost:5173".into()),
+            cors_allowed_origins: parse_origins(std::env::var("CORS_ALLOWED_ORIGINS").ok().as_deref())
+                .unwrap_or_else(|| vec![app_url.trim_end_matches('/').to_string()]),
+            cors_allow_all: std::env::var("CORS_ALLOW_ALL")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            app_url,
             bcrypt_cost: std::env::var("BCRYPT_COST")
                 .unwrap_or_else(|_| "12".into())
                 .parse()
                 .unwrap_or(12),
+            reconcile_interval_secs: std::env::var("RECONCILE_INTERVAL_SECS")
+                .unwrap_or_else(|_| "60".into())
+                .parse()
+                .unwrap_or(60),
+            status_stuck_threshold_secs: std::env::var("STATUS_STUCK_THRESHOLD_SECS")
+                .unwrap_or_else(|_| "300".into())
+                .parse()
+                .unwrap_or(300),
+            reconcile_daemon_concurrency: std::env::var("RECONCILE_DAEMON_CONCURRENCY")
+                .unwrap_or_else(|_| "4".into())
+                .parse()
+                .unwrap_or(4),
+            reconcile_batch_size: std::env::var("RECONCILE_BATCH_SIZE")
+                .unwrap_or_else(|_| "50".into())
+                .parse()
+                .unwrap_or(50),
+            billing_webhook_url: std::env::var("BILLING_WEBHOOK_URL").ok().filter(|u| !u.is_empty()),
+            outbox_poll_interval_secs: std::env::var("OUTBOX_POLL_INTERVAL_SECS")
+                .unwrap_or_else(|_| "10".into())
+                .parse()
+                .unwrap_or(10),
+            metrics_token: std::env::var("METRICS_TOKEN").ok().filter(|t| !t.is_empty()),
+            container_trash_retention_hours: std::env::var("CONTAINER_TRASH_RETENTION_HOURS")
+                .unwrap_or_else(|_| "72".into())
+                .parse()
+                .unwrap_or(72),
+            ws_idle_timeout_secs: std::env::var("WS_IDLE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "3600".into())
+                .parse()
+                .unwrap_or(3600),
+            upload_chunk_size: raptor_common::uploads::chunk_size_from_env(),
             smtp,
             admin: AdminConfig {
                 username: std::env::var("ADMIN_USERNAME").unwrap_or_else(|_| "admin".into()),
@@ -58,6 +129,31 @@ impl Config {
         }
     }
 
+    fn load_db_pool_config() -> DbPoolConfig {
+        let max_connections = std::env::var("DB_MAX_CONNECTIONS")
+            .unwrap_or_else(|_| "20".into())
+            .parse()
+            .unwrap_or(20)
+            .max(1);
+
+        DbPoolConfig {
+            max_connections,
+            min_connections: std::env::var("DB_MIN_CONNECTIONS")
+                .unwrap_or_else(|_| "2".into())
+                .parse()
+                .unwrap_or(2)
+                .min(max_connections),
+            acquire_timeout_secs: std::env::var("DB_ACQUIRE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "10".into())
+                .parse()
+                .unwrap_or(10),
+            idle_timeout_secs: std::env::var("DB_IDLE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "600".into())
+                .parse()
+                .unwrap_or(600),
+        }
+    }
+
     fn load_smtp_config() -> Option<SmtpConfig> {
         let host = std::env::var("SMTP_HOST").ok()?;
         let username = std::env::var("SMTP_USERNAME").ok()?;
@@ -76,3 +172,14 @@ impl Config {
         })
     }
 }
+
+/// Comma separated origins, normalized without a trailing slash; `None` when unset or empty.
+fn parse_origins(value: Option<&str>) -> Option<Vec<String>> {
+    let origins: Vec<String> = value?
+        .split(',')
+        .map(|o| o.trim().trim_end_matches('/').to_string())
+        .filter(|o| !o.is_empty())
+        .collect();
+
+    if origins.is_empty() { None } else { Some(origins) }
+}
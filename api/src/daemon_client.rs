@@ -0,0 +1,156 @@
+use std::time::{Duration, Instant};
+
+use reqwest::Method;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::middleware::REQUEST_ID;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// HTTP client for daemon calls that logs the outcome of every request.
+#[derive(Clone)]
+pub struct DaemonClient {
+    inner: reqwest::Client,
+}
+
+impl DaemonClient {
+    pub fn new() -> Self {
+        Self::with_timeout(DEFAULT_TIMEOUT)
+    }
+
+    pub fn with_timeout(timeout: Duration) -> Self {
+        let inner = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .timeout(timeout)
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+
+        Self { inner }
+    }
+
+    pub fn get(&self, url: impl AsRef<str>) -> DaemonRequest {
+        self.request(Method::GET, url.as_ref())
+    }
+
+    pub fn post(&self, url: impl AsRef<str>) -> DaemonRequest {
+        self.request(Method::POST, url.as_ref())
+    }
+
+    pub fn put(&self, url: impl AsRef<str>) -> DaemonRequest {
+        self.request(Method::PUT, url.as_ref())
+    }
+
+    pub fn patch(&self, url: impl AsRef<str>) -> DaemonRequest {
+        self.request(Method::PATCH, url.as_ref())
+    }
+
+    pub fn delete(&self, url: impl AsRef<str>) -> DaemonRequest {
+        self.request(Method::DELETE, url.as_ref())
+    }
+
+    fn request(&self, method: Method, url: &str) -> DaemonRequest {
+        DaemonRequest {
+            builder: self.inner.request(method.clone(), url),
+            method,
+            url: redact_url(url),
+        }
+    }
+}
+
+impl Default for DaemonClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct DaemonRequest {
+    builder: reqwest::RequestBuilder,
+    method: Method,
+    url: String,
+}
+
+impl DaemonRequest {
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.builder = self.builder.header(key, value);
+        self
+    }
+
+    pub fn json<T: Serialize + ?Sized>(mut self, json: &T) -> Self {
+        self.builder = self.builder.json(json);
+        self
+    }
+
+    pub fn query<T: Serialize + ?Sized>(mut self, query: &T) -> Self {
+        self.builder = self.builder.query(query);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.builder = self.builder.timeout(timeout);
+        self
+    }
+
+    pub async fn send(self) -> reqwest::Result<reqwest::Response> {
+        let request_id = REQUEST_ID.try_with(|id| id.clone()).unwrap_or_else(|_| "-".to_string());
+
+        let builder = if request_id != "-" {
+            self.builder.header("X-Request-Id", &request_id)
+        } else {
+            self.builder
+        };
+
+        let started = Instant::now();
+        let result = builder.send().await;
+        let duration_ms = started.elapsed().as_millis() as u64;
+
+        match &result {
+            Ok(res) if res.status().is_success() => tracing::debug!(
+                request_id = %request_id,
+                method = %self.method,
+                url = %self.url,
+                status = res.status().as_u16(),
+                duration_ms,
+                "daemon call succeeded"
+            ),
+            Ok(res) => tracing::warn!(
+                request_id = %request_id,
+                method = %self.method,
+                url = %self.url,
+                status = res.status().as_u16(),
+                duration_ms,
+                "daemon call returned an error status"
+            ),
+            Err(e) => tracing::warn!(
+                request_id = %request_id,
+                method = %self.method,
+                url = %self.url,
+                duration_ms,
+                error = %e,
+                "daemon call failed"
+            ),
+        }
+
+        result
+    }
+}
+
+/// Drops the query string and replaces UUID path segments (container ids) with `:id`.
+fn redact_url(url: &str) -> String {
+    let without_query = url.split(['?', '#']).next().unwrap_or(url);
+
+    let (origin, path) = match without_query.find("://") {
+        Some(scheme_end) => match without_query[scheme_end + 3..].find('/') {
+            Some(path_start) => without_query.split_at(scheme_end + 3 + path_start),
+            None => (without_query, ""),
+        },
+        None => ("", without_query),
+    };
+
+    let path: Vec<&str> = path
+        .split('/')
+        .map(|segment| if Uuid::parse_str(segment).is_ok() { ":id" } else { segment })
+        .collect();
+
+    format!("{}{}", origin, path.join("/"))
+}
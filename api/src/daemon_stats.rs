@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::models::{AppState, Daemon};
+
+/// How often the daemon list is re-read, picking up new daemons and reconnecting dropped ones.
+const DAEMON_REFRESH_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Host stats from every daemon, fanned out to all `/ws/daemons/stats` sockets over one
+/// connection per daemon. Connections are held only while someone is subscribed.
+pub struct DaemonStatsHub {
+    tx: broadcast::Sender<String>,
+    running: AtomicBool,
+    /// Last message per daemon, sent to new subscribers right away.
+    latest: Mutex<HashMap<Uuid, String>>,
+}
+
+impl Default for DaemonStatsHub {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(64);
+        Self { tx, running: AtomicBool::new(false), latest: Mutex::new(HashMap::new()) }
+    }
+}
+
+impl DaemonStatsHub {
+    /// Subscribes to the stats of all daemons, connecting to them if nobody else is watching.
+    pub fn subscribe(state: &AppState) -> (Vec<String>, broadcast::Receiver<String>) {
+        let hub = &state.daemon_stats;
+        let rx = hub.tx.subscribe();
+
+        if hub.running.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+            tokio::spawn(run(state.clone()));
+        }
+
+        let latest = hub.latest.lock().unwrap_or_else(|e| e.into_inner()).values().cloned().collect();
+        (latest, rx)
+    }
+
+    /// False if a subscriber arrived while stopping and the connections should stay up.
+    fn try_stop(&self) -> bool {
+        self.running.store(false, Ordering::Release);
+        if self.tx.receiver_count() > 0 && self.running.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+            return false;
+        }
+        self.latest.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        true
+    }
+
+    fn publish(&self, daemon_id: Uuid, message: String) {
+        self.latest.lock().unwrap_or_else(|e| e.into_inner()).insert(daemon_id, message.clone());
+        let _ = self.tx.send(message);
+    }
+
+    fn forget(&self, daemon_id: &Uuid) {
+        self.latest.lock().unwrap_or_else(|e| e.into_inner()).remove(daemon_id);
+    }
+}
+
+async fn run(state: AppState) {
+    let hub = &state.daemon_stats;
+    let mut connections: HashMap<Uuid, JoinHandle<()>> = HashMap::new();
+    let mut refresh = tokio::time::interval(DAEMON_REFRESH_INTERVAL);
+
+    tracing::debug!("Connecting to daemon stats sockets");
+
+    loop {
+        refresh.tick().await;
+
+        if hub.tx.receiver_count() == 0 && hub.try_stop() {
+            break;
+        }
+
+        let daemons: Vec<Daemon> = match sqlx::query_as("SELECT * FROM daemons").fetch_all(&state.db).await {
+            Ok(daemons) => daemons,
+            Err(e) => {
+                tracing::warn!("Failed to load daemons for stats: {}", e);
+                continue;
+            }
+        };
+
+        connections.retain(|id, task| {
+            let keep = !task.is_finished() && daemons.iter().any(|d| d.id == *id);
+            if !keep {
+                task.abort();
+                hub.forget(id);
+            }
+            keep
+        });
+
+        for daemon in daemons {
+            if !connections.contains_key(&daemon.id) {
+                connections.insert(daemon.id, tokio::spawn(forward(state.clone(), daemon)));
+            }
+        }
+    }
+
+    for task in connections.values() {
+        task.abort();
+    }
+
+    tracing::debug!("Closed daemon stats sockets, no subscribers left");
+}
+
+/// Relays one daemon's `/ws/system` stream until it closes; the next refresh reconnects.
+async fn forward(state: AppState, daemon: Daemon) {
+    use tokio_tungstenite::tungstenite::Message;
+
+    let ws_protocol = if daemon.secure { "wss" } else { "ws" };
+    let ws_url = format!(
+        "{}://{}:{}/ws/system?api_key={}",
+        ws_protocol, daemon.host, daemon.port, daemon.api_key
+    );
+
+    let (mut stream, _) = match tokio_tungstenite::connect_async(&ws_url).await {
+        Ok(connection) => connection,
+        Err(e) => {
+            tracing::warn!("Failed to connect to daemon {} WebSocket: {}", daemon.id, e);
+            return;
+        }
+    };
+
+    while let Some(msg) = stream.next().await {
+        match msg {
+            Ok(Message::Text(text)) => {
+                let message = serde_json::json!({
+                    "daemonId": daemon.id.to_string(),
+                    "system": serde_json::from_str::<serde_json::Value>(&text).unwrap_or_default()
+                });
+                state.daemon_stats.publish(daemon.id, message.to_string());
+            }
+            Ok(Message::Close(_)) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    state.daemon_stats.forget(&daemon.id);
+}
@@ -118,6 +118,29 @@ impl EmailService {
         <div class="footer">Raptor - Container Management Panel</div>
     </div>
 </body>
+</html>"#.to_string(),
+            "notification" => r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <style>
+        body { font-family: -apple-system, BlinkMacSystemFont, sans-serif; background: #0f172a; color: #e2e8f0; padding: 20px; margin: 0; }
+        .container { max-width: 600px; margin: 0 auto; background: #1e293b; padding: 40px; border-radius: 12px; }
+        h2 { color: #0ea5e9; margin-top: 0; }
+        p { line-height: 1.6; color: #cbd5e1; }
+        .btn { display: inline-block; background: #0ea5e9; color: white !important; padding: 14px 28px; text-decoration: none; border-radius: 8px; font-weight: 600; margin: 20px 0; }
+        .footer { margin-top: 30px; font-size: 12px; color: #64748b; border-top: 1px solid #334155; padding-top: 20px; }
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h2>{{TITLE}}</h2>
+        <p>Hello {{NAME}},</p>
+        <p>{{MESSAGE}}</p>
+        <p><a href="{{APP_URL}}" class="btn">Open Panel</a></p>
+        <div class="footer">Raptor - Container Management Panel</div>
+    </div>
+</body>
 </html>"#.to_string(),
             _ => r#"<!DOCTYPE html>
 <html>
@@ -195,6 +218,39 @@ impl EmailService {
         self.mailer.send(email).await?;
         Ok(())
     }
+
+    /// Sends a plain notification (title + message) about something that happened to the user's resources.
+    pub async fn send_notification_email(
+        &self,
+        to_email: &str,
+        to_name: &str,
+        title: &str,
+        message: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let html_body = Self::load_template("notification")
+            .replace("{{APP_URL}}", &self.app_url)
+            .replace("{{NAME}}", &escape_html(to_name))
+            .replace("{{TITLE}}", &escape_html(title))
+            .replace("{{MESSAGE}}", &escape_html(message));
+
+        let email = Message::builder()
+            .from(format!("{} <{}>", self.from_name, self.from_email).parse()?)
+            .to(format!("{} <{}>", to_name, to_email).parse()?)
+            .subject(format!("{} - Raptor", title))
+            .header(ContentType::TEXT_HTML)
+            .body(html_body)?;
+
+        self.mailer.send(email).await?;
+        Ok(())
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
 pub fn generate_reset_token() -> String {
@@ -41,3 +41,81 @@ impl IntoResponse for AppError {
 }
 
 pub type AppResult<T> = Result<T, AppError>;
+
+/// Unique indexes that keep an allocation on at most one container. The older endpoint index
+/// covers the same rows, and whichever Postgres checks first reports the conflict.
+const ALLOCATION_IN_USE_INDEXES: [&str; 2] = [
+    "idx_container_allocations_unique_allocation",
+    "idx_container_allocations_unique_endpoint",
+];
+
+/// For `container_allocations` inserts: an allocation taken by a concurrent request surfaces as
+/// a bad request instead of a database error.
+pub fn allocation_conflict(e: sqlx::Error) -> AppError {
+    match &e {
+        sqlx::Error::Database(db) if db.constraint().is_some_and(|c| ALLOCATION_IN_USE_INDEXES.contains(&c)) => {
+            AppError::BadRequest("allocation already in use".into())
+        }
+        _ => AppError::Database(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db::test_db;
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn concurrent_assignments_of_one_allocation_leave_one_owner() {
+        let db = test_db().await;
+        let user = db.user().await;
+        let daemon = db.daemon().await;
+        let allocation = db.allocation(daemon, "10.0.0.1", 25565).await;
+        let containers = [db.container(user, daemon).await, db.container(user, daemon).await];
+
+        // Both transactions pass the handlers' "is it free?" check before either inserts
+        let mut txs = Vec::new();
+        for _ in containers {
+            let mut tx = db.pool.begin().await.unwrap();
+            let (taken,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM container_allocations WHERE allocation_id = $1)")
+                .bind(allocation)
+                .fetch_one(&mut *tx)
+                .await
+                .unwrap();
+            assert!(!taken);
+            txs.push(tx);
+        }
+
+        let assign = |mut tx: sqlx::Transaction<'static, sqlx::Postgres>, container: uuid::Uuid| async move {
+            sqlx::query(
+                "INSERT INTO container_allocations (container_id, allocation_id, ip, port, internal_port, protocol, is_primary)
+                 VALUES ($1, $2, '10.0.0.1', 25565, 25565, 'tcp', TRUE)"
+            )
+                .bind(container)
+                .bind(allocation)
+                .execute(&mut *tx)
+                .await
+                .map_err(allocation_conflict)?;
+            tx.commit().await?;
+            Ok::<_, AppError>(())
+        };
+
+        let mut txs = txs.into_iter();
+        let (first, second) = tokio::join!(
+            assign(txs.next().unwrap(), containers[0]),
+            assign(txs.next().unwrap(), containers[1]),
+        );
+
+        let results = [first, second];
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1, "{:?}", results);
+        assert!(results.iter().any(|r| matches!(r, Err(AppError::BadRequest(msg)) if msg == "allocation already in use")), "{:?}", results);
+
+        let (owners,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM container_allocations WHERE allocation_id = $1")
+            .bind(allocation)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(owners, 1);
+    }
+}
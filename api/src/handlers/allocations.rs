@@ -1,11 +1,12 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     Json,
 };
 use chrono::Utc;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::error::{AppError, AppResult};
+use crate::error::{allocation_conflict, AppError, AppResult};
 use crate::models::{Allocation, AppState, CreateAllocationRequest, CreateIpPoolRequest, IpPool};
 
 pub async fn list_all_allocations(State(state): State<AppState>) -> AppResult<Json<Vec<Allocation>>> {
@@ -32,25 +33,82 @@ pub async fn list_allocations(State(state): State<AppState>) -> AppResult<Json<V
     Ok(Json(allocations))
 }
 
+const MAX_LABEL_LENGTH: usize = 100;
+const MAX_NOTES_LENGTH: usize = 2000;
+
+/// Trims an allocation label/notes value; blank values are stored as NULL.
+fn normalize_annotation(value: Option<String>, max_len: usize, field: &str) -> AppResult<Option<String>> {
+    let value = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+
+    if let Some(ref v) = value {
+        if v.chars().count() > max_len {
+            return Err(AppError::BadRequest(format!("{} must be at most {} characters", field, max_len)));
+        }
+    }
+
+    Ok(value)
+}
+
+/// Rejects an endpoint another allocation on the daemon already binds. `0.0.0.0` and `::`
+/// only clash with addresses of their own family.
+async fn check_endpoint_free(
+    state: &AppState,
+    daemon_id: Uuid,
+    ip: &str,
+    port: i32,
+    protocol: &str,
+    exclude: Option<Uuid>,
+) -> AppResult<()> {
+    let candidates: Vec<(String,)> = sqlx::query_as(
+        r#"SELECT ip FROM allocations
+           WHERE daemon_id = $1 AND port = $2
+           AND (LOWER(protocol) = LOWER($3) OR LOWER(protocol) = 'both' OR LOWER($3) = 'both')
+           AND ($4::uuid IS NULL OR id <> $4)"#
+    )
+        .bind(daemon_id)
+        .bind(port)
+        .bind(protocol)
+        .bind(exclude)
+        .fetch_all(&state.db)
+        .await?;
+
+    if let Some((taken,)) = candidates.into_iter().find(|(other,)| raptor_common::net::ips_overlap(ip, other)) {
+        return Err(AppError::BadRequest(format!(
+            "{} overlaps the existing allocation {}",
+            raptor_common::net::endpoint(ip, port),
+            raptor_common::net::endpoint(&taken, port)
+        )));
+    }
+
+    Ok(())
+}
+
 pub async fn create_allocation(
     State(state): State<AppState>,
     Json(req): Json<CreateAllocationRequest>,
 ) -> AppResult<Json<Allocation>> {
     let now = Utc::now();
     let protocol = req.protocol.as_deref().unwrap_or("tcp");
+    let label = normalize_annotation(req.label, MAX_LABEL_LENGTH, "Label")?;
+    let notes = normalize_annotation(req.notes, MAX_NOTES_LENGTH, "Notes")?;
+    let ip = raptor_common::net::normalize_ip(&req.ip).map_err(AppError::BadRequest)?;
+
+    check_endpoint_free(&state, req.daemon_id, &ip, req.port, protocol, None).await?;
 
     let allocation: Allocation = sqlx::query_as(
         r#"
-        INSERT INTO allocations (id, daemon_id, ip, port, protocol, created_at, updated_at)
-        VALUES ($1, $2, $3, $4, $5, $6, $6)
+        INSERT INTO allocations (id, daemon_id, ip, port, protocol, label, notes, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8)
         RETURNING *
         "#,
     )
     .bind(Uuid::new_v4())
     .bind(req.daemon_id)
-    .bind(&req.ip)
+    .bind(&ip)
     .bind(req.port)
     .bind(protocol)
+    .bind(&label)
+    .bind(&notes)
     .bind(now)
     .fetch_one(&state.db)
     .await?;
@@ -86,6 +144,85 @@ pub async fn delete_allocation(
     Ok(Json(serde_json::json!({"message": "Allocation deleted successfully"})))
 }
 
+#[derive(Debug, sqlx::FromRow)]
+struct AllocationUsageRow {
+    #[sqlx(flatten)]
+    allocation: Allocation,
+    container_id: Option<Uuid>,
+    container_name: Option<String>,
+    owner_id: Option<Uuid>,
+    owner_username: Option<String>,
+    is_primary: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllocationAssignment {
+    pub container_id: Uuid,
+    pub container_name: String,
+    pub owner_id: Uuid,
+    pub owner_username: String,
+    pub is_primary: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllocationUsage {
+    #[serde(flatten)]
+    pub allocation: Allocation,
+    /// `None` when no container uses the allocation.
+    pub assignment: Option<AllocationAssignment>,
+}
+
+/// GET /daemons/:id/allocations - every allocation of a daemon with the container using it, if any
+pub async fn list_daemon_allocations(
+    State(state): State<AppState>,
+    Path(daemon_id): Path<Uuid>,
+) -> AppResult<Json<Vec<AllocationUsage>>> {
+    let exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM daemons WHERE id = $1")
+        .bind(daemon_id)
+        .fetch_optional(&state.db)
+        .await?;
+
+    if exists.is_none() {
+        return Err(AppError::NotFound);
+    }
+
+    let rows: Vec<AllocationUsageRow> = sqlx::query_as(
+        r#"SELECT a.*, ca.container_id, c.name AS container_name, c.user_id AS owner_id,
+                  u.username AS owner_username, ca.is_primary
+           FROM allocations a
+           LEFT JOIN container_allocations ca ON ca.allocation_id = a.id
+           LEFT JOIN containers c ON c.id = ca.container_id
+           LEFT JOIN users u ON u.id = c.user_id
+           WHERE a.daemon_id = $1
+           ORDER BY a.ip, a.port"#
+    )
+        .bind(daemon_id)
+        .fetch_all(&state.db)
+        .await?;
+
+    let allocations = rows
+        .into_iter()
+        .map(|row| {
+            let assignment = match (row.container_id, row.container_name, row.owner_id) {
+                (Some(container_id), Some(container_name), Some(owner_id)) => Some(AllocationAssignment {
+                    container_id,
+                    container_name,
+                    owner_id,
+                    owner_username: row.owner_username.unwrap_or_default(),
+                    is_primary: row.is_primary.unwrap_or(false),
+                }),
+                _ => None,
+            };
+
+            AllocationUsage { allocation: row.allocation, assignment }
+        })
+        .collect();
+
+    Ok(Json(allocations))
+}
+
 pub async fn list_ip_pools(State(state): State<AppState>) -> AppResult<Json<Vec<IpPool>>> {
     let pools: Vec<IpPool> = sqlx::query_as("SELECT * FROM ip_pools ORDER BY created_at DESC")
         .fetch_all(&state.db)
@@ -108,6 +245,14 @@ pub async fn create_ip_pool(
     State(state): State<AppState>,
     Json(req): Json<CreateIpPoolRequest>,
 ) -> AppResult<Json<IpPool>> {
+    let ip_address = raptor_common::net::normalize_ip(&req.ip_address).map_err(AppError::BadRequest)?;
+    let max_cidr = raptor_common::net::max_prefix_len(&ip_address).unwrap_or(32);
+    let cidr = req.cidr.unwrap_or(max_cidr);
+
+    if !(0..=max_cidr).contains(&cidr) {
+        return Err(AppError::BadRequest(format!("CIDR must be between 0 and {}", max_cidr)));
+    }
+
     let pool: IpPool = sqlx::query_as(
         r#"
         INSERT INTO ip_pools (id, daemon_id, ip_address, cidr, description, is_primary)
@@ -117,8 +262,8 @@ pub async fn create_ip_pool(
     )
     .bind(Uuid::new_v4())
     .bind(req.daemon_id)
-    .bind(&req.ip_address)
-    .bind(req.cidr.unwrap_or(32))
+    .bind(&ip_address)
+    .bind(cidr)
     .bind(&req.description)
     .bind(req.is_primary.unwrap_or(false))
     .fetch_one(&state.db)
@@ -127,20 +272,155 @@ pub async fn create_ip_pool(
     Ok(Json(pool))
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteIpPoolQuery {
+    /// Also unassign allocations containers still hold; they lose the ports on their next restart.
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct PoolAllocationUser {
+    container_id: Uuid,
+    container_name: String,
+    daemon_id: Uuid,
+}
+
+/// Whether `ip` is one of the addresses of `pool`.
+fn pool_contains(pool: &IpPool, ip: &str) -> bool {
+    let prefix_len = pool
+        .cidr
+        .or_else(|| raptor_common::net::max_prefix_len(&pool.ip_address))
+        .unwrap_or(32);
+
+    raptor_common::net::in_network(ip, &pool.ip_address, prefix_len)
+}
+
+/// Allocations that go with `pool`: on its addresses and not also covered by another pool of
+/// the daemon, which keeps them. Allocations only record an IP, so overlap is all there is.
+fn pool_allocations(pool: &IpPool, other_pools: &[IpPool], allocations: &[(Uuid, String)]) -> Vec<Uuid> {
+    allocations
+        .iter()
+        .filter(|(_, ip)| pool_contains(pool, ip) && !other_pools.iter().any(|other| pool_contains(other, ip)))
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+/// Deletes `pool` and the allocations that go with it in one transaction, refusing while
+/// containers hold any of them unless `force`. Returns the containers that lost allocations
+/// and how many allocations were removed.
+async fn remove_ip_pool(db: &sqlx::PgPool, pool: &IpPool, force: bool) -> AppResult<(Vec<PoolAllocationUser>, u64)> {
+    let mut tx = db.begin().await?;
+
+    let other_pools: Vec<IpPool> = sqlx::query_as("SELECT * FROM ip_pools WHERE daemon_id = $1 AND id <> $2 FOR UPDATE")
+        .bind(pool.daemon_id)
+        .bind(pool.id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    // Locked until the delete commits; assigning one of them has to wait, since the foreign key
+    // check of a new container_allocations row needs a share lock on it
+    let daemon_allocations: Vec<(Uuid, String)> = sqlx::query_as("SELECT id, ip FROM allocations WHERE daemon_id = $1 FOR UPDATE")
+        .bind(pool.daemon_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let allocation_ids = pool_allocations(pool, &other_pools, &daemon_allocations);
+
+    sqlx::query("SELECT id FROM container_allocations WHERE allocation_id = ANY($1) FOR UPDATE")
+        .bind(&allocation_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    let users: Vec<PoolAllocationUser> = sqlx::query_as(
+        r#"SELECT DISTINCT ca.container_id, c.name AS container_name, c.daemon_id
+           FROM container_allocations ca
+           JOIN containers c ON c.id = ca.container_id
+           WHERE ca.allocation_id = ANY($1)
+           ORDER BY c.name"#
+    )
+        .bind(&allocation_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    if !users.is_empty() && !force {
+        let names: Vec<&str> = users.iter().map(|u| u.container_name.as_str()).collect();
+        return Err(AppError::BadRequest(format!(
+            "IP pool is in use by {} container(s): {}. Reassign their allocations or delete with force=true",
+            names.len(),
+            names.join(", ")
+        )));
+    }
+
+    let container_ids: Vec<Uuid> = users.iter().map(|u| u.container_id).collect();
+
+    sqlx::query("DELETE FROM container_allocations WHERE allocation_id = ANY($1)")
+        .bind(&allocation_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    // Containers that lost their primary allocation fall back to their oldest remaining one
+    sqlx::query(
+        r#"UPDATE container_allocations
+           SET is_primary = TRUE
+           WHERE id IN (
+               SELECT DISTINCT ON (container_id) id FROM container_allocations
+               WHERE container_id = ANY($1)
+               ORDER BY container_id, created_at ASC
+           )
+           AND NOT EXISTS (
+               SELECT 1 FROM container_allocations p
+               WHERE p.container_id = container_allocations.container_id AND p.is_primary
+           )"#
+    )
+        .bind(&container_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    let removed = sqlx::query("DELETE FROM allocations WHERE id = ANY($1)")
+        .bind(&allocation_ids)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    sqlx::query("DELETE FROM ip_pools WHERE id = $1")
+        .bind(pool.id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok((users, removed))
+}
+
+/// DELETE /ip-pools/:id - remove a pool and the allocations on its addresses that no other
+/// pool of the daemon covers. Refused while containers hold any of them, unless `?force=true`.
 pub async fn delete_ip_pool(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
+    Query(query): Query<DeleteIpPoolQuery>,
 ) -> AppResult<Json<serde_json::Value>> {
-    let result = sqlx::query("DELETE FROM ip_pools WHERE id = $1")
+    let pool: IpPool = sqlx::query_as("SELECT * FROM ip_pools WHERE id = $1")
         .bind(id)
-        .execute(&state.db)
-        .await?;
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
 
-    if result.rows_affected() == 0 {
-        return Err(AppError::NotFound);
+    let (users, removed) = remove_ip_pool(&state.db, &pool, query.force).await?;
+    let container_ids: Vec<Uuid> = users.iter().map(|u| u.container_id).collect();
+
+    for user in &users {
+        crate::handlers::containers::mark_pending_changes(&state, user.container_id).await?;
+        if let Err(e) = push_container_allocations(&state, user.container_id, user.daemon_id).await {
+            tracing::warn!("Failed to update allocations of {} after deleting IP pool {}: {:?}", user.container_id, id, e);
+        }
     }
 
-    Ok(Json(serde_json::json!({"message": "IP pool deleted successfully"})))
+    Ok(Json(serde_json::json!({
+        "message": "IP pool deleted successfully",
+        "removedAllocations": removed,
+        "unassignedContainers": container_ids,
+    })))
 }
 
 pub async fn list_container_allocations(
@@ -161,13 +441,6 @@ pub async fn create_container_allocation(
     Json(req): Json<crate::models::CreateContainerAllocationRequest>,
 ) -> AppResult<Json<crate::models::ContainerAllocation>> {
 
-    if req.is_primary.unwrap_or(false) {
-        sqlx::query("UPDATE container_allocations SET is_primary = FALSE WHERE container_id = $1 AND is_primary = TRUE")
-            .bind(req.container_id)
-            .execute(&state.db)
-            .await?;
-    }
-
     let allocation: crate::models::Allocation = sqlx::query_as(
         "SELECT * FROM allocations WHERE id = $1"
     )
@@ -176,6 +449,15 @@ pub async fn create_container_allocation(
         .await?
         .ok_or(AppError::NotFound)?;
 
+    let mut tx = state.db.begin().await?;
+
+    if req.is_primary.unwrap_or(false) {
+        sqlx::query("UPDATE container_allocations SET is_primary = FALSE WHERE container_id = $1 AND is_primary = TRUE")
+            .bind(req.container_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
     let container_allocation: crate::models::ContainerAllocation = sqlx::query_as(
         r#"
         INSERT INTO container_allocations (id, container_id, allocation_id, ip, port, internal_port, protocol, is_primary)
@@ -190,8 +472,11 @@ pub async fn create_container_allocation(
     .bind(allocation.port)
     .bind(&allocation.protocol)
     .bind(req.is_primary.unwrap_or(false))
-    .fetch_one(&state.db)
-    .await?;
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(allocation_conflict)?;
+
+    tx.commit().await?;
 
     Ok(Json(container_allocation))
 }
@@ -212,12 +497,50 @@ pub async fn delete_container_allocation(
     Ok(Json(serde_json::json!({"message": "Container allocation deleted successfully"})))
 }
 
+/// Sends a container's current allocations to its daemon, which binds them on the next recreate.
+async fn push_container_allocations(state: &AppState, container_id: Uuid, daemon_id: Uuid) -> AppResult<()> {
+    let allocs: Vec<crate::models::ContainerAllocation> = sqlx::query_as(
+        "SELECT * FROM container_allocations WHERE container_id = $1 ORDER BY created_at"
+    )
+    .bind(container_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let daemon: crate::models::Daemon = sqlx::query_as(
+        "SELECT * FROM daemons WHERE id = $1"
+    )
+    .bind(daemon_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let allocations_for_daemon = crate::handlers::containers::daemon_allocations_json(&allocs);
+
+    let client = crate::handlers::containers::daemon_client();
+    let update_url = format!("{}/containers/{}", daemon.base_url(), container_id);
+
+    let _ = client
+        .patch(&update_url)
+        .header("X-API-Key", &daemon.api_key)
+        .json(&serde_json::json!({
+            "allocations": allocations_for_daemon
+        }))
+        .send()
+        .await;
+
+    Ok(())
+}
+
 #[derive(Debug, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdateAllocationRequest {
     pub ip: Option<String>,
     pub port: Option<i32>,
     pub protocol: Option<String>,
+    /// Empty string clears the label.
+    pub label: Option<String>,
+    /// Empty string clears the notes.
+    pub notes: Option<String>,
 }
 
 pub async fn update_allocation(
@@ -233,7 +556,14 @@ pub async fn update_allocation(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if req.ip.is_some() || req.port.is_some() {
+    let req_ip = req
+        .ip
+        .as_deref()
+        .map(raptor_common::net::normalize_ip)
+        .transpose()
+        .map_err(AppError::BadRequest)?;
+
+    if req_ip.is_some() || req.port.is_some() {
         #[derive(sqlx::FromRow)]
         struct ContainerAllocationCheck {
             id: Uuid,
@@ -245,25 +575,37 @@ pub async fn update_allocation(
         .fetch_optional(&state.db)
         .await?;
 
-        if in_use.is_some() && (req.ip.is_some() && req.ip.as_ref() != Some(&existing.ip) || req.port.is_some() && req.port != Some(existing.port)) {
+        if in_use.is_some() && (req_ip.is_some() && req_ip.as_ref() != Some(&existing.ip) || req.port.is_some() && req.port != Some(existing.port)) {
             return Err(AppError::BadRequest("Cannot change IP or port of an allocation that is in use".to_string()));
         }
     }
 
-    let ip = req.ip.unwrap_or(existing.ip);
+    let ip = req_ip.unwrap_or(existing.ip);
     let port = req.port.unwrap_or(existing.port);
     let protocol = req.protocol.unwrap_or(existing.protocol);
 
+    check_endpoint_free(&state, existing.daemon_id, &ip, port, &protocol, Some(id)).await?;
+    let label = match req.label {
+        Some(l) => normalize_annotation(Some(l), MAX_LABEL_LENGTH, "Label")?,
+        None => existing.label,
+    };
+    let notes = match req.notes {
+        Some(n) => normalize_annotation(Some(n), MAX_NOTES_LENGTH, "Notes")?,
+        None => existing.notes,
+    };
+
     let updated: crate::models::Allocation = sqlx::query_as(
         r#"
-        UPDATE allocations SET ip = $1, port = $2, protocol = $3, updated_at = NOW()
-        WHERE id = $4
+        UPDATE allocations SET ip = $1, port = $2, protocol = $3, label = $4, notes = $5, updated_at = NOW()
+        WHERE id = $6
         RETURNING *
         "#,
     )
     .bind(&ip)
     .bind(port)
     .bind(&protocol)
+    .bind(&label)
+    .bind(&notes)
     .bind(id)
     .fetch_one(&state.db)
     .await?;
@@ -297,57 +639,122 @@ pub async fn update_allocation(
     .await?;
 
     for container_info in containers {
-        #[derive(sqlx::FromRow)]
-        struct AllocationRow {
-            id: Uuid,
-            allocation_id: Option<Uuid>,
-            ip: String,
-            port: i32,
-            internal_port: i32,
-            protocol: String,
-            is_primary: Option<bool>,
+        push_container_allocations(&state, container_info.container_id, container_info.daemon_id).await?;
+    }
+
+    Ok(Json(updated))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db::{test_db, TestDb};
+
+    fn pool(ip: &str, cidr: i32) -> IpPool {
+        IpPool {
+            id: Uuid::new_v4(),
+            daemon_id: Uuid::new_v4(),
+            ip_address: ip.to_string(),
+            cidr: Some(cidr),
+            description: None,
+            is_primary: None,
+            created_at: Utc::now(),
         }
+    }
 
-        let allocs: Vec<AllocationRow> = sqlx::query_as(
-            "SELECT id, allocation_id, ip, port, internal_port, protocol, is_primary FROM container_allocations WHERE container_id = $1"
-        )
-        .bind(container_info.container_id)
-        .fetch_all(&state.db)
-        .await?;
+    async fn load_pool(db: &TestDb, id: Uuid) -> IpPool {
+        sqlx::query_as("SELECT * FROM ip_pools WHERE id = $1").bind(id).fetch_one(&db.pool).await.unwrap()
+    }
 
-        let daemon: crate::models::Daemon = sqlx::query_as(
-            "SELECT * FROM daemons WHERE id = $1"
-        )
-        .bind(container_info.daemon_id)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or(AppError::NotFound)?;
+    async fn count(db: &TestDb, sql: &str, id: Uuid) -> i64 {
+        let (n,): (i64,) = sqlx::query_as(sql).bind(id).fetch_one(&db.pool).await.unwrap();
+        n
+    }
 
-        let allocations_for_daemon: Vec<serde_json::Value> = allocs.iter().map(|a| {
-            serde_json::json!({
-                "id": a.id.to_string(),
-                "allocationId": a.allocation_id.map(|id| id.to_string()),
-                "ip": a.ip,
-                "port": a.port,
-                "internalPort": a.internal_port,
-                "protocol": a.protocol,
-                "isPrimary": a.is_primary.unwrap_or(false)
-            })
-        }).collect();
-
-        let client = crate::handlers::containers::daemon_client();
-        let update_url = format!("{}/containers/{}", daemon.base_url(), container_info.container_id);
-
-        let _ = client
-            .patch(&update_url)
-            .header("X-API-Key", &daemon.api_key)
-            .json(&serde_json::json!({
-                "allocations": allocations_for_daemon
-            }))
-            .send()
-            .await;
+    #[test]
+    fn pool_allocations_leave_addresses_another_pool_covers() {
+        let subnet = pool("10.0.0.0", 24);
+        let single = pool("10.0.0.5", 32);
+        let allocations = vec![
+            (Uuid::new_v4(), "10.0.0.4".to_string()),
+            (Uuid::new_v4(), "10.0.0.5".to_string()),
+            (Uuid::new_v4(), "10.0.1.4".to_string()),
+            (Uuid::new_v4(), "2001:db8::1".to_string()),
+        ];
+
+        assert_eq!(pool_allocations(&subnet, std::slice::from_ref(&single), &allocations), vec![allocations[0].0]);
+        assert!(pool_allocations(&single, std::slice::from_ref(&subnet), &allocations).is_empty());
+        assert_eq!(pool_allocations(&single, &[], &allocations), vec![allocations[1].0]);
     }
 
-    Ok(Json(updated))
-}
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn deleting_a_pool_in_use_is_refused_without_force() {
+        let db = test_db().await;
+        let user = db.user().await;
+        let daemon = db.daemon().await;
+        let pool_id = db.ip_pool(daemon, "10.0.0.0", 24).await;
+        let allocation = db.allocation(daemon, "10.0.0.4", 25565).await;
+        let container = db.container(user, daemon).await;
+        db.assign(container, allocation, true).await;
+
+        let result = remove_ip_pool(&db.pool, &load_pool(&db, pool_id).await, false).await;
+        assert!(matches!(&result, Err(AppError::BadRequest(msg)) if msg.contains("in use by 1 container")), "{:?}", result.err());
+
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM ip_pools WHERE id = $1", pool_id).await, 1);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM container_allocations WHERE allocation_id = $1", allocation).await, 1);
+    }
 
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn forced_delete_unassigns_and_promotes_a_new_primary() {
+        let db = test_db().await;
+        let user = db.user().await;
+        let daemon = db.daemon().await;
+        let pool_id = db.ip_pool(daemon, "10.0.0.0", 24).await;
+        db.ip_pool(daemon, "10.0.1.0", 24).await;
+        let doomed = db.allocation(daemon, "10.0.0.4", 25565).await;
+        let kept = db.allocation(daemon, "10.0.1.4", 25565).await;
+        let container = db.container(user, daemon).await;
+        db.assign(container, doomed, true).await;
+        db.assign(container, kept, false).await;
+
+        let (users, removed) = remove_ip_pool(&db.pool, &load_pool(&db, pool_id).await, true).await.unwrap();
+        assert_eq!(users.iter().map(|u| u.container_id).collect::<Vec<_>>(), vec![container]);
+        assert_eq!(removed, 1);
+
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM allocations WHERE id = $1", doomed).await, 0);
+        assert_eq!(
+            count(&db, "SELECT COUNT(*) FROM container_allocations WHERE container_id = $1 AND is_primary", container).await,
+            1
+        );
+        assert_eq!(
+            count(&db, "SELECT COUNT(*) FROM container_allocations WHERE container_id = $1 AND allocation_id IS NOT NULL", container).await,
+            1
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn deleting_a_pool_keeps_allocations_an_overlapping_pool_covers() {
+        let db = test_db().await;
+        let user = db.user().await;
+        let daemon = db.daemon().await;
+        let subnet = db.ip_pool(daemon, "10.0.0.0", 24).await;
+        db.ip_pool(daemon, "10.0.0.5", 32).await;
+        let free = db.allocation(daemon, "10.0.0.4", 25565).await;
+        let covered = db.allocation(daemon, "10.0.0.5", 25565).await;
+        let container = db.container(user, daemon).await;
+        db.assign(container, covered, true).await;
+
+        // The only allocation in use is also on the /32 pool, so nothing blocks the delete
+        let (users, removed) = remove_ip_pool(&db.pool, &load_pool(&db, subnet).await, false).await.unwrap();
+        assert!(users.is_empty());
+        assert_eq!(removed, 1);
+
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM allocations WHERE id = $1", free).await, 0);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM allocations WHERE id = $1", covered).await, 1);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM container_allocations WHERE allocation_id = $1", covered).await, 1);
+    }
+}
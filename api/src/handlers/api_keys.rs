@@ -0,0 +1,209 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{AppState, Claims};
+use crate::permissions;
+
+/// Bearer tokens starting with this are API keys rather than JWTs.
+pub const KEY_PREFIX: &str = "rpt_";
+const KEY_LENGTH: usize = 40;
+/// Leading characters kept in the clear so users can tell their keys apart.
+const DISPLAY_PREFIX_LENGTH: usize = 12;
+const MAX_KEYS_PER_USER: i64 = 25;
+const MAX_EXPIRY_DAYS: i64 = 3650;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    /// Permissions the key is limited to; empty means everything its owner has.
+    pub permissions: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub key: ApiKey,
+    /// The full key. Only returned once; the API keeps just its hash.
+    pub secret: String,
+}
+
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+fn generate_key() -> String {
+    const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    let mut rng = rand::thread_rng();
+    let body: String = (0..KEY_LENGTH)
+        .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+        .collect();
+    format!("{}{}", KEY_PREFIX, body)
+}
+
+/// GET /users/me/api-keys
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> AppResult<Json<Vec<ApiKey>>> {
+    let keys: Vec<ApiKey> = sqlx::query_as("SELECT id, name, prefix, permissions, expires_at, last_used_at, created_at FROM user_api_keys WHERE user_id = $1 ORDER BY created_at DESC")
+        .bind(claims.sub)
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(Json(keys))
+}
+
+/// POST /users/me/api-keys - the response is the only time the full key is shown
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<CreateApiKeyRequest>,
+) -> AppResult<Json<CreatedApiKey>> {
+    let name = req.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(AppError::BadRequest("Name must be between 1 and 100 characters".into()));
+    }
+
+    let mut scopes = req.permissions;
+    scopes.sort();
+    scopes.dedup();
+    for scope in &scopes {
+        if !permissions::CATALOG.iter().any(|p| p.name == scope) {
+            return Err(AppError::BadRequest(format!("Unknown permission '{}'", scope)));
+        }
+        if !claims.has_permission(scope) {
+            return Err(AppError::Forbidden(format!("You don't have the '{}' permission", scope)));
+        }
+    }
+
+    let expires_at = match req.expires_in_days {
+        Some(days) if !(1..=MAX_EXPIRY_DAYS).contains(&days) => {
+            return Err(AppError::BadRequest(format!("Expiry must be between 1 and {} days", MAX_EXPIRY_DAYS)));
+        }
+        Some(days) => Some(Utc::now() + Duration::days(days)),
+        None => None,
+    };
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_api_keys WHERE user_id = $1")
+        .bind(claims.sub)
+        .fetch_one(&state.db)
+        .await?;
+
+    if count >= MAX_KEYS_PER_USER {
+        return Err(AppError::BadRequest(format!("At most {} API keys are allowed", MAX_KEYS_PER_USER)));
+    }
+
+    let secret = generate_key();
+
+    let key: ApiKey = sqlx::query_as(
+        r#"INSERT INTO user_api_keys (id, user_id, name, prefix, key_hash, permissions, expires_at)
+           VALUES ($1, $2, $3, $4, $5, $6, $7)
+           RETURNING id, name, prefix, permissions, expires_at, last_used_at, created_at"#
+    )
+        .bind(Uuid::new_v4())
+        .bind(claims.sub)
+        .bind(name)
+        .bind(&secret[..DISPLAY_PREFIX_LENGTH])
+        .bind(hash_key(&secret))
+        .bind(&scopes)
+        .bind(expires_at)
+        .fetch_one(&state.db)
+        .await?;
+
+    tracing::info!("User {} created API key {} ({})", claims.username, key.id, key.prefix);
+
+    Ok(Json(CreatedApiKey { key, secret }))
+}
+
+/// DELETE /users/me/api-keys/:id
+pub async fn revoke_api_key(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let result = sqlx::query("DELETE FROM user_api_keys WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(claims.sub)
+        .execute(&state.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// Claims for an API key, or None when it is unknown or expired. Permissions are read fresh
+/// from the owner's role, narrowed to the key's scopes. A scoped key carries no role name,
+/// since `is_manager`/`is_admin` would otherwise let it past the scopes.
+pub async fn resolve(state: &AppState, key: &str) -> AppResult<Option<Claims>> {
+    let row: Option<(Uuid, Vec<String>, Option<DateTime<Utc>>, Uuid, String, Option<Uuid>, i32)> = sqlx::query_as(
+        r#"SELECT k.id, k.permissions, k.expires_at, u.id, u.username, u.role_id, u.token_version
+           FROM user_api_keys k
+           JOIN users u ON u.id = k.user_id
+           WHERE k.key_hash = $1 AND (k.expires_at IS NULL OR k.expires_at > NOW())"#
+    )
+        .bind(hash_key(key))
+        .fetch_optional(&state.db)
+        .await?;
+
+    let Some((key_id, scopes, expires_at, user_id, username, role_id, token_version)) = row else {
+        return Ok(None);
+    };
+
+    let (role_name, _) = crate::handlers::auth::fetch_user_role(state, role_id).await?;
+    let granted = crate::handlers::auth::fetch_user_permissions(&state.db, user_id, role_id).await?;
+
+    let (role_name, granted) = if scopes.is_empty() {
+        (role_name, granted)
+    } else {
+        let has_all = granted.iter().any(|p| p == permissions::ALL);
+        (None, scopes.into_iter().filter(|s| has_all || granted.contains(s)).collect())
+    };
+
+    if let Err(e) = sqlx::query(
+        "UPDATE user_api_keys SET last_used_at = NOW() WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')"
+    )
+        .bind(key_id)
+        .execute(&state.db)
+        .await
+    {
+        tracing::warn!("Failed to record use of API key {}: {}", key_id, e);
+    }
+
+    Ok(Some(Claims {
+        sub: user_id,
+        username,
+        role_id,
+        role_name,
+        permissions: serde_json::json!(granted.iter().map(|p| (p.clone(), true)).collect::<std::collections::HashMap<_, _>>()),
+        token_version,
+        exp: expires_at.map_or(usize::MAX, |t| t.timestamp() as usize),
+        api_key_id: Some(key_id),
+    }))
+}
@@ -1,4 +1,4 @@
-use axum::{extract::State, Json};
+use axum::{extract::State, Extension, Json};
 use bcrypt::{hash, verify};
 use chrono::{Duration, Utc};
 use jsonwebtoken::{encode, EncodingKey, Header};
@@ -65,7 +65,9 @@ pub async fn login(
         role_id: user.role_id,
         role_name: role_name.clone(),
         permissions: permissions.clone(),
+        token_version: user.token_version,
         exp,
+        api_key_id: None,
     };
 
     let token = encode(
@@ -227,7 +229,7 @@ pub async fn reset_password(
     let password_hash = hash(&req.password, state.config.bcrypt_cost)
         .map_err(|e| AppError::Internal(e.to_string()))?;
 
-    sqlx::query("UPDATE users SET password_hash = $1, updated_at = NOW() WHERE id = $2")
+    sqlx::query("UPDATE users SET password_hash = $1, token_version = token_version + 1, updated_at = NOW() WHERE id = $2")
         .bind(&password_hash)
         .bind(token_record.user_id)
         .execute(&state.db)
@@ -241,6 +243,18 @@ pub async fn reset_password(
     Ok(Json(serde_json::json!({"message": "Password has been reset successfully"})))
 }
 
+pub async fn logout_all(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> AppResult<Json<serde_json::Value>> {
+    sqlx::query("UPDATE users SET token_version = token_version + 1, updated_at = NOW() WHERE id = $1")
+        .bind(claims.sub)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Json(serde_json::json!({"message": "All sessions have been signed out"})))
+}
+
 pub async fn fetch_user_role(
     state: &AppState,
     role_id: Option<Uuid>,
@@ -0,0 +1,228 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::backup_scheduler;
+use crate::error::{AppError, AppResult};
+use crate::handlers::containers::{can_access_container, daemon_client, ensure_not_trashed, owns};
+use crate::models::{AppState, Claims, Container, Daemon};
+use crate::permissions;
+
+const MAX_KEEP_LAST: i32 = 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backup {
+    pub name: String,
+    pub size: u64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupSchedule {
+    pub container_id: Uuid,
+    pub hour: i16,
+    pub minute: i16,
+    pub keep_last: i32,
+    pub enabled: bool,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateBackupScheduleRequest {
+    /// Time of day in the container's timezone.
+    pub hour: i16,
+    #[serde(default)]
+    pub minute: i16,
+    pub keep_last: Option<i32>,
+    pub enabled: Option<bool>,
+}
+
+async fn load_container(state: &AppState, claims: &Claims, id: Uuid, manage: bool) -> AppResult<(Container, Daemon)> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let allowed = if manage {
+        owns(claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+            || claims.has_permission(permissions::CONTAINERS_MANAGE)
+            || claims.is_manager()
+    } else {
+        can_access_container(claims, &container)
+    };
+
+    if !allowed {
+        return Err(AppError::Unauthorized);
+    }
+
+    if manage {
+        ensure_not_trashed(&container)?;
+    }
+
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    Ok((container, daemon))
+}
+
+/// GET /containers/:id/backups
+pub async fn list_backups(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Vec<Backup>>> {
+    let (container, daemon) = load_container(&state, &claims, id, false).await?;
+
+    let url = format!("{}/containers/{}/backups", daemon.base_url(), container.id);
+    let res = daemon_client()
+        .get(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .send()
+        .await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    if !res.status().is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to list backups: {}", error_text)));
+    }
+
+    let backups: Vec<Backup> = res.json().await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    Ok(Json(backups))
+}
+
+/// POST /containers/:id/backups - take a manual backup now
+pub async fn create_backup(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let (container, daemon) = load_container(&state, &claims, id, true).await?;
+
+    let backup = backup_scheduler::create_backup(&daemon, container.id, "manual")
+        .await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    Ok(Json(serde_json::json!({ "success": true, "name": backup.name })))
+}
+
+/// DELETE /containers/:id/backups/:backup
+pub async fn delete_backup(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((id, backup)): Path<(Uuid, String)>,
+) -> AppResult<Json<serde_json::Value>> {
+    let (container, daemon) = load_container(&state, &claims, id, true).await?;
+
+    let url = format!(
+        "{}/containers/{}/backups/{}",
+        daemon.base_url(), container.id, urlencoding::encode(&backup)
+    );
+    let res = daemon_client()
+        .delete(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .send()
+        .await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    if !res.status().is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to delete backup: {}", error_text)));
+    }
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// GET /containers/:id/backup-schedule
+pub async fn get_backup_schedule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Option<BackupSchedule>>> {
+    load_container(&state, &claims, id, false).await?;
+
+    let schedule: Option<BackupSchedule> = sqlx::query_as("SELECT * FROM backup_schedules WHERE container_id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?;
+
+    Ok(Json(schedule))
+}
+
+/// PUT /containers/:id/backup-schedule - create or replace the daily backup schedule
+pub async fn update_backup_schedule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateBackupScheduleRequest>,
+) -> AppResult<Json<BackupSchedule>> {
+    load_container(&state, &claims, id, true).await?;
+
+    if !(0..=23).contains(&req.hour) || !(0..=59).contains(&req.minute) {
+        return Err(AppError::BadRequest("Backup time must be a valid hour (0-23) and minute (0-59)".into()));
+    }
+
+    let keep_last = req.keep_last.unwrap_or(7);
+    if !(1..=MAX_KEEP_LAST).contains(&keep_last) {
+        return Err(AppError::BadRequest(format!("keepLast must be between 1 and {}", MAX_KEEP_LAST)));
+    }
+
+    let schedule: BackupSchedule = sqlx::query_as(
+        r#"
+        INSERT INTO backup_schedules (container_id, hour, minute, keep_last, enabled)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (container_id) DO UPDATE SET
+            hour = EXCLUDED.hour,
+            minute = EXCLUDED.minute,
+            keep_last = EXCLUDED.keep_last,
+            enabled = EXCLUDED.enabled,
+            updated_at = NOW()
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(req.hour)
+    .bind(req.minute)
+    .bind(keep_last)
+    .bind(req.enabled.unwrap_or(true))
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(Json(schedule))
+}
+
+/// DELETE /containers/:id/backup-schedule - existing backups are kept
+pub async fn delete_backup_schedule(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    load_container(&state, &claims, id, true).await?;
+
+    let result = sqlx::query("DELETE FROM backup_schedules WHERE container_id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound);
+    }
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
@@ -5,19 +5,18 @@ use axum::{
 };
 use base64::{Engine as _, engine::general_purpose};
 use chrono::Utc;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use std::collections::HashMap;
+use raptor_common::resources::UNLIMITED;
 
-use crate::error::{AppError, AppResult};
-use crate::models::{AppState, Claims, Container, ContainerPort, CreateContainerRequest, Daemon};
+use crate::daemon_client::DaemonClient;
+use crate::error::{allocation_conflict, AppError, AppResult};
+use crate::models::{AppState, Claims, Container, ContainerPort, CreateContainerRequest, Daemon, PortMapping};
+use crate::permissions;
 
-pub fn daemon_client() -> reqwest::Client {
-    reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)
-        .timeout(std::time::Duration::from_secs(30))
-        .build()
-        .unwrap_or_else(|_| reqwest::Client::new())
+pub fn daemon_client() -> DaemonClient {
+    DaemonClient::new()
 }
 
 #[derive(Debug, Serialize, Clone, sqlx::FromRow)]
@@ -40,6 +39,46 @@ pub struct ContainerResponse {
     pub allocations: Vec<AllocationInfo>,
     pub allocation_ip: Option<String>,
     pub allocation_port: Option<i32>,
+    /// Build the container is running; `None` when the daemon could not be asked.
+    pub image_version: Option<ImageVersion>,
+    /// Set while running.
+    pub started_at: Option<chrono::DateTime<Utc>>,
+    pub uptime_secs: Option<i64>,
+    /// Restarts by Docker's restart policy since the container was last recreated.
+    pub restart_count: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageVersion {
+    pub image_id: String,
+    pub digest: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContainerRuntime {
+    started_at: Option<chrono::DateTime<Utc>>,
+    restart_count: i64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DaemonContainerDetails {
+    image_version: Option<ImageVersion>,
+    #[serde(default)]
+    runtime: Option<ContainerRuntime>,
+}
+
+/// Best effort: a slow or offline daemon must not break loading the container.
+async fn fetch_daemon_details(state: &AppState, container: &Container) -> Option<DaemonContainerDetails> {
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await
+        .ok()??;
+
+    fetch_daemon_json::<DaemonContainerDetails>(&daemon, format!("/containers/{}", container.id)).await
 }
 
 #[derive(Debug, Serialize, sqlx::FromRow)]
@@ -59,21 +98,102 @@ pub struct ContainerWithAllocation {
     pub io_weight: Option<i32>,
     pub sftp_user: Option<String>,
     pub sftp_pass: Option<String>,
+    pub timezone: String,
+    pub cpuset_cpus: Option<String>,
+    pub isolated: bool,
     pub created_at: chrono::DateTime<Utc>,
     pub updated_at: chrono::DateTime<Utc>,
     pub allocation_ip: Option<String>,
     pub allocation_port: Option<i32>,
 }
 
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ListContainersQuery {
+    /// Ask each daemon for live statuses instead of trusting the stored ones.
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+/// Replaces stored statuses with what the daemons report, one request per daemon,
+/// and writes any corrections back. Containers on unreachable daemons keep their stored status.
+async fn refresh_container_statuses(state: &AppState, containers: &mut [ContainerWithAllocation]) -> AppResult<()> {
+    use futures_util::StreamExt;
+
+    let mut daemon_ids: Vec<Uuid> = containers.iter().map(|c| c.daemon_id).collect();
+    daemon_ids.sort();
+    daemon_ids.dedup();
+
+    if daemon_ids.is_empty() {
+        return Ok(());
+    }
+
+    let daemons: Vec<Daemon> = sqlx::query_as("SELECT * FROM daemons WHERE id = ANY($1)")
+        .bind(&daemon_ids)
+        .fetch_all(&state.db)
+        .await?;
+
+    let live: HashMap<Uuid, crate::reconciler::DaemonContainerStatus> = futures_util::stream::iter(daemons)
+        .map(|daemon| async move {
+            let statuses = crate::reconciler::fetch_daemon_statuses(&daemon).await;
+            if statuses.is_none() {
+                tracing::warn!("Daemon {} did not report container statuses", daemon.id);
+            }
+            statuses.unwrap_or_default()
+        })
+        .buffer_unordered(state.config.reconcile_daemon_concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let mut ids = Vec::new();
+    let mut statuses = Vec::new();
+
+    for container in containers.iter_mut() {
+        let Some(actual) = live.get(&container.id) else {
+            continue;
+        };
+
+        // Docker itself is mid-restart; leave the stored status alone
+        if actual.status.eq_ignore_ascii_case("restarting") {
+            continue;
+        }
+
+        let resolved = actual.resolved_status();
+        if container.status != resolved {
+            container.status = resolved.to_string();
+            ids.push(container.id);
+            statuses.push(resolved.to_string());
+        }
+    }
+
+    if !ids.is_empty() {
+        sqlx::query(
+            r#"UPDATE containers c SET status = u.status, updated_at = NOW()
+               FROM UNNEST($1::uuid[], $2::text[]) AS u(id, status)
+               WHERE c.id = u.id"#
+        )
+            .bind(&ids)
+            .bind(&statuses)
+            .execute(&state.db)
+            .await?;
+    }
+
+    Ok(())
+}
+
 pub async fn list_containers(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
+    Query(query): Query<ListContainersQuery>,
 ) -> AppResult<Json<Vec<ContainerWithAllocation>>> {
-    let containers: Vec<ContainerWithAllocation> = if claims.has_permission("containers.view_all") || claims.is_manager() {
+    let mut containers: Vec<ContainerWithAllocation> = if claims.has_permission(permissions::CONTAINERS_VIEW_ALL) || claims.is_manager() {
         sqlx::query_as(
             r#"SELECT c.*, ca.ip as allocation_ip, ca.port as allocation_port
                FROM containers c
                LEFT JOIN container_allocations ca ON ca.container_id = c.id AND ca.is_primary = TRUE
+               WHERE c.deleted_at IS NULL
                ORDER BY c.created_at DESC"#
         )
             .fetch_all(&state.db)
@@ -83,53 +203,163 @@ pub async fn list_containers(
             r#"SELECT c.*, ca.ip as allocation_ip, ca.port as allocation_port
                FROM containers c
                LEFT JOIN container_allocations ca ON ca.container_id = c.id AND ca.is_primary = TRUE
-               WHERE c.user_id = $1
+               WHERE (c.user_id = $1 OR EXISTS(SELECT 1 FROM container_users cu WHERE cu.container_id = c.id AND cu.user_id = $1))
+               AND c.deleted_at IS NULL
                ORDER BY c.created_at DESC"#
         )
             .bind(claims.sub)
             .fetch_all(&state.db)
             .await?
     };
+
+    if query.refresh {
+        refresh_container_statuses(&state, &mut containers).await?;
+    }
+
     Ok(Json(containers))
 }
 
 pub async fn list_all_containers(
     State(state): State<AppState>,
+    Query(query): Query<ListContainersQuery>,
 ) -> AppResult<Json<Vec<ContainerWithAllocation>>> {
-    let containers: Vec<ContainerWithAllocation> =
+    let mut containers: Vec<ContainerWithAllocation> =
         sqlx::query_as(
             r#"SELECT c.*, ca.ip as allocation_ip, ca.port as allocation_port
                FROM containers c
                LEFT JOIN container_allocations ca ON ca.container_id = c.id AND ca.is_primary = TRUE
+               WHERE c.deleted_at IS NULL
                ORDER BY c.created_at DESC"#
         )
             .fetch_all(&state.db)
             .await?;
+
+    if query.refresh {
+        refresh_container_statuses(&state, &mut containers).await?;
+    }
+
     Ok(Json(containers))
 }
 
+/// What a new container is built from: its flake, or just an image and startup script.
+struct ContainerTemplate {
+    image: String,
+    startup_script: Option<String>,
+    flake_id: Option<Uuid>,
+    install_script: Option<String>,
+    install_timeout_secs: Option<i32>,
+    environment: HashMap<String, String>,
+    restart_policy: String,
+    tty: bool,
+    labels: HashMap<String, String>,
+    readiness: Option<raptor_common::readiness::ReadinessCheck>,
+    start_timeout_secs: Option<i32>,
+    variables_file: Option<raptor_common::variables_file::VariablesFile>,
+    asset_mounts: Vec<raptor_common::mounts::AssetMount>,
+    read_only: bool,
+    stop_command: Option<String>,
+    registry_credentials: Option<String>,
+}
+
+impl ContainerTemplate {
+    fn from_image(image: String, startup_script: Option<String>) -> Self {
+        Self {
+            image,
+            startup_script,
+            flake_id: None,
+            install_script: None,
+            install_timeout_secs: None,
+            environment: HashMap::new(),
+            restart_policy: "unless-stopped".to_string(),
+            tty: false,
+            labels: HashMap::new(),
+            readiness: None,
+            start_timeout_secs: None,
+            variables_file: None,
+            asset_mounts: Vec::new(),
+            read_only: false,
+            stop_command: None,
+            registry_credentials: None,
+        }
+    }
+}
+
 pub async fn create_container(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Json(req): Json<CreateContainerRequest>,
 ) -> AppResult<Json<Container>> {
-    if !claims.has_permission("containers.create") && !claims.is_manager() {
+    if !claims.has_permission(permissions::CONTAINERS_CREATE) && !claims.is_manager() {
         return Err(AppError::Unauthorized);
     }
 
+    validate_resource_limits(Some(req.memory_limit), Some(req.disk_limit), Some(req.swap_limit))?;
+    let (cpu_limit, cpu_limit_decimal) = validate_cpu_limit(req.cpu_limit)?;
+
+    let daemon_id = match req.daemon_id {
+        Some(id) => id,
+        None if req.allocation_id.is_some() || !req.additional_allocations.is_empty() => {
+            return Err(AppError::BadRequest("Allocations belong to a daemon, pick one explicitly to assign them".into()));
+        }
+        None => crate::handlers::daemons::pick_daemon(&state, req.memory_limit, cpu_limit, req.disk_limit).await?,
+    };
+
     let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
-        .bind(req.daemon_id)
+        .bind(daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let (image, startup_script, flake_id, install_script, mut flake_variables, restart_policy, tty) = if let Some(fid) = req.flake_id {
+    if daemon.maintenance {
+        return Err(AppError::BadRequest(format!("Daemon {} is in maintenance", daemon.name)));
+    }
+
+    enforce_role_limits(
+        &state,
+        req.user_id.unwrap_or(claims.sub),
+        Some(req.memory_limit),
+        Some(cpu_limit),
+        Some(req.disk_limit),
+        true,
+    ).await?;
+
+    if let Some(server_memory) = req.server_memory {
+        raptor_common::resources::validate_server_memory(server_memory, req.memory_limit).map_err(AppError::BadRequest)?;
+    }
+
+    check_port_collisions(&state, daemon.id, &req.ports).await?;
+
+    let timezone = validate_timezone(req.timezone.as_deref().unwrap_or(DEFAULT_TIMEZONE))?;
+
+    let cpuset_cpus = match req.cpuset_cpus.as_deref() {
+        Some(cpuset) if !cpuset.is_empty() => {
+            if !claims.has_permission(permissions::CONTAINERS_MANAGE) && !claims.is_manager() {
+                return Err(AppError::Forbidden("Only managers can pin containers to CPUs".into()));
+            }
+            Some(validate_cpuset(cpuset)?)
+        }
+        _ => None,
+    };
+
+    if (req.disable_swap || !req.tmpfs.is_empty()) && !claims.has_permission(permissions::CONTAINERS_MANAGE) && !claims.is_manager() {
+        return Err(AppError::Forbidden("Only managers can configure tmpfs mounts or disable swap".into()));
+    }
+    raptor_common::mounts::validate_tmpfs_mounts(&req.tmpfs).map_err(AppError::BadRequest)?;
+
+    if !req.labels.is_empty() && !claims.has_permission(permissions::CONTAINERS_MANAGE) && !claims.is_manager() {
+        return Err(AppError::Forbidden("Only managers can set Docker labels".into()));
+    }
+    crate::handlers::flakes::validate_docker_labels(&req.labels)?;
+
+    let mut template = if let Some(fid) = req.flake_id {
         let flake: crate::handlers::flakes::Flake = sqlx::query_as("SELECT * FROM flakes WHERE id = $1")
             .bind(fid)
             .fetch_optional(&state.db)
             .await?
             .ok_or(AppError::BadRequest("Flake not found".into()))?;
 
+        flake.check_minimum_resources(req.memory_limit, req.disk_limit)?;
+
         let vars: Vec<crate::handlers::flakes::FlakeVariable> = sqlx::query_as(
             "SELECT * FROM flake_variables WHERE flake_id = $1 ORDER BY sort_order"
         )
@@ -145,27 +375,44 @@ pub async fn create_container(
             env_vars.insert(var.env_variable.clone(), value);
         }
 
-        let server_memory = req.server_memory.unwrap_or(req.memory_limit);
-        env_vars.insert("SERVER_MEMORY".to_string(), server_memory.to_string());
+        // With unlimited memory the daemon sizes SERVER_MEMORY from the host instead
+        let server_memory = req.server_memory
+            .unwrap_or_else(|| raptor_common::resources::default_server_memory(req.memory_limit));
+        if server_memory > 0 {
+            env_vars.insert("SERVER_MEMORY".to_string(), server_memory.to_string());
+        }
 
         let startup = req.startup_script.clone()
             .unwrap_or_else(|| flake.startup_command.clone());
 
-        (
-            flake.docker_image,
-            Some(startup),
-            Some(fid),
-            flake.install_script,
-            env_vars,
-            flake.restart_policy,
-            flake.tty,
-        )
+        ContainerTemplate {
+            readiness: flake.readiness(),
+            variables_file: flake.variables_file(),
+            asset_mounts: flake.asset_mounts(),
+            labels: serde_json::from_value(flake.docker_labels).unwrap_or_default(),
+            image: flake.docker_image,
+            startup_script: Some(startup),
+            flake_id: Some(fid),
+            install_script: flake.install_script,
+            install_timeout_secs: flake.install_timeout_secs,
+            environment: env_vars,
+            restart_policy: flake.restart_policy,
+            tty: flake.tty,
+            start_timeout_secs: flake.start_timeout_secs,
+            read_only: flake.read_only,
+            stop_command: flake.stop_command,
+            registry_credentials: flake.registry_credentials,
+        }
     } else {
-
         let image = req.image.clone().ok_or(AppError::BadRequest("Either flake_id or image is required".into()))?;
-        (image, req.startup_script.clone(), None, None, std::collections::HashMap::new(), "unless-stopped".to_string(), false)
+        ContainerTemplate::from_image(image, req.startup_script.clone())
     };
 
+    let restart_policy = req.restart_policy.clone().unwrap_or(template.restart_policy);
+    let read_only = req.read_only.unwrap_or(template.read_only);
+
+    template.labels.extend(req.labels.clone());
+
     let container_id = Uuid::new_v4();
     let container_name_for_docker = container_id.to_string();
 
@@ -180,7 +427,7 @@ pub async fn create_container(
         })
     }).collect();
 
-    let mut allocations_for_daemon: Vec<serde_json::Value> = Vec::new();
+    let mut container_allocations: Vec<crate::models::ContainerAllocation> = Vec::new();
     let mut primary_port: Option<i32> = None;
     let mut primary_ip: Option<String> = None;
 
@@ -189,7 +436,7 @@ pub async fn create_container(
             "SELECT * FROM allocations WHERE id = $1 AND daemon_id = $2"
         )
             .bind(allocation_id)
-            .bind(req.daemon_id)
+            .bind(daemon.id)
             .fetch_optional(&state.db)
             .await?
             .ok_or(AppError::BadRequest("Primary allocation not found or belongs to different daemon".into()))?;
@@ -197,15 +444,7 @@ pub async fn create_container(
         primary_port = Some(allocation.port);
         primary_ip = Some(allocation.ip.clone());
 
-        allocations_for_daemon.push(serde_json::json!({
-            "id": Uuid::new_v4().to_string(),
-            "allocationId": allocation_id.to_string(),
-            "ip": allocation.ip,
-            "port": allocation.port,
-            "internalPort": allocation.port,
-            "protocol": allocation.protocol,
-            "isPrimary": true
-        }));
+        container_allocations.push(assigned_allocation(container_id, &allocation, true));
     }
 
     for additional_allocation_id in &req.additional_allocations {
@@ -213,47 +452,58 @@ pub async fn create_container(
             "SELECT * FROM allocations WHERE id = $1 AND daemon_id = $2"
         )
             .bind(additional_allocation_id)
-            .bind(req.daemon_id)
+            .bind(daemon.id)
             .fetch_optional(&state.db)
             .await?
             .ok_or(AppError::BadRequest("Additional allocation not found or belongs to different daemon".into()))?;
 
-        allocations_for_daemon.push(serde_json::json!({
-            "id": Uuid::new_v4().to_string(),
-            "allocationId": additional_allocation_id.to_string(),
-            "ip": allocation.ip,
-            "port": allocation.port,
-            "internalPort": allocation.port,
-            "protocol": allocation.protocol,
-            "isPrimary": false
-        }));
+        container_allocations.push(assigned_allocation(container_id, &allocation, false));
+    }
+
+    for key in req.environment.keys() {
+        validate_env_key(key)?;
     }
+    template.environment.extend(req.environment.clone());
 
     if let Some(port) = primary_port {
-        flake_variables.insert("SERVER_PORT".to_string(), port.to_string());
+        template.environment.insert("SERVER_PORT".to_string(), port.to_string());
     }
     if let Some(ip) = primary_ip {
-        flake_variables.insert("SERVER_IP".to_string(), ip);
+        template.environment.insert("SERVER_IP".to_string(), ip);
     }
+    template.environment.insert("TZ".to_string(), timezone.clone());
 
-    let server_memory = req.server_memory.unwrap_or(req.memory_limit);
+    let server_memory = req.server_memory
+        .unwrap_or_else(|| raptor_common::resources::default_server_memory(req.memory_limit));
 
     let daemon_req = serde_json::json!({
         "name": container_name_for_docker,
-        "image": image,
-        "startupScript": startup_script,
+        "image": template.image,
+        "startupScript": template.startup_script,
         "memoryLimit": req.memory_limit,
         "serverMemory": server_memory,
-        "cpuLimit": req.cpu_limit,
+        "cpuLimit": cpu_limit,
         "diskLimit": req.disk_limit,
         "swapLimit": req.swap_limit,
         "ioWeight": req.io_weight,
+        "cpusetCpus": cpuset_cpus,
+        "disableSwap": req.disable_swap,
+        "tmpfs": req.tmpfs,
+        "readOnly": read_only,
+        "isolated": req.isolated,
         "ports": port_mappings,
-        "allocations": allocations_for_daemon,
-        "installScript": install_script,
-        "environment": flake_variables,
+        "allocations": daemon_allocations_json(&container_allocations),
+        "installScript": template.install_script,
+        "installTimeoutSecs": template.install_timeout_secs,
+        "environment": template.environment,
         "restartPolicy": restart_policy,
-        "tty": tty
+        "tty": template.tty,
+        "labels": template.labels,
+        "readiness": template.readiness,
+        "startTimeoutSecs": template.start_timeout_secs,
+        "variablesFile": template.variables_file,
+        "assetMounts": template.asset_mounts,
+        "registryCredentials": template.registry_credentials
     });
 
     let res = client
@@ -269,12 +519,8 @@ pub async fn create_container(
         return Err(AppError::Daemon(format!("Failed to create container: {}", error_text)));
     }
 
-    let daemon_container: serde_json::Value = res
-        .json()
-        .await
-        .map_err(|e| AppError::Daemon(e.to_string()))?;
-
-    let now = Utc::now();
+    // The daemon has created the container at this point; from here on a failure must not leave it orphaned
+    let daemon_container: serde_json::Value = res.json().await.unwrap_or_default();
 
     let user_id = if claims.is_manager() {
         req.user_id.unwrap_or(claims.sub)
@@ -284,74 +530,139 @@ pub async fn create_container(
 
     let sftp_user = container_id.to_string().replace("-", "")[..8].to_string();
 
-    let stop_command = req.stop_command.clone().unwrap_or_else(|| "stop".to_string());
+    let stop_command = req.stop_command.clone()
+        .or(template.stop_command)
+        .unwrap_or_else(|| "stop".to_string());
+
+    let row = NewContainer {
+        id: container_id,
+        user_id,
+        daemon_id: daemon.id,
+        flake_id: template.flake_id,
+        image: &template.image,
+        startup_script: template.startup_script.as_deref(),
+        stop_command: &stop_command,
+        cpu_limit: cpu_limit_decimal,
+        sftp_user: &sftp_user,
+        timezone: &timezone,
+        cpuset_cpus: cpuset_cpus.as_deref(),
+        restart_policy: &restart_policy,
+        read_only,
+    };
+    let container = store_created_container(
+        &daemon,
+        container_id,
+        insert_created_container(&state.db, &row, &req, &container_allocations),
+    ).await?;
+
+    tracing::info!("Created container on daemon: {:?}", daemon_container);
+
+    // The container exists either way; a failed start shows up as status 'stopped' rather than an error
+    let container = if req.auto_start {
+        match auto_start_container(&state, &container, &daemon, template.install_timeout_secs).await {
+            Ok(()) => sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+                .bind(container.id)
+                .fetch_one(&state.db)
+                .await?,
+            Err(e) => {
+                tracing::warn!("Created container {} but could not auto-start it: {}", container.id, e);
+                container
+            }
+        }
+    } else {
+        container
+    };
+
+    Ok(Json(container))
+}
+
+/// The containers row `create_container` settles on from the request, the flake and the caller.
+struct NewContainer<'a> {
+    id: Uuid,
+    user_id: Uuid,
+    daemon_id: Uuid,
+    flake_id: Option<Uuid>,
+    image: &'a str,
+    startup_script: Option<&'a str>,
+    stop_command: &'a str,
+    cpu_limit: rust_decimal::Decimal,
+    sftp_user: &'a str,
+    timezone: &'a str,
+    cpuset_cpus: Option<&'a str>,
+    restart_policy: &'a str,
+    read_only: bool,
+}
+
+/// Writes a container the daemon has created, with its allocations, environment, ports and
+/// outbox event, in one transaction.
+async fn insert_created_container(
+    db: &sqlx::PgPool,
+    row: &NewContainer<'_>,
+    req: &CreateContainerRequest,
+    allocations: &[crate::models::ContainerAllocation],
+) -> AppResult<Container> {
+    let mut tx = db.begin().await?;
 
     let container: Container = sqlx::query_as(
         r#"
-        INSERT INTO containers (id, user_id, daemon_id, flake_id, name, image, startup_script, stop_command, status, memory_limit, cpu_limit, disk_limit, swap_limit, io_weight, sftp_user, created_at, updated_at)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'stopped', $9, $10, $11, $12, $13, $14, $15, $15)
+        INSERT INTO containers (id, user_id, daemon_id, flake_id, name, image, startup_script, stop_command, status, memory_limit, cpu_limit, disk_limit, swap_limit, io_weight, sftp_user, timezone, cpuset_cpus, isolated, restart_policy, tmpfs, disable_swap, docker_labels, read_only, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'stopped', $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $23)
         RETURNING *
         "#,
     )
-    .bind(container_id)
-    .bind(user_id)
-    .bind(req.daemon_id)
-    .bind(flake_id)
+    .bind(row.id)
+    .bind(row.user_id)
+    .bind(row.daemon_id)
+    .bind(row.flake_id)
     .bind(&req.name)
-    .bind(&image)
-    .bind(&startup_script)
-    .bind(&stop_command)
+    .bind(row.image)
+    .bind(row.startup_script)
+    .bind(row.stop_command)
     .bind(req.memory_limit)
-    .bind(rust_decimal::Decimal::try_from(req.cpu_limit).unwrap_or_default())
+    .bind(row.cpu_limit)
     .bind(req.disk_limit)
     .bind(req.swap_limit)
     .bind(req.io_weight)
-    .bind(&sftp_user)
-    .bind(now)
-    .fetch_one(&state.db)
+    .bind(row.sftp_user)
+    .bind(row.timezone)
+    .bind(row.cpuset_cpus)
+    .bind(req.isolated)
+    .bind(row.restart_policy)
+    .bind(serde_json::json!(req.tmpfs))
+    .bind(req.disable_swap)
+    .bind(serde_json::json!(req.labels))
+    .bind(row.read_only)
+    .bind(Utc::now())
+    .fetch_one(&mut *tx)
     .await?;
 
-    if let Some(allocation_id) = req.allocation_id {
-
-        if let Some(alloc_json) = allocations_for_daemon.iter().find(|a| a["isPrimary"].as_bool() == Some(true)) {
-            let ip = alloc_json["ip"].as_str().unwrap_or("");
-            let port = alloc_json["port"].as_i64().unwrap_or(0) as i32;
-
-            sqlx::query(
-                r#"INSERT INTO container_allocations (id, container_id, allocation_id, ip, port, internal_port, protocol, is_primary, created_at)
-                   VALUES ($1, $2, $3, $4, $5, $5, 'tcp', TRUE, NOW())"#
-            )
-                .bind(Uuid::new_v4())
-                .bind(container_id)
-                .bind(allocation_id)
-                .bind(ip)
-                .bind(port)
-                .execute(&state.db)
-                .await?;
-        }
+    // The same rows the daemon was given, so both sides agree on ids, ports and protocols
+    for allocation in allocations {
+        sqlx::query(
+            r#"INSERT INTO container_allocations (id, container_id, allocation_id, ip, port, internal_port, protocol, is_primary, created_at)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#
+        )
+            .bind(allocation.id)
+            .bind(allocation.container_id)
+            .bind(allocation.allocation_id)
+            .bind(&allocation.ip)
+            .bind(allocation.port)
+            .bind(allocation.internal_port)
+            .bind(&allocation.protocol)
+            .bind(allocation.is_primary.unwrap_or(false))
+            .bind(allocation.created_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(allocation_conflict)?;
     }
 
-    for additional_allocation_id in &req.additional_allocations {
-
-        let alloc_id_str = additional_allocation_id.to_string();
-        if let Some(alloc_json) = allocations_for_daemon.iter().find(|a| {
-            a["allocationId"].as_str() == Some(alloc_id_str.as_str())
-        }) {
-            let ip = alloc_json["ip"].as_str().unwrap_or("");
-            let port = alloc_json["port"].as_i64().unwrap_or(0) as i32;
-
-            sqlx::query(
-                r#"INSERT INTO container_allocations (id, container_id, allocation_id, ip, port, internal_port, protocol, is_primary, created_at)
-                   VALUES ($1, $2, $3, $4, $5, $5, 'tcp', FALSE, NOW())"#
-            )
-                .bind(Uuid::new_v4())
-                .bind(container_id)
-                .bind(additional_allocation_id)
-                .bind(ip)
-                .bind(port)
-                .execute(&state.db)
-                .await?;
-        }
+    for (key, value) in &req.environment {
+        sqlx::query("INSERT INTO container_env (container_id, key, value) VALUES ($1, $2, $3)")
+            .bind(row.id)
+            .bind(key)
+            .bind(value)
+            .execute(&mut *tx)
+            .await?;
     }
 
     for port in &req.ports {
@@ -359,15 +670,249 @@ pub async fn create_container(
             "INSERT INTO container_ports (id, container_id, host_port, container_port, protocol) VALUES ($1, $2, $3, $4, $5)"
         )
         .bind(Uuid::new_v4())
-        .bind(container_id)
+        .bind(row.id)
         .bind(port.host_port)
         .bind(port.container_port)
         .bind(&port.protocol)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    crate::outbox::record(
+        &mut tx,
+        container.id,
+        crate::outbox::CONTAINER_CREATED,
+        serde_json::json!({
+            "userId": container.user_id,
+            "daemonId": container.daemon_id,
+            "flakeId": container.flake_id,
+            "name": container.name,
+            "memoryLimit": container.memory_limit,
+            "cpuLimit": container.cpu_limit,
+            "diskLimit": container.disk_limit,
+        }),
+    ).await?;
+
+    tx.commit().await?;
+
+    Ok::<_, AppError>(container)
+}
+
+/// Starts a freshly created container. Nobody is attached to the log websocket yet, so a
+/// pending install script is run through the daemon's headless install endpoint instead.
+async fn auto_start_container(
+    state: &AppState,
+    container: &Container,
+    daemon: &Daemon,
+    install_timeout_secs: Option<i32>,
+) -> AppResult<()> {
+    let started = start_on_daemon(state, container, daemon).await?;
+
+    if started["needsInstall"].as_bool() == Some(true) {
+        let install_timeout = install_timeout_secs.filter(|t| *t > 0).map(|t| t as u64).unwrap_or(300);
+        let url = format!("{}/containers/{}/install", daemon.base_url(), container.id);
+
+        let res = DaemonClient::with_timeout(std::time::Duration::from_secs(install_timeout + 60))
+            .post(&url)
+            .header("X-API-Key", &daemon.api_key)
+            .send()
+            .await
+            .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+        if !res.status().is_success() {
+            let error_text = res.text().await.unwrap_or_default();
+            return Err(AppError::Daemon(format!("Failed to install container: {}", error_text)));
+        }
+
+        start_on_daemon(state, container, daemon).await?;
+    }
+
+    sqlx::query("UPDATE containers SET status = 'running', updated_at = NOW() WHERE id = $1")
+        .bind(container.id)
         .execute(&state.db)
         .await?;
+
+    Ok(())
+}
+
+/// Runs the database writes for a container the daemon has already created. If they fail,
+/// nothing is stored and the daemon container is removed again, so it isn't orphaned.
+async fn store_created_container<T>(
+    daemon: &Daemon,
+    container_id: Uuid,
+    store: impl std::future::Future<Output = AppResult<T>>,
+) -> AppResult<T> {
+    match store.await {
+        Ok(stored) => Ok(stored),
+        Err(e) => {
+            tracing::error!("Failed to store container {} after daemon creation, rolling back: {}", container_id, e);
+            remove_daemon_container(daemon, container_id).await;
+            Err(e)
+        }
     }
+}
 
-    tracing::info!("Created container on daemon: {:?}", daemon_container);
+/// Best-effort removal of a daemon container whose database records could not be written.
+async fn remove_daemon_container(daemon: &Daemon, container_id: Uuid) {
+    let url = format!("{}/containers/{}", daemon.base_url(), container_id);
+
+    match daemon_client()
+        .delete(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .send()
+        .await
+    {
+        Ok(res) if res.status().is_success() => {
+            tracing::info!("Removed orphaned container {} from daemon {}", container_id, daemon.id);
+        }
+        Ok(res) => {
+            tracing::warn!("Daemon {} refused to remove orphaned container {}: {}", daemon.id, container_id, res.status());
+        }
+        Err(e) => {
+            tracing::warn!("Failed to remove orphaned container {} from daemon {}: {}", container_id, daemon.id, e);
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloneContainerRequest {
+    /// Defaults to "<source name> (copy)".
+    pub name: Option<String>,
+    /// Defaults to the source container's daemon.
+    pub daemon_id: Option<Uuid>,
+    /// Copy the volume contents too; only possible on the same daemon.
+    #[serde(default)]
+    pub copy_files: bool,
+}
+
+/// POST /containers/:id/clone - create a stopped copy with the same flake, resources, startup,
+/// variables and environment on a fresh allocation
+pub async fn clone_container(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    body: Option<Json<CloneContainerRequest>>,
+) -> AppResult<Json<Container>> {
+    use rust_decimal::prelude::ToPrimitive;
+
+    let req = body.map(|Json(r)| r).unwrap_or_default();
+
+    let source: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let can_manage = owns(&claims, &source, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
+        || claims.is_manager();
+
+    if !can_manage {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&source)?;
+
+    let daemon_id = req.daemon_id.unwrap_or(source.daemon_id);
+    if req.copy_files && daemon_id != source.daemon_id {
+        return Err(AppError::BadRequest("Files can only be copied to a clone on the same daemon".into()));
+    }
+
+    let has_allocation: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM container_allocations WHERE container_id = $1)")
+        .bind(source.id)
+        .fetch_one(&state.db)
+        .await?;
+
+    // The clone gets the first free allocation instead of sharing the source's ports
+    let allocation_id: Option<Uuid> = if has_allocation {
+        let free: Option<Uuid> = sqlx::query_scalar(
+            r#"SELECT a.id FROM allocations a
+               WHERE a.daemon_id = $1
+               AND NOT EXISTS (SELECT 1 FROM container_allocations ca WHERE ca.allocation_id = a.id)
+               ORDER BY a.ip, a.port
+               LIMIT 1"#
+        )
+            .bind(daemon_id)
+            .fetch_optional(&state.db)
+            .await?;
+
+        Some(free.ok_or_else(|| AppError::BadRequest("No free allocation left on the target daemon".into()))?)
+    } else {
+        None
+    };
+
+    let variables: HashMap<String, String> = resolve_startup_variables(&state, &source)
+        .await?
+        .into_iter()
+        .map(|v| (v.env_variable, v.value))
+        .collect();
+
+    let can_pin = claims.has_permission(permissions::CONTAINERS_MANAGE) || claims.is_manager();
+
+    let create = CreateContainerRequest {
+        daemon_id: Some(daemon_id),
+        name: req.name.unwrap_or_else(|| format!("{} (copy)", source.name)),
+        flake_id: source.flake_id,
+        image: Some(source.image.clone()),
+        startup_script: source.startup_script.clone(),
+        stop_command: source.stop_command.clone(),
+        allocation_id,
+        additional_allocations: Vec::new(),
+        memory_limit: source.memory_limit.unwrap_or_else(crate::models::default_memory),
+        // A defaulted server memory (the limit, or what fits in it) is derived by the copy itself
+        server_memory: variables.get("SERVER_MEMORY")
+            .and_then(|v| v.parse().ok())
+            .filter(|memory| {
+                source.memory_limit.is_none_or(|limit| {
+                    *memory != limit && *memory != raptor_common::resources::default_server_memory(limit)
+                })
+            }),
+        cpu_limit: source.cpu_limit.and_then(|c| c.to_f64()).unwrap_or_else(crate::models::default_cpu),
+        disk_limit: source.disk_limit.unwrap_or_else(crate::models::default_disk),
+        swap_limit: source.swap_limit.unwrap_or(0),
+        io_weight: source.io_weight.unwrap_or_else(crate::models::default_io),
+        ports: Vec::new(),
+        user_id: Some(source.user_id),
+        variables,
+        timezone: Some(source.timezone.clone()),
+        cpuset_cpus: source.cpuset_cpus.clone().filter(|_| can_pin),
+        isolated: source.isolated,
+        disable_swap: source.disable_swap && can_pin,
+        tmpfs: if can_pin { source.tmpfs() } else { Vec::new() },
+        read_only: Some(source.read_only),
+        labels: if can_pin { source.docker_labels() } else { HashMap::new() },
+        auto_start: false,
+        environment: fetch_container_env(&state, source.id).await?,
+        restart_policy: Some(source.restart_policy.clone()),
+    };
+
+    let Json(container) = create_container(State(state.clone()), Extension(claims), Json(create)).await?;
+
+    if req.copy_files {
+        let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+            .bind(daemon_id)
+            .fetch_one(&state.db)
+            .await?;
+
+        let url = format!("{}/containers/{}/copy-volume", daemon.base_url(), container.id);
+        let res = DaemonClient::with_timeout(std::time::Duration::from_secs(30 * 60))
+            .post(&url)
+            .header("X-API-Key", &daemon.api_key)
+            .json(&serde_json::json!({ "source": source.id.to_string() }))
+            .send()
+            .await
+            .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+        if !res.status().is_success() {
+            let error_text = res.text().await.unwrap_or_default();
+            return Err(AppError::Daemon(format!(
+                "Created clone {} but failed to copy files: {}", container.id, error_text
+            )));
+        }
+    }
+
+    tracing::info!("Cloned container {} into {}", source.id, container.id);
 
     Ok(Json(container))
 }
@@ -383,7 +928,9 @@ pub async fn get_container(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if container.user_id != claims.sub && !claims.has_permission("containers.view_all") && !claims.is_manager() {
+    if !owns(&claims, &container, permissions::CONTAINERS_VIEW_OWN)? && !claims.has_permission(permissions::CONTAINERS_VIEW_ALL) && !claims.is_manager()
+        && sub_user_permissions(&state, &claims, &container).await?.is_none()
+    {
         return Err(AppError::Unauthorized);
     }
 
@@ -401,11 +948,20 @@ pub async fn get_container(
     let allocation_ip = primary.map(|a| a.ip.clone());
     let allocation_port = primary.map(|a| a.port);
 
+    let details = fetch_daemon_details(&state, &container).await.unwrap_or_default();
+    let running = container.status == "running";
+    let started_at = details.runtime.as_ref().and_then(|r| r.started_at).filter(|_| running);
+    let uptime_secs = started_at.map(|at| (Utc::now() - at).num_seconds().max(0));
+
     Ok(Json(ContainerResponse {
         container,
         allocations,
         allocation_ip,
         allocation_port,
+        image_version: details.image_version,
+        started_at,
+        uptime_secs,
+        restart_count: details.runtime.map(|r| r.restart_count),
     }))
 }
 
@@ -420,7 +976,7 @@ pub async fn get_container_ports(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if container.user_id != claims.sub && !claims.has_permission("containers.view_all") && !claims.is_manager() {
+    if !owns(&claims, &container, permissions::CONTAINERS_VIEW_OWN)? && !claims.has_permission(permissions::CONTAINERS_VIEW_ALL) && !claims.is_manager() {
         return Err(AppError::Unauthorized);
     }
 
@@ -432,6 +988,8 @@ pub async fn get_container_ports(
     Ok(Json(ports))
 }
 
+/// DELETE /containers/:id - moves the container to the trash (stopped, files kept) for
+/// `CONTAINER_TRASH_RETENTION_HOURS`; deleting a container already in the trash removes it for good
 pub async fn delete_container(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
@@ -443,7 +1001,7 @@ pub async fn delete_container(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if container.user_id != claims.sub && !claims.has_permission("containers.delete") && !claims.is_manager() {
+    if !owns(&claims, &container, permissions::CONTAINERS_DELETE)? && !claims.has_permission(permissions::CONTAINERS_DELETE) && !claims.is_manager() {
         return Err(AppError::Unauthorized);
     }
 
@@ -453,35 +1011,306 @@ pub async fn delete_container(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let client = daemon_client();
-    let daemon_url = format!("{}/containers/{}", daemon.base_url(), container.id);
+    if container.deleted_at.is_some() || state.config.container_trash_retention_hours <= 0 {
+        purge_container(&state, &container, &daemon).await?;
+        return Ok(Json(()));
+    }
 
-    client
-        .delete(&daemon_url)
-        .header("X-API-Key", &daemon.api_key)
-        .send()
-        .await
-        .map_err(|e| AppError::Daemon(e.to_string()))?;
+    // A trashed container is recorded as stopped, so it has to actually be stopped first
+    if let Err(e) = stop_on_daemon(&daemon, &container, STOP_TIMEOUT_SECS).await {
+        tracing::warn!("Failed to stop container {} before moving it to the trash: {}", container.id, e);
+        return Err(AppError::Daemon(format!("Couldn't stop the container, so it wasn't moved to the trash: {}", e)));
+    }
 
-    sqlx::query("DELETE FROM containers WHERE id = $1")
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query("UPDATE containers SET deleted_at = NOW(), status = 'stopped', updated_at = NOW() WHERE id = $1")
         .bind(id)
-        .execute(&state.db)
+        .execute(&mut *tx)
         .await?;
 
+    crate::outbox::record(
+        &mut tx,
+        container.id,
+        crate::outbox::CONTAINER_TRASHED,
+        serde_json::json!({
+            "userId": container.user_id,
+            "daemonId": container.daemon_id,
+            "name": container.name,
+        }),
+    ).await?;
+
+    tx.commit().await?;
+
+    tracing::info!("Moved container {} to the trash", container.id);
+
     Ok(Json(()))
 }
 
-#[derive(Debug, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct UpdateContainerRequest {
-    #[serde(default)]
-    pub memory_limit: Option<i64>,
-    #[serde(default)]
-    pub server_memory: Option<i64>,
-    #[serde(default)]
-    pub cpu_limit: Option<f64>,
-    #[serde(default)]
-    pub disk_limit: Option<i64>,
+/// POST /containers/:id/restore - takes a container out of the trash; it stays stopped
+pub async fn restore_container(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Container>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !owns(&claims, &container, permissions::CONTAINERS_DELETE)? && !claims.has_permission(permissions::CONTAINERS_DELETE) && !claims.is_manager() {
+        return Err(AppError::Unauthorized);
+    }
+
+    if container.deleted_at.is_none() {
+        return Err(AppError::BadRequest("Container is not in the trash".into()));
+    }
+
+    enforce_role_limits(&state, container.user_id, None, None, None, true).await?;
+
+    let mut tx = state.db.begin().await?;
+
+    let restored: Container = sqlx::query_as(
+        "UPDATE containers SET deleted_at = NULL, updated_at = NOW() WHERE id = $1 RETURNING *"
+    )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    crate::outbox::record(
+        &mut tx,
+        container.id,
+        crate::outbox::CONTAINER_RESTORED,
+        serde_json::json!({
+            "userId": container.user_id,
+            "daemonId": container.daemon_id,
+            "name": container.name,
+        }),
+    ).await?;
+
+    tx.commit().await?;
+
+    tracing::info!("Restored container {} from the trash", container.id);
+
+    Ok(Json(restored))
+}
+
+/// GET /admin/containers/trash
+pub async fn list_trashed_containers(
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<Container>>> {
+    let containers: Vec<Container> = sqlx::query_as(
+        "SELECT * FROM containers WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"
+    )
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(Json(containers))
+}
+
+/// Removes the container and its files from the daemon, then the row.
+pub async fn purge_container(state: &AppState, container: &Container, daemon: &Daemon) -> AppResult<()> {
+    let daemon_url = format!("{}/containers/{}?remove_volume=true", daemon.base_url(), container.id);
+
+    let res = daemon_client()
+        .delete(&daemon_url)
+        .header("X-API-Key", &daemon.api_key)
+        .send()
+        .await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    if !res.status().is_success() && res.status() != reqwest::StatusCode::NOT_FOUND {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to remove container: {}", error_text)));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query("DELETE FROM containers WHERE id = $1")
+        .bind(container.id)
+        .execute(&mut *tx)
+        .await?;
+
+    crate::outbox::record(
+        &mut tx,
+        container.id,
+        crate::outbox::CONTAINER_DELETED,
+        serde_json::json!({
+            "userId": container.user_id,
+            "daemonId": container.daemon_id,
+            "name": container.name,
+        }),
+    ).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Memory and disk must be positive or UNLIMITED; swap may also be 0 (no swap).
+fn validate_resource_limits(memory_limit: Option<i64>, disk_limit: Option<i64>, swap_limit: Option<i64>) -> AppResult<()> {
+    let checks = [
+        ("Memory limit", memory_limit, 1),
+        ("Disk limit", disk_limit, 1),
+        ("Swap limit", swap_limit, 0),
+    ];
+
+    for (field, value, min) in checks {
+        if let Some(v) = value {
+            if v != UNLIMITED && v < min {
+                return Err(AppError::BadRequest(format!(
+                    "{} must be at least {} MB, or {} for unlimited",
+                    field, min, UNLIMITED
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `containers.cpu_limit` is DECIMAL(4,2), and Docker needs a quota of at least 1ms per 100ms period.
+const MIN_CPU_LIMIT: f64 = 0.01;
+const MAX_CPU_LIMIT: f64 = 99.99;
+
+/// Rounds a CPU limit to the precision it is stored with, returning it both as sent to the daemon
+/// and as stored.
+fn validate_cpu_limit(cpu_limit: f64) -> AppResult<(f64, rust_decimal::Decimal)> {
+    use rust_decimal::prelude::ToPrimitive;
+
+    if !cpu_limit.is_finite() || !(MIN_CPU_LIMIT..=MAX_CPU_LIMIT).contains(&cpu_limit) {
+        return Err(AppError::BadRequest(format!(
+            "CPU limit must be between {} and {} cores", MIN_CPU_LIMIT, MAX_CPU_LIMIT
+        )));
+    }
+
+    let decimal = rust_decimal::Decimal::try_from(cpu_limit)
+        .map_err(|_| AppError::BadRequest("CPU limit is not a valid number".into()))?
+        .round_dp(2);
+
+    Ok((decimal.to_f64().unwrap_or(cpu_limit), decimal))
+}
+
+async fn enforce_role_limits(
+    state: &AppState,
+    owner_id: Uuid,
+    memory_limit: Option<i64>,
+    cpu_limit: Option<f64>,
+    disk_limit: Option<i64>,
+    creating: bool,
+) -> AppResult<()> {
+    use rust_decimal::prelude::ToPrimitive;
+
+    let limits = crate::handlers::roles::fetch_role_limits(&state.db, owner_id).await?;
+
+    if let (Some(UNLIMITED), Some(max)) = (memory_limit, limits.max_memory) {
+        return Err(AppError::Forbidden(format!("Unlimited memory exceeds the role maximum of {} MB", max)));
+    }
+
+    if let (Some(UNLIMITED), Some(max)) = (disk_limit, limits.max_disk) {
+        return Err(AppError::Forbidden(format!("Unlimited disk exceeds the role maximum of {} MB", max)));
+    }
+
+    if let (Some(memory), Some(max)) = (memory_limit, limits.max_memory) {
+        if memory > max {
+            return Err(AppError::Forbidden(format!("Memory limit of {} MB exceeds the role maximum of {} MB", memory, max)));
+        }
+    }
+
+    if let (Some(cpu), Some(max)) = (cpu_limit, limits.max_cpu.and_then(|m| m.to_f64())) {
+        if cpu > max {
+            return Err(AppError::Forbidden(format!("CPU limit of {} cores exceeds the role maximum of {} cores", cpu, max)));
+        }
+    }
+
+    if let (Some(disk), Some(max)) = (disk_limit, limits.max_disk) {
+        if disk > max {
+            return Err(AppError::Forbidden(format!("Disk limit of {} MB exceeds the role maximum of {} MB", disk, max)));
+        }
+    }
+
+    if creating {
+        if let Some(max) = limits.max_containers {
+            let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM containers WHERE user_id = $1 AND deleted_at IS NULL")
+                .bind(owner_id)
+                .fetch_one(&state.db)
+                .await?;
+
+            if count >= max as i64 {
+                return Err(AppError::Forbidden(format!("The role allows at most {} containers", max)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn protocols_overlap(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b) || a.eq_ignore_ascii_case("both") || b.eq_ignore_ascii_case("both")
+}
+
+/// Host ports are bound on 0.0.0.0, so they clash with any IPv4 allocation or other
+/// container port on the same daemon regardless of IP.
+async fn check_port_collisions(state: &AppState, daemon_id: Uuid, ports: &[PortMapping]) -> AppResult<()> {
+    for (idx, port) in ports.iter().enumerate() {
+        if ports[..idx].iter().any(|p| p.host_port == port.host_port && protocols_overlap(&p.protocol, &port.protocol)) {
+            return Err(AppError::BadRequest(format!("Host port {} is mapped more than once", port.host_port)));
+        }
+
+        let allocations: Vec<(String, i32)> = sqlx::query_as(
+            r#"SELECT ip, port FROM allocations
+               WHERE daemon_id = $1 AND port = $2
+               AND (LOWER(protocol) = LOWER($3) OR LOWER(protocol) = 'both' OR LOWER($3) = 'both')"#
+        )
+            .bind(daemon_id)
+            .bind(port.host_port)
+            .bind(&port.protocol)
+            .fetch_all(&state.db)
+            .await?;
+
+        if let Some((ip, alloc_port)) = allocations.into_iter().find(|(ip, _)| raptor_common::net::ips_overlap("0.0.0.0", ip)) {
+            return Err(AppError::BadRequest(format!(
+                "Host port {} collides with allocation {}",
+                port.host_port, raptor_common::net::endpoint(&ip, alloc_port)
+            )));
+        }
+
+        let mapped: Option<(String,)> = sqlx::query_as(
+            r#"SELECT c.name FROM container_ports cp
+               JOIN containers c ON c.id = cp.container_id
+               WHERE c.daemon_id = $1 AND cp.host_port = $2
+               AND (LOWER(cp.protocol) = LOWER($3) OR LOWER(cp.protocol) = 'both' OR LOWER($3) = 'both')
+               LIMIT 1"#
+        )
+            .bind(daemon_id)
+            .bind(port.host_port)
+            .bind(&port.protocol)
+            .fetch_optional(&state.db)
+            .await?;
+
+        if let Some((name,)) = mapped {
+            return Err(AppError::BadRequest(format!(
+                "Host port {} is already mapped by container {}",
+                port.host_port, name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateContainerRequest {
+    #[serde(default)]
+    pub memory_limit: Option<i64>,
+    #[serde(default)]
+    pub server_memory: Option<i64>,
+    #[serde(default)]
+    pub cpu_limit: Option<f64>,
+    #[serde(default)]
+    pub disk_limit: Option<i64>,
     #[serde(default)]
     pub swap_limit: Option<i64>,
     #[serde(default)]
@@ -490,6 +1319,33 @@ pub struct UpdateContainerRequest {
     pub allocation_id: Option<Uuid>,
     #[serde(default)]
     pub startup_script: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// An empty string removes the CPU pinning.
+    #[serde(default)]
+    pub cpuset_cpus: Option<String>,
+    /// Applied the next time the container is started.
+    #[serde(default)]
+    pub isolated: Option<bool>,
+    /// One of `no`, `always`, `on-failure` or `unless-stopped`; applied the next time the
+    /// container is started.
+    #[serde(default)]
+    pub restart_policy: Option<String>,
+    /// Container to start before this one; an empty string removes the dependency.
+    #[serde(default)]
+    pub depends_on: Option<String>,
+    /// Managers only; thresholds without a CPU or memory percent turn alerts off.
+    #[serde(default)]
+    pub resource_alerts: Option<raptor_common::resources::ResourceAlerts>,
+    /// Managers only.
+    #[serde(default)]
+    pub disable_swap: Option<bool>,
+    /// Managers only; replaces the tmpfs mounts, applied the next time the container is started.
+    #[serde(default)]
+    pub tmpfs: Option<Vec<raptor_common::mounts::TmpfsMount>>,
+    /// Managers only; applied the next time the container is started.
+    #[serde(default)]
+    pub read_only: Option<bool>,
 }
 
 pub async fn update_container(
@@ -504,13 +1360,15 @@ pub async fn update_container(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let is_owner = container.user_id == claims.sub;
-    let is_manager = claims.has_permission("containers.manage") || claims.is_manager();
+    let is_owner = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?;
+    let is_manager = claims.has_permission(permissions::CONTAINERS_MANAGE) || claims.is_manager();
 
     if !is_owner && !is_manager {
         return Err(AppError::Unauthorized);
     }
 
+    ensure_not_trashed(&container)?;
+
     let changing_resources = req.memory_limit.is_some()
         || req.server_memory.is_some()
         || req.cpu_limit.is_some()
@@ -518,10 +1376,99 @@ pub async fn update_container(
         || req.swap_limit.is_some()
         || req.io_weight.is_some();
 
-    if changing_resources && !is_manager && !claims.has_permission("containers.edit_resources") {
+    if changing_resources && !is_manager && !claims.has_permission(permissions::CONTAINERS_EDIT_RESOURCES) {
         return Err(AppError::Forbidden("You don't have permission to change resource limits".into()));
     }
 
+    let cpu_limit = req.cpu_limit.map(validate_cpu_limit).transpose()?;
+
+    if changing_resources {
+        validate_resource_limits(req.memory_limit, req.disk_limit, req.swap_limit)?;
+        enforce_role_limits(&state, container.user_id, req.memory_limit, cpu_limit.map(|(cpu, _)| cpu), req.disk_limit, false).await?;
+    }
+
+    // Without a new server memory the daemon keeps the old one in step with the memory limit
+    if let Some(server_memory) = req.server_memory {
+        let memory_limit = req.memory_limit.or(container.memory_limit).unwrap_or(UNLIMITED);
+        raptor_common::resources::validate_server_memory(server_memory, memory_limit).map_err(AppError::BadRequest)?;
+    }
+
+    let timezone = match req.timezone {
+        Some(ref tz) => validate_timezone(tz)?,
+        None => container.timezone.clone(),
+    };
+
+    if req.cpuset_cpus.is_some() && !is_manager {
+        return Err(AppError::Forbidden("Only managers can pin containers to CPUs".into()));
+    }
+
+    let cpuset_cpus = match req.cpuset_cpus.as_deref() {
+        Some("") => None,
+        Some(cpuset) => Some(validate_cpuset(cpuset)?),
+        None => container.cpuset_cpus.clone(),
+    };
+
+    let restart_policy = match req.restart_policy.as_deref() {
+        Some(policy) => raptor_common::restart_policy::normalize(policy)
+            .ok_or_else(|| AppError::BadRequest(format!(
+                "Restart policy must be one of: {}", raptor_common::restart_policy::RESTART_POLICIES.join(", ")
+            )))?
+            .to_string(),
+        None => container.restart_policy.clone(),
+    };
+
+    let depends_on = match req.depends_on.as_deref() {
+        Some("") => None,
+        Some(dependency) => {
+            let dependency = Uuid::parse_str(dependency)
+                .map_err(|_| AppError::BadRequest("dependsOn must be a container id".into()))?;
+            validate_dependency(&state, &container, dependency).await?;
+            Some(dependency)
+        }
+        None => container.depends_on,
+    };
+
+    if req.resource_alerts.is_some() && !is_manager {
+        return Err(AppError::Forbidden("Only managers can configure resource alerts".into()));
+    }
+
+    let resource_alerts = match &req.resource_alerts {
+        Some(alerts) => {
+            alerts.validate().map_err(AppError::BadRequest)?;
+            (!alerts.is_empty()).then(|| serde_json::json!(alerts))
+        }
+        None => container.resource_alerts.clone(),
+    };
+
+    if (req.disable_swap.is_some() || req.tmpfs.is_some()) && !is_manager {
+        return Err(AppError::Forbidden("Only managers can configure tmpfs mounts or disable swap".into()));
+    }
+
+    let tmpfs = match &req.tmpfs {
+        Some(mounts) => {
+            raptor_common::mounts::validate_tmpfs_mounts(mounts).map_err(AppError::BadRequest)?;
+            serde_json::json!(mounts)
+        }
+        None => container.tmpfs.clone(),
+    };
+
+    if req.read_only.is_some() && !is_manager {
+        return Err(AppError::Forbidden("Only managers can change read-only mode".into()));
+    }
+    let read_only = req.read_only.unwrap_or(container.read_only);
+
+    // Limits are applied live, but memory also sizes the server in its startup command;
+    // everything else waits for the container to be recreated
+    let needs_restart = req.server_memory.is_some()
+        || req.memory_limit.is_some_and(|memory| container.memory_limit != Some(memory))
+        || req.allocation_id.is_some()
+        || req.startup_script.as_ref().is_some_and(|s| container.startup_script.as_ref() != Some(s))
+        || timezone != container.timezone
+        || req.isolated.is_some_and(|isolated| isolated != container.isolated)
+        || restart_policy != container.restart_policy
+        || tmpfs != container.tmpfs
+        || read_only != container.read_only;
+
     let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
@@ -536,7 +1483,7 @@ pub async fn update_container(
     if let Some(server_memory) = req.server_memory {
         daemon_payload["serverMemory"] = serde_json::json!(server_memory);
     }
-    if let Some(cpu) = req.cpu_limit {
+    if let Some((cpu, _)) = cpu_limit {
         daemon_payload["cpuLimit"] = serde_json::json!(cpu);
     }
     if let Some(disk) = req.disk_limit {
@@ -551,6 +1498,30 @@ pub async fn update_container(
     if let Some(ref startup) = req.startup_script {
         daemon_payload["startupScript"] = serde_json::json!(startup);
     }
+    if req.timezone.is_some() {
+        daemon_payload["environment"] = serde_json::json!({ "TZ": timezone });
+    }
+    if req.cpuset_cpus.is_some() {
+        daemon_payload["cpusetCpus"] = serde_json::json!(cpuset_cpus.clone().unwrap_or_default());
+    }
+    if let Some(isolated) = req.isolated {
+        daemon_payload["isolated"] = serde_json::json!(isolated);
+    }
+    if req.restart_policy.is_some() {
+        daemon_payload["restartPolicy"] = serde_json::json!(restart_policy);
+    }
+    if let Some(ref alerts) = req.resource_alerts {
+        daemon_payload["resourceAlerts"] = serde_json::json!(alerts);
+    }
+    if let Some(disable_swap) = req.disable_swap {
+        daemon_payload["disableSwap"] = serde_json::json!(disable_swap);
+    }
+    if req.tmpfs.is_some() {
+        daemon_payload["tmpfs"] = tmpfs.clone();
+    }
+    if req.read_only.is_some() {
+        daemon_payload["readOnly"] = serde_json::json!(read_only);
+    }
 
     let client = daemon_client();
     let daemon_url = format!("{}/containers/{}", daemon.base_url(), container.id);
@@ -569,7 +1540,7 @@ pub async fn update_container(
     }
 
     let memory_limit = req.memory_limit.or(container.memory_limit);
-    let cpu_limit = req.cpu_limit.map(|c| rust_decimal::Decimal::try_from(c).ok()).flatten().or(container.cpu_limit);
+    let cpu_limit = cpu_limit.map(|(_, stored)| stored).or(container.cpu_limit);
     let disk_limit = req.disk_limit.or(container.disk_limit);
     let swap_limit = req.swap_limit.or(container.swap_limit);
     let io_weight = req.io_weight.or(container.io_weight);
@@ -583,8 +1554,18 @@ pub async fn update_container(
             swap_limit = $4,
             io_weight = $5,
             startup_script = $6,
+            timezone = $7,
+            cpuset_cpus = $8,
+            isolated = $9,
+            restart_policy = $10,
+            depends_on = $11,
+            resource_alerts = $12,
+            tmpfs = $13,
+            disable_swap = $14,
+            read_only = $15,
+            pending_changes = pending_changes OR ($16 AND status IN ('running', 'starting', 'restarting')),
             updated_at = NOW()
-        WHERE id = $7
+        WHERE id = $17
         RETURNING *"#
     )
     .bind(memory_limit)
@@ -593,17 +1574,22 @@ pub async fn update_container(
     .bind(swap_limit)
     .bind(io_weight)
     .bind(&startup_script)
+    .bind(&timezone)
+    .bind(&cpuset_cpus)
+    .bind(req.isolated.unwrap_or(container.isolated))
+    .bind(&restart_policy)
+    .bind(depends_on)
+    .bind(&resource_alerts)
+    .bind(&tmpfs)
+    .bind(req.disable_swap.unwrap_or(container.disable_swap))
+    .bind(read_only)
+    .bind(needs_restart)
     .bind(id)
     .fetch_one(&state.db)
     .await?;
 
     if let Some(allocation_id) = req.allocation_id {
 
-        sqlx::query("DELETE FROM container_allocations WHERE container_id = $1 AND is_primary = TRUE")
-            .bind(id)
-            .execute(&state.db)
-            .await?;
-
         let allocation: crate::models::Allocation = sqlx::query_as(
             "SELECT * FROM allocations WHERE id = $1"
         )
@@ -612,22 +1598,98 @@ pub async fn update_container(
             .await?
             .ok_or(AppError::BadRequest("Allocation not found".into()))?;
 
+        let mut tx = state.db.begin().await?;
+
+        sqlx::query("DELETE FROM container_allocations WHERE container_id = $1 AND is_primary = TRUE")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
         sqlx::query(
             r#"INSERT INTO container_allocations (id, container_id, allocation_id, ip, port, internal_port, protocol, is_primary, created_at)
-               VALUES ($1, $2, $3, $4, $5, $5, 'tcp', TRUE, NOW())"#
+               VALUES ($1, $2, $3, $4, $5, $5, $6, TRUE, NOW())"#
         )
             .bind(Uuid::new_v4())
             .bind(id)
             .bind(allocation_id)
             .bind(&allocation.ip)
             .bind(allocation.port)
-            .execute(&state.db)
-            .await?;
+            .bind(&allocation.protocol)
+            .execute(&mut *tx)
+            .await
+            .map_err(allocation_conflict)?;
+
+        tx.commit().await?;
     }
 
+    record_activity(&state.db, id, claims.sub, "settings_update", serde_json::json!({})).await;
+
     Ok(Json(updated_container))
 }
 
+/// A dependency belongs to the same owner and is one level deep, which also rules out cycles:
+/// it may not depend on anything, and nothing may depend on the container being linked.
+async fn validate_dependency(state: &AppState, container: &Container, dependency_id: Uuid) -> AppResult<()> {
+    if dependency_id == container.id {
+        return Err(AppError::BadRequest("A container cannot depend on itself".into()));
+    }
+
+    let dependency: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1 AND deleted_at IS NULL")
+        .bind(dependency_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::BadRequest("Dependency not found".into()))?;
+
+    if dependency.user_id != container.user_id {
+        return Err(AppError::BadRequest("A dependency must belong to the same owner".into()));
+    }
+
+    if dependency.depends_on.is_some() {
+        return Err(AppError::BadRequest(format!("\"{}\" depends on another container itself", dependency.name)));
+    }
+
+    let has_dependents: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM containers WHERE depends_on = $1)")
+        .bind(container.id)
+        .fetch_one(&state.db)
+        .await?;
+
+    if has_dependents {
+        return Err(AppError::BadRequest("Other containers depend on this one, so it cannot depend on another".into()));
+    }
+
+    Ok(())
+}
+
+/// A new `container_allocations` row for `allocation`. Allocations have no separate internal
+/// port, so the server listens on the public one.
+fn assigned_allocation(container_id: Uuid, allocation: &crate::models::Allocation, is_primary: bool) -> crate::models::ContainerAllocation {
+    crate::models::ContainerAllocation {
+        id: Uuid::new_v4(),
+        container_id,
+        allocation_id: Some(allocation.id),
+        ip: allocation.ip.clone(),
+        port: allocation.port,
+        internal_port: allocation.port,
+        protocol: allocation.protocol.clone(),
+        is_primary: Some(is_primary),
+        created_at: Utc::now(),
+    }
+}
+
+pub(crate) fn daemon_allocations_json(allocations: &[crate::models::ContainerAllocation]) -> Vec<serde_json::Value> {
+    allocations.iter().map(|a| {
+        serde_json::json!({
+            "id": a.id.to_string(),
+            "allocationId": a.allocation_id.map(|id| id.to_string()),
+            "ip": a.ip,
+            "port": a.port,
+            "internalPort": a.internal_port,
+            "protocol": a.protocol,
+            "isPrimary": a.is_primary.unwrap_or(false)
+        })
+    }).collect()
+}
+
 async fn proxy_container_action(
     state: &AppState,
     claims: &Claims,
@@ -640,49 +1702,89 @@ async fn proxy_container_action(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let can_manage = container.user_id == claims.sub
-        || claims.has_permission("containers.manage")
-        || claims.has_permission("containers.manage_own") && container.user_id == claims.sub
+    let can_manage = owns(claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
         || claims.is_manager();
 
-    if !can_manage {
+    if !can_manage && !sub_user_can(state, claims, &container, permissions::SUB_POWER).await? {
         return Err(AppError::Unauthorized);
     }
 
+    ensure_not_trashed(&container)?;
+
     let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
+    // The final status comes from the daemon (its state callback or the status read below);
+    // until then the container sits in a transitional status the reconciler also watches.
+    let transitional = match action {
+        "start" => "starting",
+        "restart" => "restarting",
+        _ => "stopping",
+    };
+    sqlx::query("UPDATE containers SET status = $1, updated_at = NOW() WHERE id = $2")
+        .bind(transitional)
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+
     let client = daemon_client();
     let daemon_url = format!(
         "{}/containers/{}/{}",
         daemon.base_url(), container.id, action
     );
 
-    let res = client
+    let res = match client
         .post(&daemon_url)
         .header("X-API-Key", &daemon.api_key)
         .send()
         .await
-        .map_err(|e| AppError::Daemon(e.to_string()))?;
-
-    let status = match action {
-        "start" | "restart" => "running",
-        "stop" | "kill" => "stopped",
-        _ => "unknown"
+    {
+        Ok(res) if res.status().is_success() => res,
+        Ok(res) => {
+            resolve_transitional_status(state, id, transitional, &container.status).await;
+            let error_text = res.text().await.unwrap_or_default();
+            return Err(AppError::Daemon(format!("Failed to {} container: {}", action, error_text)));
+        }
+        Err(e) => {
+            resolve_transitional_status(state, id, transitional, &container.status).await;
+            return Err(AppError::Daemon(e.to_string()));
+        }
     };
-    sqlx::query("UPDATE containers SET status = $1, updated_at = NOW() WHERE id = $2")
-        .bind(status)
-        .bind(id)
-        .execute(&state.db)
-        .await?;
+
+    if action == "start" {
+        clear_pending_changes(state, id).await?;
+    }
+
+    if let Some(actual) = crate::reconciler::fetch_daemon_status(&daemon, container.id).await {
+        if !actual.status.eq_ignore_ascii_case("restarting") {
+            let resolved = actual.resolved_status();
+            resolve_transitional_status(state, id, transitional, resolved).await;
+        }
+    }
+
+    record_activity(&state.db, id, claims.sub, action, serde_json::json!({})).await;
 
     let body: serde_json::Value = res.json().await.unwrap_or(serde_json::json!({"success": true}));
     Ok(Json(body))
 }
 
+/// Moves a container out of `from` unless something (e.g. a daemon callback) already did.
+async fn resolve_transitional_status(state: &AppState, id: Uuid, from: &str, to: &str) {
+    if let Err(e) = sqlx::query("UPDATE containers SET status = $1, updated_at = NOW() WHERE id = $2 AND status = $3")
+        .bind(to)
+        .bind(id)
+        .bind(from)
+        .execute(&state.db)
+        .await
+    {
+        tracing::error!("Failed to update status of container {}: {}", id, e);
+    }
+}
+
 pub async fn start_container(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
@@ -694,51 +1796,132 @@ pub async fn start_container(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let can_manage = container.user_id == claims.sub
-        || claims.has_permission("containers.manage")
-        || claims.has_permission("containers.manage_own") && container.user_id == claims.sub
+    let can_manage = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
         || claims.is_manager();
 
-    if !can_manage {
+    if !can_manage && !sub_user_can(&state, &claims, &container, permissions::SUB_POWER).await? {
         return Err(AppError::Unauthorized);
     }
 
+    ensure_not_trashed(&container)?;
+
     let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let allocations: Vec<crate::models::ContainerAllocation> = sqlx::query_as(
-        r#"SELECT ca.id, ca.container_id, ca.allocation_id, ca.ip, ca.port, ca.internal_port, ca.protocol, COALESCE(ca.is_primary, FALSE) as is_primary, ca.created_at
-           FROM container_allocations ca
-           WHERE ca.container_id = $1
-           ORDER BY ca.is_primary DESC, ca.ip, ca.port"#
-    )
+    let dependency_started = start_dependency(&state, &container).await?;
+
+    let started = start_on_daemon(&state, &container, &daemon).await?;
+
+    sqlx::query("UPDATE containers SET status = 'running', updated_at = NOW() WHERE id = $1")
         .bind(id)
-        .fetch_all(&state.db)
+        .execute(&state.db)
         .await?;
 
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(60))
-        .danger_accept_invalid_certs(true)
-        .build()
-        .map_err(|e| AppError::Internal(e.to_string()))?;
+    record_activity(&state.db, id, claims.sub, "start", serde_json::json!({})).await;
 
-    if !allocations.is_empty() {
-        let allocations_json: Vec<serde_json::Value> = allocations.iter().map(|a| {
-            serde_json::json!({
-                "id": a.id.to_string(),
-                "allocationId": a.allocation_id.map(|id| id.to_string()),
-                "ip": a.ip,
-                "port": a.port,
-                "internalPort": a.internal_port,
-                "protocol": a.protocol,
-                "isPrimary": a.is_primary
-            })
-        }).collect();
+    let body: serde_json::Value = serde_json::json!({
+        "success": true,
+        "allocations": started["allocations"],
+        "dependencyStarted": dependency_started,
+    });
+    Ok(Json(body))
+}
 
-        let update_url = format!("{}/containers/{}", daemon.base_url(), container.id);
+/// Starts the container's dependency unless the daemon reports it running already, since a
+/// start recreates the container. Returns the id of the container it started.
+async fn start_dependency(state: &AppState, container: &Container) -> AppResult<Option<Uuid>> {
+    let Some(dependency_id) = container.depends_on else {
+        return Ok(None);
+    };
+
+    let dependency: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(dependency_id)
+        .fetch_one(&state.db)
+        .await?;
+
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(dependency.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let running = match crate::reconciler::fetch_daemon_status(&daemon, dependency.id).await {
+        Some(actual) => actual.running,
+        None => dependency.status == "running",
+    };
+    if running {
+        return Ok(None);
+    }
+
+    tracing::info!("Starting {} before its dependent {}", dependency.id, container.id);
+    start_on_daemon(state, &dependency, &daemon)
+        .await
+        .map_err(|e| AppError::Daemon(format!("Failed to start dependency \"{}\": {}", dependency.name, e)))?;
+
+    sqlx::query("UPDATE containers SET status = 'running', updated_at = NOW() WHERE id = $1")
+        .bind(dependency.id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Some(dependency.id))
+}
+
+/// Flags settings saved while the container runs, which it only picks up once it is recreated.
+pub async fn mark_pending_changes(state: &AppState, id: Uuid) -> AppResult<()> {
+    sqlx::query("UPDATE containers SET pending_changes = TRUE WHERE id = $1 AND status IN ('running', 'starting', 'restarting')")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+/// The daemon recreates the container from the saved settings when it starts it.
+async fn clear_pending_changes(state: &AppState, id: Uuid) -> AppResult<()> {
+    sqlx::query("UPDATE containers SET pending_changes = FALSE WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+/// Containers in the trash can only be restored or purged; everything else that would change
+/// them, or wake them up on the daemon, is refused until they're restored.
+pub fn ensure_not_trashed(container: &Container) -> AppResult<()> {
+    if container.deleted_at.is_some() {
+        return Err(AppError::BadRequest("Container is in the trash; restore it first".into()));
+    }
+    Ok(())
+}
+
+/// Pushes the current allocations to the daemon and starts the container there. Refuses to
+/// start when the daemon doesn't take them, since the server would come up without its ports.
+/// Returns the daemon's response, which has `needsInstall` set when the install script hasn't
+/// run yet, with the allocations the daemon confirmed under `allocations`.
+async fn start_on_daemon(state: &AppState, container: &Container, daemon: &Daemon) -> AppResult<serde_json::Value> {
+    ensure_not_trashed(container)?;
+
+    let allocations: Vec<crate::models::ContainerAllocation> = sqlx::query_as(
+        r#"SELECT ca.id, ca.container_id, ca.allocation_id, ca.ip, ca.port, ca.internal_port, ca.protocol, COALESCE(ca.is_primary, FALSE) as is_primary, ca.created_at
+           FROM container_allocations ca
+           WHERE ca.container_id = $1
+           ORDER BY ca.is_primary DESC, ca.ip, ca.port"#
+    )
+        .bind(container.id)
+        .fetch_all(&state.db)
+        .await?;
+
+    let client = DaemonClient::with_timeout(std::time::Duration::from_secs(60));
+
+    let mut synced = serde_json::json!([]);
+
+    if !allocations.is_empty() {
+        let allocations_json = daemon_allocations_json(&allocations);
+
+        let update_url = format!("{}/containers/{}", daemon.base_url(), container.id);
         let update_res = client
             .patch(&update_url)
             .header("X-API-Key", &daemon.api_key)
@@ -746,10 +1929,24 @@ pub async fn start_container(
                 "allocations": allocations_json
             }))
             .send()
-            .await;
+            .await
+            .map_err(|e| AppError::Daemon(format!("Failed to sync allocations to daemon: {}", e)))?;
+
+        if !update_res.status().is_success() {
+            let error_text = update_res.text().await.unwrap_or_default();
+            return Err(AppError::Daemon(format!("Failed to sync allocations to daemon: {}", error_text)));
+        }
+
+        let managed: serde_json::Value = update_res.json().await
+            .map_err(|e| AppError::Daemon(format!("Failed to sync allocations to daemon: {}", e)))?;
+        synced = managed["allocations"].clone();
 
-        if let Err(e) = update_res {
-            tracing::warn!("Failed to sync allocations to daemon: {}", e);
+        let binding = |a: &serde_json::Value| (a["ip"].clone(), a["port"].clone(), a["protocol"].clone());
+        let confirmed: Vec<_> = synced.as_array().map(|a| a.iter().map(binding).collect()).unwrap_or_default();
+        if let Some(missing) = allocations_json.iter().find(|a| !confirmed.contains(&binding(a))) {
+            return Err(AppError::Daemon(format!(
+                "Daemon did not accept allocation {}:{}", missing["ip"].as_str().unwrap_or_default(), missing["port"]
+            )));
         }
     }
 
@@ -766,13 +1963,13 @@ pub async fn start_container(
         return Err(AppError::Daemon(format!("Failed to start container: {}", error_text)));
     }
 
-    sqlx::query("UPDATE containers SET status = 'running', updated_at = NOW() WHERE id = $1")
-        .bind(id)
-        .execute(&state.db)
-        .await?;
+    clear_pending_changes(state, container.id).await?;
 
-    let body: serde_json::Value = serde_json::json!({ "success": true });
-    Ok(Json(body))
+    let mut started: serde_json::Value = start_res.json().await.unwrap_or_else(|_| serde_json::json!({ "success": true }));
+    if let Some(body) = started.as_object_mut() {
+        body.insert("allocations".to_string(), synced);
+    }
+    Ok(started)
 }
 
 pub async fn stop_container(
@@ -786,71 +1983,110 @@ pub async fn stop_container(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let can_manage = container.user_id == claims.sub
-        || claims.has_permission("containers.manage")
-        || claims.has_permission("containers.manage_own") && container.user_id == claims.sub
+    let can_manage = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
         || claims.is_manager();
 
-    if !can_manage {
+    if !can_manage && !sub_user_can(&state, &claims, &container, permissions::SUB_POWER).await? {
         return Err(AppError::Unauthorized);
     }
 
+    ensure_not_trashed(&container)?;
+
     let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let client = daemon_client();
+    let dependents_stopped = stop_dependents(&state, &container).await?;
 
-    let stop_command = container.stop_command.unwrap_or_else(|| "stop".to_string());
+    let stage = stop_on_daemon(&daemon, &container, STOP_TIMEOUT_SECS).await?;
 
-    let graceful_url = format!("{}/containers/{}/graceful-stop", daemon.base_url(), container.id);
-    let graceful_res = client
-        .post(&graceful_url)
-        .header("X-API-Key", &daemon.api_key)
-        .json(&serde_json::json!({
-            "stopCommand": stop_command,
-            "timeoutSecs": 30
-        }))
-        .send()
-        .await;
+    sqlx::query("UPDATE containers SET status = 'stopped', updated_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
 
-    match graceful_res {
-        Ok(res) if res.status().is_success() => {
+    record_activity(&state.db, id, claims.sub, "stop", serde_json::json!({ "stage": stage })).await;
 
-            sqlx::query("UPDATE containers SET status = 'stopped', updated_at = NOW() WHERE id = $1")
-                .bind(id)
-                .execute(&state.db)
-                .await?;
+    Ok(Json(serde_json::json!({ "success": true, "stage": stage, "dependentsStopped": dependents_stopped })))
+}
 
-            Ok(Json(serde_json::json!({ "success": true, "method": "graceful" })))
-        }
-        _ => {
+/// Stops the containers depending on this one first, so they never run without it.
+/// Returns the ids of the ones it stopped.
+async fn stop_dependents(state: &AppState, container: &Container) -> AppResult<Vec<Uuid>> {
+    let dependents: Vec<Container> = sqlx::query_as(
+        "SELECT * FROM containers WHERE depends_on = $1 AND deleted_at IS NULL AND status NOT IN ('stopped', 'crash_looping')"
+    )
+        .bind(container.id)
+        .fetch_all(&state.db)
+        .await?;
 
-            tracing::warn!("Graceful stop failed for container {}, using Docker stop", id);
+    let mut stopped = Vec::new();
 
-            let docker_stop_url = format!("{}/containers/{}/stop", daemon.base_url(), container.id);
-            let docker_res = client
-                .post(&docker_stop_url)
-                .header("X-API-Key", &daemon.api_key)
-                .send()
-                .await
-                .map_err(|e| AppError::Daemon(e.to_string()))?;
+    for dependent in dependents {
+        let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+            .bind(dependent.daemon_id)
+            .fetch_optional(&state.db)
+            .await?
+            .ok_or(AppError::NotFound)?;
 
-            if !docker_res.status().is_success() {
-                let error_text = docker_res.text().await.unwrap_or_default();
-                return Err(AppError::Daemon(format!("Failed to stop container: {}", error_text)));
-            }
+        tracing::info!("Stopping {} before its dependency {}", dependent.id, container.id);
+        stop_on_daemon(&daemon, &dependent, STOP_TIMEOUT_SECS)
+            .await
+            .map_err(|e| AppError::Daemon(format!("Failed to stop dependent \"{}\": {}", dependent.name, e)))?;
 
-            sqlx::query("UPDATE containers SET status = 'stopped', updated_at = NOW() WHERE id = $1")
-                .bind(id)
-                .execute(&state.db)
-                .await?;
+        sqlx::query("UPDATE containers SET status = 'stopped', updated_at = NOW() WHERE id = $1")
+            .bind(dependent.id)
+            .execute(&state.db)
+            .await?;
 
-            Ok(Json(serde_json::json!({ "success": true, "method": "force" })))
-        }
+        stopped.push(dependent.id);
+    }
+
+    Ok(stopped)
+}
+
+/// Seconds the daemon gives the stop command, and then SIGTERM, before escalating.
+const STOP_TIMEOUT_SECS: u64 = 30;
+const STOP_TERM_TIMEOUT_SECS: u64 = 10;
+/// Longest stop command wait a caller can ask for with graceful-stop.
+const MAX_STOP_TIMEOUT_SECS: u64 = 3600;
+
+/// Stops the container with its stop command, escalating to SIGTERM and SIGKILL on the daemon.
+/// Returns the stage it exited at: "already_stopped", "command", "sigterm" or "sigkill".
+async fn stop_on_daemon(daemon: &Daemon, container: &Container, timeout_secs: u64) -> AppResult<String> {
+    let stop_command = container.stop_command.clone().unwrap_or_else(|| "stop".to_string());
+
+    // Both waits, plus time for the SIGKILL to land
+    let request_timeout = std::time::Duration::from_secs(timeout_secs + STOP_TERM_TIMEOUT_SECS + 30);
+
+    let url = format!("{}/containers/{}/graceful-stop", daemon.base_url(), container.id);
+    let res = DaemonClient::with_timeout(request_timeout)
+        .post(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .json(&serde_json::json!({
+            "stopCommand": stop_command,
+            "timeoutSecs": timeout_secs,
+            "termTimeoutSecs": STOP_TERM_TIMEOUT_SECS
+        }))
+        .send()
+        .await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    if !res.status().is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to stop container: {}", error_text)));
+    }
+
+    let body: serde_json::Value = res.json().await.unwrap_or_default();
+    let stage = body.get("stage").and_then(|v| v.as_str()).unwrap_or("unknown").to_string();
+    if stage == "sigkill" {
+        tracing::warn!("Container {} had to be killed to stop", container.id);
     }
+
+    Ok(stage)
 }
 
 pub async fn restart_container(
@@ -864,14 +2100,16 @@ pub async fn restart_container(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let can_manage = container.user_id == claims.sub
-        || claims.has_permission("containers.manage")
+    let can_manage = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
         || claims.is_manager();
 
-    if !can_manage {
+    if !can_manage && !sub_user_can(&state, &claims, &container, permissions::SUB_POWER).await? {
         return Err(AppError::Unauthorized);
     }
 
+    ensure_not_trashed(&container)?;
+
     let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
@@ -880,31 +2118,12 @@ pub async fn restart_container(
 
     let client = daemon_client();
 
-    let stop_command = container.stop_command.clone().unwrap_or_else(|| "stop".to_string());
-
-    let stop_url = format!("{}/containers/{}/graceful-stop", daemon.base_url(), container.id);
-    let stop_res = client
-        .post(&stop_url)
-        .header("X-API-Key", &daemon.api_key)
-        .json(&serde_json::json!({
-            "stopCommand": stop_command,
-            "timeoutSecs": 15
-        }))
-        .send()
-        .await
-        .map_err(|e| AppError::Daemon(e.to_string()))?;
-
-    if !stop_res.status().is_success() {
-        tracing::warn!("Graceful stop failed, trying force stop");
+    sqlx::query("UPDATE containers SET status = 'restarting', updated_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
 
-        let force_stop_url = format!("{}/containers/{}/stop", daemon.base_url(), container.id);
-        client
-            .post(&force_stop_url)
-            .header("X-API-Key", &daemon.api_key)
-            .send()
-            .await
-            .map_err(|e| AppError::Daemon(e.to_string()))?;
-    }
+    stop_on_daemon(&daemon, &container, 15).await?;
 
     tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
@@ -921,33 +2140,131 @@ pub async fn restart_container(
         return Err(AppError::Daemon(format!("Failed to start after stop: {}", error_text)));
     }
 
-    sqlx::query("UPDATE containers SET status = 'running', updated_at = NOW() WHERE id = $1")
+    sqlx::query("UPDATE containers SET status = 'running', pending_changes = FALSE, updated_at = NOW() WHERE id = $1")
         .bind(id)
         .execute(&state.db)
         .await?;
 
+    record_activity(&state.db, id, claims.sub, "restart", serde_json::json!({})).await;
+
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
-pub async fn kill_container(
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecreateContainerRequest {
+    /// Archive the current volume on the daemon and start from an empty one.
+    #[serde(default)]
+    pub fresh: bool,
+}
+
+/// Rebuilds the container on its daemon and records the resulting status.
+async fn recreate_on_daemon(
+    state: &AppState,
+    container: &Container,
+    daemon: &Daemon,
+    fresh: bool,
+    install_script: Option<String>,
+) -> AppResult<serde_json::Value> {
+    sqlx::query("UPDATE containers SET status = 'restarting', updated_at = NOW() WHERE id = $1")
+        .bind(container.id)
+        .execute(&state.db)
+        .await?;
+
+    let url = format!("{}/containers/{}/recreate", daemon.base_url(), container.id);
+    let res = DaemonClient::with_timeout(std::time::Duration::from_secs(60))
+        .post(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .json(&serde_json::json!({
+            "fresh": fresh,
+            "installScript": install_script
+        }))
+        .send()
+        .await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    if !res.status().is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to recreate container: {}", error_text)));
+    }
+
+    let result: serde_json::Value = res.json().await.unwrap_or_default();
+    let needs_install = result.get("needsInstall").and_then(|v| v.as_bool()).unwrap_or(false);
+    // The daemon only starts the new container if the old one was running
+    let started = result.get("started").and_then(|v| v.as_bool()).unwrap_or(!needs_install);
+
+    let status = if started && !needs_install { "running" } else { "stopped" };
+    sqlx::query("UPDATE containers SET status = $1, pending_changes = FALSE, updated_at = NOW() WHERE id = $2")
+        .bind(status)
+        .bind(container.id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(result)
+}
+
+/// POST /containers/:id/recreate - rebuild the container, optionally on a fresh volume.
+/// Allocations and configuration are kept either way.
+pub async fn recreate_container(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
+    body: Option<Json<RecreateContainerRequest>>,
 ) -> AppResult<Json<serde_json::Value>> {
-    proxy_container_action(&state, &claims, id, "kill").await
-}
+    let req = body.map(|Json(r)| r).unwrap_or_default();
 
-#[derive(Debug, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SendCommandRequest {
-    pub command: String,
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let can_manage = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
+        || claims.is_manager();
+
+    if !can_manage {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&container)?;
+
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    // A fresh volume needs the flake's install script again to be usable
+    let install_script: Option<String> = match (req.fresh, container.flake_id) {
+        (true, Some(flake_id)) => sqlx::query_scalar("SELECT install_script FROM flakes WHERE id = $1")
+            .bind(flake_id)
+            .fetch_optional(&state.db)
+            .await?
+            .flatten(),
+        _ => None,
+    };
+
+    let result = recreate_on_daemon(&state, &container, &daemon, req.fresh, install_script).await?;
+    let needs_install = result.get("needsInstall").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    record_activity(&state.db, id, claims.sub, "recreate", serde_json::json!({ "fresh": req.fresh })).await;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "fresh": req.fresh,
+        "archivedVolume": result.get("archivedVolume").cloned().unwrap_or(serde_json::Value::Null),
+        "needsInstall": needs_install
+    })))
 }
 
-pub async fn send_command(
+const IMAGE_PULL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
+/// POST /containers/:id/pull - pull the newest build of the image tag and recreate onto it
+pub async fn pull_container_image(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    Json(req): Json<SendCommandRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
@@ -955,55 +2272,63 @@ pub async fn send_command(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let can_manage = container.user_id == claims.sub
-        || claims.has_permission("containers.manage")
+    let can_manage = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
         || claims.is_manager();
 
     if !can_manage {
         return Err(AppError::Unauthorized);
     }
 
+    ensure_not_trashed(&container)?;
+
     let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let client = daemon_client();
-    let url = format!("{}/containers/{}/command", daemon.base_url(), container.id);
-
-    let res = client
+    let url = format!("{}/containers/{}/pull", daemon.base_url(), container.id);
+    let res = DaemonClient::with_timeout(IMAGE_PULL_TIMEOUT)
         .post(&url)
         .header("X-API-Key", &daemon.api_key)
-        .json(&serde_json::json!({ "command": req.command }))
         .send()
         .await
         .map_err(|e| AppError::Daemon(e.to_string()))?;
 
     if !res.status().is_success() {
         let error_text = res.text().await.unwrap_or_default();
-        return Err(AppError::Daemon(format!("Failed to send command: {}", error_text)));
+        return Err(AppError::Daemon(format!("Failed to pull image: {}", error_text)));
     }
 
-    Ok(Json(serde_json::json!({ "success": true })))
-}
+    let pulled: serde_json::Value = res.json().await.unwrap_or_default();
+    let updated = pulled.get("updated").and_then(|v| v.as_bool()).unwrap_or(true);
 
-#[derive(Debug, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct GracefulStopRequest {
-    #[serde(default = "default_stop_timeout")]
-    pub timeout_secs: u64,
+    // Already on the newest build: nothing to restart for
+    if updated {
+        recreate_on_daemon(&state, &container, &daemon, false, None).await?;
+    }
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "updated": updated,
+        "previous": pulled.get("previous").cloned().unwrap_or(serde_json::Value::Null),
+        "imageVersion": pulled.get("latest").cloned().unwrap_or(serde_json::Value::Null)
+    })))
 }
 
-fn default_stop_timeout() -> u64 {
-    30
+#[derive(Debug, serde::Deserialize)]
+pub struct WipeContainerRequest {
+    /// Must repeat the container name, so a stray request cannot delete everything.
+    pub confirm: String,
 }
 
-pub async fn graceful_stop_container(
+/// POST /containers/:id/wipe - stop the container and delete all of its files
+pub async fn wipe_container(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    Json(req): Json<GracefulStopRequest>,
+    Json(req): Json<WipeContainerRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
@@ -1011,338 +2336,539 @@ pub async fn graceful_stop_container(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let can_manage = container.user_id == claims.sub
-        || claims.has_permission("containers.manage")
+    let can_manage = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
         || claims.is_manager();
 
     if !can_manage {
         return Err(AppError::Unauthorized);
     }
 
+    ensure_not_trashed(&container)?;
+
+    if req.confirm.trim() != container.name {
+        return Err(AppError::BadRequest("Type the container name to confirm wiping its files".into()));
+    }
+
     let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let client = daemon_client();
-    let url = format!("{}/containers/{}/graceful-stop", daemon.base_url(), container.id);
-
-    let res = client
+    // The daemon knows the container by its id and checks the confirmation again
+    let url = format!("{}/containers/{}/wipe", daemon.base_url(), container.id);
+    let res = DaemonClient::with_timeout(std::time::Duration::from_secs(10 * 60))
         .post(&url)
         .header("X-API-Key", &daemon.api_key)
-        .json(&serde_json::json!({
-            "timeoutSecs": req.timeout_secs
-        }))
+        .json(&serde_json::json!({ "confirm": container.id.to_string() }))
         .send()
         .await
         .map_err(|e| AppError::Daemon(e.to_string()))?;
 
     if !res.status().is_success() {
         let error_text = res.text().await.unwrap_or_default();
-        return Err(AppError::Daemon(format!("Failed to stop: {}", error_text)));
+        return Err(AppError::Daemon(format!("Failed to wipe container: {}", error_text)));
     }
 
+    let result: serde_json::Value = res.json().await.unwrap_or_default();
+
     sqlx::query("UPDATE containers SET status = 'stopped', updated_at = NOW() WHERE id = $1")
         .bind(id)
         .execute(&state.db)
         .await?;
 
-    Ok(Json(serde_json::json!({ "success": true })))
-}
+    tracing::info!("User {} wiped the files of container {}", claims.sub, container.id);
+    record_activity(&state.db, container.id, claims.sub, "wipe", serde_json::json!({})).await;
 
-#[derive(Debug, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct AssignAllocationRequest {
-    pub allocation_id: Uuid,
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "removed": result.get("removed").cloned().unwrap_or(serde_json::Value::Null)
+    })))
 }
 
-pub async fn assign_allocation(
+pub async fn kill_container(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    Json(req): Json<AssignAllocationRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
-    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or(AppError::NotFound)?;
+    proxy_container_action(&state, &claims, id, "kill").await
+}
 
-    if container.user_id != claims.sub && !claims.is_manager() {
-        return Err(AppError::Unauthorized);
-    }
+const BULK_ACTIONS: [&str; 4] = ["start", "stop", "restart", "kill"];
+const MAX_BULK_CONTAINERS: usize = 100;
+const BULK_ACTION_CONCURRENCY: usize = 8;
 
-    let allocation: crate::models::Allocation = sqlx::query_as(
-        "SELECT * FROM allocations WHERE id = $1 AND daemon_id = $2"
-    )
-        .bind(req.allocation_id)
-        .bind(container.daemon_id)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or(AppError::BadRequest("Allocation not found or belongs to different daemon".into()))?;
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkActionRequest {
+    pub container_ids: Vec<Uuid>,
+    pub action: String,
+}
 
-    let existing_other: Option<(Uuid,)> = sqlx::query_as(
-        "SELECT id FROM container_allocations WHERE allocation_id = $1 AND container_id != $2"
-    )
-        .bind(req.allocation_id)
-        .bind(container.id)
-        .fetch_optional(&state.db)
-        .await?;
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkActionResult {
+    pub container_id: Uuid,
+    pub success: bool,
+    pub error: Option<String>,
+}
 
-    if existing_other.is_some() {
-        return Err(AppError::BadRequest("Allocation is already in use by another container".into()));
+/// POST /admin/containers/bulk-action - run one power action on many containers.
+/// Each container is handled independently; failures are reported per container.
+pub async fn bulk_container_action(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<BulkActionRequest>,
+) -> AppResult<Json<Vec<BulkActionResult>>> {
+    use futures_util::StreamExt;
+
+    let action = req.action.to_lowercase();
+    if !BULK_ACTIONS.contains(&action.as_str()) {
+        return Err(AppError::BadRequest(format!("Unsupported action '{}', expected one of: {}", req.action, BULK_ACTIONS.join(", "))));
     }
 
-    let existing_for_container: Option<(Uuid, bool)> = sqlx::query_as(
-        "SELECT id, COALESCE(is_primary, FALSE) FROM container_allocations WHERE allocation_id = $1 AND container_id = $2"
-    )
-        .bind(req.allocation_id)
-        .bind(container.id)
-        .fetch_optional(&state.db)
-        .await?;
+    if req.container_ids.is_empty() {
+        return Err(AppError::BadRequest("No containers given".into()));
+    }
 
-    if let Some((existing_id, is_already_primary)) = existing_for_container {
-        if is_already_primary {
+    if req.container_ids.len() > MAX_BULK_CONTAINERS {
+        return Err(AppError::BadRequest(format!("At most {} containers can be changed at once", MAX_BULK_CONTAINERS)));
+    }
 
-            return Ok(Json(serde_json::json!({
-                "message": "Allocation is already primary",
-                "allocationIp": allocation.ip,
-                "allocationPort": allocation.port
-            })));
-        }
+    let mut ids = req.container_ids;
+    let mut seen = std::collections::HashSet::new();
+    ids.retain(|id| seen.insert(*id));
+
+    let results: Vec<BulkActionResult> = futures_util::stream::iter(ids)
+        .map(|id| {
+            let state = &state;
+            let claims = &claims;
+            let action = action.as_str();
+            async move {
+                match proxy_container_action(state, claims, id, action).await {
+                    Ok(_) => BulkActionResult { container_id: id, success: true, error: None },
+                    Err(e) => BulkActionResult { container_id: id, success: false, error: Some(e.to_string()) },
+                }
+            }
+        })
+        .buffered(BULK_ACTION_CONCURRENCY)
+        .collect()
+        .await;
 
-        sqlx::query("UPDATE container_allocations SET is_primary = FALSE WHERE container_id = $1 AND is_primary = TRUE")
-            .bind(container.id)
-            .execute(&state.db)
-            .await?;
+    let failed = results.iter().filter(|r| !r.success).count();
+    tracing::info!(
+        "Bulk {} by {}: {} succeeded, {} failed",
+        action, claims.username, results.len() - failed, failed
+    );
 
-        sqlx::query("UPDATE container_allocations SET is_primary = TRUE WHERE id = $1")
-            .bind(existing_id)
-            .execute(&state.db)
-            .await?;
-    } else {
+    Ok(Json(results))
+}
 
-        sqlx::query("UPDATE container_allocations SET is_primary = FALSE WHERE container_id = $1 AND is_primary = TRUE")
-            .bind(container.id)
-            .execute(&state.db)
-            .await?;
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendCommandRequest {
+    pub command: String,
+    #[serde(default)]
+    pub capture_output: bool,
+}
 
-        sqlx::query(
-            r#"INSERT INTO container_allocations (id, container_id, allocation_id, ip, port, internal_port, protocol, is_primary, created_at)
-               VALUES ($1, $2, $3, $4, $5, $5, 'tcp', TRUE, NOW())"#
-        )
-            .bind(Uuid::new_v4())
-            .bind(container.id)
-            .bind(req.allocation_id)
-            .bind(&allocation.ip)
-            .bind(allocation.port)
-            .execute(&state.db)
-            .await?;
-    }
+const COMMAND_CAPTURE_MS: u64 = 1500;
 
-    Ok(Json(serde_json::json!({
-        "message": "Allocation assigned successfully",
-        "allocationIp": allocation.ip,
-        "allocationPort": allocation.port
-    })))
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerCommand {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub command: String,
+    pub source: String,
+    pub output: Option<String>,
+    pub created_at: chrono::DateTime<Utc>,
 }
 
-pub async fn get_available_allocations(
+#[derive(Debug, serde::Deserialize)]
+pub struct CommandHistoryQuery {
+    pub limit: Option<i64>,
+}
+
+pub async fn record_command(
+    db: &sqlx::PgPool,
+    container_id: Uuid,
+    user_id: Option<Uuid>,
+    command: &str,
+    source: &str,
+    output: Option<String>,
+) {
+    let result = sqlx::query(
+        "INSERT INTO container_commands (container_id, user_id, command, source, output) VALUES ($1, $2, $3, $4, $5)"
+    )
+    .bind(container_id)
+    .bind(user_id)
+    .bind(command)
+    .bind(source)
+    .bind(output)
+    .execute(db)
+    .await;
+
+    if let Err(e) = result {
+        tracing::warn!("Failed to record command for container {}: {}", container_id, e);
+    }
+}
+
+pub async fn send_command(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-) -> AppResult<Json<Vec<crate::models::Allocation>>> {
+    Json(req): Json<SendCommandRequest>,
+) -> AppResult<Json<serde_json::Value>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if container.user_id != claims.sub && !claims.is_manager() {
+    let can_manage = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
+        || claims.is_manager();
+
+    if !can_manage && !sub_user_can(&state, &claims, &container, permissions::SUB_CONSOLE).await? {
         return Err(AppError::Unauthorized);
     }
 
-    let allocations: Vec<crate::models::Allocation> = sqlx::query_as(
-        r#"SELECT a.* FROM allocations a
-           WHERE a.daemon_id = $1
-           AND NOT EXISTS (SELECT 1 FROM container_allocations ca WHERE ca.allocation_id = a.id)
-           ORDER BY a.ip, a.port"#
-    )
+    ensure_not_trashed(&container)?;
+
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
-        .fetch_all(&state.db)
-        .await?;
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
 
-    Ok(Json(allocations))
+    let client = daemon_client();
+    let url = format!("{}/containers/{}/command", daemon.base_url(), container.id);
+
+    let res = client
+        .post(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .json(&serde_json::json!({
+            "command": req.command,
+            "captureOutputMs": req.capture_output.then_some(COMMAND_CAPTURE_MS),
+        }))
+        .send()
+        .await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    if !res.status().is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to send command: {}", error_text)));
+    }
+
+    let body: serde_json::Value = res.json().await.unwrap_or_default();
+    let output: Option<Vec<String>> = body
+        .get("output")
+        .and_then(|o| serde_json::from_value(o.clone()).ok());
+
+    record_command(
+        &state.db,
+        container.id,
+        Some(claims.sub),
+        &req.command,
+        "api",
+        output.as_ref().map(|lines| lines.join("\n")),
+    )
+    .await;
+    record_activity(&state.db, container.id, claims.sub, "command", serde_json::json!({ "command": req.command })).await;
+
+    match output {
+        Some(lines) => Ok(Json(serde_json::json!({ "success": true, "output": lines }))),
+        None => Ok(Json(serde_json::json!({ "success": true }))),
+    }
 }
 
-pub async fn get_container_allocations(
+pub async fn get_container_commands(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-) -> AppResult<Json<Vec<AllocationInfo>>> {
+    Query(query): Query<CommandHistoryQuery>,
+) -> AppResult<Json<Vec<ContainerCommand>>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if container.user_id != claims.sub && !claims.has_permission("containers.view_all") && !claims.is_manager() {
+    if !can_access_container(&claims, &container) && !sub_user_can(&state, &claims, &container, permissions::SUB_CONSOLE).await? {
         return Err(AppError::Unauthorized);
     }
 
-    let allocations: Vec<AllocationInfo> = sqlx::query_as(
-        r#"SELECT ca.id, ca.allocation_id, ca.ip, ca.port, ca.internal_port, ca.protocol, COALESCE(ca.is_primary, FALSE) as is_primary
-           FROM container_allocations ca
-           WHERE ca.container_id = $1
-           ORDER BY ca.is_primary DESC, ca.ip, ca.port"#
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+
+    let commands: Vec<ContainerCommand> = sqlx::query_as(
+        r#"
+        SELECT cc.id, cc.user_id, u.username, cc.command, cc.source, cc.output, cc.created_at
+        FROM container_commands cc
+        LEFT JOIN users u ON u.id = cc.user_id
+        WHERE cc.container_id = $1
+        ORDER BY cc.created_at DESC
+        LIMIT $2
+        "#
     )
-        .bind(id)
-        .fetch_all(&state.db)
-        .await?;
+    .bind(container.id)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await?;
 
-    Ok(Json(allocations))
+    Ok(Json(commands))
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Serialize, sqlx::FromRow)]
 #[serde(rename_all = "camelCase")]
-pub struct AddAllocationRequest {
-    pub allocation_id: Uuid,
-    #[serde(default)]
-    pub is_primary: bool,
+pub struct ContainerActivity {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub action: String,
+    pub details: serde_json::Value,
+    pub created_at: chrono::DateTime<Utc>,
 }
 
-pub async fn add_allocation(
+/// Adds an entry to the container's activity feed. Failures are logged, never returned.
+pub async fn record_activity(
+    db: &sqlx::PgPool,
+    container_id: Uuid,
+    user_id: Uuid,
+    action: &str,
+    details: serde_json::Value,
+) {
+    let result = sqlx::query(
+        "INSERT INTO container_activity (container_id, user_id, action, details) VALUES ($1, $2, $3, $4)"
+    )
+    .bind(container_id)
+    .bind(user_id)
+    .bind(action)
+    .bind(details)
+    .execute(db)
+    .await;
+
+    if let Err(e) = result {
+        tracing::warn!("Failed to record {} activity for container {}: {}", action, container_id, e);
+    }
+}
+
+/// GET /containers/:id/activity - who did what, newest first.
+/// Owners and managers see every entry; sub-users only their own.
+pub async fn get_container_activity(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    Json(req): Json<AddAllocationRequest>,
-) -> AppResult<Json<serde_json::Value>> {
+    Query(params): Query<crate::handlers::users::PaginationParams>,
+) -> AppResult<Json<crate::handlers::users::PaginatedResponse<ContainerActivity>>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if container.user_id != claims.sub && !claims.is_manager() {
+    let only_user = if can_access_container(&claims, &container) {
+        None
+    } else if sub_user_permissions(&state, &claims, &container).await?.is_some() {
+        Some(claims.sub)
+    } else {
         return Err(AppError::Unauthorized);
-    }
+    };
 
-    let allocation: crate::models::Allocation = sqlx::query_as(
-        "SELECT * FROM allocations WHERE id = $1 AND daemon_id = $2"
-    )
-        .bind(req.allocation_id)
-        .bind(container.daemon_id)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or(AppError::BadRequest("Allocation not found or belongs to different daemon".into()))?;
+    let per_page = params.per_page.min(100).max(1);
+    let page = params.page.max(1);
+    let offset = (page - 1) * per_page;
 
-    let existing: Option<(Uuid,)> = sqlx::query_as(
-        "SELECT id FROM container_allocations WHERE allocation_id = $1"
+    let (total,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM container_activity WHERE container_id = $1 AND ($2::uuid IS NULL OR user_id = $2)"
     )
-        .bind(req.allocation_id)
-        .fetch_optional(&state.db)
+        .bind(container.id)
+        .bind(only_user)
+        .fetch_one(&state.db)
         .await?;
 
-    if existing.is_some() {
-        return Err(AppError::BadRequest("Allocation is already in use".into()));
-    }
+    let data: Vec<ContainerActivity> = sqlx::query_as(
+        r#"
+        SELECT ca.id, ca.user_id, u.username, ca.action, ca.details, ca.created_at
+        FROM container_activity ca
+        LEFT JOIN users u ON u.id = ca.user_id
+        WHERE ca.container_id = $1 AND ($2::uuid IS NULL OR ca.user_id = $2)
+        ORDER BY ca.created_at DESC
+        LIMIT $3 OFFSET $4
+        "#
+    )
+    .bind(container.id)
+    .bind(only_user)
+    .bind(per_page)
+    .bind(offset)
+    .fetch_all(&state.db)
+    .await?;
 
-    if req.is_primary {
-        sqlx::query("UPDATE container_allocations SET is_primary = FALSE WHERE container_id = $1 AND is_primary = TRUE")
-            .bind(container.id)
-            .execute(&state.db)
-            .await?;
-    }
+    let total_pages = (total as f64 / per_page as f64).ceil() as i64;
 
-    let has_allocations: Option<(i64,)> = sqlx::query_as(
-        "SELECT COUNT(*) FROM container_allocations WHERE container_id = $1"
-    )
-        .bind(container.id)
-        .fetch_one(&state.db)
-        .await
-        .ok();
+    Ok(Json(crate::handlers::users::PaginatedResponse {
+        data,
+        total,
+        page,
+        per_page,
+        total_pages,
+    }))
+}
 
-    let is_primary = req.is_primary || has_allocations.map(|c| c.0 == 0).unwrap_or(true);
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerAlert {
+    pub id: Uuid,
+    pub container_id: Uuid,
+    pub container_name: String,
+    pub metric: String,
+    pub percent: f64,
+    pub threshold: f64,
+    pub window_secs: i32,
+    pub created_at: chrono::DateTime<Utc>,
+}
 
-    sqlx::query(
-        r#"INSERT INTO container_allocations (id, container_id, allocation_id, ip, port, internal_port, protocol, is_primary, created_at)
-           VALUES ($1, $2, $3, $4, $5, $5, $6, $7, NOW())"#
+async fn list_alerts(
+    state: &AppState,
+    container_id: Option<Uuid>,
+    params: crate::handlers::users::PaginationParams,
+) -> AppResult<crate::handlers::users::PaginatedResponse<ContainerAlert>> {
+    let per_page = params.per_page.min(100).max(1);
+    let page = params.page.max(1);
+    let offset = (page - 1) * per_page;
+
+    let (total,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM container_alerts WHERE $1::uuid IS NULL OR container_id = $1"
     )
-        .bind(Uuid::new_v4())
-        .bind(container.id)
-        .bind(req.allocation_id)
-        .bind(&allocation.ip)
-        .bind(allocation.port)
-        .bind(&allocation.protocol)
-        .bind(is_primary)
-        .execute(&state.db)
+        .bind(container_id)
+        .fetch_one(&state.db)
         .await?;
 
-    Ok(Json(serde_json::json!({
-        "message": "Allocation added successfully",
-        "allocationIp": allocation.ip,
-        "allocationPort": allocation.port,
-        "isPrimary": is_primary
-    })))
+    let data: Vec<ContainerAlert> = sqlx::query_as(
+        r#"
+        SELECT a.id, a.container_id, c.name AS container_name, a.metric, a.percent, a.threshold, a.window_secs, a.created_at
+        FROM container_alerts a
+        JOIN containers c ON c.id = a.container_id
+        WHERE $1::uuid IS NULL OR a.container_id = $1
+        ORDER BY a.created_at DESC
+        LIMIT $2 OFFSET $3
+        "#
+    )
+    .bind(container_id)
+    .bind(per_page)
+    .bind(offset)
+    .fetch_all(&state.db)
+    .await?;
+
+    let total_pages = (total as f64 / per_page as f64).ceil() as i64;
+
+    Ok(crate::handlers::users::PaginatedResponse {
+        data,
+        total,
+        page,
+        per_page,
+        total_pages,
+    })
 }
 
-pub async fn remove_allocation(
+/// GET /containers/:id/alerts - sustained CPU/memory usage reported for the container, newest first
+pub async fn get_container_alerts(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
-    Path((id, allocation_id)): Path<(Uuid, Uuid)>,
-) -> AppResult<Json<serde_json::Value>> {
+    Path(id): Path<Uuid>,
+    Query(params): Query<crate::handlers::users::PaginationParams>,
+) -> AppResult<Json<crate::handlers::users::PaginatedResponse<ContainerAlert>>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if container.user_id != claims.sub && !claims.is_manager() {
+    if !can_access_container(&claims, &container) {
         return Err(AppError::Unauthorized);
     }
 
-    let container_allocation: (Uuid, bool) = sqlx::query_as(
-        "SELECT id, COALESCE(is_primary, FALSE) FROM container_allocations WHERE allocation_id = $1 AND container_id = $2"
-    )
-        .bind(allocation_id)
-        .bind(container.id)
+    Ok(Json(list_alerts(&state, Some(container.id), params).await?))
+}
+
+/// GET /admin/containers/alerts - recent resource alerts across every container
+pub async fn list_all_alerts(
+    State(state): State<AppState>,
+    Query(params): Query<crate::handlers::users::PaginationParams>,
+) -> AppResult<Json<crate::handlers::users::PaginatedResponse<ContainerAlert>>> {
+    Ok(Json(list_alerts(&state, None, params).await?))
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GracefulStopRequest {
+    #[serde(default = "default_stop_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_stop_timeout() -> u64 {
+    STOP_TIMEOUT_SECS
+}
+
+pub async fn graceful_stop_container(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<GracefulStopRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
         .fetch_optional(&state.db)
         .await?
-        .ok_or(AppError::BadRequest("Allocation not found or doesn't belong to this container".into()))?;
+        .ok_or(AppError::NotFound)?;
 
-    let was_primary = container_allocation.1;
+    let can_manage = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
+        || claims.is_manager();
 
-    sqlx::query("DELETE FROM container_allocations WHERE id = $1")
-        .bind(container_allocation.0)
+    if !can_manage && !sub_user_can(&state, &claims, &container, permissions::SUB_POWER).await? {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&container)?;
+
+    if req.timeout_secs > MAX_STOP_TIMEOUT_SECS {
+        return Err(AppError::BadRequest(format!("timeoutSecs must be at most {}", MAX_STOP_TIMEOUT_SECS)));
+    }
+
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let dependents_stopped = stop_dependents(&state, &container).await?;
+
+    let stage = stop_on_daemon(&daemon, &container, req.timeout_secs).await?;
+
+    sqlx::query("UPDATE containers SET status = 'stopped', updated_at = NOW() WHERE id = $1")
+        .bind(id)
         .execute(&state.db)
         .await?;
 
-    if was_primary {
-        sqlx::query(
-            r#"UPDATE container_allocations
-               SET is_primary = TRUE
-               WHERE id = (
-                   SELECT id FROM container_allocations
-                   WHERE container_id = $1
-                   ORDER BY created_at ASC
-                   LIMIT 1
-               )"#
-        )
-            .bind(container.id)
-            .execute(&state.db)
-            .await?;
-    }
+    record_activity(&state.db, id, claims.sub, "stop", serde_json::json!({ "timeoutSecs": req.timeout_secs, "stage": stage })).await;
 
-    Ok(Json(serde_json::json!({
-        "message": "Allocation removed successfully"
-    })))
+    Ok(Json(serde_json::json!({ "success": true, "stage": stage, "dependentsStopped": dependents_stopped })))
 }
 
-pub async fn set_primary_allocation(
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssignAllocationRequest {
+    pub allocation_id: Uuid,
+}
+
+pub async fn assign_allocation(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
-    Path((id, allocation_id)): Path<(Uuid, Uuid)>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<AssignAllocationRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
@@ -1350,52 +2876,159 @@ pub async fn set_primary_allocation(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if container.user_id != claims.sub && !claims.is_manager() {
+    if !owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)? && !claims.is_manager()
+        && !sub_user_can(&state, &claims, &container, permissions::SUB_ALLOCATIONS).await?
+    {
         return Err(AppError::Unauthorized);
     }
 
-    let container_allocation: Option<(Uuid, String, i32)> = sqlx::query_as(
-        "SELECT id, ip, port FROM container_allocations WHERE allocation_id = $1 AND container_id = $2"
+    ensure_not_trashed(&container)?;
+
+    let allocation: crate::models::Allocation = sqlx::query_as(
+        "SELECT * FROM allocations WHERE id = $1 AND daemon_id = $2"
     )
-        .bind(allocation_id)
+        .bind(req.allocation_id)
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::BadRequest("Allocation not found or belongs to different daemon".into()))?;
+
+    let existing_other: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT id FROM container_allocations WHERE allocation_id = $1 AND container_id != $2"
+    )
+        .bind(req.allocation_id)
         .bind(container.id)
         .fetch_optional(&state.db)
         .await?;
 
-    if container_allocation.is_none() {
-        return Err(AppError::BadRequest("Allocation not found or doesn't belong to this container".into()));
+    if existing_other.is_some() {
+        return Err(AppError::BadRequest("Allocation is already in use by another container".into()));
     }
 
-    let (ca_id, ip, port) = container_allocation.unwrap();
-
-    sqlx::query("UPDATE container_allocations SET is_primary = FALSE WHERE container_id = $1")
+    let existing_for_container: Option<(Uuid, bool)> = sqlx::query_as(
+        "SELECT id, COALESCE(is_primary, FALSE) FROM container_allocations WHERE allocation_id = $1 AND container_id = $2"
+    )
+        .bind(req.allocation_id)
         .bind(container.id)
-        .execute(&state.db)
+        .fetch_optional(&state.db)
         .await?;
 
-    sqlx::query("UPDATE container_allocations SET is_primary = TRUE WHERE id = $1")
-        .bind(ca_id)
-        .execute(&state.db)
-        .await?;
+    if let Some((existing_id, is_already_primary)) = existing_for_container {
+        if is_already_primary {
+
+            return Ok(Json(serde_json::json!({
+                "message": "Allocation is already primary",
+                "allocationIp": allocation.ip,
+                "allocationPort": allocation.port
+            })));
+        }
+
+        swap_primary_allocation(&state.db, container.id, existing_id).await?;
+    } else {
+        let mut tx = state.db.begin().await?;
+
+        sqlx::query("UPDATE container_allocations SET is_primary = FALSE WHERE container_id = $1 AND is_primary = TRUE")
+            .bind(container.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"INSERT INTO container_allocations (id, container_id, allocation_id, ip, port, internal_port, protocol, is_primary, created_at)
+               VALUES ($1, $2, $3, $4, $5, $5, $6, TRUE, NOW())"#
+        )
+            .bind(Uuid::new_v4())
+            .bind(container.id)
+            .bind(req.allocation_id)
+            .bind(&allocation.ip)
+            .bind(allocation.port)
+            .bind(&allocation.protocol)
+            .execute(&mut *tx)
+            .await
+            .map_err(allocation_conflict)?;
+
+        tx.commit().await?;
+    }
 
     Ok(Json(serde_json::json!({
-        "message": "Allocation set as primary",
-        "allocationIp": ip,
-        "allocationPort": port
+        "message": "Allocation assigned successfully",
+        "allocationIp": allocation.ip,
+        "allocationPort": allocation.port
     })))
 }
 
+pub async fn get_available_allocations(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Vec<crate::models::Allocation>>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !owns(&claims, &container, permissions::CONTAINERS_VIEW_OWN)? && !claims.is_manager()
+        && !sub_user_can(&state, &claims, &container, permissions::SUB_ALLOCATIONS).await?
+    {
+        return Err(AppError::Unauthorized);
+    }
+
+    let allocations: Vec<crate::models::Allocation> = sqlx::query_as(
+        r#"SELECT a.* FROM allocations a
+           WHERE a.daemon_id = $1
+           AND NOT EXISTS (SELECT 1 FROM container_allocations ca WHERE ca.allocation_id = a.id)
+           ORDER BY a.ip, a.port"#
+    )
+        .bind(container.daemon_id)
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(Json(allocations))
+}
+
+pub async fn get_container_allocations(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Vec<AllocationInfo>>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !owns(&claims, &container, permissions::CONTAINERS_VIEW_OWN)? && !claims.has_permission(permissions::CONTAINERS_VIEW_ALL) && !claims.is_manager()
+        && !sub_user_can(&state, &claims, &container, permissions::SUB_ALLOCATIONS).await?
+    {
+        return Err(AppError::Unauthorized);
+    }
+
+    let allocations: Vec<AllocationInfo> = sqlx::query_as(
+        r#"SELECT ca.id, ca.allocation_id, ca.ip, ca.port, ca.internal_port, ca.protocol, COALESCE(ca.is_primary, FALSE) as is_primary
+           FROM container_allocations ca
+           WHERE ca.container_id = $1
+           ORDER BY ca.is_primary DESC, ca.ip, ca.port"#
+    )
+        .bind(id)
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(Json(allocations))
+}
+
 #[derive(Debug, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct SetSftpPasswordRequest {
-    pub password: String,
+pub struct AddAllocationRequest {
+    pub allocation_id: Uuid,
+    #[serde(default)]
+    pub is_primary: bool,
 }
 
-pub async fn set_sftp_password(
+pub async fn add_allocation(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    Json(req): Json<SetSftpPasswordRequest>,
+    Json(req): Json<AddAllocationRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
@@ -1403,176 +3036,1176 @@ pub async fn set_sftp_password(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if container.user_id != claims.sub && !claims.is_manager() {
+    if !owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)? && !claims.is_manager()
+        && !sub_user_can(&state, &claims, &container, permissions::SUB_ALLOCATIONS).await?
+    {
         return Err(AppError::Unauthorized);
     }
 
-    if req.password.len() < 8 {
-        return Err(AppError::BadRequest("Password must be at least 8 characters".into()));
-    }
+    ensure_not_trashed(&container)?;
 
-    let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+    let allocation: crate::models::Allocation = sqlx::query_as(
+        "SELECT * FROM allocations WHERE id = $1 AND daemon_id = $2"
+    )
+        .bind(req.allocation_id)
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
-        .ok_or(AppError::NotFound)?;
-
-    let client = daemon_client();
-    let url = format!("{}/containers/{}/ftp", daemon.base_url(), container.id);
+        .ok_or(AppError::BadRequest("Allocation not found or belongs to different daemon".into()))?;
 
-    let daemon_result = client
-        .post(&url)
-        .header("X-API-Key", &daemon.api_key)
-        .json(&serde_json::json!({ "password": req.password }))
-        .send()
-        .await;
+    let existing: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT id FROM container_allocations WHERE allocation_id = $1"
+    )
+        .bind(req.allocation_id)
+        .fetch_optional(&state.db)
+        .await?;
 
-    if let Err(e) = daemon_result {
-        tracing::warn!("Failed to register FTP user with daemon: {}", e);
+    if existing.is_some() {
+        return Err(AppError::BadRequest("Allocation is already in use".into()));
     }
 
-    let password_hash = bcrypt::hash(&req.password, state.config.bcrypt_cost)
-        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut tx = state.db.begin().await?;
 
-    let sftp_user = container.id.to_string().replace("-", "")[..8].to_string();
+    if req.is_primary {
+        sqlx::query("UPDATE container_allocations SET is_primary = FALSE WHERE container_id = $1 AND is_primary = TRUE")
+            .bind(container.id)
+            .execute(&mut *tx)
+            .await?;
+    }
 
-    sqlx::query("UPDATE containers SET sftp_user = $1, sftp_pass = $2, updated_at = NOW() WHERE id = $3")
-        .bind(&sftp_user)
-        .bind(&password_hash)
-        .bind(id)
-        .execute(&state.db)
-        .await?;
+    let has_allocations: Option<(i64,)> = sqlx::query_as(
+        "SELECT COUNT(*) FROM container_allocations WHERE container_id = $1"
+    )
+        .bind(container.id)
+        .fetch_one(&mut *tx)
+        .await
+        .ok();
 
-    Ok(Json(serde_json::json!({
-        "message": "SFTP password set successfully",
-        "sftpUser": sftp_user
-    })))
+    let is_primary = req.is_primary || has_allocations.map(|c| c.0 == 0).unwrap_or(true);
+
+    sqlx::query(
+        r#"INSERT INTO container_allocations (id, container_id, allocation_id, ip, port, internal_port, protocol, is_primary, created_at)
+           VALUES ($1, $2, $3, $4, $5, $5, $6, $7, NOW())"#
+    )
+        .bind(Uuid::new_v4())
+        .bind(container.id)
+        .bind(req.allocation_id)
+        .bind(&allocation.ip)
+        .bind(allocation.port)
+        .bind(&allocation.protocol)
+        .bind(is_primary)
+        .execute(&mut *tx)
+        .await
+        .map_err(allocation_conflict)?;
+
+    tx.commit().await?;
+
+    mark_pending_changes(&state, container.id).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Allocation added successfully",
+        "allocationIp": allocation.ip,
+        "allocationPort": allocation.port,
+        "isPrimary": is_primary
+    })))
+}
+
+pub async fn remove_allocation(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((id, allocation_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<serde_json::Value>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)? && !claims.is_manager()
+        && !sub_user_can(&state, &claims, &container, permissions::SUB_ALLOCATIONS).await?
+    {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&container)?;
+
+    let container_allocation: (Uuid, bool) = sqlx::query_as(
+        "SELECT id, COALESCE(is_primary, FALSE) FROM container_allocations WHERE allocation_id = $1 AND container_id = $2"
+    )
+        .bind(allocation_id)
+        .bind(container.id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::BadRequest("Allocation not found or doesn't belong to this container".into()))?;
+
+    let was_primary = container_allocation.1;
+
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query("DELETE FROM container_allocations WHERE id = $1")
+        .bind(container_allocation.0)
+        .execute(&mut *tx)
+        .await?;
+
+    if was_primary {
+        sqlx::query(
+            r#"UPDATE container_allocations
+               SET is_primary = TRUE
+               WHERE id = (
+                   SELECT id FROM container_allocations
+                   WHERE container_id = $1
+                   ORDER BY created_at ASC
+                   LIMIT 1
+               )"#
+        )
+            .bind(container.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    mark_pending_changes(&state, container.id).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Allocation removed successfully"
+    })))
+}
+
+/// Moves the primary flag in one transaction, so readers never see a container without one.
+async fn swap_primary_allocation(db: &sqlx::PgPool, container_id: Uuid, container_allocation_id: Uuid) -> AppResult<()> {
+    let mut tx = db.begin().await?;
+
+    // Concurrent swaps queue here; otherwise both could clear the old primary and set their
+    // own, and the second would trip the one-primary index
+    sqlx::query("SELECT id FROM container_allocations WHERE container_id = $1 FOR UPDATE")
+        .bind(container_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE container_allocations SET is_primary = FALSE WHERE container_id = $1 AND is_primary = TRUE")
+        .bind(container_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE container_allocations SET is_primary = TRUE WHERE id = $1")
+        .bind(container_allocation_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn set_primary_allocation(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((id, allocation_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<serde_json::Value>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)? && !claims.is_manager()
+        && !sub_user_can(&state, &claims, &container, permissions::SUB_ALLOCATIONS).await?
+    {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&container)?;
+
+    let container_allocation: Option<(Uuid, String, i32)> = sqlx::query_as(
+        "SELECT id, ip, port FROM container_allocations WHERE allocation_id = $1 AND container_id = $2"
+    )
+        .bind(allocation_id)
+        .bind(container.id)
+        .fetch_optional(&state.db)
+        .await?;
+
+    if container_allocation.is_none() {
+        return Err(AppError::BadRequest("Allocation not found or doesn't belong to this container".into()));
+    }
+
+    let (ca_id, ip, port) = container_allocation.unwrap();
+
+    swap_primary_allocation(&state.db, container.id, ca_id).await?;
+
+    mark_pending_changes(&state, container.id).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Allocation set as primary",
+        "allocationIp": ip,
+        "allocationPort": port
+    })))
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSftpPasswordRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct SyncAllocationsRequest {
+    /// Recreate the Docker container so the corrected port bindings take effect.
+    #[serde(default)]
+    pub recreate: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllocationBinding {
+    pub ip: String,
+    pub port: i32,
+    pub internal_port: i32,
+    pub protocol: String,
+    #[serde(default)]
+    pub is_primary: bool,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct DaemonContainerAllocations {
+    #[serde(default)]
+    allocations: Vec<AllocationBinding>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllocationSyncResponse {
+    pub in_sync: bool,
+    /// In the database but not stored on the daemon.
+    pub missing_on_daemon: Vec<AllocationBinding>,
+    /// Stored on the daemon but no longer in the database.
+    pub stale_on_daemon: Vec<AllocationBinding>,
+    pub repaired: bool,
+    pub recreated: bool,
+}
+
+/// POST /containers/:id/allocations/sync - make the daemon's stored allocations match the database
+pub async fn sync_container_allocations(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    body: Option<Json<SyncAllocationsRequest>>,
+) -> AppResult<Json<AllocationSyncResponse>> {
+    let req = body.map(|Json(r)| r).unwrap_or_default();
+
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let can_manage = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
+        || claims.is_manager();
+
+    if !can_manage {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&container)?;
+
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let allocations: Vec<crate::models::ContainerAllocation> = sqlx::query_as(
+        r#"SELECT ca.id, ca.container_id, ca.allocation_id, ca.ip, ca.port, ca.internal_port, ca.protocol, COALESCE(ca.is_primary, FALSE) as is_primary, ca.created_at
+           FROM container_allocations ca
+           WHERE ca.container_id = $1
+           ORDER BY ca.is_primary DESC, ca.ip, ca.port"#
+    )
+        .bind(id)
+        .fetch_all(&state.db)
+        .await?;
+
+    let client = DaemonClient::with_timeout(std::time::Duration::from_secs(60));
+    let container_url = format!("{}/containers/{}", daemon.base_url(), container.id);
+
+    let res = client
+        .get(&container_url)
+        .header("X-API-Key", &daemon.api_key)
+        .send()
+        .await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    if !res.status().is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to fetch container from daemon: {}", error_text)));
+    }
+
+    let on_daemon: DaemonContainerAllocations = res.json().await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    let expected: Vec<AllocationBinding> = allocations.iter().map(|a| AllocationBinding {
+        ip: a.ip.clone(),
+        port: a.port,
+        internal_port: a.internal_port,
+        protocol: a.protocol.clone(),
+        is_primary: a.is_primary.unwrap_or(false),
+    }).collect();
+
+    let missing_on_daemon: Vec<AllocationBinding> = expected.iter()
+        .filter(|a| !on_daemon.allocations.contains(a))
+        .cloned()
+        .collect();
+    let stale_on_daemon: Vec<AllocationBinding> = on_daemon.allocations.into_iter()
+        .filter(|a| !expected.contains(a))
+        .collect();
+
+    let in_sync = missing_on_daemon.is_empty() && stale_on_daemon.is_empty();
+
+    if !in_sync {
+        tracing::info!(
+            "Repairing allocations of {} on daemon: {} missing, {} stale",
+            container.id, missing_on_daemon.len(), stale_on_daemon.len()
+        );
+
+        let res = client
+            .patch(&container_url)
+            .header("X-API-Key", &daemon.api_key)
+            .json(&serde_json::json!({
+                "allocations": daemon_allocations_json(&allocations)
+            }))
+            .send()
+            .await
+            .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+        if !res.status().is_success() {
+            let error_text = res.text().await.unwrap_or_default();
+            return Err(AppError::Daemon(format!("Failed to update allocations on daemon: {}", error_text)));
+        }
+    }
+
+    // Docker bindings can be stale even when the stored list matches, so recreate whenever asked
+    if req.recreate {
+        let res = client
+            .post(&format!("{}/recreate", container_url))
+            .header("X-API-Key", &daemon.api_key)
+            .json(&serde_json::json!({}))
+            .send()
+            .await
+            .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+        if !res.status().is_success() {
+            let error_text = res.text().await.unwrap_or_default();
+            return Err(AppError::Daemon(format!("Failed to recreate container: {}", error_text)));
+        }
+
+        let result: serde_json::Value = res.json().await.unwrap_or_default();
+        let needs_install = result.get("needsInstall").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        sqlx::query("UPDATE containers SET status = $1, pending_changes = FALSE, updated_at = NOW() WHERE id = $2")
+            .bind(if needs_install { "stopped" } else { "running" })
+            .bind(id)
+            .execute(&state.db)
+            .await?;
+    }
+
+    Ok(Json(AllocationSyncResponse {
+        in_sync,
+        missing_on_daemon,
+        stale_on_daemon,
+        repaired: !in_sync,
+        recreated: req.recreate,
+    }))
+}
+
+pub async fn set_sftp_password(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<SetSftpPasswordRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)? && !claims.is_manager() {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&container)?;
+
+    if req.password.len() < 8 {
+        return Err(AppError::BadRequest("Password must be at least 8 characters".into()));
+    }
+
+    let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let client = daemon_client();
+    let url = format!("{}/containers/{}/ftp", daemon.base_url(), container.id);
+
+    let daemon_result = client
+        .post(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .json(&serde_json::json!({ "password": req.password }))
+        .send()
+        .await;
+
+    if let Err(e) = daemon_result {
+        tracing::warn!("Failed to register FTP user with daemon: {}", e);
+    }
+
+    let password_hash = bcrypt::hash(&req.password, state.config.bcrypt_cost)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let sftp_user = container.id.to_string().replace("-", "")[..8].to_string();
+
+    sqlx::query("UPDATE containers SET sftp_user = $1, sftp_pass = $2, updated_at = NOW() WHERE id = $3")
+        .bind(&sftp_user)
+        .bind(&password_hash)
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "SFTP password set successfully",
+        "sftpUser": sftp_user
+    })))
+}
+
+pub async fn revoke_sftp_access(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)? && !claims.is_manager() {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&container)?;
+
+    let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let client = daemon_client();
+    let url = format!("{}/containers/{}/ftp", daemon.base_url(), container.id);
+
+    // The credential must be gone from the daemon before we report it as revoked
+    let res = client
+        .delete(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .send()
+        .await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    if !res.status().is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to revoke FTP access: {}", error_text)));
+    }
+
+    sqlx::query("UPDATE containers SET sftp_pass = NULL, updated_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "SFTP access revoked"
+    })))
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddContainerUserRequest {
+    pub user_id: Uuid,
+    /// Only picks the default permissions when `permissions` is left out.
+    pub permission_level: Option<String>,
+    /// Any of `permissions::SUB_USER_PERMISSIONS`.
+    pub permissions: Option<Vec<String>>,
+}
+
+pub async fn list_container_users(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Vec<crate::models::ContainerUserResponse>>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !can_access_container(&claims, &container) {
+        return Err(AppError::Unauthorized);
+    }
+
+    let users: Vec<crate::models::ContainerUserResponse> = sqlx::query_as(
+        r#"
+        SELECT cu.id, cu.container_id, cu.user_id, u.username, cu.permission_level, cu.permissions, cu.created_at
+        FROM container_users cu
+        JOIN users u ON u.id = cu.user_id
+        WHERE cu.container_id = $1
+        ORDER BY cu.created_at
+        "#
+    )
+    .bind(id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(users))
+}
+
+pub async fn add_container_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<AddContainerUserRequest>,
+) -> AppResult<Json<crate::models::ContainerUser>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)? && !claims.is_manager() {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&container)?;
+
+    let _user: crate::models::User = sqlx::query_as("SELECT * FROM users WHERE id = $1")
+        .bind(req.user_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::BadRequest("User not found".into()))?;
+
+    let permission_level = match req.permission_level.as_deref() {
+        None => crate::models::ContainerPermissionLevel::User,
+        Some(level) => match crate::models::ContainerPermissionLevel::parse(level) {
+            Some(level @ (crate::models::ContainerPermissionLevel::User | crate::models::ContainerPermissionLevel::Admin)) => level,
+            _ => return Err(AppError::BadRequest("permissionLevel must be 'user' or 'admin'".into())),
+        },
+    };
+
+    let mut granted = req.permissions.unwrap_or_else(|| permission_level.default_permissions());
+    if let Some(unknown) = granted.iter().find(|p| !permissions::SUB_USER_PERMISSIONS.contains(&p.as_str())) {
+        return Err(AppError::BadRequest(format!(
+            "Unknown permission '{}', expected any of: {}", unknown, permissions::SUB_USER_PERMISSIONS.join(", ")
+        )));
+    }
+    // Files can't be changed without being listed
+    if granted.iter().any(|p| p == permissions::SUB_FILES_WRITE) {
+        granted.push(permissions::SUB_FILES_READ.to_string());
+    }
+    granted.sort_by_key(|p| permissions::SUB_USER_PERMISSIONS.iter().position(|known| known == p));
+    granted.dedup();
+
+    // Kept in step with the permissions for clients that still read the level
+    let permission_level = if granted.iter().any(|p| p == permissions::SUB_FILES_WRITE) {
+        crate::models::ContainerPermissionLevel::Admin
+    } else {
+        crate::models::ContainerPermissionLevel::User
+    };
+
+    let container_user: crate::models::ContainerUser = sqlx::query_as(
+        r#"
+        INSERT INTO container_users (id, container_id, user_id, permission_level, permissions, created_at)
+        VALUES ($1, $2, $3, $4, $5, NOW())
+        ON CONFLICT (container_id, user_id) DO UPDATE SET permission_level = $4, permissions = $5
+        RETURNING *
+        "#
+    )
+    .bind(Uuid::new_v4())
+    .bind(id)
+    .bind(req.user_id)
+    .bind(permission_level.as_str())
+    .bind(&granted)
+    .fetch_one(&state.db)
+    .await?;
+
+    record_activity(&state.db, id, claims.sub, "user_add", serde_json::json!({
+        "userId": req.user_id,
+        "permissionLevel": permission_level.as_str(),
+        "permissions": granted,
+    })).await;
+
+    Ok(Json(container_user))
+}
+
+pub async fn remove_container_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((container_id, user_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<serde_json::Value>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(container_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)? && !claims.is_manager() {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&container)?;
+
+    if container.user_id == user_id {
+        return Err(AppError::BadRequest("Cannot remove the container owner".into()));
+    }
+
+    sqlx::query("DELETE FROM container_users WHERE container_id = $1 AND user_id = $2")
+        .bind(container_id)
+        .bind(user_id)
+        .execute(&state.db)
+        .await?;
+
+    record_activity(&state.db, container_id, claims.sub, "user_remove", serde_json::json!({ "userId": user_id })).await;
+
+    Ok(Json(serde_json::json!({ "message": "User removed from container" })))
+}
+
+/// Whether the user owns the container. Through an API key ownership only counts when the key
+/// carries `scope`; an owner whose key lacks it is refused outright rather than falling back
+/// to broader permissions the key may hold.
+pub fn owns(claims: &Claims, container: &Container, scope: &str) -> AppResult<bool> {
+    if container.user_id != claims.sub {
+        return Ok(false);
+    }
+
+    if claims.api_key_id.is_some() && !claims.has_permission(scope) {
+        return Err(AppError::Forbidden(format!("This API key lacks the {} scope", scope)));
+    }
+
+    Ok(true)
+}
+
+/// Read access: the owner (a key without `containers.view_own` doesn't count), anyone who may
+/// view all containers, and managers.
+pub fn can_access_container(claims: &Claims, container: &Container) -> bool {
+    owns(claims, container, permissions::CONTAINERS_VIEW_OWN).unwrap_or(false)
+        || claims.has_permission(permissions::CONTAINERS_VIEW_ALL)
+        || claims.is_manager()
+}
+
+/// What the user was granted as a sub-user of the container; None when they aren't one, or
+/// when the request's API key lacks `containers.view_own`.
+pub async fn sub_user_permissions(
+    state: &AppState,
+    claims: &Claims,
+    container: &Container,
+) -> AppResult<Option<Vec<String>>> {
+    if claims.api_key_id.is_some() && !claims.has_permission(permissions::CONTAINERS_VIEW_OWN) {
+        return Ok(None);
+    }
+
+    let granted: Option<Vec<String>> = sqlx::query_scalar(
+        "SELECT permissions FROM container_users WHERE container_id = $1 AND user_id = $2"
+    )
+        .bind(container.id)
+        .bind(claims.sub)
+        .fetch_optional(&state.db)
+        .await?;
+
+    Ok(granted)
+}
+
+/// Whether the user is a sub-user of the container with `permission` (one of `permissions::SUB_USER_PERMISSIONS`).
+/// Through an API key, anything beyond reading files also needs `containers.manage_own`.
+pub async fn sub_user_can(state: &AppState, claims: &Claims, container: &Container, permission: &str) -> AppResult<bool> {
+    if claims.api_key_id.is_some() && permission != permissions::SUB_FILES_READ
+        && !claims.has_permission(permissions::CONTAINERS_MANAGE_OWN)
+    {
+        return Ok(false);
+    }
+
+    Ok(sub_user_permissions(state, claims, container)
+        .await?
+        .is_some_and(|granted| granted.iter().any(|p| p == permission)))
+}
+
+/// Anyone who can access the container, plus sub-users allowed to read files.
+pub async fn can_read_files(state: &AppState, claims: &Claims, container: &Container) -> AppResult<bool> {
+    if can_access_container(claims, container) {
+        return Ok(true);
+    }
+
+    sub_user_can(state, claims, container, permissions::SUB_FILES_READ).await
+}
+
+/// The owner, container managers, and sub-users allowed to change files.
+/// Errors for trashed and read-only containers, so callers that may write report why they can't.
+pub async fn can_write_files(state: &AppState, claims: &Claims, container: &Container) -> AppResult<bool> {
+    let allowed = owns(claims, container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
+        || claims.is_manager()
+        || sub_user_can(state, claims, container, permissions::SUB_FILES_WRITE).await?;
+
+    if allowed {
+        ensure_not_trashed(container)?;
+    }
+
+    if allowed && container.read_only {
+        return Err(AppError::Forbidden("Container is read-only".into()));
+    }
+
+    Ok(allowed)
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ListFilesQuery {
+    pub path: Option<String>,
+}
+
+pub async fn list_files(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ListFilesQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !can_read_files(&state, &claims, &container).await? {
+        return Err(AppError::Unauthorized);
+    }
+
+    let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let client = daemon_client();
+    let path = query.path.unwrap_or_else(|| "/".to_string());
+
+    let url = format!("{}/containers/{}/files?path={}", daemon.base_url(), container.id, path);
+
+    let resp = client
+        .get(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .send()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Daemon error: {}", e)))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let error_text = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(AppError::BadRequest(format!("Daemon returned {}: {}", status, error_text)));
+    }
+
+    let files: serde_json::Value = resp.json().await
+        .map_err(|e| AppError::BadRequest(format!("Parse error: {}", e)))?;
+
+    Ok(Json(files))
 }
 
 #[derive(Debug, serde::Deserialize)]
+pub struct ReadFileQuery {
+    pub path: String,
+    /// Only the last this many bytes; for files over the daemon's read limit.
+    #[serde(default)]
+    pub tail: Option<u64>,
+    /// Start of a byte range of `length` bytes.
+    #[serde(default)]
+    pub offset: Option<u64>,
+    #[serde(default)]
+    pub length: Option<u64>,
+}
+
+/// Headers of a daemon file read passed through to the client.
+const FILE_READ_HEADERS: [&str; 5] = ["content-type", "x-file-size", "x-file-offset", "x-file-encoding", "x-file-content-type"];
+
+pub async fn read_file(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ReadFileQuery>,
+) -> AppResult<axum::response::Response> {
+    use axum::response::IntoResponse;
+
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !can_read_files(&state, &claims, &container).await? {
+        return Err(AppError::Unauthorized);
+    }
+
+    let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let client = daemon_client();
+
+    let mut url = format!("{}/containers/{}/files/read?path={}", daemon.base_url(), container.id, urlencoding::encode(&query.path));
+    let range = [("tail", query.tail), ("offset", query.offset), ("length", query.length)];
+    for (name, value) in range {
+        if let Some(value) = value {
+            url.push_str(&format!("&{}={}", name, value));
+        }
+    }
+
+    let resp = client
+        .get(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .send()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Daemon error: {}", e)))?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        let message = resp.text().await.unwrap_or_default();
+        return Ok(match status.as_u16() {
+            // Too large or too many reads at once: the daemon's message says what to do
+            413 | 429 => (
+                axum::http::StatusCode::from_u16(status.as_u16()).unwrap_or(axum::http::StatusCode::BAD_REQUEST),
+                Json(serde_json::json!({ "error": message })),
+            ).into_response(),
+            404 => AppError::NotFound.into_response(),
+            _ => AppError::BadRequest(format!("Read error: {}", message)).into_response(),
+        });
+    }
+
+    let mut headers = axum::http::HeaderMap::new();
+    for name in FILE_READ_HEADERS {
+        if let Some(value) = resp.headers().get(name).and_then(|v| axum::http::HeaderValue::from_bytes(v.as_bytes()).ok()) {
+            headers.insert(name, value);
+        }
+    }
+
+    let content = resp.bytes().await
+        .map_err(|e| AppError::BadRequest(format!("Read error: {}", e)))?;
+
+    Ok((headers, content.to_vec()).into_response())
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct AddContainerUserRequest {
-    pub user_id: Uuid,
-    pub permission_level: Option<String>,
+pub struct WriteFileRequest {
+    pub path: String,
+    pub content: String,
+}
+
+pub async fn write_file(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<WriteFileRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !can_write_files(&state, &claims, &container).await? {
+        return Err(AppError::Forbidden("You only have read access to this container's files".into()));
+    }
+
+    let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let client = daemon_client();
+
+    let url = format!("{}/containers/{}/files/write", daemon.base_url(), container.id);
+
+    let resp = client
+        .post(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .json(&req)
+        .send()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Daemon error: {}", e)))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let error_text = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(AppError::BadRequest(format!("Daemon returned {}: {}", status, error_text)));
+    }
+
+    let result: serde_json::Value = resp.json().await
+        .map_err(|e| AppError::BadRequest(format!("Parse error: {}", e)))?;
+
+    record_activity(&state.db, container.id, claims.sub, "file_write", serde_json::json!({ "path": req.path })).await;
+
+    Ok(Json(result))
+}
+
+pub async fn upload_file(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    mut multipart: axum::extract::Multipart,
+) -> AppResult<Json<serde_json::Value>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !can_write_files(&state, &claims, &container).await? {
+        return Err(AppError::Forbidden("You only have read access to this container's files".into()));
+    }
+
+    let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let mut path: Option<String> = None;
+    let mut file_content: Option<Vec<u8>> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| AppError::BadRequest(format!("Multipart error: {}", e)))? {
+        let field_name = field.name().unwrap_or("").to_string();
+
+        if field_name == "path" {
+            path = Some(field.text().await.map_err(|e| AppError::BadRequest(format!("Read error: {}", e)))?);
+        } else if field_name == "file" {
+            file_content = Some(field.bytes().await.map_err(|e| AppError::BadRequest(format!("Read error: {}", e)))?.to_vec());
+        }
+    }
+
+    let path = path.ok_or_else(|| AppError::BadRequest("Missing 'path' field".to_string()))?;
+    let content = file_content.ok_or_else(|| AppError::BadRequest("Missing 'file' field".to_string()))?;
+
+    let content_base64 = general_purpose::STANDARD.encode(&content);
+
+    let client = daemon_client();
+    let url = format!("{}/containers/{}/files/write", daemon.base_url(), container.id);
+
+    let resp = client
+        .post(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .json(&serde_json::json!({
+            "path": path,
+            "content": content_base64,
+            "encoding": "base64"
+        }))
+        .send()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Daemon error: {}", e)))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let error_text = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(AppError::BadRequest(format!("Daemon returned {}: {}", status, error_text)));
+    }
+
+    record_activity(&state.db, container.id, claims.sub, "file_upload", serde_json::json!({ "path": path })).await;
+
+    Ok(Json(serde_json::json!({ "message": "File uploaded successfully" })))
+}
+
+/// GET /containers/:id/files/upload-limits - the chunk size both the API and the container's
+/// daemon accept; clients split uploads at this size.
+pub async fn get_upload_limits(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<raptor_common::uploads::UploadLimits>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !can_write_files(&state, &claims, &container).await? {
+        return Err(AppError::Forbidden("You only have read access to this container's files".into()));
+    }
+
+    let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    // Daemons predating the endpoint accept the default chunk size
+    let daemon_chunk_size = fetch_daemon_json::<raptor_common::uploads::UploadLimits>(&daemon, "/uploads/limits".into())
+        .await
+        .map(|limits| limits.chunk_size)
+        .unwrap_or(raptor_common::uploads::DEFAULT_CHUNK_SIZE_MB * 1024 * 1024);
+
+    let chunk_size = state.config.upload_chunk_size.min(daemon_chunk_size);
+
+    Ok(Json(raptor_common::uploads::UploadLimits {
+        chunk_size,
+        max_body_size: raptor_common::uploads::multipart_body_limit(state.config.upload_chunk_size),
+    }))
 }
 
-pub async fn list_container_users(
-    State(state): State<AppState>,
-    Extension(claims): Extension<Claims>,
-    Path(id): Path<Uuid>,
-) -> AppResult<Json<Vec<crate::models::ContainerUserResponse>>> {
-    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
-        .bind(id)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or(AppError::NotFound)?;
+pub async fn upload_file_chunk(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    mut multipart: axum::extract::Multipart,
+) -> AppResult<Json<serde_json::Value>> {
+    tracing::info!("upload_file_chunk: Starting chunk upload for container {}", id);
+
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !can_write_files(&state, &claims, &container).await? {
+        return Err(AppError::Forbidden("You only have read access to this container's files".into()));
+    }
+
+    let mut upload_id: Option<String> = None;
+    let mut chunk_index: Option<u32> = None;
+    let mut total_chunks: Option<u32> = None;
+    let mut path: Option<String> = None;
+    let mut chunk_data: Option<Vec<u8>> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        tracing::error!("upload_file_chunk: Multipart next_field error: {}", e);
+        AppError::BadRequest(format!("Multipart error: {}", e))
+    })? {
+        let field_name = field.name().unwrap_or("").to_string();
+        tracing::debug!("upload_file_chunk: Processing field: {}", field_name);
+
+        match field_name.as_str() {
+            "uploadId" => upload_id = Some(field.text().await.map_err(|e| {
+                tracing::error!("upload_file_chunk: Error reading uploadId: {}", e);
+                AppError::BadRequest(format!("Read error: {}", e))
+            })?),
+            "chunkIndex" => chunk_index = Some(field.text().await.map_err(|e| {
+                tracing::error!("upload_file_chunk: Error reading chunkIndex: {}", e);
+                AppError::BadRequest(format!("Read error: {}", e))
+            })?.parse().map_err(|_| AppError::BadRequest("Invalid chunk index".to_string()))?),
+            "totalChunks" => total_chunks = Some(field.text().await.map_err(|e| {
+                tracing::error!("upload_file_chunk: Error reading totalChunks: {}", e);
+                AppError::BadRequest(format!("Read error: {}", e))
+            })?.parse().map_err(|_| AppError::BadRequest("Invalid total chunks".to_string()))?),
+            "path" => path = Some(field.text().await.map_err(|e| {
+                tracing::error!("upload_file_chunk: Error reading path: {}", e);
+                AppError::BadRequest(format!("Read error: {}", e))
+            })?),
+            "fileName" | "fileSize" => {  }
+            "chunk" => {
+                tracing::info!("upload_file_chunk: Reading chunk data...");
+                chunk_data = Some(field.bytes().await.map_err(|e| {
+                    tracing::error!("upload_file_chunk: Error reading chunk bytes: {}", e);
+                    AppError::BadRequest(format!("Read error: {}", e))
+                })?.to_vec());
+                tracing::info!("upload_file_chunk: Chunk data read, size: {} bytes", chunk_data.as_ref().map(|d| d.len()).unwrap_or(0));
+            }
+            _ => {
+                tracing::debug!("upload_file_chunk: Ignoring unknown field: {}", field_name);
+            }
+        }
+    }
+
+    tracing::info!("upload_file_chunk: Parsed - uploadId: {:?}, chunkIndex: {:?}, totalChunks: {:?}, path: {:?}, chunk_size: {:?}",
+        upload_id, chunk_index, total_chunks, path, chunk_data.as_ref().map(|d| d.len()));
 
-    if !can_access_container(&claims, &container) {
-        return Err(AppError::Unauthorized);
-    }
+    let upload_id = upload_id.ok_or_else(|| AppError::BadRequest("Missing uploadId".to_string()))?;
+    let chunk_index = chunk_index.ok_or_else(|| AppError::BadRequest("Missing chunkIndex".to_string()))?;
+    let total_chunks = total_chunks.ok_or_else(|| AppError::BadRequest("Missing totalChunks".to_string()))?;
+    let path = path.ok_or_else(|| AppError::BadRequest("Missing path".to_string()))?;
+    let chunk_data = chunk_data.ok_or_else(|| AppError::BadRequest("Missing chunk data".to_string()))?;
 
-    let users: Vec<crate::models::ContainerUserResponse> = sqlx::query_as(
-        r#"
-        SELECT cu.id, cu.container_id, cu.user_id, u.username, cu.permission_level, cu.created_at
-        FROM container_users cu
-        JOIN users u ON u.id = cu.user_id
-        WHERE cu.container_id = $1
-        ORDER BY cu.created_at
-        "#
-    )
-    .bind(id)
-    .fetch_all(&state.db)
-    .await?;
+    if chunk_data.len() > state.config.upload_chunk_size {
+        return Err(AppError::BadRequest(format!(
+            "Chunk of {} bytes exceeds the {} byte limit, see GET /containers/{}/files/upload-limits",
+            chunk_data.len(), state.config.upload_chunk_size, id
+        )));
+    }
 
-    Ok(Json(users))
-}
+    tracing::info!("upload_file_chunk: Getting daemon info for container {}", container.id);
 
-pub async fn add_container_user(
-    State(state): State<AppState>,
-    Extension(claims): Extension<Claims>,
-    Path(id): Path<Uuid>,
-    Json(req): Json<AddContainerUserRequest>,
-) -> AppResult<Json<crate::models::ContainerUser>> {
-    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
-        .bind(id)
+    let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if container.user_id != claims.sub && !claims.is_manager() {
-        return Err(AppError::Unauthorized);
-    }
+    tracing::info!("upload_file_chunk: Found daemon {}, encoding {} bytes to base64", daemon.id, chunk_data.len());
 
-    let _user: crate::models::User = sqlx::query_as("SELECT * FROM users WHERE id = $1")
-        .bind(req.user_id)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or(AppError::BadRequest("User not found".into()))?;
+    let content_base64 = general_purpose::STANDARD.encode(&chunk_data);
 
-    let permission_level = req.permission_level.unwrap_or_else(|| "user".to_string());
+    tracing::info!("upload_file_chunk: Base64 encoded size: {} bytes", content_base64.len());
 
-    let container_user: crate::models::ContainerUser = sqlx::query_as(
-        r#"
-        INSERT INTO container_users (id, container_id, user_id, permission_level, created_at)
-        VALUES ($1, $2, $3, $4, NOW())
-        ON CONFLICT (container_id, user_id) DO UPDATE SET permission_level = $4
-        RETURNING *
-        "#
-    )
-    .bind(Uuid::new_v4())
-    .bind(id)
-    .bind(req.user_id)
-    .bind(&permission_level)
-    .fetch_one(&state.db)
-    .await?;
+    let client = daemon_client();
+    let url = format!("{}/containers/{}/files/write-chunk", daemon.base_url(), container.id);
 
-    Ok(Json(container_user))
-}
+    tracing::info!("upload_file_chunk: Sending chunk {} of {} to daemon at {}", chunk_index, total_chunks, url);
 
-pub async fn remove_container_user(
-    State(state): State<AppState>,
-    Extension(claims): Extension<Claims>,
-    Path((container_id, user_id)): Path<(Uuid, Uuid)>,
-) -> AppResult<Json<serde_json::Value>> {
-    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
-        .bind(container_id)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or(AppError::NotFound)?;
+    let resp = client
+        .post(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .timeout(std::time::Duration::from_secs(120))
+        .json(&serde_json::json!({
+            "uploadId": upload_id,
+            "chunkIndex": chunk_index,
+            "totalChunks": total_chunks,
+            "path": path,
+            "content": content_base64
+        }))
+        .send()
+        .await
+        .map_err(|e| {
+            tracing::error!("upload_file_chunk: Daemon request failed: {}", e);
+            AppError::BadRequest(format!("Daemon error: {}", e))
+        })?;
 
-    if container.user_id != claims.sub && !claims.is_manager() {
-        return Err(AppError::Unauthorized);
-    }
+    tracing::info!("upload_file_chunk: Daemon responded with status {}", resp.status());
 
-    if container.user_id == user_id {
-        return Err(AppError::BadRequest("Cannot remove the container owner".into()));
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let error_text = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        tracing::error!("upload_file_chunk: Daemon error {}: {}", status, error_text);
+        return Err(AppError::BadRequest(format!("Daemon returned {}: {}", status, error_text)));
     }
 
-    sqlx::query("DELETE FROM container_users WHERE container_id = $1 AND user_id = $2")
-        .bind(container_id)
-        .bind(user_id)
-        .execute(&state.db)
-        .await?;
+    let daemon_response: serde_json::Value = resp.json().await
+        .map_err(|e| {
+            tracing::error!("upload_file_chunk: Failed to parse daemon response: {}", e);
+            AppError::BadRequest(format!("Invalid daemon response: {}", e))
+        })?;
 
-    Ok(Json(serde_json::json!({ "message": "User removed from container" })))
-}
+    tracing::info!("upload_file_chunk: Chunk {} uploaded successfully", chunk_index);
 
-fn can_access_container(claims: &Claims, container: &Container) -> bool {
-    container.user_id == claims.sub
-        || claims.has_permission("containers.view_all")
-        || claims.is_manager()
+    if chunk_index + 1 == total_chunks {
+        record_activity(&state.db, container.id, claims.sub, "file_upload", serde_json::json!({ "path": path })).await;
+    }
+
+    Ok(Json(daemon_response))
 }
 
-#[derive(Debug, serde::Deserialize)]
-pub struct ListFilesQuery {
-    pub path: Option<String>,
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateFolderRequest {
+    pub path: String,
 }
 
-pub async fn list_files(
+pub async fn create_folder(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    Query(query): Query<ListFilesQuery>,
+    Json(req): Json<CreateFolderRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
@@ -1580,8 +4213,8 @@ pub async fn list_files(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if !can_access_container(&claims, &container) {
-        return Err(AppError::Unauthorized);
+    if !can_write_files(&state, &claims, &container).await? {
+        return Err(AppError::Forbidden("You only have read access to this container's files".into()));
     }
 
     let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
@@ -1591,13 +4224,13 @@ pub async fn list_files(
         .ok_or(AppError::NotFound)?;
 
     let client = daemon_client();
-    let path = query.path.unwrap_or_else(|| "/".to_string());
 
-    let url = format!("{}/containers/{}/files?path={}", daemon.base_url(), container.id, path);
+    let url = format!("{}/containers/{}/files/folder", daemon.base_url(), container.id);
 
     let resp = client
-        .get(&url)
+        .post(&url)
         .header("X-API-Key", &daemon.api_key)
+        .json(&req)
         .send()
         .await
         .map_err(|e| AppError::BadRequest(format!("Daemon error: {}", e)))?;
@@ -1608,31 +4241,33 @@ pub async fn list_files(
         return Err(AppError::BadRequest(format!("Daemon returned {}: {}", status, error_text)));
     }
 
-    let files: serde_json::Value = resp.json().await
+    let result: serde_json::Value = resp.json().await
         .map_err(|e| AppError::BadRequest(format!("Parse error: {}", e)))?;
 
-    Ok(Json(files))
+    record_activity(&state.db, container.id, claims.sub, "folder_create", serde_json::json!({ "path": req.path })).await;
+
+    Ok(Json(result))
 }
 
 #[derive(Debug, serde::Deserialize)]
-pub struct ReadFileQuery {
+pub struct DeleteFileQuery {
     pub path: String,
 }
 
-pub async fn read_file(
+pub async fn delete_file(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    Query(query): Query<ReadFileQuery>,
-) -> AppResult<String> {
+    Query(query): Query<DeleteFileQuery>,
+) -> AppResult<Json<serde_json::Value>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if !can_access_container(&claims, &container) {
-        return Err(AppError::Unauthorized);
+    if !can_write_files(&state, &claims, &container).await? {
+        return Err(AppError::Forbidden("You only have read access to this container's files".into()));
     }
 
     let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
@@ -1643,41 +4278,54 @@ pub async fn read_file(
 
     let client = daemon_client();
 
-    let url = format!("{}/containers/{}/files/read?path={}", daemon.base_url(), container.id, query.path);
+    let url = format!("{}/containers/{}/files/delete?path={}", daemon.base_url(), container.id, query.path);
 
     let resp = client
-        .get(&url)
+        .delete(&url)
         .header("X-API-Key", &daemon.api_key)
         .send()
         .await
         .map_err(|e| AppError::BadRequest(format!("Daemon error: {}", e)))?;
 
-    let content = resp.text().await
-        .map_err(|e| AppError::BadRequest(format!("Read error: {}", e)))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let error_text = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(AppError::BadRequest(format!("Daemon returned {}: {}", status, error_text)));
+    }
+
+    let result: serde_json::Value = resp.json().await
+        .map_err(|e| AppError::BadRequest(format!("Parse error: {}", e)))?;
+
+    record_activity(&state.db, container.id, claims.sub, "file_delete", serde_json::json!({ "path": query.path })).await;
 
-    Ok(content)
+    Ok(Json(result))
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct WriteFileRequest {
-    pub path: String,
-    pub content: String,
+pub struct ContainerStats {
+    pub cpu_percent: f64,
+    pub memory_usage: u64,
+    pub memory_limit: u64,
+    pub memory_percent: f64,
+    pub network_rx: u64,
+    pub network_tx: u64,
+    pub block_read: u64,
+    pub block_write: u64,
 }
 
-pub async fn write_file(
+pub async fn get_container_stats(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    Json(req): Json<WriteFileRequest>,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Json<ContainerStats>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if !can_access_container(&claims, &container) {
+    if !can_access_container(&claims, &container) && sub_user_permissions(&state, &claims, &container).await?.is_none() {
         return Err(AppError::Unauthorized);
     }
 
@@ -1688,100 +4336,203 @@ pub async fn write_file(
         .ok_or(AppError::NotFound)?;
 
     let client = daemon_client();
-
-    let url = format!("{}/containers/{}/files/write", daemon.base_url(), container.id);
+    let url = format!("{}/containers/{}/stats", daemon.base_url(), container.id);
 
     let resp = client
-        .post(&url)
+        .get(&url)
         .header("X-API-Key", &daemon.api_key)
-        .json(&req)
         .send()
         .await
-        .map_err(|e| AppError::BadRequest(format!("Daemon error: {}", e)))?;
+        .map_err(|e| AppError::Daemon(format!("Failed to fetch stats: {}", e)))?;
 
     if !resp.status().is_success() {
-        let status = resp.status();
-        let error_text = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(AppError::BadRequest(format!("Daemon returned {}: {}", status, error_text)));
+        return Err(AppError::Daemon("Failed to fetch container stats".into()));
     }
 
-    let result: serde_json::Value = resp.json().await
+    let stats: ContainerStats = resp.json().await
         .map_err(|e| AppError::BadRequest(format!("Parse error: {}", e)))?;
 
-    Ok(Json(result))
+    Ok(Json(stats))
 }
 
-pub async fn upload_file(
+const SUMMARY_DAEMON_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+#[derive(Debug, serde::Deserialize)]
+struct DaemonStatusSnapshot {
+    status: String,
+    running: bool,
+    /// Missing from daemons without readiness checks, where running means ready.
+    ready: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerSummary {
+    pub id: Uuid,
+    pub name: String,
+    /// Live status from the daemon, or the last stored status when it can't be reached.
+    pub status: String,
+    pub running: bool,
+    pub daemon_reachable: bool,
+    pub stats: Option<ContainerStats>,
+    pub primary_allocation: Option<AllocationInfo>,
+    /// Running, past the flake's readiness check, and not in the middle of a start/stop/restart.
+    pub ready: bool,
+}
+
+async fn fetch_daemon_json<T: serde::de::DeserializeOwned>(daemon: &Daemon, path: String) -> Option<T> {
+    let url = format!("{}{}", daemon.base_url(), path);
+
+    let res = daemon_client()
+        .get(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .timeout(SUMMARY_DAEMON_TIMEOUT)
+        .send()
+        .await
+        .ok()?;
+
+    if !res.status().is_success() {
+        return None;
+    }
+
+    res.json::<T>().await.ok()
+}
+
+/// GET /containers/:id/summary - status, stats and primary allocation in one response.
+/// A slow or offline daemon only blanks the live fields instead of failing the request.
+pub async fn get_container_summary(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    mut multipart: axum::extract::Multipart,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Json<ContainerSummary>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if !can_access_container(&claims, &container) {
+    if !can_access_container(&claims, &container) && sub_user_permissions(&state, &claims, &container).await?.is_none() {
         return Err(AppError::Unauthorized);
     }
 
-    let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let mut path: Option<String> = None;
-    let mut file_content: Option<Vec<u8>> = None;
+    let primary_allocation: Option<AllocationInfo> = sqlx::query_as(
+        r#"SELECT ca.id, ca.allocation_id, ca.ip, ca.port, ca.internal_port, ca.protocol, COALESCE(ca.is_primary, FALSE) as is_primary
+           FROM container_allocations ca
+           WHERE ca.container_id = $1
+           ORDER BY ca.is_primary DESC, ca.ip, ca.port
+           LIMIT 1"#
+    )
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?;
 
-    while let Some(field) = multipart.next_field().await.map_err(|e| AppError::BadRequest(format!("Multipart error: {}", e)))? {
-        let field_name = field.name().unwrap_or("").to_string();
+    let (live_status, stats) = tokio::join!(
+        fetch_daemon_json::<DaemonStatusSnapshot>(&daemon, format!("/containers/{}/status", container.id)),
+        fetch_daemon_json::<ContainerStats>(&daemon, format!("/containers/{}/stats", container.id)),
+    );
 
-        if field_name == "path" {
-            path = Some(field.text().await.map_err(|e| AppError::BadRequest(format!("Read error: {}", e)))?);
-        } else if field_name == "file" {
-            file_content = Some(field.bytes().await.map_err(|e| AppError::BadRequest(format!("Read error: {}", e)))?.to_vec());
-        }
-    }
+    let daemon_reachable = live_status.is_some();
+    let (status, running, live_ready) = match live_status {
+        Some(s) => (s.status, s.running, s.ready.unwrap_or(s.running)),
+        None => (container.status.clone(), false, false),
+    };
+
+    let transitioning = matches!(container.status.as_str(), "starting" | "stopping" | "restarting");
+
+    Ok(Json(ContainerSummary {
+        id: container.id,
+        name: container.name,
+        status,
+        running,
+        daemon_reachable,
+        stats: if running { stats } else { None },
+        primary_allocation,
+        ready: live_ready && !transitioning,
+    }))
+}
 
-    let path = path.ok_or_else(|| AppError::BadRequest("Missing 'path' field".to_string()))?;
-    let content = file_content.ok_or_else(|| AppError::BadRequest("Missing 'file' field".to_string()))?;
+/// One set of limits: memory, swap and disk in MB (`UNLIMITED` for none), CPU in cores.
+#[derive(Debug, Default, Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceValues {
+    #[serde(default)]
+    pub memory_limit: Option<i64>,
+    #[serde(default)]
+    pub cpu_limit: Option<f64>,
+    #[serde(default)]
+    pub disk_limit: Option<i64>,
+    #[serde(default)]
+    pub swap_limit: Option<i64>,
+    #[serde(default)]
+    pub io_weight: Option<i32>,
+    #[serde(default)]
+    pub cpuset_cpus: Option<String>,
+    #[serde(default)]
+    pub disable_swap: bool,
+}
 
-    let content_base64 = general_purpose::STANDARD.encode(&content);
+#[derive(Debug, serde::Deserialize)]
+struct DaemonResources {
+    configured: ResourceValues,
+    applied: ResourceValues,
+}
 
-    let client = daemon_client();
-    let url = format!("{}/containers/{}/files/write", daemon.base_url(), container.id);
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerResourceReport {
+    /// Limits saved on the container.
+    pub stored: ResourceValues,
+    /// Ceilings from the owner's role; `None` fields are unrestricted.
+    pub role_limits: crate::models::RoleResourceLimits,
+    pub daemon_reachable: bool,
+    /// What the daemon will apply on the next recreate.
+    pub configured: Option<ResourceValues>,
+    /// What Docker enforces right now.
+    pub applied: Option<ResourceValues>,
+    /// Fields where the applied limit differs from the stored one.
+    pub drift: Vec<&'static str>,
+}
 
-    let resp = client
-        .post(&url)
-        .header("X-API-Key", &daemon.api_key)
-        .json(&serde_json::json!({
-            "path": path,
-            "content": content_base64,
-            "encoding": "base64"
-        }))
-        .send()
-        .await
-        .map_err(|e| AppError::BadRequest(format!("Daemon error: {}", e)))?;
+fn resource_drift(stored: &ResourceValues, applied: &ResourceValues) -> Vec<&'static str> {
+    let mut drift = Vec::new();
 
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let error_text = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(AppError::BadRequest(format!("Daemon returned {}: {}", status, error_text)));
+    if stored.memory_limit.is_some() && stored.memory_limit != applied.memory_limit {
+        drift.push("memoryLimit");
+    }
+    // Docker doesn't limit swap separately once memory is unlimited
+    let stored_swap = if stored.disable_swap { Some(0) } else { stored.swap_limit };
+    if stored.memory_limit != Some(UNLIMITED) && stored_swap.is_some() && stored_swap != applied.swap_limit {
+        drift.push("swapLimit");
+    }
+    if let (Some(stored_cpu), Some(applied_cpu)) = (stored.cpu_limit, applied.cpu_limit) {
+        if (stored_cpu - applied_cpu).abs() > 0.005 {
+            drift.push("cpuLimit");
+        }
+    }
+    if stored.io_weight.is_some() && stored.io_weight != applied.io_weight {
+        drift.push("ioWeight");
+    }
+    if stored.cpuset_cpus != applied.cpuset_cpus {
+        drift.push("cpusetCpus");
     }
 
-    Ok(Json(serde_json::json!({ "message": "File uploaded successfully" })))
+    drift
 }
 
-pub async fn upload_file_chunk(
+/// GET /containers/:id/resources - stored limits next to the owner's role ceilings and what
+/// Docker actually enforces, to explain a container running with different limits than it shows
+pub async fn get_container_resources(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    mut multipart: axum::extract::Multipart,
-) -> AppResult<Json<serde_json::Value>> {
-    tracing::info!("upload_file_chunk: Starting chunk upload for container {}", id);
+) -> AppResult<Json<ContainerResourceReport>> {
+    use rust_decimal::prelude::ToPrimitive;
 
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
@@ -1789,140 +4540,73 @@ pub async fn upload_file_chunk(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if !can_access_container(&claims, &container) {
+    if !can_access_container(&claims, &container) && sub_user_permissions(&state, &claims, &container).await?.is_none() {
         return Err(AppError::Unauthorized);
     }
 
-    let mut upload_id: Option<String> = None;
-    let mut chunk_index: Option<u32> = None;
-    let mut total_chunks: Option<u32> = None;
-    let mut path: Option<String> = None;
-    let mut chunk_data: Option<Vec<u8>> = None;
-
-    while let Some(field) = multipart.next_field().await.map_err(|e| {
-        tracing::error!("upload_file_chunk: Multipart next_field error: {}", e);
-        AppError::BadRequest(format!("Multipart error: {}", e))
-    })? {
-        let field_name = field.name().unwrap_or("").to_string();
-        tracing::debug!("upload_file_chunk: Processing field: {}", field_name);
-
-        match field_name.as_str() {
-            "uploadId" => upload_id = Some(field.text().await.map_err(|e| {
-                tracing::error!("upload_file_chunk: Error reading uploadId: {}", e);
-                AppError::BadRequest(format!("Read error: {}", e))
-            })?),
-            "chunkIndex" => chunk_index = Some(field.text().await.map_err(|e| {
-                tracing::error!("upload_file_chunk: Error reading chunkIndex: {}", e);
-                AppError::BadRequest(format!("Read error: {}", e))
-            })?.parse().map_err(|_| AppError::BadRequest("Invalid chunk index".to_string()))?),
-            "totalChunks" => total_chunks = Some(field.text().await.map_err(|e| {
-                tracing::error!("upload_file_chunk: Error reading totalChunks: {}", e);
-                AppError::BadRequest(format!("Read error: {}", e))
-            })?.parse().map_err(|_| AppError::BadRequest("Invalid total chunks".to_string()))?),
-            "path" => path = Some(field.text().await.map_err(|e| {
-                tracing::error!("upload_file_chunk: Error reading path: {}", e);
-                AppError::BadRequest(format!("Read error: {}", e))
-            })?),
-            "fileName" | "fileSize" => {  }
-            "chunk" => {
-                tracing::info!("upload_file_chunk: Reading chunk data...");
-                chunk_data = Some(field.bytes().await.map_err(|e| {
-                    tracing::error!("upload_file_chunk: Error reading chunk bytes: {}", e);
-                    AppError::BadRequest(format!("Read error: {}", e))
-                })?.to_vec());
-                tracing::info!("upload_file_chunk: Chunk data read, size: {} bytes", chunk_data.as_ref().map(|d| d.len()).unwrap_or(0));
-            }
-            _ => {
-                tracing::debug!("upload_file_chunk: Ignoring unknown field: {}", field_name);
-            }
-        }
-    }
-
-    tracing::info!("upload_file_chunk: Parsed - uploadId: {:?}, chunkIndex: {:?}, totalChunks: {:?}, path: {:?}, chunk_size: {:?}",
-        upload_id, chunk_index, total_chunks, path, chunk_data.as_ref().map(|d| d.len()));
-
-    let upload_id = upload_id.ok_or_else(|| AppError::BadRequest("Missing uploadId".to_string()))?;
-    let chunk_index = chunk_index.ok_or_else(|| AppError::BadRequest("Missing chunkIndex".to_string()))?;
-    let total_chunks = total_chunks.ok_or_else(|| AppError::BadRequest("Missing totalChunks".to_string()))?;
-    let path = path.ok_or_else(|| AppError::BadRequest("Missing path".to_string()))?;
-    let chunk_data = chunk_data.ok_or_else(|| AppError::BadRequest("Missing chunk data".to_string()))?;
-
-    tracing::info!("upload_file_chunk: Getting daemon info for container {}", container.id);
-
-    let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    tracing::info!("upload_file_chunk: Found daemon {}, encoding {} bytes to base64", daemon.id, chunk_data.len());
-
-    let content_base64 = general_purpose::STANDARD.encode(&chunk_data);
-
-    tracing::info!("upload_file_chunk: Base64 encoded size: {} bytes", content_base64.len());
-
-    let client = daemon_client();
-    let url = format!("{}/containers/{}/files/write-chunk", daemon.base_url(), container.id);
-
-    tracing::info!("upload_file_chunk: Sending chunk {} of {} to daemon at {}", chunk_index, total_chunks, url);
-
-    let resp = client
-        .post(&url)
-        .header("X-API-Key", &daemon.api_key)
-        .timeout(std::time::Duration::from_secs(120))
-        .json(&serde_json::json!({
-            "uploadId": upload_id,
-            "chunkIndex": chunk_index,
-            "totalChunks": total_chunks,
-            "path": path,
-            "content": content_base64
-        }))
-        .send()
-        .await
-        .map_err(|e| {
-            tracing::error!("upload_file_chunk: Daemon request failed: {}", e);
-            AppError::BadRequest(format!("Daemon error: {}", e))
-        })?;
+    let role_limits = crate::handlers::roles::fetch_role_limits(&state.db, container.user_id).await?;
 
-    tracing::info!("upload_file_chunk: Daemon responded with status {}", resp.status());
+    let stored = ResourceValues {
+        memory_limit: container.memory_limit,
+        cpu_limit: container.cpu_limit.and_then(|c| c.to_f64()),
+        disk_limit: container.disk_limit,
+        swap_limit: container.swap_limit,
+        io_weight: container.io_weight,
+        cpuset_cpus: container.cpuset_cpus.clone(),
+        disable_swap: container.disable_swap,
+    };
 
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let error_text = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        tracing::error!("upload_file_chunk: Daemon error {}: {}", status, error_text);
-        return Err(AppError::BadRequest(format!("Daemon returned {}: {}", status, error_text)));
-    }
+    let live = fetch_daemon_json::<DaemonResources>(&daemon, format!("/containers/{}/resources", container.id)).await;
 
-    let daemon_response: serde_json::Value = resp.json().await
-        .map_err(|e| {
-            tracing::error!("upload_file_chunk: Failed to parse daemon response: {}", e);
-            AppError::BadRequest(format!("Invalid daemon response: {}", e))
-        })?;
+    let drift = live.as_ref().map(|l| resource_drift(&stored, &l.applied)).unwrap_or_default();
+    let (configured, applied) = match live {
+        Some(l) => (Some(l.configured), Some(l.applied)),
+        None => (None, None),
+    };
 
-    tracing::info!("upload_file_chunk: Chunk {} uploaded successfully", chunk_index);
+    Ok(Json(ContainerResourceReport {
+        stored,
+        role_limits,
+        daemon_reachable: applied.is_some(),
+        configured,
+        applied,
+        drift,
+    }))
+}
 
-    Ok(Json(daemon_response))
+/// Validates `?tail=` with the same rules and cap as the daemon.
+pub fn parse_log_tail(value: Option<&str>) -> AppResult<Option<usize>> {
+    raptor_common::logs::parse_tail(value).map_err(AppError::BadRequest)
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CreateFolderRequest {
-    pub path: String,
+#[derive(Debug, serde::Deserialize)]
+pub struct LogsQuery {
+    pub tail: Option<String>,
+    pub since: Option<String>,
 }
 
-pub async fn create_folder(
+/// GET /containers/:id/logs - recent console output as JSON, without a websocket
+pub async fn get_logs(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    Json(req): Json<CreateFolderRequest>,
+    Query(query): Query<LogsQuery>,
 ) -> AppResult<Json<serde_json::Value>> {
+    let tail = parse_log_tail(query.tail.as_deref())?;
+
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if !can_access_container(&claims, &container) {
+    if !can_access_container(&claims, &container) && !sub_user_can(&state, &claims, &container, permissions::SUB_CONSOLE).await? {
         return Err(AppError::Unauthorized);
     }
 
@@ -1932,48 +4616,63 @@ pub async fn create_folder(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let client = daemon_client();
-
-    let url = format!("{}/containers/{}/files/folder", daemon.base_url(), container.id);
+    let mut params: Vec<(&str, String)> = Vec::new();
+    if let Some(tail) = tail {
+        params.push(("tail", tail.to_string()));
+    }
+    if let Some(since) = query.since {
+        params.push(("since", since));
+    }
 
-    let resp = client
-        .post(&url)
+    let url = format!("{}/containers/{}/logs", daemon.base_url(), container.id);
+    let resp = daemon_client()
+        .get(&url)
         .header("X-API-Key", &daemon.api_key)
-        .json(&req)
+        .query(&params)
         .send()
         .await
-        .map_err(|e| AppError::BadRequest(format!("Daemon error: {}", e)))?;
+        .map_err(|e| AppError::Daemon(format!("Failed to fetch logs: {}", e)))?;
+
+    if resp.status() == reqwest::StatusCode::BAD_REQUEST {
+        return Err(AppError::BadRequest(resp.text().await.unwrap_or_default()));
+    }
 
     if !resp.status().is_success() {
-        let status = resp.status();
-        let error_text = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(AppError::BadRequest(format!("Daemon returned {}: {}", status, error_text)));
+        let error_text = resp.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to fetch logs: {}", error_text)));
     }
 
-    let result: serde_json::Value = resp.json().await
+    let logs: serde_json::Value = resp.json().await
         .map_err(|e| AppError::BadRequest(format!("Parse error: {}", e)))?;
 
-    Ok(Json(result))
+    Ok(Json(logs))
 }
 
+/// A full log can take a while to stream from a busy daemon.
+const LOG_DOWNLOAD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
 #[derive(Debug, serde::Deserialize)]
-pub struct DeleteFileQuery {
-    pub path: String,
+pub struct LogDownloadQuery {
+    pub since: Option<String>,
 }
 
-pub async fn delete_file(
+/// GET /containers/:id/logs/download - the console log as a text file for bug reports:
+/// the persisted history when there is one, otherwise everything Docker kept.
+pub async fn download_logs(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-    Query(query): Query<DeleteFileQuery>,
-) -> AppResult<Json<serde_json::Value>> {
+    Query(query): Query<LogDownloadQuery>,
+) -> AppResult<axum::response::Response> {
+    use axum::response::IntoResponse;
+
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if !can_access_container(&claims, &container) {
+    if !can_access_container(&claims, &container) && !sub_user_can(&state, &claims, &container, permissions::SUB_CONSOLE).await? {
         return Err(AppError::Unauthorized);
     }
 
@@ -1983,54 +4682,65 @@ pub async fn delete_file(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let client = daemon_client();
-
-    let url = format!("{}/containers/{}/files/delete?path={}", daemon.base_url(), container.id, query.path);
+    let mut params: Vec<(&str, String)> = Vec::new();
+    if let Some(since) = query.since {
+        params.push(("since", since));
+    }
 
-    let resp = client
-        .delete(&url)
+    let url = format!("{}/containers/{}/logs/download", daemon.base_url(), container.id);
+    let resp = DaemonClient::with_timeout(LOG_DOWNLOAD_TIMEOUT)
+        .get(&url)
         .header("X-API-Key", &daemon.api_key)
+        .query(&params)
         .send()
         .await
-        .map_err(|e| AppError::BadRequest(format!("Daemon error: {}", e)))?;
+        .map_err(|e| AppError::Daemon(format!("Failed to download logs: {}", e)))?;
+
+    if resp.status() == reqwest::StatusCode::BAD_REQUEST {
+        return Err(AppError::BadRequest(resp.text().await.unwrap_or_default()));
+    }
 
     if !resp.status().is_success() {
-        let status = resp.status();
-        let error_text = resp.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(AppError::BadRequest(format!("Daemon returned {}: {}", status, error_text)));
+        let error_text = resp.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to download logs: {}", error_text)));
     }
 
-    let result: serde_json::Value = resp.json().await
-        .map_err(|e| AppError::BadRequest(format!("Parse error: {}", e)))?;
+    let safe_name: String = container.name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let file_name = format!("{}-{}.log", safe_name, Utc::now().format("%Y%m%d-%H%M%S"));
 
-    Ok(Json(result))
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+            (axum::http::header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        axum::body::Body::from_stream(resp.bytes_stream()),
+    ).into_response())
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct ContainerStats {
-    pub cpu_percent: f64,
-    pub memory_usage: u64,
-    pub memory_limit: u64,
-    pub memory_percent: f64,
-    pub network_rx: u64,
-    pub network_tx: u64,
-    pub block_read: u64,
-    pub block_write: u64,
+pub struct LogHistoryQuery {
+    pub search: Option<String>,
+    pub limit: Option<usize>,
+    pub include_current: Option<bool>,
 }
 
-pub async fn get_container_stats(
+pub async fn get_log_history(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-) -> AppResult<Json<ContainerStats>> {
+    Query(query): Query<LogHistoryQuery>,
+) -> AppResult<Json<serde_json::Value>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if !can_access_container(&claims, &container) {
+    if !can_access_container(&claims, &container) && !sub_user_can(&state, &claims, &container, permissions::SUB_CONSOLE).await? {
         return Err(AppError::Unauthorized);
     }
 
@@ -2040,24 +4750,37 @@ pub async fn get_container_stats(
         .await?
         .ok_or(AppError::NotFound)?;
 
+    let mut params: Vec<(&str, String)> = Vec::new();
+    if let Some(search) = query.search {
+        params.push(("search", search));
+    }
+    if let Some(limit) = query.limit {
+        params.push(("limit", limit.to_string()));
+    }
+    if let Some(include_current) = query.include_current {
+        params.push(("includeCurrent", include_current.to_string()));
+    }
+
     let client = daemon_client();
-    let url = format!("{}/containers/{}/stats", daemon.base_url(), container.id);
+    let url = format!("{}/containers/{}/logs/history", daemon.base_url(), container.id);
 
     let resp = client
         .get(&url)
         .header("X-API-Key", &daemon.api_key)
+        .query(&params)
         .send()
         .await
-        .map_err(|e| AppError::Daemon(format!("Failed to fetch stats: {}", e)))?;
+        .map_err(|e| AppError::Daemon(format!("Failed to fetch log history: {}", e)))?;
 
     if !resp.status().is_success() {
-        return Err(AppError::Daemon("Failed to fetch container stats".into()));
+        let error_text = resp.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to fetch log history: {}", error_text)));
     }
 
-    let stats: ContainerStats = resp.json().await
+    let entries: serde_json::Value = resp.json().await
         .map_err(|e| AppError::BadRequest(format!("Parse error: {}", e)))?;
 
-    Ok(Json(stats))
+    Ok(Json(entries))
 }
 
 // --- Container Variables ---
@@ -2074,6 +4797,22 @@ pub struct ContainerVariableResponse {
     pub user_editable: bool,
     pub rules: Option<String>,
     pub sort_order: i32,
+    /// `value` is masked in responses and `****` is accepted back as "unchanged".
+    pub secret: bool,
+}
+
+impl ContainerVariableResponse {
+    fn masked(mut self) -> Self {
+        if self.secret {
+            if !self.value.is_empty() {
+                self.value = crate::secrets::MASK.to_string();
+            }
+            if self.default_value.as_deref().is_some_and(|v| !v.is_empty()) {
+                self.default_value = Some(crate::secrets::MASK.to_string());
+            }
+        }
+        self
+    }
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -2095,10 +4834,26 @@ pub async fn get_container_startup(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if container.user_id != claims.sub && !claims.has_permission("containers.view_all") && !claims.is_manager() {
+    if !owns(&claims, &container, permissions::CONTAINERS_VIEW_OWN)? && !claims.has_permission(permissions::CONTAINERS_VIEW_ALL) && !claims.is_manager()
+        && !sub_user_can(&state, &claims, &container, permissions::SUB_STARTUP_EDIT).await?
+    {
         return Err(AppError::Unauthorized);
     }
 
+    let variables = resolve_startup_variables(&state, &container)
+        .await?
+        .into_iter()
+        .map(ContainerVariableResponse::masked)
+        .collect();
+
+    Ok(Json(ContainerStartupResponse {
+        startup_script: container.startup_script,
+        variables,
+    }))
+}
+
+/// Flake variables with their current values: stored, then the daemon's, then the default.
+async fn resolve_startup_variables(state: &AppState, container: &Container) -> AppResult<Vec<ContainerVariableResponse>> {
     let mut variables: Vec<ContainerVariableResponse> = Vec::new();
 
     if let Some(flake_id) = container.flake_id {
@@ -2113,7 +4868,7 @@ pub async fn get_container_startup(
         let stored_values: Vec<(Uuid, String)> = sqlx::query_as(
             "SELECT flake_variable_id, value FROM container_variables WHERE container_id = $1"
         )
-            .bind(id)
+            .bind(container.id)
             .fetch_all(&state.db)
             .await?;
 
@@ -2121,14 +4876,23 @@ pub async fn get_container_startup(
 
         // If no stored values, try to get current values from daemon
         let daemon_env = if stored_map.is_empty() {
-            get_daemon_environment(&state, &container).await.unwrap_or_default()
+            get_daemon_environment(state, container).await.unwrap_or_default()
         } else {
             HashMap::new()
         };
 
-        for var in &flake_vars {
-            let value = stored_map.get(&var.id)
-                .cloned()
+        for var in &flake_vars {
+            let stored = stored_map.get(&var.id).and_then(|value| {
+                match crate::secrets::decrypt(&state.config.secret_key, value) {
+                    Ok(value) => Some(value),
+                    Err(e) => {
+                        tracing::warn!("Ignoring stored value of {} for container {}: {}", var.env_variable, container.id, e);
+                        None
+                    }
+                }
+            });
+
+            let value = stored
                 .or_else(|| daemon_env.get(&var.env_variable).cloned())
                 .unwrap_or_else(|| var.default_value.clone().unwrap_or_default());
 
@@ -2142,13 +4906,74 @@ pub async fn get_container_startup(
                 user_editable: var.user_editable,
                 rules: var.rules.clone(),
                 sort_order: var.sort_order,
+                secret: var.secret,
             });
         }
     }
 
-    Ok(Json(ContainerStartupResponse {
+    Ok(variables)
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupPreviewResponse {
+    pub startup_script: Option<String>,
+    /// The command the daemon will run, with placeholders resolved; `None` without a startup script.
+    pub resolved_command: Option<String>,
+}
+
+/// GET /containers/:id/startup/preview - the startup command with all placeholders resolved
+pub async fn preview_container_startup(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<StartupPreviewResponse>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !owns(&claims, &container, permissions::CONTAINERS_VIEW_OWN)? && !claims.has_permission(permissions::CONTAINERS_VIEW_ALL) && !claims.is_manager()
+        && !sub_user_can(&state, &claims, &container, permissions::SUB_STARTUP_EDIT).await?
+    {
+        return Err(AppError::Unauthorized);
+    }
+
+    // Same environment the daemon builds: flake variables, custom env, allocation and timezone
+    let mut environment: HashMap<String, String> = resolve_startup_variables(&state, &container)
+        .await?
+        .into_iter()
+        .map(ContainerVariableResponse::masked)
+        .map(|v| (v.env_variable, v.value))
+        .collect();
+    environment.extend(fetch_container_env(&state, container.id).await?);
+
+    let primary: Option<(String, i32)> = sqlx::query_as(
+        "SELECT ip, port FROM container_allocations WHERE container_id = $1 AND is_primary = TRUE"
+    )
+        .bind(container.id)
+        .fetch_optional(&state.db)
+        .await?;
+    if let Some((ip, port)) = primary {
+        environment.insert("SERVER_IP".to_string(), ip);
+        environment.insert("SERVER_PORT".to_string(), port.to_string());
+    }
+    environment.insert("TZ".to_string(), container.timezone.clone());
+
+    // Unlimited memory is sized from the daemon host at start, so it stays a placeholder here
+    let server_memory = environment.get("SERVER_MEMORY")
+        .and_then(|v| v.parse::<i64>().ok())
+        .or(container.memory_limit)
+        .filter(|m| *m > 0);
+
+    let resolved_command = container.startup_script.as_deref().map(|script| {
+        raptor_common::startup::replace_startup_placeholders(script, &environment, server_memory)
+    });
+
+    Ok(Json(StartupPreviewResponse {
         startup_script: container.startup_script,
-        variables,
+        resolved_command,
     }))
 }
 
@@ -2196,26 +5021,47 @@ pub struct UpdateContainerStartupRequest {
     pub variables: Option<HashMap<String, String>>,
 }
 
+/// What became of each submitted variable, by env key.
+#[derive(Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariableChanges {
+    pub applied: Vec<String>,
+    /// Not defined by the container's flake.
+    pub ignored: Vec<String>,
+    /// Changed, but not user editable. Resubmitting the current value is not a rejection.
+    pub rejected: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateContainerStartupResponse {
+    #[serde(flatten)]
+    pub startup: ContainerStartupResponse,
+    pub variable_changes: VariableChanges,
+}
+
 /// PUT /containers/:id/startup - update variables and/or startup script
 pub async fn update_container_startup(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
     Json(req): Json<UpdateContainerStartupRequest>,
-) -> AppResult<Json<ContainerStartupResponse>> {
+) -> AppResult<Json<UpdateContainerStartupResponse>> {
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let is_owner = container.user_id == claims.sub;
-    let is_manager = claims.has_permission("containers.manage") || claims.is_manager();
+    let is_owner = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?;
+    let is_manager = claims.has_permission(permissions::CONTAINERS_MANAGE) || claims.is_manager();
 
-    if !is_owner && !is_manager {
+    if !is_owner && !is_manager && !sub_user_can(&state, &claims, &container, permissions::SUB_STARTUP_EDIT).await? {
         return Err(AppError::Unauthorized);
     }
 
+    ensure_not_trashed(&container)?;
+
     let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
@@ -2234,46 +5080,80 @@ pub async fn update_container_startup(
         daemon_payload["startupScript"] = serde_json::json!(startup);
     }
 
+    let mut changes = VariableChanges::default();
+
     // Update variables
     if let Some(ref variables) = req.variables {
-        if let Some(flake_id) = container.flake_id {
-            let flake_vars: Vec<crate::handlers::flakes::FlakeVariable> = sqlx::query_as(
+        let flake_vars: Vec<crate::handlers::flakes::FlakeVariable> = match container.flake_id {
+            Some(flake_id) => sqlx::query_as(
                 "SELECT * FROM flake_variables WHERE flake_id = $1 ORDER BY sort_order"
             )
                 .bind(flake_id)
                 .fetch_all(&state.db)
-                .await?;
+                .await?,
+            None => Vec::new(),
+        };
 
-            let mut env_updates: HashMap<String, String> = HashMap::new();
+        // Forms send every variable back, so locked ones only count as rejected when changed
+        let current: HashMap<String, String> = resolve_startup_variables(&state, &container)
+            .await?
+            .into_iter()
+            .map(|v| (v.env_variable, v.value))
+            .collect();
 
-            for var in &flake_vars {
-                if let Some(new_value) = variables.get(&var.env_variable) {
-                    // Only allow editing user_editable vars (unless manager)
-                    if !var.user_editable && !is_manager {
-                        continue;
-                    }
+        let mut keys: Vec<&String> = variables.keys().collect();
+        keys.sort();
 
-                    // Upsert into container_variables
-                    sqlx::query(
-                        r#"INSERT INTO container_variables (id, container_id, flake_variable_id, value, created_at, updated_at)
-                           VALUES ($1, $2, $3, $4, NOW(), NOW())
-                           ON CONFLICT (container_id, flake_variable_id)
-                           DO UPDATE SET value = $4, updated_at = NOW()"#
-                    )
-                        .bind(Uuid::new_v4())
-                        .bind(id)
-                        .bind(var.id)
-                        .bind(new_value)
-                        .execute(&state.db)
-                        .await?;
-
-                    env_updates.insert(var.env_variable.clone(), new_value.clone());
-                }
+        let mut env_updates: HashMap<String, String> = HashMap::new();
+
+        for key in keys {
+            let new_value = &variables[key];
+
+            let Some(var) = flake_vars.iter().find(|v| &v.env_variable == key) else {
+                changes.ignored.push(key.clone());
+                continue;
+            };
+
+            // The masked value coming back from a form means "keep the current secret"
+            if var.secret && new_value == crate::secrets::MASK {
+                continue;
             }
 
-            if !env_updates.is_empty() {
-                daemon_payload["environment"] = serde_json::json!(env_updates);
+            // Only allow editing user_editable vars (unless manager)
+            if !var.user_editable && !is_manager {
+                if current.get(key) != Some(new_value) {
+                    changes.rejected.push(key.clone());
+                }
+                continue;
             }
+
+            let stored_value = if var.secret {
+                crate::secrets::encrypt(&state.config.secret_key, new_value)
+                    .map_err(|e| AppError::Internal(e.to_string()))?
+            } else {
+                new_value.clone()
+            };
+
+            // Upsert into container_variables
+            sqlx::query(
+                r#"INSERT INTO container_variables (id, container_id, flake_variable_id, value, created_at, updated_at)
+                   VALUES ($1, $2, $3, $4, NOW(), NOW())
+                   ON CONFLICT (container_id, flake_variable_id)
+                   DO UPDATE SET value = $4, updated_at = NOW()"#
+            )
+                .bind(Uuid::new_v4())
+                .bind(id)
+                .bind(var.id)
+                .bind(&stored_value)
+                .execute(&state.db)
+                .await?;
+
+            env_updates.insert(var.env_variable.clone(), new_value.clone());
+            changes.applied.push(key.clone());
+        }
+
+        if !env_updates.is_empty() {
+            daemon_payload["environment"] = serde_json::json!(env_updates);
         }
     }
 
@@ -2296,8 +5176,193 @@ pub async fn update_container_startup(
         }
     }
 
+    if req.startup_script.is_some() || !changes.applied.is_empty() {
+        mark_pending_changes(&state, id).await?;
+    }
+
+    record_activity(&state.db, id, claims.sub, "startup_update", serde_json::json!({ "variables": changes.applied })).await;
+
     // Return updated state
-    get_container_startup(State(state), Extension(claims), Path(id)).await
+    let Json(startup) = get_container_startup(State(state), Extension(claims), Path(id)).await?;
+    Ok(Json(UpdateContainerStartupResponse { startup, variable_changes: changes }))
+}
+
+/// Keys the daemon sets itself or derives from container settings/resources/allocations
+const RESERVED_ENV_KEYS: [&str; 7] = ["HOME", "USER", "STARTUP", "SERVER_MEMORY", "SERVER_PORT", "SERVER_IP", "TZ"];
+
+const DEFAULT_TIMEZONE: &str = "UTC";
+
+fn validate_cpuset(cpuset: &str) -> AppResult<String> {
+    let cpuset = cpuset.trim();
+    if !raptor_common::resources::is_valid_cpuset(cpuset) {
+        return Err(AppError::BadRequest(format!(
+            "Invalid cpuset '{}': expected CPUs or ranges like 0-3,8",
+            cpuset
+        )));
+    }
+    Ok(cpuset.to_string())
+}
+
+/// Checks a timezone against the tz database and returns its canonical name.
+fn validate_timezone(tz: &str) -> AppResult<String> {
+    tz.trim()
+        .parse::<chrono_tz::Tz>()
+        .map(|tz| tz.name().to_string())
+        .map_err(|_| AppError::BadRequest(format!("Unknown timezone '{}'", tz)))
+}
+
+fn validate_env_key(key: &str) -> AppResult<()> {
+    let valid = !key.is_empty()
+        && key.len() <= 255
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if !valid {
+        return Err(AppError::BadRequest(format!(
+            "Invalid environment variable name '{}': use letters, digits and underscores, not starting with a digit",
+            key
+        )));
+    }
+
+    if RESERVED_ENV_KEYS.iter().any(|r| r.eq_ignore_ascii_case(key)) {
+        return Err(AppError::BadRequest(format!("Environment variable '{}' is reserved", key)));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerEnv {
+    pub environment: HashMap<String, String>,
+}
+
+async fn fetch_container_env(state: &AppState, container_id: Uuid) -> AppResult<HashMap<String, String>> {
+    let rows: Vec<(String, String)> = sqlx::query_as("SELECT key, value FROM container_env WHERE container_id = $1")
+        .bind(container_id)
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(rows.into_iter().collect())
+}
+
+/// GET /containers/:id/env - custom environment variables
+pub async fn get_container_env(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ContainerEnv>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    if !can_access_container(&claims, &container) && !sub_user_can(&state, &claims, &container, permissions::SUB_STARTUP_EDIT).await? {
+        return Err(AppError::Unauthorized);
+    }
+
+    let environment = fetch_container_env(&state, container.id).await?;
+
+    Ok(Json(ContainerEnv { environment }))
+}
+
+/// PUT /containers/:id/env - replace custom environment variables, applied on next recreate
+pub async fn update_container_env(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<ContainerEnv>,
+) -> AppResult<Json<ContainerEnv>> {
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let is_owner = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?;
+    let is_manager = claims.has_permission(permissions::CONTAINERS_MANAGE) || claims.is_manager();
+
+    if !is_owner && !is_manager && !sub_user_can(&state, &claims, &container, permissions::SUB_STARTUP_EDIT).await? {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&container)?;
+
+    for key in req.environment.keys() {
+        validate_env_key(key)?;
+    }
+
+    if let Some(flake_id) = container.flake_id {
+        let (flake_key,): (Option<String>,) = sqlx::query_as(
+            "SELECT MIN(env_variable) FROM flake_variables WHERE flake_id = $1 AND env_variable = ANY($2)"
+        )
+            .bind(flake_id)
+            .bind(req.environment.keys().cloned().collect::<Vec<_>>())
+            .fetch_one(&state.db)
+            .await?;
+
+        if let Some(key) = flake_key {
+            return Err(AppError::BadRequest(format!(
+                "'{}' is a flake variable; edit it through the startup settings",
+                key
+            )));
+        }
+    }
+
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let previous = fetch_container_env(&state, container.id).await?;
+    let removed: Vec<&String> = previous.keys().filter(|k| !req.environment.contains_key(*k)).collect();
+
+    // The daemon goes first so a rejected update never leaves the stored environment ahead of it
+    let daemon_payload = serde_json::json!({
+        "environment": req.environment,
+        "removeEnvironment": removed,
+    });
+
+    let res = daemon_client()
+        .patch(format!("{}/containers/{}", daemon.base_url(), container.id))
+        .header("X-API-Key", &daemon.api_key)
+        .json(&daemon_payload)
+        .send()
+        .await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    if !res.status().is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to update environment: {}", error_text)));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query("DELETE FROM container_env WHERE container_id = $1")
+        .bind(container.id)
+        .execute(&mut *tx)
+        .await?;
+
+    for (key, value) in &req.environment {
+        sqlx::query("INSERT INTO container_env (container_id, key, value) VALUES ($1, $2, $3)")
+            .bind(container.id)
+            .bind(key)
+            .bind(value)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    if previous != req.environment {
+        mark_pending_changes(&state, id).await?;
+    }
+
+    record_activity(&state.db, id, claims.sub, "environment_update", serde_json::json!({})).await;
+
+    Ok(Json(ContainerEnv { environment: req.environment }))
 }
 
 pub async fn download_file(
@@ -2312,7 +5377,7 @@ pub async fn download_file(
         .await?
         .ok_or(AppError::NotFound)?;
 
-    if !can_access_container(&claims, &container) {
+    if !can_read_files(&state, &claims, &container).await? {
         return Err(AppError::Unauthorized);
     }
 
@@ -2356,36 +5421,66 @@ pub async fn download_file(
     ).into_response())
 }
 
+/// A full pass over a large world can take a while.
+const FIX_PERMISSIONS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct FixPermissionsQuery {
+    /// Pass the daemon's NDJSON progress lines through instead of waiting for the summary.
+    #[serde(default)]
+    pub stream: bool,
+}
+
+#[derive(Debug, Default, Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixPermissionsRequest {
+    /// Directory inside the container volume; the whole volume when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// `.dockerignore`-style patterns to leave untouched, e.g. `world/region` or `**/*.log`.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// POST /containers/:id/fix-permissions
 pub async fn fix_permissions(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-) -> AppResult<Json<serde_json::Value>> {
+    Query(query): Query<FixPermissionsQuery>,
+    body: Option<Json<FixPermissionsRequest>>,
+) -> AppResult<axum::response::Response> {
+    use axum::response::IntoResponse;
+
+    let req = body.map(|Json(b)| b).unwrap_or_default();
+
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let is_owner = container.user_id == claims.sub;
-    let is_manager = claims.has_permission("containers.manage") || claims.is_manager();
+    let is_owner = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?;
+    let is_manager = claims.has_permission(permissions::CONTAINERS_MANAGE) || claims.is_manager();
 
     if !is_owner && !is_manager {
         return Err(AppError::Unauthorized);
     }
 
+    ensure_not_trashed(&container)?;
+
     let daemon: crate::models::Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    let client = daemon_client();
     let url = format!("{}/containers/{}/fix-permissions", daemon.base_url(), container.id);
 
-    let resp = client
+    let resp = DaemonClient::with_timeout(FIX_PERMISSIONS_TIMEOUT)
         .post(&url)
         .header("X-API-Key", &daemon.api_key)
+        .json(&req)
         .send()
         .await
         .map_err(|e| AppError::Daemon(format!("Fix permissions error: {}", e)))?;
@@ -2395,5 +5490,352 @@ pub async fn fix_permissions(
         return Err(AppError::Daemon(format!("Failed to fix permissions: {}", error_text)));
     }
 
-    Ok(Json(serde_json::json!({"message": "Permissions fixed successfully"})))
+    if query.stream {
+        return Ok((
+            [(axum::http::header::CONTENT_TYPE, "application/x-ndjson")],
+            axum::body::Body::from_stream(resp.bytes_stream()),
+        ).into_response());
+    }
+
+    let text = resp.text().await
+        .map_err(|e| AppError::Daemon(format!("Fix permissions error: {}", e)))?;
+
+    // The last line is the summary, unless the daemon stopped before finishing
+    let summary = text
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .and_then(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|line| line["type"] == "done")
+        .ok_or_else(|| AppError::Daemon("Fix permissions ended without a summary".into()))?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Permissions fixed successfully",
+        "processed": summary["processed"],
+        "changed": summary["changed"],
+        "skipped": summary["skipped"],
+        "failed": summary["failed"],
+        "errors": summary["errors"],
+    })).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Allocation;
+    use crate::test_db::test_db;
+    use axum::response::IntoResponse;
+
+    fn allocation(ip: &str, port: i32, protocol: &str) -> Allocation {
+        Allocation {
+            id: Uuid::new_v4(),
+            daemon_id: Uuid::new_v4(),
+            ip: ip.to_string(),
+            port,
+            protocol: protocol.to_string(),
+            label: None,
+            notes: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn allocations_sent_to_the_daemon_match_the_stored_rows() {
+        let container_id = Uuid::new_v4();
+        let primary = allocation("10.0.0.5", 25565, "tcp");
+        let extra = allocation("10.0.0.5", 25566, "udp");
+
+        let rows = vec![
+            assigned_allocation(container_id, &primary, true),
+            assigned_allocation(container_id, &extra, false),
+        ];
+        let sent = daemon_allocations_json(&rows);
+
+        assert_eq!(sent.len(), rows.len());
+        for (row, json) in rows.iter().zip(&sent) {
+            assert_eq!(row.container_id, container_id);
+            assert_eq!(json["id"], row.id.to_string());
+            assert_eq!(json["allocationId"], row.allocation_id.unwrap().to_string());
+            assert_eq!(json["ip"], row.ip);
+            assert_eq!(json["port"], row.port);
+            assert_eq!(json["internalPort"], row.internal_port);
+            assert_eq!(json["protocol"], row.protocol);
+            assert_eq!(json["isPrimary"], row.is_primary.unwrap());
+        }
+    }
+
+    #[test]
+    fn assigned_allocations_copy_the_allocation() {
+        let container_id = Uuid::new_v4();
+        let source = allocation("2001:db8::1", 27015, "udp");
+        let row = assigned_allocation(container_id, &source, false);
+
+        assert_eq!(row.allocation_id, Some(source.id));
+        assert_eq!((row.ip.as_str(), row.port, row.internal_port), ("2001:db8::1", 27015, 27015));
+        assert_eq!(row.protocol, "udp");
+        assert_eq!(row.is_primary, Some(false));
+    }
+
+    async fn primaries(db: &sqlx::PgPool, container_id: Uuid) -> Vec<Uuid> {
+        let rows: Vec<(Uuid,)> = sqlx::query_as("SELECT id FROM container_allocations WHERE container_id = $1 AND is_primary")
+            .bind(container_id)
+            .fetch_all(db)
+            .await
+            .unwrap();
+        rows.into_iter().map(|(id,)| id).collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn readers_see_exactly_one_primary_while_it_is_swapped() {
+        let db = test_db().await;
+        let user = db.user().await;
+        let daemon = db.daemon().await;
+        let container = db.container(user, daemon).await;
+        let first = db.assign(container, db.allocation(daemon, "10.0.0.1", 25565).await, true).await;
+        let second = db.assign(container, db.allocation(daemon, "10.0.0.1", 25566).await, false).await;
+
+        let pool = db.pool.clone();
+        let swapper = tokio::spawn(async move {
+            for i in 0..50 {
+                let target = if i % 2 == 0 { second } else { first };
+                swap_primary_allocation(&pool, container, target).await.unwrap();
+            }
+        });
+
+        let mut reads = 0;
+        while !swapper.is_finished() {
+            let seen = primaries(&db.pool, container).await;
+            assert_eq!(seen.len(), 1, "saw primaries {:?}", seen);
+            reads += 1;
+        }
+        swapper.await.unwrap();
+
+        assert!(reads > 0);
+        assert_eq!(primaries(&db.pool, container).await, vec![first]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn concurrent_swaps_leave_one_primary() {
+        let db = test_db().await;
+        let user = db.user().await;
+        let daemon = db.daemon().await;
+        let container = db.container(user, daemon).await;
+        let mut rows = Vec::new();
+        for (port, primary) in [(25565, true), (25566, false), (25567, false)] {
+            rows.push(db.assign(container, db.allocation(daemon, "10.0.0.1", port).await, primary).await);
+        }
+
+        for _ in 0..20 {
+            let (a, b) = tokio::join!(
+                swap_primary_allocation(&db.pool, container, rows[1]),
+                swap_primary_allocation(&db.pool, container, rows[2]),
+            );
+            a.unwrap();
+            b.unwrap();
+
+            let seen = primaries(&db.pool, container).await;
+            assert_eq!(seen.len(), 1, "saw primaries {:?}", seen);
+            assert!(seen[0] == rows[1] || seen[0] == rows[2]);
+        }
+    }
+
+    fn key_claims(user: Uuid, scopes: &[&str]) -> Claims {
+        Claims {
+            sub: user,
+            username: user.to_string(),
+            role_id: None,
+            role_name: None,
+            permissions: serde_json::json!(scopes.iter().map(|s| (s.to_string(), true)).collect::<std::collections::HashMap<_, _>>()),
+            token_version: 0,
+            exp: usize::MAX,
+            api_key_id: Some(Uuid::new_v4()),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn a_view_only_key_cannot_act_on_its_owners_container() {
+        let db = test_db().await;
+        let user = db.user().await;
+        let container_id = db.container(user, db.daemon().await).await;
+        let claims = key_claims(user, &[permissions::CONTAINERS_VIEW_OWN]);
+
+        let response = kill_container(State(db.state()), Extension(claims.clone()), Path(container_id))
+            .await
+            .into_response();
+        assert_eq!(response.status(), axum::http::StatusCode::FORBIDDEN);
+
+        let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+            .bind(container_id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert!(can_access_container(&claims, &container));
+        assert!(owns(&key_claims(user, &[permissions::CONTAINERS_MANAGE_OWN]), &container, permissions::CONTAINERS_MANAGE_OWN).unwrap());
+        assert!(!owns(&key_claims(Uuid::new_v4(), &[]), &container, permissions::CONTAINERS_MANAGE_OWN).unwrap());
+    }
+
+    /// A daemon whose only route records the containers it is asked to delete.
+    async fn mock_daemon() -> (Daemon, std::sync::Arc<std::sync::Mutex<Vec<Uuid>>>) {
+        let deleted = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = deleted.clone();
+        let app = axum::Router::new().route(
+            "/containers/:id",
+            axum::routing::delete(move |Path(id): Path<Uuid>| async move {
+                recorder.lock().unwrap().push(id);
+                Json(serde_json::json!({ "success": true }))
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (test_daemon(port), deleted)
+    }
+
+    fn test_daemon(port: u16) -> Daemon {
+        Daemon {
+            id: Uuid::new_v4(),
+            name: "test".into(),
+            host: "127.0.0.1".into(),
+            port: port as i32,
+            api_key: "key".into(),
+            location: None,
+            secure: false,
+            maintenance: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// Stores a container whose primary allocation another container already holds, so the
+    /// transaction fails after the containers row went in.
+    async fn store_conflicting_container(db: &sqlx::PgPool, container_id: Uuid, user: Uuid, daemon: Uuid, allocation: Uuid) -> AppResult<Container> {
+        let allocation: Allocation = sqlx::query_as("SELECT * FROM allocations WHERE id = $1")
+            .bind(allocation)
+            .fetch_one(db)
+            .await?;
+        let req: CreateContainerRequest = serde_json::from_value(serde_json::json!({
+            "daemonId": daemon,
+            "name": container_id.to_string(),
+            "image": "test",
+        })).unwrap();
+        let row = NewContainer {
+            id: container_id,
+            user_id: user,
+            daemon_id: daemon,
+            flake_id: None,
+            image: "test",
+            startup_script: None,
+            stop_command: "stop",
+            cpu_limit: rust_decimal::Decimal::ONE,
+            sftp_user: "test",
+            timezone: DEFAULT_TIMEZONE,
+            cpuset_cpus: None,
+            restart_policy: "unless-stopped",
+            read_only: false,
+        };
+
+        insert_created_container(db, &row, &req, &[assigned_allocation(container_id, &allocation, true)]).await
+    }
+
+    async fn stored(db: &sqlx::PgPool, container_id: Uuid) -> bool {
+        let (exists,): (bool,) = sqlx::query_as("SELECT EXISTS (SELECT 1 FROM containers WHERE id = $1)")
+            .bind(container_id)
+            .fetch_one(db)
+            .await
+            .unwrap();
+        exists
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn a_failed_insert_rolls_back_and_removes_the_daemon_container() {
+        let db = test_db().await;
+        let user = db.user().await;
+        let daemon_id = db.daemon().await;
+        let allocation = db.allocation(daemon_id, "10.0.0.1", 25565).await;
+        db.assign(db.container(user, daemon_id).await, allocation, true).await;
+        let (daemon, deleted) = mock_daemon().await;
+
+        let container_id = Uuid::new_v4();
+        let result = store_created_container(
+            &daemon,
+            container_id,
+            store_conflicting_container(&db.pool, container_id, user, daemon_id, allocation),
+        ).await;
+
+        assert!(matches!(&result, Err(AppError::BadRequest(msg)) if msg == "allocation already in use"), "{:?}", result);
+        assert!(!stored(&db.pool, container_id).await);
+        assert_eq!(*deleted.lock().unwrap(), vec![container_id]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn a_failed_insert_keeps_its_error_when_the_daemon_cannot_remove_the_container() {
+        let db = test_db().await;
+        let user = db.user().await;
+        let daemon_id = db.daemon().await;
+        let allocation = db.allocation(daemon_id, "10.0.0.1", 25565).await;
+        db.assign(db.container(user, daemon_id).await, allocation, true).await;
+
+        // Nothing listens on a port that was just released
+        let port = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let daemon = test_daemon(port);
+
+        let container_id = Uuid::new_v4();
+        let result = store_created_container(
+            &daemon,
+            container_id,
+            store_conflicting_container(&db.pool, container_id, user, daemon_id, allocation),
+        ).await;
+
+        assert!(matches!(&result, Err(AppError::BadRequest(msg)) if msg == "allocation already in use"), "{:?}", result);
+        assert!(!stored(&db.pool, container_id).await);
+    }
+
+    #[tokio::test]
+    async fn a_successful_insert_leaves_the_daemon_container() {
+        let (daemon, deleted) = mock_daemon().await;
+
+        let result = store_created_container(&daemon, Uuid::new_v4(), async { Ok::<_, AppError>(7) }).await;
+
+        assert_eq!(result.unwrap(), 7);
+        assert!(deleted.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn cpu_limits_round_to_the_stored_precision() {
+        let (limit, stored) = validate_cpu_limit(1.234).unwrap();
+        assert_eq!(limit, 1.23);
+        assert_eq!(stored.to_string(), "1.23");
+
+        assert!(validate_cpu_limit(MIN_CPU_LIMIT).is_ok());
+        assert!(validate_cpu_limit(MAX_CPU_LIMIT).is_ok());
+    }
+
+    #[test]
+    fn cpu_limits_reject_nan_and_infinity() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(matches!(validate_cpu_limit(value), Err(AppError::BadRequest(_))), "{} should be rejected", value);
+        }
+    }
+
+    #[test]
+    fn cpu_limits_reject_zero_and_negative_values() {
+        for value in [0.0, -0.0, 0.001, -1.0, -f64::MAX] {
+            assert!(matches!(validate_cpu_limit(value), Err(AppError::BadRequest(_))), "{} should be rejected", value);
+        }
+    }
+
+    #[test]
+    fn cpu_limits_reject_values_past_the_column() {
+        for value in [100.0, 1e9, f64::MAX] {
+            assert!(matches!(validate_cpu_limit(value), Err(AppError::BadRequest(_))), "{} should be rejected", value);
+        }
+    }
 }
@@ -1,13 +1,16 @@
 use axum::{
     extract::{Path, State},
+    http::HeaderMap,
     Json,
 };
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::daemon_client::DaemonClient;
 use crate::error::{AppError, AppResult};
-use crate::models::{AppState, CreateDaemonRequest, Daemon};
+use crate::models::{AppState, Container, CreateDaemonRequest, Daemon};
+use crate::notifications;
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -19,6 +22,7 @@ pub struct DaemonResponse {
     pub api_key: String,
     pub location: Option<String>,
     pub secure: bool,
+    pub maintenance: bool,
     pub status: String,
     pub created_at: chrono::DateTime<Utc>,
     pub updated_at: chrono::DateTime<Utc>,
@@ -42,6 +46,21 @@ pub struct SystemResources {
     pub total_disk: u64,
     pub available_disk: u64,
     pub hostname: String,
+    #[serde(default)]
+    pub load_average: Option<LoadAverage>,
+    #[serde(default)]
+    pub running_containers: Option<usize>,
+    #[serde(default)]
+    pub allocated_memory: Option<u64>,
+    #[serde(default)]
+    pub allocated_cpu: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -52,36 +71,38 @@ pub struct UpdateDaemonRequest {
     pub port: Option<i32>,
     pub location: Option<String>,
     pub secure: Option<bool>,
+    /// Set to false to take a drained daemon out of maintenance.
+    pub maintenance: Option<bool>,
+}
+
+/// Older daemons answer `/health` with plain text, which is treated as Docker being reachable.
+#[derive(Debug, Deserialize)]
+struct DaemonHealth {
+    docker: bool,
 }
 
 async fn check_daemon_status(host: &str, port: i32, api_key: &str, secure: bool) -> (String, Option<SystemResources>) {
-    let client = match reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .danger_accept_invalid_certs(true)
-        .build()
-    {
-        Ok(c) => c,
-        Err(_) => return ("offline".to_string(), None),
-    };
+    let client = DaemonClient::with_timeout(std::time::Duration::from_secs(5));
 
     let scheme = if secure { "https" } else { "http" };
     let health_url = format!("{}://{}:{}/health", scheme, host, port);
-    let health_ok = match client.get(&health_url).header("X-API-Key", api_key).send().await {
-        Ok(resp) => resp.status().is_success(),
-        Err(_) => false,
+    let docker_ok = match client.get(&health_url).header("X-API-Key", api_key).send().await {
+        Ok(resp) if resp.status().is_success() => resp
+            .json::<DaemonHealth>()
+            .await
+            .map(|h| h.docker)
+            .unwrap_or(true),
+        _ => return ("offline".to_string(), None),
     };
 
-    if !health_ok {
-        return ("offline".to_string(), None);
-    }
-
     let system_url = format!("{}://{}:{}/system", scheme, host, port);
     let system = match client.get(&system_url).header("X-API-Key", api_key).send().await {
         Ok(resp) if resp.status().is_success() => resp.json::<SystemResources>().await.ok(),
         _ => None,
     };
 
-    ("online".to_string(), system)
+    let status = if docker_ok { "online" } else { "degraded" };
+    (status.to_string(), system)
 }
 
 pub async fn list_daemons(State(state): State<AppState>) -> AppResult<Json<Vec<DaemonResponse>>> {
@@ -99,6 +120,7 @@ pub async fn list_daemons(State(state): State<AppState>) -> AppResult<Json<Vec<D
             api_key: daemon.api_key,
             location: daemon.location,
             secure: daemon.secure,
+            maintenance: daemon.maintenance,
             status: "unknown".to_string(),
             created_at: daemon.created_at,
             updated_at: daemon.updated_at,
@@ -156,6 +178,7 @@ pub async fn create_daemon(
         api_key: daemon.api_key,
         location: daemon.location,
         secure: daemon.secure,
+        maintenance: daemon.maintenance,
         status: "unknown".to_string(),
         created_at: daemon.created_at,
         updated_at: daemon.updated_at,
@@ -180,6 +203,7 @@ pub async fn get_daemon(
         api_key: daemon.api_key,
         location: daemon.location,
         secure: daemon.secure,
+        maintenance: daemon.maintenance,
         status: "unknown".to_string(),
         created_at: daemon.created_at,
         updated_at: daemon.updated_at,
@@ -199,6 +223,7 @@ pub async fn update_daemon(
             port = COALESCE($4, port),
             location = COALESCE($5, location),
             secure = COALESCE($6, secure),
+            maintenance = COALESCE($7, maintenance),
             updated_at = NOW()
         WHERE id = $1
         RETURNING *
@@ -210,6 +235,7 @@ pub async fn update_daemon(
     .bind(req.port)
     .bind(&req.location)
     .bind(req.secure)
+    .bind(req.maintenance)
     .fetch_optional(&state.db)
     .await?
     .ok_or(AppError::NotFound)?;
@@ -222,6 +248,7 @@ pub async fn update_daemon(
         api_key: daemon.api_key,
         location: daemon.location,
         secure: daemon.secure,
+        maintenance: daemon.maintenance,
         status: "unknown".to_string(),
         created_at: daemon.created_at,
         updated_at: daemon.updated_at,
@@ -244,6 +271,335 @@ pub async fn delete_daemon(
     Ok(Json(serde_json::json!({"message": "Daemon deleted successfully"})))
 }
 
+const CAPACITY_CONCURRENCY: usize = 8;
+
+const MB: u64 = 1024 * 1024;
+
+/// A daemon's host resources next to what its containers have been promised. Memory and disk
+/// are in bytes, CPU in cores; host figures are `None` while the daemon is unreachable.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonCapacity {
+    pub daemon_id: Uuid,
+    pub name: String,
+    pub location: Option<String>,
+    pub status: String,
+    pub maintenance: bool,
+    pub containers: i64,
+    pub total_memory: Option<u64>,
+    pub total_cpu: Option<f64>,
+    pub total_disk: Option<u64>,
+    /// Sums of container limits; unlimited containers don't count.
+    pub allocated_memory: u64,
+    pub allocated_cpu: f64,
+    pub allocated_disk: u64,
+    pub available_memory: Option<u64>,
+    pub available_cpu: Option<f64>,
+    pub available_disk: Option<u64>,
+}
+
+impl DaemonCapacity {
+    /// Whether a container with these limits (MB, cores; `UNLIMITED` needs nothing) fits.
+    fn fits(&self, memory_limit: i64, cpu_limit: f64, disk_limit: i64) -> bool {
+        let (Some(memory), Some(cpu), Some(disk)) = (self.available_memory, self.available_cpu, self.available_disk) else {
+            return false;
+        };
+
+        memory >= memory_limit.max(0) as u64 * MB && cpu >= cpu_limit && disk >= disk_limit.max(0) as u64 * MB
+    }
+}
+
+async fn daemon_capacities(state: &AppState) -> AppResult<Vec<DaemonCapacity>> {
+    use futures_util::StreamExt;
+
+    let daemons: Vec<Daemon> = sqlx::query_as("SELECT * FROM daemons ORDER BY created_at")
+        .fetch_all(&state.db)
+        .await?;
+
+    let allocated: Vec<(Uuid, i64, i64, f64, i64)> = sqlx::query_as(
+        r#"SELECT daemon_id,
+                  COUNT(*),
+                  COALESCE(SUM(memory_limit) FILTER (WHERE memory_limit > 0), 0)::BIGINT,
+                  COALESCE(SUM(cpu_limit), 0)::FLOAT8,
+                  COALESCE(SUM(disk_limit) FILTER (WHERE disk_limit > 0), 0)::BIGINT
+           FROM containers
+           WHERE deleted_at IS NULL
+           GROUP BY daemon_id"#
+    )
+        .fetch_all(&state.db)
+        .await?;
+
+    let capacities = futures_util::stream::iter(daemons)
+        .map(|daemon| {
+            let allocated = allocated.iter().find(|row| row.0 == daemon.id).copied();
+            async move {
+                let (status, system) = check_daemon_status(&daemon.host, daemon.port, &daemon.api_key, daemon.secure).await;
+                let (_, containers, memory_mb, cpu, disk_mb) = allocated.unwrap_or((daemon.id, 0, 0, 0.0, 0));
+                let allocated_memory = memory_mb as u64 * MB;
+                let allocated_disk = disk_mb as u64 * MB;
+
+                DaemonCapacity {
+                    daemon_id: daemon.id,
+                    name: daemon.name,
+                    location: daemon.location,
+                    status,
+                    maintenance: daemon.maintenance,
+                    containers,
+                    total_memory: system.as_ref().map(|s| s.total_memory),
+                    total_cpu: system.as_ref().map(|s| s.cpu_cores as f64),
+                    total_disk: system.as_ref().map(|s| s.total_disk),
+                    allocated_memory,
+                    allocated_cpu: cpu,
+                    allocated_disk,
+                    available_memory: system.as_ref().map(|s| s.total_memory.saturating_sub(allocated_memory)),
+                    available_cpu: system.as_ref().map(|s| (s.cpu_cores as f64 - cpu).max(0.0)),
+                    available_disk: system.as_ref().map(|s| s.total_disk.saturating_sub(allocated_disk)),
+                }
+            }
+        })
+        .buffered(CAPACITY_CONCURRENCY)
+        .collect()
+        .await;
+
+    Ok(capacities)
+}
+
+/// GET /daemons/capacity - host resources and allocated container limits of every daemon
+pub async fn get_daemon_capacity(State(state): State<AppState>) -> AppResult<Json<Vec<DaemonCapacity>>> {
+    Ok(Json(daemon_capacities(&state).await?))
+}
+
+/// The online, non-maintenance daemon a new container fits on with the most memory left over.
+pub async fn pick_daemon(state: &AppState, memory_limit: i64, cpu_limit: f64, disk_limit: i64) -> AppResult<Uuid> {
+    daemon_capacities(state)
+        .await?
+        .into_iter()
+        .filter(|c| c.status == "online" && !c.maintenance && c.fits(memory_limit, cpu_limit, disk_limit))
+        .max_by_key(|c| c.available_memory)
+        .map(|c| c.daemon_id)
+        .ok_or_else(|| AppError::BadRequest("No online daemon has enough free capacity for this container".into()))
+}
+
+const DRAIN_CONCURRENCY: usize = 4;
+const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 300;
+const MAX_DRAIN_TIMEOUT_SECS: u64 = 1800;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DrainDaemonRequest {
+    /// How long each server gets to save and exit after its stop command before Docker stops it.
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DrainResult {
+    pub container_id: Uuid,
+    pub name: String,
+    pub success: bool,
+    /// False when the server ignored its stop command and had to be stopped with a signal.
+    pub clean: bool,
+    pub error: Option<String>,
+}
+
+/// One drain of a daemon; `results` fills in as its containers stop.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonDrain {
+    pub id: Uuid,
+    pub daemon_id: Uuid,
+    pub status: String,
+    pub timeout_secs: i32,
+    pub total: i32,
+    pub results: serde_json::Value,
+    pub started_at: chrono::DateTime<Utc>,
+    pub finished_at: Option<chrono::DateTime<Utc>>,
+}
+
+/// POST /admin/daemons/:id/drain - put a daemon in maintenance and gracefully stop everything on
+/// it in the background; poll `GET` on the same path for progress
+pub async fn drain_daemon(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    body: Option<Json<DrainDaemonRequest>>,
+) -> AppResult<Json<DaemonDrain>> {
+    let req = body.map(|Json(r)| r).unwrap_or_default();
+    let timeout_secs = req.timeout_secs.unwrap_or(DEFAULT_DRAIN_TIMEOUT_SECS);
+    if timeout_secs == 0 || timeout_secs > MAX_DRAIN_TIMEOUT_SECS {
+        return Err(AppError::BadRequest(format!(
+            "timeoutSecs must be between 1 and {}", MAX_DRAIN_TIMEOUT_SECS
+        )));
+    }
+
+    let running_drain: Option<(Uuid,)> = sqlx::query_as(
+        "SELECT id FROM daemon_drains WHERE daemon_id = $1 AND status = 'running'"
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?;
+    if running_drain.is_some() {
+        return Err(AppError::BadRequest("This daemon is already being drained".into()));
+    }
+
+    // Maintenance first so nothing new lands on the daemon while it drains
+    let daemon: Daemon = sqlx::query_as(
+        "UPDATE daemons SET maintenance = TRUE, updated_at = NOW() WHERE id = $1 RETURNING *"
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let containers: Vec<Container> = sqlx::query_as("SELECT * FROM containers WHERE daemon_id = $1 ORDER BY name")
+        .bind(id)
+        .fetch_all(&state.db)
+        .await?;
+
+    let live = crate::reconciler::fetch_daemon_statuses(&daemon).await;
+    let running: Vec<Container> = containers
+        .into_iter()
+        .filter(|c| match &live {
+            Some(statuses) => statuses.get(&c.id).map(|s| s.running).unwrap_or(false),
+            None => c.status != "stopped" && c.status != "crash_looping",
+        })
+        .collect();
+
+    let drain: DaemonDrain = sqlx::query_as(
+        "INSERT INTO daemon_drains (id, daemon_id, timeout_secs, total) VALUES ($1, $2, $3, $4) RETURNING *"
+    )
+    .bind(Uuid::new_v4())
+    .bind(daemon.id)
+    .bind(timeout_secs as i32)
+    .bind(running.len() as i32)
+    .fetch_one(&state.db)
+    .await?;
+
+    tracing::info!("Draining daemon {}: stopping {} containers", daemon.name, running.len());
+
+    tokio::spawn(run_drain(state.clone(), daemon, drain.id, running, timeout_secs));
+
+    Ok(Json(drain))
+}
+
+/// GET /admin/daemons/:id/drain - the daemon's most recent drain
+pub async fn get_daemon_drain(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<DaemonDrain>> {
+    let drain: DaemonDrain = sqlx::query_as(
+        "SELECT * FROM daemon_drains WHERE daemon_id = $1 ORDER BY started_at DESC LIMIT 1"
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    Ok(Json(drain))
+}
+
+/// Drains cut short by an API restart would otherwise block new ones forever.
+pub async fn interrupt_stale_drains(db: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let interrupted = sqlx::query(
+        "UPDATE daemon_drains SET status = 'interrupted', finished_at = NOW() WHERE status = 'running'"
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+
+    if interrupted > 0 {
+        tracing::warn!("Marked {} unfinished daemon drains as interrupted", interrupted);
+    }
+    Ok(())
+}
+
+async fn run_drain(state: AppState, daemon: Daemon, drain_id: Uuid, containers: Vec<Container>, timeout_secs: u64) {
+    use futures_util::StreamExt;
+
+    let client = DaemonClient::with_timeout(
+        std::time::Duration::from_secs(timeout_secs + 60)
+    );
+
+    futures_util::stream::iter(containers)
+        .for_each_concurrent(DRAIN_CONCURRENCY, |container| {
+            let state = &state;
+            let daemon = &daemon;
+            let client = &client;
+            async move {
+                let result = drain_container(state, client, daemon, &container, timeout_secs).await;
+                let (success, clean, error) = match result {
+                    Ok(clean) => (true, clean, None),
+                    Err(e) => {
+                        tracing::warn!("Failed to stop {} ({}) while draining: {}", container.name, container.id, e);
+                        (false, false, Some(e.to_string()))
+                    }
+                };
+                let result = DrainResult { container_id: container.id, name: container.name, success, clean, error };
+
+                if let Err(e) = sqlx::query("UPDATE daemon_drains SET results = results || $2 WHERE id = $1")
+                    .bind(drain_id)
+                    .bind(serde_json::json!([result]))
+                    .execute(&state.db)
+                    .await
+                {
+                    tracing::error!("Failed to record drain result for {}: {}", result.container_id, e);
+                }
+            }
+        })
+        .await;
+
+    if let Err(e) = sqlx::query("UPDATE daemon_drains SET status = 'finished', finished_at = NOW() WHERE id = $1")
+        .bind(drain_id)
+        .execute(&state.db)
+        .await
+    {
+        tracing::error!("Failed to finish drain {}: {}", drain_id, e);
+    }
+
+    tracing::info!("Finished draining daemon {}", daemon.name);
+}
+
+async fn drain_container(
+    state: &AppState,
+    client: &DaemonClient,
+    daemon: &Daemon,
+    container: &Container,
+    timeout_secs: u64,
+) -> AppResult<bool> {
+    sqlx::query("UPDATE containers SET status = 'stopping', updated_at = NOW() WHERE id = $1")
+        .bind(container.id)
+        .execute(&state.db)
+        .await?;
+
+    let url = format!("{}/containers/{}/graceful-stop", daemon.base_url(), container.id);
+    let res = client
+        .post(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .json(&serde_json::json!({
+            "stopCommand": container.stop_command.clone().unwrap_or_else(|| "stop".to_string()),
+            "timeoutSecs": timeout_secs
+        }))
+        .send()
+        .await
+        .map_err(|e| AppError::Daemon(e.to_string()))?;
+
+    if !res.status().is_success() {
+        let error_text = res.text().await.unwrap_or_default();
+        return Err(AppError::Daemon(format!("Failed to stop: {}", error_text)));
+    }
+
+    let body: serde_json::Value = res.json().await.unwrap_or_default();
+    // Clean only when the server shut down through its stop command, not a signal
+    let stage = body.get("stage").and_then(|v| v.as_str()).unwrap_or_default();
+    let clean = matches!(stage, "command" | "already_stopped");
+
+    sqlx::query("UPDATE containers SET status = 'stopped', updated_at = NOW() WHERE id = $1")
+        .bind(container.id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(clean)
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PingDaemonRequest {
@@ -267,20 +623,7 @@ pub struct PingDaemonResponse {
 pub async fn ping_daemon(
     Json(req): Json<PingDaemonRequest>,
 ) -> AppResult<Json<PingDaemonResponse>> {
-    let client = match reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .danger_accept_invalid_certs(true)
-        .build()
-    {
-        Ok(c) => c,
-        Err(e) => return Ok(Json(PingDaemonResponse {
-            online: false,
-            latency_ms: None,
-            version: None,
-            system: None,
-            error: Some(format!("Failed to create HTTP client: {}", e)),
-        })),
-    };
+    let client = DaemonClient::with_timeout(std::time::Duration::from_secs(5));
 
     let scheme = if req.secure { "https" } else { "http" };
     let health_url = format!("{}://{}:{}/health", scheme, req.host, req.port);
@@ -332,6 +675,154 @@ pub async fn ping_daemon(
     }
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonEvent {
+    pub container_id: Uuid,
+    pub event: String,
+    #[serde(default)]
+    pub details: serde_json::Value,
+    /// When the daemon saw it; older daemons don't send it
+    #[serde(default)]
+    pub occurred_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// POST /daemon/events - container events reported by daemons, authenticated with the daemon's API key
+pub async fn receive_daemon_event(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(event): Json<DaemonEvent>,
+) -> AppResult<Json<serde_json::Value>> {
+    let api_key = headers
+        .get("X-API-Key")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE api_key = $1")
+        .bind(api_key)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    // A daemon may only report on containers it hosts
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1 AND daemon_id = $2")
+        .bind(event.container_id)
+        .bind(daemon.id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let (title, message) = match event.event.as_str() {
+        notifications::CONTAINER_STATE => {
+            let transition = event.details.get("transition").and_then(|v| v.as_str()).unwrap_or_default();
+            let status = match transition {
+                "started" => "running",
+                "stopped" | "died" | "start_timeout" => "stopped",
+                // Docker follows an OOM kill with a "die", which carries the final state
+                "oom" => return Ok(Json(serde_json::json!({ "success": true }))),
+                // Readiness is read live from the daemon; the stored status stays "running"
+                "ready" => {
+                    tracing::debug!("Daemon {} reported container {} ready", daemon.name, container.id);
+                    return Ok(Json(serde_json::json!({ "success": true })));
+                }
+                other => return Err(AppError::BadRequest(format!("Unknown transition '{}'", other))),
+            };
+
+            // A crash looping container stays flagged until it is running again, and an event
+            // older than the one behind the current status is stale
+            sqlx::query(
+                r#"UPDATE containers SET status = $1, status_event_at = COALESCE($3, status_event_at), updated_at = NOW()
+                   WHERE id = $2
+                     AND NOT (status = 'crash_looping' AND $1 = 'stopped')
+                     AND ($3 IS NULL OR status_event_at IS NULL OR status_event_at <= $3)"#
+            )
+                .bind(status)
+                .bind(container.id)
+                .bind(event.occurred_at)
+                .execute(&state.db)
+                .await?;
+
+            if transition == "start_timeout" {
+                let timeout = event.details.get("timeoutSecs").and_then(|v| v.as_u64()).unwrap_or_default();
+                tracing::info!("Daemon {} stopped container {} after its {}s start timeout", daemon.name, container.id, timeout);
+                let message = format!(
+                    "Your server \"{}\" did not finish starting within {} seconds and was stopped.", container.name, timeout
+                );
+                notifications::notify_user(&state, container.user_id, notifications::CONTAINER_SUSPENDED, "Server failed to start", &message).await;
+            }
+
+            tracing::debug!("Daemon {} reported container {} {}", daemon.name, container.id, transition);
+            return Ok(Json(serde_json::json!({ "success": true })));
+        }
+        notifications::CONTAINER_CRASHED => {
+            let exit_code = event.details.get("exitCode").and_then(|v| v.as_i64());
+            (
+                "Server crashed",
+                match exit_code {
+                    Some(code) => format!("Your server \"{}\" stopped unexpectedly with exit code {}.", container.name, code),
+                    None => format!("Your server \"{}\" stopped unexpectedly.", container.name),
+                },
+            )
+        }
+        notifications::INSTALL_COMPLETED => {
+            let success = event.details.get("success").and_then(|v| v.as_bool()).unwrap_or(true);
+            if success {
+                ("Installation finished", format!("Your server \"{}\" has finished installing and is ready to start.", container.name))
+            } else {
+                let error = event.details.get("error").and_then(|v| v.as_str()).unwrap_or("unknown error");
+                ("Installation failed", format!("Installing your server \"{}\" failed: {}.", container.name, error))
+            }
+        }
+        notifications::CONTAINER_SUSPENDED => {
+            let reason = event.details.get("reason").and_then(|v| v.as_str()).unwrap_or("repeated failures");
+
+            if event.details.get("crashLooping").and_then(|v| v.as_bool()).unwrap_or(false) {
+                sqlx::query(
+                    r#"UPDATE containers SET status = 'crash_looping', status_event_at = COALESCE($2, status_event_at), updated_at = NOW()
+                       WHERE id = $1 AND ($2 IS NULL OR status_event_at IS NULL OR status_event_at <= $2)"#
+                )
+                    .bind(container.id)
+                    .bind(event.occurred_at)
+                    .execute(&state.db)
+                    .await?;
+            }
+
+            ("Server suspended", format!("Your server \"{}\" was suspended: {}.", container.name, reason))
+        }
+        notifications::RESOURCE_ALERT => {
+            let metric = event.details.get("metric").and_then(|v| v.as_str()).unwrap_or_default();
+            if !matches!(metric, "cpu" | "memory") {
+                return Err(AppError::BadRequest(format!("Unknown alert metric '{}'", metric)));
+            }
+            let percent = event.details.get("percent").and_then(|v| v.as_f64()).unwrap_or_default();
+            let threshold = event.details.get("threshold").and_then(|v| v.as_f64()).unwrap_or_default();
+            let window_secs = event.details.get("windowSecs").and_then(|v| v.as_i64()).unwrap_or_default();
+
+            sqlx::query(
+                "INSERT INTO container_alerts (container_id, metric, percent, threshold, window_secs) VALUES ($1, $2, $3, $4, $5)"
+            )
+                .bind(container.id)
+                .bind(metric)
+                .bind(percent)
+                .bind(threshold)
+                .bind(window_secs as i32)
+                .execute(&state.db)
+                .await?;
+
+            tracing::info!(
+                "Daemon {} reported {} usage of container {} at {}% (threshold {}%)", daemon.name, metric, container.id, percent, threshold
+            );
+            return Ok(Json(serde_json::json!({ "success": true })));
+        }
+        other => return Err(AppError::BadRequest(format!("Unknown event '{}'", other))),
+    };
+
+    tracing::info!("Daemon {} reported {} for container {}", daemon.name, event.event, container.id);
+    notifications::notify_user(&state, container.user_id, &event.event, title, &message).await;
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
 pub async fn ws_daemon_stats(
     State(state): State<AppState>,
     ws: axum::extract::ws::WebSocketUpgrade,
@@ -342,69 +833,48 @@ pub async fn ws_daemon_stats(
 async fn handle_daemon_stats_socket(socket: axum::extract::ws::WebSocket, state: AppState) {
     use axum::extract::ws::Message;
     use futures_util::{SinkExt, StreamExt};
-    use tokio_tungstenite::tungstenite::Message as TungMessage;
 
     let (mut sender, mut receiver) = socket.split();
+    let keepalive = crate::keepalive::Keepalive::new(state.config.ws_idle_timeout_secs);
+    let (latest, mut rx) = crate::daemon_stats::DaemonStatsHub::subscribe(&state);
 
-    let daemons: Vec<Daemon> = match sqlx::query_as("SELECT * FROM daemons")
-        .fetch_all(&state.db)
-        .await
-    {
-        Ok(d) => d,
-        Err(_) => return,
-    };
-
-    let mut daemon_sockets: std::collections::HashMap<Uuid, tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>> = std::collections::HashMap::new();
-
-    for daemon in &daemons {
-        let ws_protocol = if daemon.secure { "wss" } else { "ws" };
-        let ws_url = format!(
-            "{}://{}:{}/ws/system?api_key={}",
-            ws_protocol, daemon.host, daemon.port, daemon.api_key
-        );
-
-        match tokio_tungstenite::connect_async(&ws_url).await {
-            Ok((ws_stream, _)) => {
-                daemon_sockets.insert(daemon.id, ws_stream);
-            }
-            Err(e) => {
-                tracing::warn!("Failed to connect to daemon {} WebSocket: {}", daemon.id, e);
+    let send_task = async {
+        for message in latest {
+            if sender.send(Message::Text(message)).await.is_err() {
+                return;
             }
         }
-    }
-
-    let daemon_ids: Vec<Uuid> = daemon_sockets.keys().cloned().collect();
 
-    let send_task = tokio::spawn(async move {
-        let mut interval = tokio::time::interval(std::time::Duration::from_millis(100));
+        let mut ping = crate::keepalive::Keepalive::interval();
 
         loop {
-            interval.tick().await;
-
-            for daemon_id in &daemon_ids {
-                if let Some(ws) = daemon_sockets.get_mut(daemon_id) {
-                    if let Some(Ok(TungMessage::Text(text))) = ws.next().await {
-                        let response = serde_json::json!({
-                            "daemonId": daemon_id.to_string(),
-                            "system": serde_json::from_str::<serde_json::Value>(&text).unwrap_or_default()
-                        });
-
-                        if sender.send(Message::Text(response.to_string())).await.is_err() {
-                            return;
+            tokio::select! {
+                message = rx.recv() => match message {
+                    Ok(message) => {
+                        if sender.send(Message::Text(message)).await.is_err() {
+                            break;
                         }
                     }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                },
+                _ = ping.tick() => {
+                    if !keepalive.ping(&mut sender).await {
+                        break;
+                    }
                 }
             }
         }
-    });
+    };
 
-    let recv_task = tokio::spawn(async move {
+    let recv_task = async {
         while let Some(Ok(msg)) = receiver.next().await {
+            keepalive.seen(&msg);
             if matches!(msg, Message::Close(_)) {
                 break;
             }
         }
-    });
+    };
 
     tokio::select! {
         _ = send_task => {},
@@ -14,12 +14,8 @@ use crate::{
     },
 };
 
-fn daemon_client() -> reqwest::Client {
-    reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)
-        .timeout(std::time::Duration::from_secs(60))
-        .build()
-        .unwrap_or_else(|_| reqwest::Client::new())
+fn daemon_client() -> crate::daemon_client::DaemonClient {
+    crate::daemon_client::DaemonClient::with_timeout(std::time::Duration::from_secs(60))
 }
 
 pub async fn list_databases(
@@ -101,6 +97,9 @@ pub async fn create_database(
     let db_user = format!("u_{}", &Uuid::new_v4().to_string().replace("-", "")[..12]);
     let db_password = generate_password(24);
 
+    validate_identifier(&db_name)?;
+    validate_identifier(&db_user)?;
+
     let client = daemon_client();
     let daemon_url = format!("{}/database-servers/{}/databases", daemon.base_url(), server.id);
 
@@ -269,6 +268,9 @@ pub async fn delete_database(
         .await?;
 
         if let Some(daemon) = daemon {
+            validate_identifier(&database.db_name)?;
+            validate_identifier(&database.db_user)?;
+
             let client = daemon_client();
             let daemon_url = format!("{}/database-servers/{}/databases", daemon.base_url(), server.id);
 
@@ -334,6 +336,9 @@ pub async fn reset_database_password(
             .await?;
 
             if let Some(daemon) = daemon {
+                validate_identifier(&database.db_name)?;
+                validate_identifier(&database.db_user)?;
+
                 let client = daemon_client();
                 let daemon_url = format!("{}/database-servers/{}/databases/reset-password", daemon.base_url(), server.id);
 
@@ -982,6 +987,10 @@ fn generate_password(length: usize) -> String {
         .collect()
 }
 
+fn validate_identifier(value: &str) -> AppResult<()> {
+    raptor_common::databases::validate_identifier(value).map_err(AppError::BadRequest)
+}
+
 fn build_connection_string(
     db_type: &str,
     db_user: &str,
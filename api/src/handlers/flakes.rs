@@ -1,12 +1,17 @@
 use axum::{
-    extract::{Extension, Path, State},
+    extract::{Extension, Path, Query, State},
     Json,
 };
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::{error::{AppError, AppResult}, models::{AppState, Claims}};
+use raptor_common::readiness::ReadinessCheck;
+use raptor_common::mounts::AssetMount;
+use raptor_common::variables_file::VariablesFile;
+
+use crate::{error::{AppError, AppResult}, models::{AppState, Claims}, permissions};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 #[serde(rename_all = "camelCase")]
@@ -18,17 +23,38 @@ pub struct Flake {
     pub description: Option<String>,
     pub docker_image: String,
     pub startup_command: String,
+    /// Console command that stops the server; containers default to "stop" without one.
+    pub stop_command: Option<String>,
     pub config_files: serde_json::Value,
     pub startup_detection: Option<String>,
     pub install_script: Option<String>,
+    pub install_timeout_secs: Option<i32>,
     pub install_container: Option<String>,
     pub install_entrypoint: Option<String>,
     pub features: serde_json::Value,
     pub file_denylist: serde_json::Value,
+    pub docker_labels: serde_json::Value,
+    pub readiness_check: Option<serde_json::Value>,
+    /// Seconds to pass the readiness check after a start before the daemon stops the container.
+    pub start_timeout_secs: Option<i32>,
+    /// Written into the volume from the container's variables before each start.
+    pub variables_file: Option<serde_json::Value>,
+    /// Read-only binds from the daemon's asset directory, e.g. shared maps or mod packs.
+    pub asset_mounts: serde_json::Value,
+    /// Resource guidance in MB; containers below a minimum cannot be created.
+    pub min_memory: Option<i64>,
+    pub recommended_memory: Option<i64>,
+    pub min_disk: Option<i64>,
+    pub recommended_disk: Option<i64>,
     #[serde(default = "default_restart_policy")]
     pub restart_policy: String,
     #[serde(default)]
     pub tty: bool,
+    /// Containers created from this flake default to a read-only volume and root filesystem.
+    pub read_only: bool,
+    /// Named entry of the daemon's registry credentials file to pull the image with; without
+    /// one the daemon uses whatever is configured for the image's registry.
+    pub registry_credentials: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
@@ -50,6 +76,8 @@ pub struct FlakeVariable {
     pub user_viewable: bool,
     pub user_editable: bool,
     pub sort_order: i32,
+    /// Write-only: container values are stored encrypted and never returned.
+    pub secret: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -70,16 +98,61 @@ pub struct CreateFlakeRequest {
     pub description: Option<String>,
     pub docker_image: String,
     pub startup_command: String,
+    pub stop_command: Option<String>,
     #[serde(default)]
     pub config_files: serde_json::Value,
     pub startup_detection: Option<String>,
     pub install_script: Option<String>,
+    pub install_timeout_secs: Option<i32>,
     #[serde(default = "default_restart_policy")]
     pub restart_policy: String,
     #[serde(default)]
+    pub docker_labels: HashMap<String, String>,
+    pub readiness_check: Option<ReadinessCheck>,
+    pub start_timeout_secs: Option<i32>,
+    pub variables_file: Option<VariablesFile>,
+    #[serde(default)]
+    pub asset_mounts: Vec<AssetMount>,
+    #[serde(default)]
+    pub read_only: bool,
+    pub registry_credentials: Option<String>,
+    #[serde(flatten)]
+    pub resources: FlakeResources,
+    #[serde(default)]
     pub variables: Vec<CreateVariableRequest>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlakeResources {
+    pub min_memory: Option<i64>,
+    pub recommended_memory: Option<i64>,
+    pub min_disk: Option<i64>,
+    pub recommended_disk: Option<i64>,
+}
+
+impl FlakeResources {
+    fn validate(&self) -> AppResult<()> {
+        let pairs = [
+            ("memory", self.min_memory, self.recommended_memory),
+            ("disk", self.min_disk, self.recommended_disk),
+        ];
+
+        for (resource, min, recommended) in pairs {
+            if min.into_iter().chain(recommended).any(|v| v <= 0) {
+                return Err(AppError::BadRequest(format!("Minimum and recommended {} must be positive", resource)));
+            }
+            if let (Some(min), Some(recommended)) = (min, recommended) {
+                if recommended < min {
+                    return Err(AppError::BadRequest(format!("Recommended {} cannot be below the minimum", resource)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateVariableRequest {
@@ -94,6 +167,78 @@ pub struct CreateVariableRequest {
     pub user_viewable: bool,
     #[serde(default = "default_true")]
     pub user_editable: bool,
+    #[serde(default)]
+    pub secret: bool,
+}
+
+/// Prefix of the labels the daemon sets itself.
+const RESERVED_LABEL_PREFIX: &str = "raptor.";
+
+pub fn validate_docker_labels(labels: &HashMap<String, String>) -> AppResult<()> {
+    for key in labels.keys() {
+        if key.trim().is_empty() {
+            return Err(AppError::BadRequest("Docker label keys cannot be empty".into()));
+        }
+        if key.starts_with(RESERVED_LABEL_PREFIX) {
+            return Err(AppError::BadRequest(format!(
+                "Docker label '{}' uses the reserved '{}' prefix", key, RESERVED_LABEL_PREFIX
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_readiness_check(check: Option<&ReadinessCheck>) -> AppResult<()> {
+    match check {
+        Some(check) => check.validate().map_err(AppError::BadRequest),
+        None => Ok(()),
+    }
+}
+
+fn validate_variables_file(file: Option<&VariablesFile>) -> AppResult<()> {
+    match file {
+        Some(file) => file.validate().map_err(AppError::BadRequest),
+        None => Ok(()),
+    }
+}
+
+fn validate_asset_mounts(mounts: &[AssetMount]) -> AppResult<()> {
+    raptor_common::mounts::validate_asset_mounts(mounts).map_err(AppError::BadRequest)
+}
+
+impl Flake {
+    pub fn variables_file(&self) -> Option<VariablesFile> {
+        self.variables_file.clone().and_then(|v| serde_json::from_value(v).ok())
+    }
+
+    pub fn asset_mounts(&self) -> Vec<AssetMount> {
+        serde_json::from_value(self.asset_mounts.clone()).unwrap_or_default()
+    }
+
+    /// The configured readiness check, or one built from the legacy `startup_detection` line.
+    pub fn readiness(&self) -> Option<ReadinessCheck> {
+        self.readiness_check
+            .clone()
+            .and_then(|v| serde_json::from_value(v).ok())
+            .or_else(|| self.startup_detection.as_deref().and_then(ReadinessCheck::from_startup_detection))
+    }
+
+    /// Rejects limits below the flake's minimums; unlimited (-1) always passes.
+    pub fn check_minimum_resources(&self, memory_limit: i64, disk_limit: i64) -> AppResult<()> {
+        let checks = [("memory", self.min_memory, memory_limit), ("disk", self.min_disk, disk_limit)];
+
+        for (resource, min, limit) in checks {
+            if let Some(min) = min {
+                if limit != raptor_common::resources::UNLIMITED && limit < min {
+                    return Err(AppError::BadRequest(format!(
+                        "{} needs at least {} MB of {}", self.name, min, resource
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 fn default_rules() -> String {
@@ -156,15 +301,29 @@ pub async fn create_flake(
     Extension(claims): Extension<Claims>,
     Json(req): Json<CreateFlakeRequest>,
 ) -> AppResult<Json<FlakeWithVariables>> {
-    if !claims.has_permission("flakes.create") && !claims.is_admin() {
+    if !claims.has_permission(permissions::FLAKES_CREATE) && !claims.is_admin() {
         return Err(AppError::Unauthorized);
     }
 
+    if req.install_timeout_secs.map(|t| t <= 0).unwrap_or(false) {
+        return Err(AppError::BadRequest("installTimeoutSecs must be positive".into()));
+    }
+
+    if req.start_timeout_secs.map(|t| t <= 0).unwrap_or(false) {
+        return Err(AppError::BadRequest("startTimeoutSecs must be positive".into()));
+    }
+
+    validate_docker_labels(&req.docker_labels)?;
+    validate_readiness_check(req.readiness_check.as_ref())?;
+    validate_variables_file(req.variables_file.as_ref())?;
+    validate_asset_mounts(&req.asset_mounts)?;
+    req.resources.validate()?;
+
     let flake_id = Uuid::new_v4();
 
     let flake: Flake = sqlx::query_as(
-        r#"INSERT INTO flakes (id, name, slug, author, description, docker_image, startup_command, config_files, startup_detection, install_script, restart_policy)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        r#"INSERT INTO flakes (id, name, slug, author, description, docker_image, startup_command, config_files, startup_detection, install_script, restart_policy, install_timeout_secs, docker_labels, readiness_check, min_memory, recommended_memory, min_disk, recommended_disk, start_timeout_secs, variables_file, asset_mounts, read_only, stop_command, registry_credentials)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24)
         RETURNING *"#
     )
         .bind(flake_id)
@@ -178,14 +337,27 @@ pub async fn create_flake(
         .bind(&req.startup_detection)
         .bind(&req.install_script)
         .bind(&req.restart_policy)
+        .bind(req.install_timeout_secs)
+        .bind(serde_json::json!(req.docker_labels))
+        .bind(req.readiness_check.as_ref().map(|c| serde_json::json!(c)))
+        .bind(req.resources.min_memory)
+        .bind(req.resources.recommended_memory)
+        .bind(req.resources.min_disk)
+        .bind(req.resources.recommended_disk)
+        .bind(req.start_timeout_secs)
+        .bind(req.variables_file.as_ref().map(|f| serde_json::json!(f)))
+        .bind(serde_json::json!(req.asset_mounts))
+        .bind(req.read_only)
+        .bind(req.stop_command.as_deref().filter(|c| !c.is_empty()))
+        .bind(req.registry_credentials.as_deref().map(str::trim).filter(|c| !c.is_empty()))
         .fetch_one(&state.db)
         .await?;
 
     let mut variables = Vec::new();
     for (idx, var) in req.variables.iter().enumerate() {
         let v: FlakeVariable = sqlx::query_as(
-            r#"INSERT INTO flake_variables (id, flake_id, name, description, env_variable, default_value, rules, user_viewable, user_editable, sort_order)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            r#"INSERT INTO flake_variables (id, flake_id, name, description, env_variable, default_value, rules, user_viewable, user_editable, sort_order, secret)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             RETURNING *"#
         )
             .bind(Uuid::new_v4())
@@ -198,6 +370,7 @@ pub async fn create_flake(
             .bind(var.user_viewable)
             .bind(var.user_editable)
             .bind(idx as i32)
+            .bind(var.secret)
             .fetch_one(&state.db)
             .await?;
         variables.push(v);
@@ -211,7 +384,7 @@ pub async fn delete_flake(
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
 ) -> AppResult<Json<serde_json::Value>> {
-    if !claims.has_permission("flakes.delete") && !claims.is_admin() {
+    if !claims.has_permission(permissions::FLAKES_DELETE) && !claims.is_admin() {
         return Err(AppError::Unauthorized);
     }
 
@@ -228,7 +401,7 @@ pub async fn import_flake(
     Extension(claims): Extension<Claims>,
     Json(req): Json<ImportFlakeRequest>,
 ) -> AppResult<Json<FlakeWithVariables>> {
-    if !claims.has_permission("flakes.create") && !claims.is_admin() {
+    if !claims.has_permission(permissions::FLAKES_CREATE) && !claims.is_admin() {
         return Err(AppError::Unauthorized);
     }
 
@@ -259,9 +432,16 @@ pub async fn import_flake(
         .or_else(|| flake_data["startupCommand"].as_str())
         .or_else(|| flake_data["startup_command"].as_str())
         .unwrap_or("").to_string();
+    let stop_command = flake_data["config"]["stop"].as_str()
+        .or_else(|| flake_data["stopCommand"].as_str())
+        .or_else(|| flake_data["stop_command"].as_str())
+        .filter(|c| !c.is_empty())
+        .map(|c| c.to_string());
 
+    // Eggs list their images by display name; a flake runs a single one
     let docker_image = flake_data["dockerImage"].as_str()
         .or_else(|| flake_data["docker_image"].as_str())
+        .or_else(|| flake_data["docker_images"].as_object().and_then(|images| images.values().find_map(|v| v.as_str())))
         .unwrap_or("artifacts.lstan.eu/java:21").to_string();
 
     let mut config_files = serde_json::json!({});
@@ -288,6 +468,72 @@ pub async fn import_flake(
         }
     }
 
+    let install_container = flake_data["scripts"]["installation"]["container"].as_str().map(|s| s.to_string());
+    let install_entrypoint = flake_data["scripts"]["installation"]["entrypoint"].as_str().map(|s| s.to_string());
+
+    let install_timeout_secs = flake_data["scripts"]["installation"]["timeout"].as_i64()
+        .or_else(|| flake_data["installTimeoutSecs"].as_i64())
+        .or_else(|| flake_data["install_timeout_secs"].as_i64())
+        .filter(|t| *t > 0)
+        .map(|t| t.min(i32::MAX as i64) as i32);
+
+    let docker_labels: HashMap<String, String> = flake_data["dockerLabels"].as_object()
+        .or_else(|| flake_data["docker_labels"].as_object())
+        .map(|labels| labels.iter()
+            .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+            .collect())
+        .unwrap_or_default();
+    validate_docker_labels(&docker_labels)?;
+
+    let readiness_check = match flake_data.get("readinessCheck").or_else(|| flake_data.get("readiness_check")) {
+        Some(v) if !v.is_null() => Some(
+            serde_json::from_value::<ReadinessCheck>(v.clone())
+                .map_err(|e| AppError::BadRequest(format!("Invalid readiness check: {}", e)))?,
+        ),
+        _ => None,
+    };
+    validate_readiness_check(readiness_check.as_ref())?;
+
+    let start_timeout_secs = flake_data["startTimeoutSecs"].as_i64()
+        .or_else(|| flake_data["start_timeout_secs"].as_i64())
+        .filter(|t| *t > 0)
+        .map(|t| t.min(i32::MAX as i64) as i32);
+
+    let variables_file = match flake_data.get("variablesFile").or_else(|| flake_data.get("variables_file")) {
+        Some(v) if !v.is_null() => Some(
+            serde_json::from_value::<VariablesFile>(v.clone())
+                .map_err(|e| AppError::BadRequest(format!("Invalid variables file: {}", e)))?,
+        ),
+        _ => None,
+    };
+    validate_variables_file(variables_file.as_ref())?;
+
+    let asset_mounts: Vec<AssetMount> = match flake_data.get("assetMounts").or_else(|| flake_data.get("asset_mounts")) {
+        Some(v) if !v.is_null() => serde_json::from_value(v.clone())
+            .map_err(|e| AppError::BadRequest(format!("Invalid asset mounts: {}", e)))?,
+        _ => Vec::new(),
+    };
+    validate_asset_mounts(&asset_mounts)?;
+
+    let read_only = flake_data["readOnly"].as_bool()
+        .or_else(|| flake_data["read_only"].as_bool())
+        .unwrap_or(false);
+
+    let registry_credentials = flake_data["registryCredentials"].as_str()
+        .or_else(|| flake_data["registry_credentials"].as_str())
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(|c| c.to_string());
+
+    let resource = |camel: &str, snake: &str| flake_data[camel].as_i64().or_else(|| flake_data[snake].as_i64());
+    let resources = FlakeResources {
+        min_memory: resource("minMemory", "min_memory"),
+        recommended_memory: resource("recommendedMemory", "recommended_memory"),
+        min_disk: resource("minDisk", "min_disk"),
+        recommended_disk: resource("recommendedDisk", "recommended_disk"),
+    };
+    resources.validate()?;
+
     let restart_policy = flake_data["restartPolicy"].as_str()
         .or_else(|| flake_data["restart_policy"].as_str())
         .map(|s| s.to_string())
@@ -307,8 +553,8 @@ pub async fn import_flake(
     let flake_id = Uuid::new_v4();
 
     let flake: Flake = sqlx::query_as(
-        r#"INSERT INTO flakes (id, name, slug, author, description, docker_image, startup_command, config_files, startup_detection, install_script, restart_policy)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        r#"INSERT INTO flakes (id, name, slug, author, description, docker_image, startup_command, config_files, startup_detection, install_script, restart_policy, install_timeout_secs, docker_labels, readiness_check, min_memory, recommended_memory, min_disk, recommended_disk, start_timeout_secs, variables_file, asset_mounts, read_only, stop_command, install_container, install_entrypoint, registry_credentials)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26)
         RETURNING *"#
     )
         .bind(flake_id)
@@ -322,6 +568,21 @@ pub async fn import_flake(
         .bind(&startup_detection)
         .bind(&install_script)
         .bind(&restart_policy)
+        .bind(install_timeout_secs)
+        .bind(serde_json::json!(docker_labels))
+        .bind(readiness_check.as_ref().map(|c| serde_json::json!(c)))
+        .bind(resources.min_memory)
+        .bind(resources.recommended_memory)
+        .bind(resources.min_disk)
+        .bind(resources.recommended_disk)
+        .bind(start_timeout_secs)
+        .bind(variables_file.as_ref().map(|f| serde_json::json!(f)))
+        .bind(serde_json::json!(asset_mounts))
+        .bind(read_only)
+        .bind(&stop_command)
+        .bind(&install_container)
+        .bind(&install_entrypoint)
+        .bind(&registry_credentials)
         .fetch_one(&mut *tx)
         .await?;
 
@@ -342,8 +603,8 @@ pub async fn import_flake(
             seen_env_vars.insert(env_var.clone());
 
             let v: FlakeVariable = sqlx::query_as(
-                r#"INSERT INTO flake_variables (id, flake_id, name, description, env_variable, default_value, rules, user_viewable, user_editable, sort_order)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                r#"INSERT INTO flake_variables (id, flake_id, name, description, env_variable, default_value, rules, user_viewable, user_editable, sort_order, secret)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
                 RETURNING *"#
             )
                 .bind(Uuid::new_v4())
@@ -356,6 +617,7 @@ pub async fn import_flake(
                 .bind(var["user_viewable"].as_bool().or_else(|| var["userViewable"].as_bool()).unwrap_or(true))
                 .bind(var["user_editable"].as_bool().or_else(|| var["userEditable"].as_bool()).unwrap_or(true))
                 .bind(idx as i32)
+                .bind(var["secret"].as_bool().unwrap_or(false))
                 .fetch_one(&mut *tx)
                 .await?;
             variables.push(v);
@@ -367,15 +629,29 @@ pub async fn import_flake(
     Ok(Json(FlakeWithVariables { flake, variables }))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ExportFlakeQuery {
+    /// `raptor` (default) or `pterodactyl`.
+    pub format: Option<String>,
+}
+
+/// GET /flakes/:id/export - the flake as JSON that `import_flake` accepts back, either in
+/// Raptor's own format or as a Pterodactyl egg (PTDL_v2).
 pub async fn export_flake(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
+    Query(query): Query<ExportFlakeQuery>,
 ) -> AppResult<Json<serde_json::Value>> {
     if claims.sub == Uuid::nil() {
         return Err(AppError::Unauthorized);
     }
 
+    let format = query.format.as_deref().unwrap_or("raptor");
+    if !matches!(format, "raptor" | "pterodactyl") {
+        return Err(AppError::BadRequest(format!("Unknown export format '{}', expected raptor or pterodactyl", format)));
+    }
+
     let flake: Flake = sqlx::query_as("SELECT * FROM flakes WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
@@ -389,18 +665,60 @@ pub async fn export_flake(
         .fetch_all(&state.db)
         .await?;
 
-    let export_data = serde_json::json!({
+    let export_data = if format == "pterodactyl" {
+        egg_export(&flake, &variables)
+    } else {
+        raptor_export(&flake, &variables)
+    };
+
+    Ok(Json(export_data))
+}
+
+/// The `config` block shared by both formats; eggs keep its values as JSON strings.
+fn export_config(flake: &Flake) -> serde_json::Value {
+    let startup = flake.startup_detection.as_ref()
+        .map(|done| serde_json::json!({ "done": done }))
+        .unwrap_or_else(|| serde_json::json!({}));
+
+    serde_json::json!({
+        "files": serde_json::to_string(&flake.config_files).unwrap_or_default(),
+        "startup": startup.to_string(),
+        "logs": "{}",
+        "stop": flake.stop_command.as_deref().unwrap_or("stop")
+    })
+}
+
+fn raptor_export(flake: &Flake, variables: &[FlakeVariable]) -> serde_json::Value {
+    serde_json::json!({
         "_comment": "Exported from Raptor Panel",
         "meta": { "version": "RAPTOR_v1" },
         "exported_at": chrono::Utc::now().to_rfc3339(),
         "name": flake.name,
         "author": flake.author,
         "description": flake.description,
-        "docker_images": { "Java 21": flake.docker_image },
+        "docker_image": flake.docker_image,
         "startup": flake.startup_command,
-        "config": {
-            "files": serde_json::to_string(&flake.config_files).unwrap_or_default()
+        "config": export_config(flake),
+        "scripts": {
+            "installation": {
+                "script": flake.install_script,
+                "container": flake.install_container,
+                "entrypoint": flake.install_entrypoint,
+                "timeout": flake.install_timeout_secs
+            }
         },
+        "restart_policy": flake.restart_policy,
+        "docker_labels": flake.docker_labels,
+        "readiness_check": flake.readiness_check,
+        "start_timeout_secs": flake.start_timeout_secs,
+        "variables_file": flake.variables_file,
+        "asset_mounts": flake.asset_mounts,
+        "read_only": flake.read_only,
+        "registry_credentials": flake.registry_credentials,
+        "min_memory": flake.min_memory,
+        "recommended_memory": flake.recommended_memory,
+        "min_disk": flake.min_disk,
+        "recommended_disk": flake.recommended_disk,
         "variables": variables.iter().map(|v| serde_json::json!({
             "name": v.name,
             "description": v.description,
@@ -408,9 +726,42 @@ pub async fn export_flake(
             "default_value": v.default_value,
             "user_viewable": v.user_viewable,
             "user_editable": v.user_editable,
-            "rules": v.rules
+            "rules": v.rules,
+            "secret": v.secret
         })).collect::<Vec<_>>()
-    });
+    })
+}
 
-    Ok(Json(export_data))
+/// Raptor-only settings (labels, readiness, assets, ...) have no egg equivalent and are left out.
+fn egg_export(flake: &Flake, variables: &[FlakeVariable]) -> serde_json::Value {
+    serde_json::json!({
+        "_comment": "Exported from Raptor Panel",
+        "meta": { "version": "PTDL_v2", "update_url": null },
+        "exported_at": chrono::Utc::now().to_rfc3339(),
+        "name": flake.name,
+        "author": flake.author,
+        "description": flake.description,
+        "features": flake.features.as_array(),
+        "docker_images": { (flake.docker_image.clone()): flake.docker_image },
+        "file_denylist": flake.file_denylist.as_array().cloned().unwrap_or_default(),
+        "startup": flake.startup_command,
+        "config": export_config(flake),
+        "scripts": {
+            "installation": {
+                "script": flake.install_script.as_deref().unwrap_or_default(),
+                "container": flake.install_container.as_deref().unwrap_or(&flake.docker_image),
+                "entrypoint": flake.install_entrypoint.as_deref().unwrap_or("bash")
+            }
+        },
+        "variables": variables.iter().map(|v| serde_json::json!({
+            "name": v.name,
+            "description": v.description.as_deref().unwrap_or_default(),
+            "env_variable": v.env_variable,
+            "default_value": v.default_value.as_deref().unwrap_or_default(),
+            "user_viewable": v.user_viewable,
+            "user_editable": v.user_editable,
+            "rules": v.rules.as_deref().unwrap_or("nullable|string"),
+            "field_type": "text"
+        })).collect::<Vec<_>>()
+    })
 }
@@ -0,0 +1,54 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use std::time::Duration;
+
+use crate::models::AppState;
+
+/// Longer than this to get a connection and answer counts as not ready, e.g. an exhausted pool.
+const READY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// GET /health/ready - 200 once the database answers and every bundled migration is applied,
+/// 503 otherwise. `/health` stays a plain liveness check.
+pub async fn ready(State(state): State<AppState>) -> impl IntoResponse {
+    let expected = crate::MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0);
+
+    let check = async {
+        sqlx::query("SELECT 1").execute(&state.db).await?;
+
+        let (applied,): (Option<i64>,) = sqlx::query_as("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+            .fetch_one(&state.db)
+            .await?;
+
+        let seeders: Vec<String> = sqlx::query_scalar("SELECT name FROM _seeders ORDER BY name")
+            .fetch_all(&state.db)
+            .await
+            .unwrap_or_default();
+
+        Ok::<_, sqlx::Error>((applied.unwrap_or(0), seeders))
+    };
+
+    let (status, body) = match tokio::time::timeout(READY_TIMEOUT, check).await {
+        Ok(Ok((applied, seeders))) => {
+            let migrated = applied >= expected;
+            let status = if migrated { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+            (status, serde_json::json!({
+                "ready": migrated,
+                "database": "ok",
+                "migrations": { "applied": applied, "expected": expected },
+                "seeders": seeders,
+            }))
+        }
+        Ok(Err(e)) => (StatusCode::SERVICE_UNAVAILABLE, serde_json::json!({
+            "ready": false,
+            "database": e.to_string(),
+            "migrations": { "applied": null, "expected": expected },
+        })),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, serde_json::json!({
+            "ready": false,
+            "database": format!("no answer within {:?}", READY_TIMEOUT),
+            "migrations": { "applied": null, "expected": expected },
+        })),
+    };
+
+    (status, Json(body))
+}
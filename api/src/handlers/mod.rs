@@ -1,9 +1,12 @@
 pub mod allocations;
+pub mod api_keys;
 pub mod auth;
+pub mod backups;
 pub mod containers;
 pub mod daemons;
 pub mod databases;
 pub mod flakes;
+pub mod health;
 pub mod roles;
 pub mod two_factor;
 pub mod users;
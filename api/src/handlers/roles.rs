@@ -6,7 +6,8 @@ use chrono::Utc;
 use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
-use crate::models::{AppState, Role, RoleResponse};
+use crate::models::{AppState, Role, RoleResourceLimits, RoleResponse};
+use crate::permissions::{self, Permission};
 
 pub async fn list_roles(State(state): State<AppState>) -> AppResult<Json<Vec<RoleResponse>>> {
     let roles: Vec<Role> = sqlx::query_as("SELECT * FROM roles ORDER BY name")
@@ -17,6 +18,7 @@ pub async fn list_roles(State(state): State<AppState>) -> AppResult<Json<Vec<Rol
         roles
             .into_iter()
             .map(|r| RoleResponse {
+                limits: r.limits(),
                 id: r.id,
                 name: r.name,
                 permissions: r.permissions,
@@ -36,6 +38,7 @@ pub async fn get_role(
         .ok_or(AppError::NotFound)?;
 
     Ok(Json(RoleResponse {
+        limits: role.limits(),
         id: role.id,
         name: role.name,
         permissions: role.permissions,
@@ -43,9 +46,18 @@ pub async fn get_role(
 }
 
 #[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CreateRoleRequest {
     pub name: String,
     pub permissions: serde_json::Value,
+    #[serde(default)]
+    pub max_memory: Option<i64>,
+    #[serde(default)]
+    pub max_cpu: Option<rust_decimal::Decimal>,
+    #[serde(default)]
+    pub max_disk: Option<i64>,
+    #[serde(default)]
+    pub max_containers: Option<i32>,
 }
 
 pub async fn create_role(
@@ -55,19 +67,24 @@ pub async fn create_role(
     let now = Utc::now();
     let role: Role = sqlx::query_as(
         r#"
-        INSERT INTO roles (id, name, permissions, created_at, updated_at)
-        VALUES ($1, $2, $3, $4, $4)
+        INSERT INTO roles (id, name, permissions, max_memory, max_cpu, max_disk, max_containers, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8)
         RETURNING *
         "#,
     )
     .bind(Uuid::new_v4())
     .bind(&req.name)
     .bind(&req.permissions)
+    .bind(req.max_memory)
+    .bind(req.max_cpu)
+    .bind(req.max_disk)
+    .bind(req.max_containers)
     .bind(now)
     .fetch_one(&state.db)
     .await?;
 
     Ok(Json(RoleResponse {
+        limits: role.limits(),
         id: role.id,
         name: role.name,
         permissions: role.permissions,
@@ -81,7 +98,8 @@ pub async fn update_role(
 ) -> AppResult<Json<RoleResponse>> {
     let role: Role = sqlx::query_as(
         r#"
-        UPDATE roles SET name = $2, permissions = $3, updated_at = NOW()
+        UPDATE roles SET name = $2, permissions = $3, max_memory = $4, max_cpu = $5,
+            max_disk = $6, max_containers = $7, updated_at = NOW()
         WHERE id = $1
         RETURNING *
         "#,
@@ -89,11 +107,16 @@ pub async fn update_role(
     .bind(id)
     .bind(&req.name)
     .bind(&req.permissions)
+    .bind(req.max_memory)
+    .bind(req.max_cpu)
+    .bind(req.max_disk)
+    .bind(req.max_containers)
     .fetch_optional(&state.db)
     .await?
     .ok_or(AppError::NotFound)?;
 
     Ok(Json(RoleResponse {
+        limits: role.limits(),
         id: role.id,
         name: role.name,
         permissions: role.permissions,
@@ -121,3 +144,24 @@ pub async fn delete_role(
 
     Ok(Json(()))
 }
+
+pub async fn fetch_role_limits(pool: &sqlx::PgPool, user_id: Uuid) -> AppResult<RoleResourceLimits> {
+    let limits: Option<RoleResourceLimits> = sqlx::query_as(
+        r#"
+        SELECT r.max_memory, r.max_cpu, r.max_disk, r.max_containers
+        FROM users u
+        INNER JOIN roles r ON r.id = u.role_id
+        WHERE u.id = $1
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(limits.unwrap_or_default())
+}
+
+/// GET /permissions - every permission a role can grant, for building role editors
+pub async fn list_permissions() -> Json<Vec<Permission>> {
+    Json(permissions::CATALOG.to_vec())
+}
@@ -387,14 +387,14 @@ pub async fn validate_2fa_login(
     let user_id: Uuid = req.user_id.parse()
         .map_err(|_| AppError::BadRequest("Invalid user ID".into()))?;
 
-    let user: Option<(String, Option<String>, bool, Option<String>, Option<Uuid>)> = sqlx::query_as(
-        "SELECT username, totp_secret, totp_enabled, email, role_id FROM users WHERE id = $1"
+    let user: Option<(String, Option<String>, bool, Option<String>, Option<Uuid>, i32)> = sqlx::query_as(
+        "SELECT username, totp_secret, totp_enabled, email, role_id, token_version FROM users WHERE id = $1"
     )
     .bind(user_id)
     .fetch_optional(&state.db)
     .await?;
 
-    let (username, secret, totp_enabled, email, role_id) = user.ok_or(AppError::NotFound)?;
+    let (username, secret, totp_enabled, email, role_id, token_version) = user.ok_or(AppError::NotFound)?;
 
     if !totp_enabled {
         return Err(AppError::BadRequest("2FA is not enabled for this user".into()));
@@ -464,7 +464,9 @@ pub async fn validate_2fa_login(
         role_id,
         role_name: role_name.clone(),
         permissions: permissions.clone(),
+        token_version,
         exp,
+        api_key_id: None,
     };
 
     let token = jsonwebtoken::encode(
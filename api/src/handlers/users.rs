@@ -7,7 +7,7 @@ use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
-use crate::models::{AppState, Claims, UserResponse};
+use crate::models::{AppState, Claims, RoleResourceLimits, UserResponse};
 
 #[derive(Debug, Deserialize)]
 pub struct PaginationParams {
@@ -243,18 +243,51 @@ pub async fn delete_user(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
 ) -> AppResult<Json<()>> {
+    let mut tx = state.db.begin().await?;
+
+    // The user's containers go with them via ON DELETE CASCADE
+    let containers: Vec<(Uuid, Uuid, String)> = sqlx::query_as(
+        "SELECT id, daemon_id, name FROM containers WHERE user_id = $1"
+    )
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    for (container_id, daemon_id, name) in containers {
+        crate::outbox::record(
+            &mut tx,
+            container_id,
+            crate::outbox::CONTAINER_DELETED,
+            serde_json::json!({
+                "userId": id,
+                "daemonId": daemon_id,
+                "name": name,
+            }),
+        ).await?;
+    }
+
     sqlx::query("DELETE FROM users WHERE id = $1")
         .bind(id)
-        .execute(&state.db)
+        .execute(&mut *tx)
         .await?;
 
+    tx.commit().await?;
+
     Ok(Json(()))
 }
 
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeResponse {
+    #[serde(flatten)]
+    pub user: UserResponse,
+    pub resource_limits: RoleResourceLimits,
+}
+
 pub async fn get_me(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
-) -> AppResult<Json<UserResponse>> {
+) -> AppResult<Json<MeResponse>> {
     let user: UserWithRole = sqlx::query_as(
         r#"
         SELECT
@@ -271,18 +304,85 @@ pub async fn get_me(
     .ok_or(AppError::NotFound)?;
 
     let avatar_url = UserResponse::gravatar_url(user.email.as_deref());
-
-    Ok(Json(UserResponse {
-        id: user.id,
-        username: user.username,
-        email: user.email,
-        avatar_url,
-        role_id: user.role_id,
-        role_name: user.role_name,
-        permissions: user.permissions.unwrap_or(serde_json::json!({})),
+    let resource_limits = crate::handlers::roles::fetch_role_limits(&state.db, user.id).await?;
+
+    Ok(Json(MeResponse {
+        user: UserResponse {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            avatar_url,
+            role_id: user.role_id,
+            role_name: user.role_name,
+            permissions: user.permissions.unwrap_or(serde_json::json!({})),
+        },
+        resource_limits,
     }))
 }
 
+#[derive(Debug, serde::Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationPreferences {
+    pub container_crashed: bool,
+    pub install_completed: bool,
+    pub container_suspended: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateNotificationPreferencesRequest {
+    pub container_crashed: Option<bool>,
+    pub install_completed: Option<bool>,
+    pub container_suspended: Option<bool>,
+}
+
+/// GET /users/me/notifications - all notifications are off until the user opts in
+pub async fn get_notification_preferences(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> AppResult<Json<NotificationPreferences>> {
+    let prefs: Option<NotificationPreferences> = sqlx::query_as(
+        "SELECT container_crashed, install_completed, container_suspended FROM notification_preferences WHERE user_id = $1"
+    )
+    .bind(claims.sub)
+    .fetch_optional(&state.db)
+    .await?;
+
+    Ok(Json(prefs.unwrap_or(NotificationPreferences {
+        container_crashed: false,
+        install_completed: false,
+        container_suspended: false,
+    })))
+}
+
+/// PUT /users/me/notifications
+pub async fn update_notification_preferences(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<UpdateNotificationPreferencesRequest>,
+) -> AppResult<Json<NotificationPreferences>> {
+    let prefs: NotificationPreferences = sqlx::query_as(
+        r#"
+        INSERT INTO notification_preferences (user_id, container_crashed, install_completed, container_suspended)
+        VALUES ($1, COALESCE($2, FALSE), COALESCE($3, FALSE), COALESCE($4, FALSE))
+        ON CONFLICT (user_id) DO UPDATE SET
+            container_crashed = COALESCE($2, notification_preferences.container_crashed),
+            install_completed = COALESCE($3, notification_preferences.install_completed),
+            container_suspended = COALESCE($4, notification_preferences.container_suspended),
+            updated_at = NOW()
+        RETURNING container_crashed, install_completed, container_suspended
+        "#
+    )
+    .bind(claims.sub)
+    .bind(req.container_crashed)
+    .bind(req.install_completed)
+    .bind(req.container_suspended)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(Json(prefs))
+}
+
 pub async fn invite_user(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
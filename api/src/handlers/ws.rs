@@ -10,7 +10,10 @@ use uuid::Uuid;
 use std::collections::HashMap;
 
 use crate::error::AppError;
-use crate::models::{AppState, Container, Daemon};
+use crate::handlers::containers::{ensure_not_trashed, owns, record_command};
+use crate::keepalive::Keepalive;
+use crate::models::{AppState, Claims, Container, Daemon};
+use crate::permissions;
 
 pub async fn container_logs(
     State(state): State<AppState>,
@@ -20,9 +23,12 @@ pub async fn container_logs(
 ) -> Result<Response, AppError> {
 
     let token = params.get("token").ok_or(AppError::Unauthorized)?;
-    validate_token(token, &state.config.jwt_secret)?;
+    let claims = validate_token(&state, token).await?;
 
     let since = params.get("since").cloned();
+    // "plain" strips ANSI colors, "json" sends { stream, line, ts } objects; the daemon defaults to raw terminal output
+    let format = params.get("format").cloned();
+    let tail = crate::handlers::containers::parse_log_tail(params.get("tail").map(String::as_str))?;
 
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
@@ -30,36 +36,71 @@ pub async fn container_logs(
         .await?
         .ok_or(AppError::NotFound)?;
 
+    if !crate::handlers::containers::can_access_container(&claims, &container)
+        && !crate::handlers::containers::sub_user_can(&state, &claims, &container, permissions::SUB_CONSOLE).await?
+    {
+        return Err(AppError::Unauthorized);
+    }
+
+    // The console also forwards commands, so it's closed while the container is trashed
+    ensure_not_trashed(&container)?;
+
     let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    Ok(ws.on_upgrade(move |socket| handle_logs_ws(socket, daemon, container, since)))
+    let db = state.db.clone();
+    let keepalive = Keepalive::new(state.config.ws_idle_timeout_secs);
+    Ok(ws.on_upgrade(move |socket| handle_logs_ws(socket, db, keepalive, claims.sub, daemon, container, since, tail, format)))
 }
 
-fn validate_token(token: &str, secret: &str) -> Result<(), AppError> {
+async fn validate_token(state: &AppState, token: &str) -> Result<Claims, AppError> {
     use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm};
 
+    if token.starts_with(crate::handlers::api_keys::KEY_PREFIX) {
+        return crate::handlers::api_keys::resolve(state, token).await?.ok_or(AppError::Unauthorized);
+    }
+
     let validation = Validation::new(Algorithm::HS256);
-    decode::<serde_json::Value>(
+    let claims = decode::<Claims>(
         token,
-        &DecodingKey::from_secret(secret.as_bytes()),
+        &DecodingKey::from_secret(state.config.jwt_secret.as_bytes()),
         &validation,
-    ).map_err(|_| AppError::Unauthorized)?;
+    ).map_err(|_| AppError::Unauthorized)?.claims;
+
+    let current_version: Option<(i32,)> = sqlx::query_as("SELECT token_version FROM users WHERE id = $1")
+        .bind(claims.sub)
+        .fetch_optional(&state.db)
+        .await?;
 
-    Ok(())
+    match current_version {
+        Some((version,)) if version == claims.token_version => Ok(claims),
+        _ => Err(AppError::Unauthorized),
+    }
 }
 
-async fn handle_logs_ws(socket: WebSocket, daemon: Daemon, container: Container, since: Option<String>) {
+async fn handle_logs_ws(
+    socket: WebSocket,
+    db: sqlx::PgPool,
+    keepalive: Keepalive,
+    user_id: Uuid,
+    daemon: Daemon,
+    container: Container,
+    since: Option<String>,
+    tail: Option<usize>,
+    format: Option<String>,
+) {
     let (mut sender, mut receiver) = socket.split();
 
     let ws_protocol = if daemon.secure { "wss" } else { "ws" };
     let since_param = since.as_ref().map(|s| format!("&since={}", s)).unwrap_or_default();
+    let format_param = format.as_ref().map(|f| format!("&format={}", urlencoding::encode(f))).unwrap_or_default();
+    let tail_param = tail.map(|t| format!("&tail={}", t)).unwrap_or_default();
     let daemon_ws_url = format!(
-        "{}://{}:{}/ws/containers/{}/logs?api_key={}{}",
-        ws_protocol, daemon.host, daemon.port, container.id, daemon.api_key, since_param
+        "{}://{}:{}/ws/containers/{}/logs?api_key={}{}{}{}",
+        ws_protocol, daemon.host, daemon.port, container.id, daemon.api_key, since_param, format_param, tail_param
     );
 
     tracing::info!("Connecting to daemon WebSocket: {}", daemon_ws_url);
@@ -81,7 +122,22 @@ async fn handle_logs_ws(socket: WebSocket, daemon: Daemon, container: Container,
     let (mut daemon_sender, mut daemon_receiver) = ws_stream.split();
 
     let forward_to_client = async {
-        while let Some(msg) = daemon_receiver.next().await {
+        let mut ping = Keepalive::interval();
+
+        loop {
+            let msg = tokio::select! {
+                msg = daemon_receiver.next() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                _ = ping.tick() => {
+                    if !keepalive.ping(&mut sender).await {
+                        break;
+                    }
+                    continue;
+                }
+            };
+
             match msg {
                 Ok(tokio_tungstenite::tungstenite::Message::Text(text)) => {
                     if sender.send(Message::Text(text)).await.is_err() {
@@ -105,8 +161,12 @@ async fn handle_logs_ws(socket: WebSocket, daemon: Daemon, container: Container,
 
     let forward_to_daemon = async {
         while let Some(msg) = receiver.next().await {
+            if let Ok(msg) = &msg {
+                keepalive.seen(msg);
+            }
             match msg {
                 Ok(Message::Text(text)) => {
+                    let command = text.clone();
                     if daemon_sender
                         .send(tokio_tungstenite::tungstenite::Message::Text(text))
                         .await
@@ -114,6 +174,7 @@ async fn handle_logs_ws(socket: WebSocket, daemon: Daemon, container: Container,
                     {
                         break;
                     }
+                    record_command(&db, container.id, Some(user_id), &command, "console", None).await;
                 }
                 Ok(Message::Close(_)) => break,
                 Err(_) => break,
@@ -128,6 +189,143 @@ async fn handle_logs_ws(socket: WebSocket, daemon: Daemon, container: Container,
     }
 }
 
+/// Interactive shell in the container, proxied to the daemon. `?cols=&rows=` set the initial
+/// terminal size; see the daemon's `ws_exec` for the frame protocol.
+pub async fn container_exec(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<HashMap<String, String>>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+
+    let token = params.get("token").ok_or(AppError::Unauthorized)?;
+    let claims = validate_token(&state, token).await?;
+
+    if !claims.has_permission(permissions::CONTAINERS_CONSOLE_EXEC) && !claims.is_admin() {
+        return Err(AppError::Forbidden("Shell access requires the containers.console_exec permission".into()));
+    }
+
+    let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let can_manage = owns(&claims, &container, permissions::CONTAINERS_MANAGE_OWN)?
+        || claims.has_permission(permissions::CONTAINERS_MANAGE)
+        || claims.is_manager();
+
+    if !can_manage {
+        return Err(AppError::Unauthorized);
+    }
+
+    ensure_not_trashed(&container)?;
+
+    let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+        .bind(container.daemon_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let size: String = ["cols", "rows"]
+        .iter()
+        .filter_map(|key| params.get(*key).and_then(|v| v.parse::<u16>().ok()).map(|v| format!("&{}={}", key, v)))
+        .collect();
+
+    record_command(&state.db, container.id, Some(claims.sub), "shell session", "exec", None).await;
+
+    let keepalive = Keepalive::new(state.config.ws_idle_timeout_secs);
+    Ok(ws.on_upgrade(move |socket| handle_exec_ws(socket, keepalive, daemon, container, size)))
+}
+
+async fn handle_exec_ws(socket: WebSocket, keepalive: Keepalive, daemon: Daemon, container: Container, size: String) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let ws_protocol = if daemon.secure { "wss" } else { "ws" };
+    let daemon_ws_url = format!(
+        "{}://{}:{}/ws/containers/{}/exec?api_key={}{}",
+        ws_protocol, daemon.host, daemon.port, container.id, daemon.api_key, size
+    );
+
+    let ws_stream = match tokio_tungstenite::connect_async(&daemon_ws_url).await {
+        Ok((stream, _)) => stream,
+        Err(e) => {
+            tracing::error!("Failed to connect to daemon exec WebSocket: {}", e);
+            let _ = sender
+                .send(Message::Text(format!("Error connecting to daemon: {}", e)))
+                .await;
+            return;
+        }
+    };
+
+    tracing::info!("Shell session opened for container {}", container.id);
+
+    let (mut daemon_sender, mut daemon_receiver) = ws_stream.split();
+
+    let forward_to_client = async {
+        let mut ping = Keepalive::interval();
+
+        loop {
+            let msg = tokio::select! {
+                msg = daemon_receiver.next() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                _ = ping.tick() => {
+                    if !keepalive.ping(&mut sender).await {
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            let msg = match msg {
+                Ok(tokio_tungstenite::tungstenite::Message::Text(text)) => Message::Text(text),
+                Ok(tokio_tungstenite::tungstenite::Message::Binary(data)) => Message::Binary(data),
+                Ok(tokio_tungstenite::tungstenite::Message::Close(_)) => break,
+                Err(e) => {
+                    tracing::error!("Daemon exec WebSocket error: {}", e);
+                    break;
+                }
+                _ => continue,
+            };
+
+            if sender.send(msg).await.is_err() {
+                break;
+            }
+        }
+
+        let _ = sender.send(Message::Close(None)).await;
+    };
+
+    let forward_to_daemon = async {
+        while let Some(msg) = receiver.next().await {
+            if let Ok(msg) = &msg {
+                keepalive.seen(msg);
+            }
+            let msg = match msg {
+                Ok(Message::Text(text)) => tokio_tungstenite::tungstenite::Message::Text(text),
+                Ok(Message::Binary(data)) => tokio_tungstenite::tungstenite::Message::Binary(data),
+                Ok(Message::Close(_)) | Err(_) => break,
+                _ => continue,
+            };
+
+            if daemon_sender.send(msg).await.is_err() {
+                break;
+            }
+        }
+
+        let _ = daemon_sender.close().await;
+    };
+
+    tokio::select! {
+        _ = forward_to_client => {},
+        _ = forward_to_daemon => {},
+    }
+
+    tracing::info!("Shell session closed for container {}", container.id);
+}
+
 pub async fn container_stats(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
@@ -136,7 +334,7 @@ pub async fn container_stats(
 ) -> Result<Response, AppError> {
 
     let token = params.get("token").ok_or(AppError::Unauthorized)?;
-    validate_token(token, &state.config.jwt_secret)?;
+    let claims = validate_token(&state, token).await?;
 
     let container: Container = sqlx::query_as("SELECT * FROM containers WHERE id = $1")
         .bind(id)
@@ -144,16 +342,24 @@ pub async fn container_stats(
         .await?
         .ok_or(AppError::NotFound)?;
 
+    // Same rule as GET /containers/:id/stats: any sub-user may watch usage
+    if !crate::handlers::containers::can_access_container(&claims, &container)
+        && crate::handlers::containers::sub_user_permissions(&state, &claims, &container).await?.is_none()
+    {
+        return Err(AppError::Unauthorized);
+    }
+
     let daemon: Daemon = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
         .bind(container.daemon_id)
         .fetch_optional(&state.db)
         .await?
         .ok_or(AppError::NotFound)?;
 
-    Ok(ws.on_upgrade(move |socket| handle_stats_ws(socket, daemon, container)))
+    let keepalive = Keepalive::new(state.config.ws_idle_timeout_secs);
+    Ok(ws.on_upgrade(move |socket| handle_stats_ws(socket, keepalive, daemon, container)))
 }
 
-async fn handle_stats_ws(socket: WebSocket, daemon: Daemon, container: Container) {
+async fn handle_stats_ws(socket: WebSocket, keepalive: Keepalive, daemon: Daemon, container: Container) {
     let (mut sender, mut receiver) = socket.split();
 
     let ws_protocol = if daemon.secure { "wss" } else { "ws" };
@@ -181,7 +387,22 @@ async fn handle_stats_ws(socket: WebSocket, daemon: Daemon, container: Container
     let (mut daemon_sender, mut daemon_receiver) = ws_stream.split();
 
     let forward_to_client = async {
-        while let Some(msg) = daemon_receiver.next().await {
+        let mut ping = Keepalive::interval();
+
+        loop {
+            let msg = tokio::select! {
+                msg = daemon_receiver.next() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                _ = ping.tick() => {
+                    if !keepalive.ping(&mut sender).await {
+                        break;
+                    }
+                    continue;
+                }
+            };
+
             match msg {
                 Ok(tokio_tungstenite::tungstenite::Message::Text(text)) => {
                     if sender.send(Message::Text(text)).await.is_err() {
@@ -200,6 +421,9 @@ async fn handle_stats_ws(socket: WebSocket, daemon: Daemon, container: Container
 
     let recv_task = async {
         while let Some(msg) = receiver.next().await {
+            if let Ok(msg) = &msg {
+                keepalive.seen(msg);
+            }
             match msg {
                 Ok(Message::Close(_)) => break,
                 Err(_) => break,
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use futures_util::stream::SplitSink;
+use futures_util::SinkExt;
+use raptor_common::keepalive::{KeepaliveCheck, Liveness};
+
+/// Close code for "going away", sent when a socket is closed for being idle.
+const CLOSE_GOING_AWAY: u16 = 1001;
+
+/// When a browser websocket was last heard from. Clones share state, so the receive half
+/// records frames while the send half pings.
+#[derive(Clone)]
+pub struct Keepalive {
+    liveness: Liveness,
+}
+
+impl Keepalive {
+    /// `idle_timeout_secs` caps how long a socket stays open without input from the client;
+    /// 0 keeps it open as long as it answers pings.
+    pub fn new(idle_timeout_secs: u64) -> Self {
+        Self {
+            liveness: Liveness::new((idle_timeout_secs > 0).then(|| Duration::from_secs(idle_timeout_secs))),
+        }
+    }
+
+    /// Records a frame from the client. Pongs only prove it is connected; text and binary
+    /// frames also reset the idle timer.
+    pub fn seen(&self, msg: &Message) {
+        self.liveness.seen(matches!(msg, Message::Text(_) | Message::Binary(_)));
+    }
+
+    /// Ticks every `PING_INTERVAL`, starting one interval from now.
+    pub fn interval() -> tokio::time::Interval {
+        Liveness::interval()
+    }
+
+    /// Pings the client, or closes the socket if it stopped answering or sat idle past the
+    /// cap. Returns false once the socket should be dropped.
+    pub async fn ping(&self, sender: &mut SplitSink<WebSocket, Message>) -> bool {
+        match self.liveness.check() {
+            KeepaliveCheck::Ping => sender.send(Message::Ping(Vec::new())).await.is_ok(),
+            KeepaliveCheck::Unresponsive => {
+                tracing::debug!("Closing websocket that stopped answering pings");
+                let _ = sender.send(Message::Close(None)).await;
+                false
+            }
+            KeepaliveCheck::Idle => {
+                tracing::debug!("Closing idle websocket");
+                let _ = sender.send(Message::Close(Some(CloseFrame {
+                    code: CLOSE_GOING_AWAY,
+                    reason: "Idle timeout".into(),
+                }))).await;
+                false
+            }
+        }
+    }
+}
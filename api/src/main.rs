@@ -1,10 +1,23 @@
+mod backup_scheduler;
 mod config;
+mod daemon_client;
+mod daemon_stats;
 mod email;
 mod error;
 mod handlers;
+mod keepalive;
+mod metrics;
 mod middleware;
 mod models;
+mod notifications;
+mod outbox;
+mod permissions;
+mod reconciler;
+mod secrets;
 mod seeder;
+mod trash;
+#[cfg(test)]
+mod test_db;
 
 use axum::{
     routing::{get, post, delete, patch, put},
@@ -13,90 +26,138 @@ use axum::{
     extract::DefaultBodyLimit,
 };
 use sqlx::postgres::PgPoolOptions;
-use tower_http::cors::{Any, CorsLayer};
+use axum::http::{HeaderName, HeaderValue};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use crate::config::Config;
-use crate::middleware::{require_permission, require_admin, require_manager};
+use crate::middleware::{require_permission, require_admin, require_manager, require_session};
 
-pub const UPLOAD_CHUNK_SIZE: usize = 55 * 1024 * 1024;
+/// Migrations bundled into the binary; `/health/ready` compares the database against them.
+pub static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("../migrations");
 
-pub const UPLOAD_CHUNK_BODY_LIMIT: usize = UPLOAD_CHUNK_SIZE + 10 * 1024 * 1024;
+/// Credentialed CORS for the configured origins; `Any` only with the explicit CORS_ALLOW_ALL opt-in.
+fn cors_layer(config: &Config) -> CorsLayer {
+    if config.cors_allow_all {
+        tracing::warn!("CORS_ALLOW_ALL is set: any origin may call this server");
+        return CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers(Any);
+    }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    dotenvy::dotenv().ok();
-
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
-    let config = Config::from_env();
-
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&config.database_url)
-        .await?;
+    let origins: Vec<HeaderValue> = config
+        .cors_allowed_origins
+        .iter()
+        .filter_map(|origin| match origin.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                tracing::warn!("Ignoring invalid CORS origin {}", origin);
+                None
+            }
+        })
+        .collect();
 
-    sqlx::migrate!("../migrations").run(&pool).await?;
-
-    seeder::run(&pool, &config).await?;
-
-    let app_state = models::AppState {
-        db: pool,
-        config: config.clone(),
-    };
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(AllowHeaders::mirror_request())
+        // Partial and binary file reads are described by these; downloads name their file
+        .expose_headers(
+            ["x-file-size", "x-file-offset", "x-file-encoding", "x-file-content-type", "content-disposition"]
+                .map(HeaderName::from_static),
+        )
+        .allow_credentials(true)
+}
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+/// Every route of the API. `require_permission` checks its permissions against the catalog
+/// here, so building the router is enough to catch a typo.
+fn router(app_state: models::AppState) -> Router {
+    let cors = cors_layer(&app_state.config);
+    let upload_body_limit = raptor_common::uploads::multipart_body_limit(app_state.config.upload_chunk_size);
 
     let public_routes = Router::new()
         .route("/", get(|| async { "Raptor API" }))
         .route("/health", get(|| async { "OK" }))
+        .route("/health/ready", get(handlers::health::ready))
+        .route("/metrics", get(metrics::handler))
         .route("/auth/login", post(handlers::auth::login))
         .route("/auth/register", post(handlers::auth::register))
         .route("/auth/forgot-password", post(handlers::auth::forgot_password))
         .route("/auth/reset-password", post(handlers::auth::reset_password))
         .route("/auth/accept-invite", post(handlers::users::accept_invite))
         .route("/auth/2fa/validate", post(handlers::two_factor::validate_2fa_login))
+        .route("/daemon/events", post(handlers::daemons::receive_daemon_event))
         .route("/ws/daemons/stats", get(handlers::daemons::ws_daemon_stats))
 
         .route("/ws/containers/:id/logs", get(handlers::ws::container_logs))
-        .route("/ws/containers/:id/stats", get(handlers::ws::container_stats));
+        .route("/ws/containers/:id/stats", get(handlers::ws::container_stats))
+        .route("/ws/containers/:id/exec", get(handlers::ws::container_exec));
 
     let user_routes = Router::new()
         .route("/users/me", get(handlers::users::get_me))
+        .route("/users/me/notifications", get(handlers::users::get_notification_preferences))
+        .route("/users/me/notifications", put(handlers::users::update_notification_preferences))
+        .route("/auth/logout-all", post(handlers::auth::logout_all)
+            .route_layer(axum_middleware::from_fn(require_session)))
+        .route("/users/me/api-keys", get(handlers::api_keys::list_api_keys))
+        .route("/users/me/api-keys", post(handlers::api_keys::create_api_key)
+            .route_layer(axum_middleware::from_fn(require_session)))
+        .route("/users/me/api-keys/:id", delete(handlers::api_keys::revoke_api_key)
+            .route_layer(axum_middleware::from_fn(require_session)))
         .route("/users/me/2fa", get(handlers::two_factor::get_2fa_status))
-        .route("/users/me/2fa/setup", post(handlers::two_factor::setup_2fa))
-        .route("/users/me/2fa/verify", post(handlers::two_factor::verify_2fa))
-        .route("/users/me/2fa/disable", post(handlers::two_factor::disable_2fa))
-        .route("/users/me/2fa/backup-codes", post(handlers::two_factor::regenerate_backup_codes))
+        .route("/users/me/2fa/setup", post(handlers::two_factor::setup_2fa)
+            .route_layer(axum_middleware::from_fn(require_session)))
+        .route("/users/me/2fa/verify", post(handlers::two_factor::verify_2fa)
+            .route_layer(axum_middleware::from_fn(require_session)))
+        .route("/users/me/2fa/disable", post(handlers::two_factor::disable_2fa)
+            .route_layer(axum_middleware::from_fn(require_session)))
+        .route("/users/me/2fa/backup-codes", post(handlers::two_factor::regenerate_backup_codes)
+            .route_layer(axum_middleware::from_fn(require_session)))
         .route("/containers", get(handlers::containers::list_containers))
         .route("/containers/:id", get(handlers::containers::get_container))
         .route("/containers/:id", patch(handlers::containers::update_container))
         .route("/containers/:id/ports", get(handlers::containers::get_container_ports))
         .route("/containers/:id/stats", get(handlers::containers::get_container_stats))
+        .route("/containers/:id/summary", get(handlers::containers::get_container_summary))
+        .route("/containers/:id/resources", get(handlers::containers::get_container_resources))
+        .route("/containers/:id/logs", get(handlers::containers::get_logs))
+        .route("/containers/:id/logs/history", get(handlers::containers::get_log_history))
+        .route("/containers/:id/logs/download", get(handlers::containers::download_logs))
         .route("/containers/:id/startup", get(handlers::containers::get_container_startup))
         .route("/containers/:id/startup", put(handlers::containers::update_container_startup))
+        .route("/containers/:id/startup/preview", get(handlers::containers::preview_container_startup))
+        .route("/containers/:id/env", get(handlers::containers::get_container_env))
+        .route("/containers/:id/env", put(handlers::containers::update_container_env))
         .route("/containers/:id/allocation", post(handlers::containers::assign_allocation))
         .route("/containers/:id/allocations", get(handlers::containers::get_container_allocations))
         .route("/containers/:id/allocations/available", get(handlers::containers::get_available_allocations))
         .route("/containers/:id/allocations", post(handlers::containers::add_allocation))
         .route("/containers/:id/allocations/:allocation_id", delete(handlers::containers::remove_allocation))
         .route("/containers/:id/allocations/:allocation_id/primary", post(handlers::containers::set_primary_allocation))
+        .route("/containers/:id/allocations/sync", post(handlers::containers::sync_container_allocations))
         .route("/containers/:id/start", post(handlers::containers::start_container))
         .route("/containers/:id/stop", post(handlers::containers::stop_container))
         .route("/containers/:id/restart", post(handlers::containers::restart_container))
+        .route("/containers/:id/recreate", post(handlers::containers::recreate_container))
+        .route("/containers/:id/wipe", post(handlers::containers::wipe_container))
+        .route("/containers/:id/pull", post(handlers::containers::pull_container_image))
         .route("/containers/:id/kill", post(handlers::containers::kill_container))
         .route("/containers/:id/command", post(handlers::containers::send_command))
+        .route("/containers/:id/commands", get(handlers::containers::get_container_commands))
+        .route("/containers/:id/activity", get(handlers::containers::get_container_activity))
+        .route("/containers/:id/alerts", get(handlers::containers::get_container_alerts))
+        .route("/containers/:id/backups", get(handlers::backups::list_backups))
+        .route("/containers/:id/backups", post(handlers::backups::create_backup))
+        .route("/containers/:id/backups/:backup", delete(handlers::backups::delete_backup))
+        .route("/containers/:id/backup-schedule", get(handlers::backups::get_backup_schedule))
+        .route("/containers/:id/backup-schedule", put(handlers::backups::update_backup_schedule))
+        .route("/containers/:id/backup-schedule", delete(handlers::backups::delete_backup_schedule))
         .route("/containers/:id/graceful-stop", post(handlers::containers::graceful_stop_container))
         .route("/containers/:id/sftp-password", post(handlers::containers::set_sftp_password))
+        .route("/containers/:id/sftp", delete(handlers::containers::revoke_sftp_access))
         .route("/containers/:id/users", get(handlers::containers::list_container_users))
         .route("/containers/:id/users", post(handlers::containers::add_container_user))
         .route("/containers/:id/users/:user_id", delete(handlers::containers::remove_container_user))
@@ -104,7 +165,7 @@ async fn main() -> anyhow::Result<()> {
         .route("/containers/:id/files/read", get(handlers::containers::read_file))
         .route("/containers/:id/files/write", post(handlers::containers::write_file))
         .route("/containers/:id/files/upload", post(handlers::containers::upload_file)
-            .layer(DefaultBodyLimit::max(UPLOAD_CHUNK_BODY_LIMIT)))
+            .layer(DefaultBodyLimit::max(upload_body_limit)))
 
         .route("/databases", get(handlers::databases::list_databases))
         .route("/databases", post(handlers::databases::create_database))
@@ -112,8 +173,9 @@ async fn main() -> anyhow::Result<()> {
         .route("/databases/:id", get(handlers::databases::get_database))
         .route("/databases/:id", delete(handlers::databases::delete_database))
         .route("/databases/:id/reset-password", post(handlers::databases::reset_database_password))
+        .route("/containers/:id/files/upload-limits", get(handlers::containers::get_upload_limits))
         .route("/containers/:id/files/upload-chunk", post(handlers::containers::upload_file_chunk)
-            .layer(DefaultBodyLimit::max(UPLOAD_CHUNK_BODY_LIMIT)))
+            .layer(DefaultBodyLimit::max(upload_body_limit)))
         .route("/containers/:id/files/folder", post(handlers::containers::create_folder))
         .route("/containers/:id/files/delete", delete(handlers::containers::delete_file))
         .route("/containers/:id/files/download", get(handlers::containers::download_file))
@@ -122,6 +184,7 @@ async fn main() -> anyhow::Result<()> {
         .route("/daemons/:id", get(handlers::daemons::get_daemon))
         .route("/daemons/:id/status", get(handlers::daemons::get_daemon_status))
         .route("/roles", get(handlers::roles::list_roles))
+        .route("/permissions", get(handlers::roles::list_permissions))
         .route("/roles/:id", get(handlers::roles::get_role))
 
         .route("/flakes", get(handlers::flakes::list_flakes))
@@ -133,57 +196,71 @@ async fn main() -> anyhow::Result<()> {
         .route("/users/:id", get(handlers::users::get_user))
         .route("/users/:id", patch(handlers::users::update_user))
         .route("/containers", post(handlers::containers::create_container)
-            .route_layer(axum_middleware::from_fn(require_permission("containers.create"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::CONTAINERS_CREATE))))
+        .route("/containers/:id/clone", post(handlers::containers::clone_container)
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::CONTAINERS_CREATE))))
         .route("/containers/:id", delete(handlers::containers::delete_container)
-            .route_layer(axum_middleware::from_fn(require_permission("containers.delete"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::CONTAINERS_DELETE))))
+        .route("/containers/:id/restore", post(handlers::containers::restore_container)
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::CONTAINERS_DELETE))))
+        .route("/admin/containers/trash", get(handlers::containers::list_trashed_containers)
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::CONTAINERS_DELETE))))
         .route("/admin/containers", get(handlers::containers::list_all_containers)
-            .route_layer(axum_middleware::from_fn(require_permission("containers.view_all"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::CONTAINERS_VIEW_ALL))))
+        .route("/admin/containers/alerts", get(handlers::containers::list_all_alerts)
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::CONTAINERS_VIEW_ALL))))
+        .route("/admin/containers/bulk-action", post(handlers::containers::bulk_container_action)
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::CONTAINERS_MANAGE))))
+        .route("/daemons/capacity", get(handlers::daemons::get_daemon_capacity))
         .route("/daemons/:id/ip-pools", get(handlers::allocations::list_daemon_ip_pools))
+        .route("/daemons/:id/allocations", get(handlers::allocations::list_daemon_allocations))
         .route("/allocations", get(handlers::allocations::list_allocations))
         .route("/allocations/all", get(handlers::allocations::list_all_allocations))
         .route("/allocations", post(handlers::allocations::create_allocation)
-            .route_layer(axum_middleware::from_fn(require_permission("allocations.create"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::ALLOCATIONS_CREATE))))
         .route("/allocations/:id", patch(handlers::allocations::update_allocation)
-            .route_layer(axum_middleware::from_fn(require_permission("allocations.create"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::ALLOCATIONS_CREATE))))
         .route("/allocations/:id", delete(handlers::allocations::delete_allocation)
-            .route_layer(axum_middleware::from_fn(require_permission("allocations.delete"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::ALLOCATIONS_DELETE))))
         .route("/ip-pools", get(handlers::allocations::list_ip_pools))
         .route("/ip-pools", post(handlers::allocations::create_ip_pool)
-            .route_layer(axum_middleware::from_fn(require_permission("allocations.create"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::ALLOCATIONS_CREATE))))
         .route("/ip-pools/:id", delete(handlers::allocations::delete_ip_pool)
-            .route_layer(axum_middleware::from_fn(require_permission("allocations.delete"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::ALLOCATIONS_DELETE))))
         .route("/container-allocations", post(handlers::allocations::create_container_allocation)
-            .route_layer(axum_middleware::from_fn(require_permission("allocations.create"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::ALLOCATIONS_CREATE))))
         .route("/container-allocations/:id", delete(handlers::allocations::delete_container_allocation)
-            .route_layer(axum_middleware::from_fn(require_permission("allocations.delete"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::ALLOCATIONS_DELETE))))
 
         .route("/flakes", post(handlers::flakes::create_flake)
-            .route_layer(axum_middleware::from_fn(require_permission("flakes.create"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::FLAKES_CREATE))))
         .route("/flakes/import", post(handlers::flakes::import_flake)
-            .route_layer(axum_middleware::from_fn(require_permission("flakes.create"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::FLAKES_CREATE))))
         .route("/flakes/:id", delete(handlers::flakes::delete_flake)
-            .route_layer(axum_middleware::from_fn(require_permission("flakes.delete"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::FLAKES_DELETE))))
         .layer(axum_middleware::from_fn(require_manager));
 
     let admin_routes = Router::new()
         .route("/admin/users/search", get(handlers::users::search_users))
         .route("/admin/users/invite", post(handlers::users::invite_user)
-            .route_layer(axum_middleware::from_fn(require_permission("users.create"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::USERS_CREATE))))
         .route("/admin/users/:id", delete(handlers::users::delete_user)
-            .route_layer(axum_middleware::from_fn(require_permission("users.delete"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::USERS_DELETE))))
         .route("/admin/daemons", post(handlers::daemons::create_daemon)
-            .route_layer(axum_middleware::from_fn(require_permission("daemons.create"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::DAEMONS_CREATE))))
         .route("/admin/daemons/:id", patch(handlers::daemons::update_daemon)
-            .route_layer(axum_middleware::from_fn(require_permission("daemons.update"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::DAEMONS_UPDATE))))
         .route("/admin/daemons/:id", delete(handlers::daemons::delete_daemon)
-            .route_layer(axum_middleware::from_fn(require_permission("daemons.delete"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::DAEMONS_DELETE))))
+        .route("/admin/daemons/:id/drain", post(handlers::daemons::drain_daemon).get(handlers::daemons::get_daemon_drain)
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::DAEMONS_UPDATE))))
         .route("/admin/daemons/ping", post(handlers::daemons::ping_daemon))
         .route("/admin/roles", post(handlers::roles::create_role)
-            .route_layer(axum_middleware::from_fn(require_permission("roles.create"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::ROLES_CREATE))))
         .route("/admin/roles/:id", patch(handlers::roles::update_role)
-            .route_layer(axum_middleware::from_fn(require_permission("roles.update"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::ROLES_UPDATE))))
         .route("/admin/roles/:id", delete(handlers::roles::delete_role)
-            .route_layer(axum_middleware::from_fn(require_permission("roles.delete"))))
+            .route_layer(axum_middleware::from_fn(require_permission(permissions::ROLES_DELETE))))
 
         .route("/admin/database-servers", get(handlers::databases::list_database_servers))
         .route("/admin/database-servers", post(handlers::databases::create_database_server))
@@ -199,17 +276,64 @@ async fn main() -> anyhow::Result<()> {
         .merge(user_routes)
         .merge(manager_routes)
         .merge(admin_routes)
+        .layer(axum_middleware::from_fn_with_state(
+            app_state.clone(),
+            middleware::idempotency,
+        ))
         .layer(axum_middleware::from_fn_with_state(
             app_state.clone(),
             middleware::auth,
         ));
 
-    let app = Router::new()
+    Router::new()
         .merge(public_routes)
         .merge(protected_routes)
+        .layer(axum_middleware::from_fn_with_state(app_state.clone(), metrics::track))
         .layer(cors)
+        .layer(axum_middleware::from_fn(middleware::request_id))
         .layer(TraceLayer::new_for_http())
-        .with_state(app_state);
+        .with_state(app_state)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenvy::dotenv().ok();
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new(
+            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
+        ))
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+
+    let config = Config::from_env();
+
+    let pool = PgPoolOptions::new()
+        .max_connections(config.db_pool.max_connections)
+        .min_connections(config.db_pool.min_connections)
+        .acquire_timeout(std::time::Duration::from_secs(config.db_pool.acquire_timeout_secs))
+        .idle_timeout((config.db_pool.idle_timeout_secs > 0).then(|| std::time::Duration::from_secs(config.db_pool.idle_timeout_secs)))
+        .connect(&config.database_url)
+        .await?;
+
+    MIGRATOR.run(&pool).await?;
+
+    seeder::run(&pool, &config).await?;
+    handlers::daemons::interrupt_stale_drains(&pool).await?;
+
+    let app_state = models::AppState {
+        db: pool,
+        config: config.clone(),
+        metrics: Default::default(),
+        daemon_stats: Default::default(),
+    };
+
+    reconciler::spawn(app_state.clone());
+    outbox::spawn(app_state.clone());
+    backup_scheduler::spawn(app_state.clone());
+    trash::spawn(app_state.clone());
+
+    let app = router(app_state);
 
     let listener = tokio::net::TcpListener::bind(&config.api_addr).await?;
     tracing::info!("listening on {}", config.api_addr);
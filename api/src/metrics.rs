@@ -0,0 +1,108 @@
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::models::AppState;
+
+/// Upper bounds of the latency histogram, in seconds.
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Default)]
+struct Series {
+    count: u64,
+    sum_secs: f64,
+    /// Non-cumulative; summed up when rendered.
+    buckets: [u64; BUCKETS.len()],
+}
+
+/// Request counts and latencies by method, route template and status.
+#[derive(Default)]
+pub struct HttpMetrics {
+    series: Mutex<HashMap<(String, String, u16), Series>>,
+}
+
+impl HttpMetrics {
+    fn observe(&self, method: &str, route: &str, status: u16, secs: f64) {
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = series.entry((method.to_string(), route.to_string(), status)).or_default();
+
+        entry.count += 1;
+        entry.sum_secs += secs;
+        if let Some(i) = BUCKETS.iter().position(|le| secs <= *le) {
+            entry.buckets[i] += 1;
+        }
+    }
+
+    fn render(&self) -> String {
+        let series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# HELP raptor_http_requests_total Requests handled by the API");
+        let _ = writeln!(out, "# TYPE raptor_http_requests_total counter");
+        for ((method, route, status), s) in series.iter() {
+            let _ = writeln!(out, "raptor_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}", method, route, status, s.count);
+        }
+
+        let _ = writeln!(out, "# HELP raptor_http_request_duration_seconds Time to produce a response");
+        let _ = writeln!(out, "# TYPE raptor_http_request_duration_seconds histogram");
+        for ((method, route, status), s) in series.iter() {
+            let labels = format!("method=\"{}\",route=\"{}\",status=\"{}\"", method, route, status);
+            let mut cumulative = 0;
+
+            for (le, count) in BUCKETS.iter().zip(s.buckets) {
+                cumulative += count;
+                let _ = writeln!(out, "raptor_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, le, cumulative);
+            }
+            let _ = writeln!(out, "raptor_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, s.count);
+            let _ = writeln!(out, "raptor_http_request_duration_seconds_sum{{{}}} {}", labels, s.sum_secs);
+            let _ = writeln!(out, "raptor_http_request_duration_seconds_count{{{}}} {}", labels, s.count);
+        }
+
+        out
+    }
+}
+
+/// Records every request under its route template (`/containers/:id`), never the raw path,
+/// so ids don't blow up the number of series.
+pub async fn track(State(state): State<AppState>, req: Request<Body>, next: Next) -> Response {
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let started = Instant::now();
+    let response = next.run(req).await;
+
+    state.metrics.observe(&method, &route, response.status().as_u16(), started.elapsed().as_secs_f64());
+
+    response
+}
+
+/// GET /metrics - Prometheus scrape endpoint, only served when METRICS_TOKEN is set and sent
+/// as `Authorization: Bearer <token>`.
+pub async fn handler(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let Some(token) = state.config.metrics_token.as_deref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    if !given.is_some_and(|given| raptor_common::secrets::secrets_match(given, token)) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], state.metrics.render()).into_response()
+}
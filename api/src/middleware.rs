@@ -12,6 +12,11 @@ use std::pin::Pin;
 use uuid::Uuid;
 
 use crate::models::{AppState, Claims};
+use crate::permissions;
+
+tokio::task_local! {
+    pub static REQUEST_ID: String;
+}
 
 #[derive(Debug, Clone)]
 pub struct AuthUser {
@@ -58,6 +63,16 @@ pub async fn auth(
         _ => return Err(StatusCode::UNAUTHORIZED),
     };
 
+    if token.starts_with(crate::handlers::api_keys::KEY_PREFIX) {
+        let claims = crate::handlers::api_keys::resolve(&state, token)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        req.extensions_mut().insert(claims);
+        return Ok(next.run(req).await);
+    }
+
     let claims = decode::<Claims>(
         token,
         &DecodingKey::from_secret(state.config.jwt_secret.as_bytes()),
@@ -66,13 +81,269 @@ pub async fn auth(
     .map_err(|_| StatusCode::UNAUTHORIZED)?
     .claims;
 
+    let current_version: Option<(i32,)> = sqlx::query_as("SELECT token_version FROM users WHERE id = $1")
+        .bind(claims.sub)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    match current_version {
+        Some((version,)) if version == claims.token_version => {}
+        _ => return Err(StatusCode::UNAUTHORIZED),
+    }
+
     req.extensions_mut().insert(claims);
     Ok(next.run(req).await)
 }
 
+/// Tags each request with an id (the caller's `X-Request-Id` or a fresh one) that
+/// daemon calls log and forward, and echoes it on the response.
+pub async fn request_id(req: Request<Body>, next: Next) -> Response {
+    use tracing::Instrument;
+
+    let id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|h| h.to_str().ok())
+        .filter(|h| !h.is_empty() && h.len() <= 128)
+        .map(|h| h.to_string())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!("request", request_id = %id, method = %req.method(), path = %req.uri().path());
+    let started = std::time::Instant::now();
+
+    let mut response = REQUEST_ID
+        .scope(id.clone(), next.run(req))
+        .instrument(span.clone())
+        .await;
+
+    span.in_scope(|| {
+        tracing::info!(
+            status = response.status().as_u16(),
+            duration_ms = started.elapsed().as_millis() as u64,
+            "request finished"
+        )
+    });
+
+    if let Ok(value) = header::HeaderValue::from_str(&id) {
+        response.headers_mut().insert("x-request-id", value);
+    }
+
+    response
+}
+
+/// Requests and responses larger than this are not eligible for idempotent replay.
+const IDEMPOTENT_BODY_LIMIT: usize = 1024 * 1024;
+
+/// How long a stored response answers retries of the same `Idempotency-Key`.
+const IDEMPOTENCY_KEY_TTL_HOURS: i32 = 24;
+
+/// How long an unfinished claim holds its key. Claims are released when the request fails or
+/// is dropped, so this only matters when the API died mid-request; retries take over after it.
+const IDEMPOTENCY_LEASE_SECS: f64 = 600.0;
+
+#[derive(sqlx::FromRow)]
+struct StoredResponse {
+    method: String,
+    path: String,
+    request_hash: String,
+    status_code: Option<i32>,
+    response_body: Option<Vec<u8>>,
+}
+
+/// POSTs carrying an `Idempotency-Key` run once per user and key: a retry gets the stored
+/// response back instead of creating a second container. Server errors are not stored, so
+/// those can be retried with the same key, and neither are claims left behind by a request
+/// that never finished. Runs after `auth`.
+pub async fn idempotency(State(state): State<AppState>, req: Request<Body>, next: Next) -> Response {
+    let key = req
+        .headers()
+        .get("idempotency-key")
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty());
+
+    let (Some(key), Some(user_id)) = (key, req.extensions().get::<Claims>().map(|c| c.sub)) else {
+        return next.run(req).await;
+    };
+
+    if req.method() != axum::http::Method::POST {
+        return next.run(req).await;
+    }
+
+    if key.len() > 255 {
+        return (StatusCode::BAD_REQUEST, "Idempotency-Key must be at most 255 characters").into_response();
+    }
+
+    let (parts, body) = req.into_parts();
+    let body = match axum::body::to_bytes(body, IDEMPOTENT_BODY_LIMIT).await {
+        Ok(body) => body,
+        Err(_) => return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large for an idempotent request").into_response(),
+    };
+
+    let method = parts.method.to_string();
+    let path = parts.uri.path().to_string();
+    let request_hash = {
+        use sha2::{Digest, Sha256};
+        Sha256::digest(&body).iter().map(|b| format!("{:02x}", b)).collect::<String>()
+    };
+
+    // Keeps the table bounded; expired keys are never replayed either way
+    if let Err(e) = sqlx::query("DELETE FROM idempotency_keys WHERE created_at < NOW() - make_interval(hours => $1)")
+        .bind(IDEMPOTENCY_KEY_TTL_HOURS)
+        .execute(&state.db)
+        .await
+    {
+        tracing::warn!("Failed to prune expired idempotency keys: {}", e);
+    }
+
+    // Claims the key, or takes over one that has expired or whose lease ran out unfinished
+    let claimed = sqlx::query(
+        r#"INSERT INTO idempotency_keys (user_id, key, method, path, request_hash)
+           VALUES ($1, $2, $3, $4, $5)
+           ON CONFLICT (user_id, key) DO UPDATE SET
+               method = EXCLUDED.method, path = EXCLUDED.path, request_hash = EXCLUDED.request_hash,
+               status_code = NULL, response_body = NULL, created_at = NOW()
+           WHERE idempotency_keys.created_at < NOW() - make_interval(hours => $6)
+              OR (idempotency_keys.status_code IS NULL
+                  AND idempotency_keys.created_at < NOW() - make_interval(secs => $7))"#
+    )
+        .bind(user_id)
+        .bind(&key)
+        .bind(&method)
+        .bind(&path)
+        .bind(&request_hash)
+        .bind(IDEMPOTENCY_KEY_TTL_HOURS)
+        .bind(IDEMPOTENCY_LEASE_SECS)
+        .execute(&state.db)
+        .await;
+
+    match claimed {
+        Ok(result) if result.rows_affected() == 0 => {
+            return replay_stored(&state, user_id, &key, &method, &path, &request_hash).await;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            tracing::error!("Failed to claim idempotency key: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    // Released if the client goes away and this future is dropped before a response is stored
+    let mut claim = ClaimGuard { state: state.clone(), user_id, key: key.clone(), held: true };
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let status = response.status();
+
+    if status.is_server_error() {
+        claim.held = false;
+        release_key(&state, user_id, &key).await;
+        return response;
+    }
+
+    let (response_parts, response_body) = response.into_parts();
+    let response_body = match axum::body::to_bytes(response_body, IDEMPOTENT_BODY_LIMIT).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Response to an idempotent request could not be stored: {}", e);
+            claim.held = false;
+            release_key(&state, user_id, &key).await;
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let stored = sqlx::query("UPDATE idempotency_keys SET status_code = $1, response_body = $2 WHERE user_id = $3 AND key = $4")
+        .bind(status.as_u16() as i32)
+        .bind(response_body.to_vec())
+        .bind(user_id)
+        .bind(&key)
+        .execute(&state.db)
+        .await;
+
+    claim.held = false;
+
+    if let Err(e) = stored {
+        tracing::warn!("Failed to store response for idempotency key: {}", e);
+    }
+
+    Response::from_parts(response_parts, Body::from(response_body))
+}
+
+async fn replay_stored(state: &AppState, user_id: Uuid, key: &str, method: &str, path: &str, request_hash: &str) -> Response {
+    let stored: Option<StoredResponse> = match sqlx::query_as(
+        "SELECT method, path, request_hash, status_code, response_body FROM idempotency_keys WHERE user_id = $1 AND key = $2"
+    )
+        .bind(user_id)
+        .bind(key)
+        .fetch_optional(&state.db)
+        .await
+    {
+        Ok(stored) => stored,
+        Err(e) => {
+            tracing::error!("Failed to load idempotency key: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let Some(stored) = stored else {
+        // Released by a failed attempt in the meantime
+        return (StatusCode::CONFLICT, "Request with this Idempotency-Key failed, retry it").into_response();
+    };
+
+    if stored.method != method || stored.path != path || stored.request_hash != request_hash {
+        return (StatusCode::UNPROCESSABLE_ENTITY, "Idempotency-Key was already used for a different request").into_response();
+    }
+
+    let (Some(status), Some(body)) = (stored.status_code, stored.response_body) else {
+        return (StatusCode::CONFLICT, "A request with this Idempotency-Key is still in progress").into_response();
+    };
+
+    let status = StatusCode::from_u16(status as u16).unwrap_or(StatusCode::OK);
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/json"), (header::HeaderName::from_static("idempotent-replayed"), "true")],
+        body,
+    )
+        .into_response()
+}
+
+struct ClaimGuard {
+    state: AppState,
+    user_id: Uuid,
+    key: String,
+    held: bool,
+}
+
+impl Drop for ClaimGuard {
+    fn drop(&mut self) {
+        if !self.held {
+            return;
+        }
+        let state = self.state.clone();
+        let user_id = self.user_id;
+        let key = std::mem::take(&mut self.key);
+        tokio::spawn(async move { release_key(&state, user_id, &key).await });
+    }
+}
+
+async fn release_key(state: &AppState, user_id: Uuid, key: &str) {
+    let result = sqlx::query("DELETE FROM idempotency_keys WHERE user_id = $1 AND key = $2")
+        .bind(user_id)
+        .bind(key)
+        .execute(&state.db)
+        .await;
+
+    if let Err(e) = result {
+        tracing::warn!("Failed to release idempotency key: {}", e);
+    }
+}
+
 pub fn require_permission(
     permission: &'static str,
 ) -> impl Fn(Request<Body>, Next) -> Pin<Box<dyn Future<Output = Response> + Send>> + Clone + Send {
+    // Routes are built at startup, so a typo fails loudly there instead of locking everyone out
+    assert!(permissions::is_known(permission), "unknown permission '{}'", permission);
+
     move |req: Request<Body>, next: Next| {
         Box::pin(async move {
             match req.extensions().get::<Claims>() {
@@ -86,7 +357,7 @@ pub fn require_permission(
 
 pub async fn require_admin(req: Request<Body>, next: Next) -> Response {
     match req.extensions().get::<Claims>() {
-        Some(c) if c.has_permission("*") => next.run(req).await,
+        Some(c) if c.has_permission(permissions::ALL) => next.run(req).await,
         Some(_) => StatusCode::FORBIDDEN.into_response(),
         None => StatusCode::UNAUTHORIZED.into_response(),
     }
@@ -94,9 +365,20 @@ pub async fn require_admin(req: Request<Body>, next: Next) -> Response {
 
 pub async fn require_manager(req: Request<Body>, next: Next) -> Response {
     match req.extensions().get::<Claims>() {
-        Some(c) if c.has_permission("admin.access") || c.has_permission("*") => next.run(req).await,
+        Some(c) if c.has_permission(permissions::ADMIN_ACCESS) || c.has_permission(permissions::ALL) => next.run(req).await,
         Some(_) => StatusCode::FORBIDDEN.into_response(),
         None => StatusCode::UNAUTHORIZED.into_response(),
     }
 }
 
+
+/// For account security routes (API keys, 2FA, signing out everywhere): a leaked API key must
+/// not be able to mint unscoped keys, lock the owner out or keep itself alive, so these need a
+/// signed-in session.
+pub async fn require_session(req: Request<Body>, next: Next) -> Response {
+    match req.extensions().get::<Claims>() {
+        Some(c) if c.api_key_id.is_none() => next.run(req).await,
+        Some(_) => (StatusCode::FORBIDDEN, "This action requires signing in; API keys can't perform it").into_response(),
+        None => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
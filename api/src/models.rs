@@ -13,6 +13,8 @@ fn default_protocol() -> String {
 pub struct AppState {
     pub db: PgPool,
     pub config: Config,
+    pub metrics: std::sync::Arc<crate::metrics::HttpMetrics>,
+    pub daemon_stats: std::sync::Arc<crate::daemon_stats::DaemonStatsHub>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -24,6 +26,8 @@ pub struct User {
     pub password_hash: String,
     pub email: Option<String>,
     pub role_id: Option<Uuid>,
+    #[serde(skip_serializing)]
+    pub token_version: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -45,6 +49,10 @@ pub struct Role {
     pub id: Uuid,
     pub name: String,
     pub permissions: serde_json::Value,
+    pub max_memory: Option<i64>,
+    pub max_cpu: Option<rust_decimal::Decimal>,
+    pub max_disk: Option<i64>,
+    pub max_containers: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -59,6 +67,7 @@ pub struct Daemon {
     pub api_key: String,
     pub location: Option<String>,
     pub secure: bool,
+    pub maintenance: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -89,8 +98,39 @@ pub struct Container {
     pub disk_limit: Option<i64>,
     pub swap_limit: Option<i64>,
     pub io_weight: Option<i32>,
+    pub timezone: String,
+    pub cpuset_cpus: Option<String>,
+    pub isolated: bool,
+    /// Docker restart policy, applied by the daemon whenever it recreates the container.
+    pub restart_policy: String,
+    /// Container started before this one and stopped after it.
+    pub depends_on: Option<Uuid>,
+    /// Set while the container is in the trash.
+    pub deleted_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// CPU/memory thresholds the daemon watches; see `raptor_common::resources::ResourceAlerts`.
+    pub resource_alerts: Option<serde_json::Value>,
+    /// Saved settings the running container doesn't have yet; cleared when it is recreated.
+    pub pending_changes: bool,
+    /// Size-capped in-memory mounts; see `raptor_common::mounts::TmpfsMount`.
+    pub tmpfs: serde_json::Value,
+    /// No swap at all, regardless of `swap_limit`.
+    pub disable_swap: bool,
+    /// Extra Docker labels given at creation, on top of the flake's.
+    pub docker_labels: serde_json::Value,
+    /// Volume and root filesystem are mounted read-only and file writes are refused.
+    pub read_only: bool,
+}
+
+impl Container {
+    pub fn tmpfs(&self) -> Vec<raptor_common::mounts::TmpfsMount> {
+        serde_json::from_value(self.tmpfs.clone()).unwrap_or_default()
+    }
+
+    pub fn docker_labels(&self) -> std::collections::HashMap<String, String> {
+        serde_json::from_value(self.docker_labels.clone()).unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -101,6 +141,8 @@ pub struct ContainerUser {
     pub user_id: Uuid,
     pub permission_level: String,
     pub created_at: DateTime<Utc>,
+    /// See `permissions::SUB_USER_PERMISSIONS`.
+    pub permissions: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -111,9 +153,49 @@ pub struct ContainerUserResponse {
     pub user_id: Uuid,
     pub username: String,
     pub permission_level: String,
+    pub permissions: Vec<String>,
     pub created_at: DateTime<Utc>,
 }
 
+/// `container_users.permission_level`. What a sub-user may do is decided by their
+/// `permissions`; the level only picks the defaults when none are given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerPermissionLevel {
+    /// Can see the container and read its files.
+    User,
+    /// Can also change files.
+    Admin,
+    Owner,
+}
+
+impl ContainerPermissionLevel {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "user" => Some(Self::User),
+            "admin" => Some(Self::Admin),
+            "owner" => Some(Self::Owner),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Admin => "admin",
+            Self::Owner => "owner",
+        }
+    }
+
+    pub fn default_permissions(&self) -> Vec<String> {
+        let granted: &[&str] = match self {
+            Self::User => &[crate::permissions::SUB_FILES_READ],
+            Self::Admin => &[crate::permissions::SUB_FILES_READ, crate::permissions::SUB_FILES_WRITE],
+            Self::Owner => crate::permissions::SUB_USER_PERMISSIONS,
+        };
+        granted.iter().map(|p| p.to_string()).collect()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct ContainerPort {
@@ -134,6 +216,8 @@ pub struct Allocation {
     pub port: i32,
     #[serde(default = "default_tcp")]
     pub protocol: String,
+    pub label: Option<String>,
+    pub notes: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -263,6 +347,28 @@ pub struct RoleResponse {
     pub id: Uuid,
     pub name: String,
     pub permissions: serde_json::Value,
+    #[serde(flatten)]
+    pub limits: RoleResourceLimits,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleResourceLimits {
+    pub max_memory: Option<i64>,
+    pub max_cpu: Option<rust_decimal::Decimal>,
+    pub max_disk: Option<i64>,
+    pub max_containers: Option<i32>,
+}
+
+impl Role {
+    pub fn limits(&self) -> RoleResourceLimits {
+        RoleResourceLimits {
+            max_memory: self.max_memory,
+            max_cpu: self.max_cpu,
+            max_disk: self.max_disk,
+            max_containers: self.max_containers,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -303,10 +409,20 @@ pub struct PortMapping {
     pub protocol: String,
 }
 
+fn deserialize_daemon_choice<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Uuid>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    if value.eq_ignore_ascii_case("auto") {
+        return Ok(None);
+    }
+    Uuid::parse_str(&value).map(Some).map_err(serde::de::Error::custom)
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateContainerRequest {
-    pub daemon_id: Uuid,
+    /// `None` when the client sent `"auto"`, leaving placement to `daemons::pick_daemon`.
+    #[serde(deserialize_with = "deserialize_daemon_choice")]
+    pub daemon_id: Option<Uuid>,
     pub name: String,
     pub flake_id: Option<Uuid>,
     pub image: Option<String>,
@@ -331,12 +447,41 @@ pub struct CreateContainerRequest {
     pub user_id: Option<Uuid>,
     #[serde(default)]
     pub variables: std::collections::HashMap<String, String>,
+    /// IANA timezone name, defaults to UTC.
+    pub timezone: Option<String>,
+    /// CPUs to pin the container to, e.g. "0-3,8". Managers only.
+    #[serde(default)]
+    pub cpuset_cpus: Option<String>,
+    /// Give the container its own network so other tenants' containers can't reach it.
+    #[serde(default)]
+    pub isolated: bool,
+    /// Managers only.
+    #[serde(default)]
+    pub disable_swap: bool,
+    /// Managers only.
+    #[serde(default)]
+    pub tmpfs: Vec<raptor_common::mounts::TmpfsMount>,
+    /// Mount the volume and root filesystem read-only; defaults to the flake's setting.
+    #[serde(default)]
+    pub read_only: Option<bool>,
+    /// Managers only; Docker labels for reverse proxies and monitoring, overriding the flake's.
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+    /// Start right after creation, running the install script headlessly first if needed.
+    #[serde(default)]
+    pub auto_start: bool,
+    /// Custom environment (`container_env`); only carried over when cloning.
+    #[serde(skip)]
+    pub environment: std::collections::HashMap<String, String>,
+    /// Overrides the flake's restart policy; only set when cloning.
+    #[serde(skip)]
+    pub restart_policy: Option<String>,
 }
 
-fn default_memory() -> i64 { 512 }
-fn default_cpu() -> f64 { 1.0 }
-fn default_disk() -> i64 { 5120 }
-fn default_io() -> i32 { 500 }
+pub fn default_memory() -> i64 { 512 }
+pub fn default_cpu() -> f64 { 1.0 }
+pub fn default_disk() -> i64 { 5120 }
+pub fn default_io() -> i32 { 500 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -345,6 +490,8 @@ pub struct CreateAllocationRequest {
     pub ip: String,
     pub port: i32,
     pub protocol: Option<String>,
+    pub label: Option<String>,
+    pub notes: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -354,7 +501,12 @@ pub struct Claims {
     pub role_id: Option<Uuid>,
     pub role_name: Option<String>,
     pub permissions: serde_json::Value,
+    #[serde(default)]
+    pub token_version: i32,
     pub exp: usize,
+    /// Set when the request authenticated with an API key instead of a JWT.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_id: Option<Uuid>,
 }
 
 impl Claims {
@@ -373,7 +525,7 @@ impl Claims {
     }
 
     pub fn is_admin(&self) -> bool {
-        self.role_name.as_deref() == Some("admin") || self.has_permission("*")
+        self.role_name.as_deref() == Some("admin") || self.has_permission(crate::permissions::ALL)
     }
 
     pub fn is_manager(&self) -> bool {
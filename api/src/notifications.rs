@@ -0,0 +1,74 @@
+use uuid::Uuid;
+
+use crate::email::EmailService;
+use crate::models::AppState;
+
+pub const CONTAINER_CRASHED: &str = "container.crashed";
+pub const INSTALL_COMPLETED: &str = "install.completed";
+pub const CONTAINER_SUSPENDED: &str = "container.suspended";
+/// Status transition reported by the daemon; updates `containers.status` but never emails.
+pub const CONTAINER_STATE: &str = "container.state";
+/// Sustained usage above a container's alert threshold; recorded for managers, never emailed.
+pub const RESOURCE_ALERT: &str = "container.resource_alert";
+
+/// Preference column gating an event's email; `None` for events users cannot subscribe to.
+pub fn preference_column(event: &str) -> Option<&'static str> {
+    match event {
+        CONTAINER_CRASHED => Some("container_crashed"),
+        INSTALL_COMPLETED => Some("install_completed"),
+        CONTAINER_SUSPENDED => Some("container_suspended"),
+        _ => None,
+    }
+}
+
+/// Emails a user if they opted in to `event`. Errors are logged, never returned.
+pub async fn notify_user(state: &AppState, user_id: Uuid, event: &str, title: &str, message: &str) {
+    let Some(column) = preference_column(event) else {
+        return;
+    };
+
+    let query = format!("SELECT {} FROM notification_preferences WHERE user_id = $1", column);
+    let opted_in: Option<bool> = sqlx::query_scalar(&query)
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await
+        .ok()
+        .flatten();
+
+    if opted_in.unwrap_or(false) {
+        send_to_user(state, user_id, title, message).await;
+    }
+}
+
+/// Emails a user unconditionally. No-op when SMTP is not configured or the user has no email.
+pub async fn send_to_user(state: &AppState, user_id: Uuid, title: &str, message: &str) {
+    let Some(ref smtp_config) = state.config.smtp else {
+        return;
+    };
+
+    let user: Option<(String, Option<String>)> = sqlx::query_as("SELECT username, email FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await
+        .ok()
+        .flatten();
+
+    let Some((username, Some(email))) = user else {
+        return;
+    };
+
+    let email_service = match EmailService::new(smtp_config, &state.config.app_url) {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("Failed to create email service: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = email_service
+        .send_notification_email(&email, &username, title, message)
+        .await
+    {
+        tracing::error!("Failed to send \"{}\" email to {}: {}", title, email, e);
+    }
+}
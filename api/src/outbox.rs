@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::models::AppState;
+
+pub const CONTAINER_CREATED: &str = "container.created";
+pub const CONTAINER_DELETED: &str = "container.deleted";
+/// Deleted but still restorable; `container.deleted` follows once the trash is purged.
+pub const CONTAINER_TRASHED: &str = "container.trashed";
+pub const CONTAINER_RESTORED: &str = "container.restored";
+
+const PUBLISH_BATCH_SIZE: i64 = 100;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+/// Long enough to post a whole batch with every request timing out.
+const CLAIM_LEASE_SECS: f64 = (PUBLISH_BATCH_SIZE as u64 * REQUEST_TIMEOUT_SECS + 60) as f64;
+
+#[derive(Debug, sqlx::FromRow)]
+struct OutboxEvent {
+    id: Uuid,
+    container_id: Uuid,
+    event_type: String,
+    payload: serde_json::Value,
+    attempts: i32,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Queues an event inside the caller's transaction, so it is only published if the change commits.
+pub async fn record(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    container_id: Uuid,
+    event_type: &str,
+    payload: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO container_event_outbox (container_id, event_type, payload) VALUES ($1, $2, $3)")
+        .bind(container_id)
+        .bind(event_type)
+        .bind(payload)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(())
+}
+
+pub fn spawn(state: AppState) {
+    let Some(url) = state.config.billing_webhook_url.clone() else {
+        tracing::info!("BILLING_WEBHOOK_URL not set, container events stay queued in the outbox");
+        return;
+    };
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new());
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(state.config.outbox_poll_interval_secs.max(1)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = publish_pending(&state, &client, &url).await {
+                tracing::error!("Outbox publisher failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn publish_pending(state: &AppState, client: &reqwest::Client, url: &str) -> anyhow::Result<()> {
+    let events = claim_batch(state).await?;
+
+    for (i, event) in events.iter().enumerate() {
+        let body = serde_json::json!({
+            "id": event.id,
+            "type": event.event_type,
+            "containerId": event.container_id,
+            "occurredAt": event.created_at,
+            "data": event.payload,
+        });
+
+        let result = client
+            .post(url)
+            .header("Idempotency-Key", event.id.to_string())
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|res| {
+                if res.status().is_success() {
+                    Ok(())
+                } else {
+                    Err(format!("billing endpoint returned {}", res.status()))
+                }
+            });
+
+        match result {
+            Ok(()) => {
+                sqlx::query(
+                    "UPDATE container_event_outbox SET published_at = NOW(), claimed_until = NULL, attempts = attempts + 1, last_error = NULL WHERE id = $1"
+                )
+                    .bind(event.id)
+                    .execute(&state.db)
+                    .await?;
+            }
+            Err(e) => {
+                sqlx::query(
+                    "UPDATE container_event_outbox SET claimed_until = NULL, attempts = attempts + 1, last_error = $2 WHERE id = $1"
+                )
+                    .bind(event.id)
+                    .bind(&e)
+                    .execute(&state.db)
+                    .await?;
+
+                // Stop here so consumers never see events out of order; the rest of the
+                // batch goes back for the next round
+                tracing::warn!(
+                    "Failed to publish {} event {} (attempt {}): {}",
+                    event.event_type, event.id, event.attempts + 1, e
+                );
+                let unsent: Vec<Uuid> = events[i + 1..].iter().map(|event| event.id).collect();
+                sqlx::query("UPDATE container_event_outbox SET claimed_until = NULL WHERE id = ANY($1)")
+                    .bind(&unsent)
+                    .execute(&state.db)
+                    .await?;
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Claims the oldest pending events for `CLAIM_LEASE_SECS` in a short transaction, so no locks
+/// are held while they're posted. Nothing is claimed while another publisher holds a live
+/// claim, which keeps a second API instance from delivering later events first.
+async fn claim_batch(state: &AppState) -> Result<Vec<OutboxEvent>, sqlx::Error> {
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('container_event_outbox'))")
+        .execute(&mut *tx)
+        .await?;
+
+    let mut events: Vec<OutboxEvent> = sqlx::query_as(
+        r#"
+        UPDATE container_event_outbox
+        SET claimed_until = NOW() + make_interval(secs => $2)
+        WHERE id IN (
+            SELECT id FROM container_event_outbox
+            WHERE published_at IS NULL
+            ORDER BY created_at
+            LIMIT $1
+        )
+        AND NOT EXISTS (
+            SELECT 1 FROM container_event_outbox
+            WHERE published_at IS NULL AND claimed_until > NOW()
+        )
+        RETURNING id, container_id, event_type, payload, attempts, created_at
+        "#
+    )
+    .bind(PUBLISH_BATCH_SIZE)
+    .bind(CLAIM_LEASE_SECS)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    // RETURNING doesn't keep the subquery's order
+    events.sort_by_key(|event| event.created_at);
+    Ok(events)
+}
@@ -0,0 +1,134 @@
+//! Every permission the API checks. Routes and the seeder use these constants, and
+//! `GET /permissions` serves the catalog, so the three cannot drift apart.
+
+use serde::Serialize;
+
+pub const ALL: &str = "*";
+pub const ADMIN_ACCESS: &str = "admin.access";
+
+pub const USERS_VIEW: &str = "users.view";
+pub const USERS_CREATE: &str = "users.create";
+pub const USERS_UPDATE: &str = "users.update";
+pub const USERS_DELETE: &str = "users.delete";
+
+pub const ROLES_VIEW: &str = "roles.view";
+pub const ROLES_CREATE: &str = "roles.create";
+pub const ROLES_UPDATE: &str = "roles.update";
+pub const ROLES_DELETE: &str = "roles.delete";
+
+pub const DAEMONS_VIEW: &str = "daemons.view";
+pub const DAEMONS_CREATE: &str = "daemons.create";
+pub const DAEMONS_UPDATE: &str = "daemons.update";
+pub const DAEMONS_DELETE: &str = "daemons.delete";
+
+pub const CONTAINERS_VIEW_OWN: &str = "containers.view_own";
+pub const CONTAINERS_VIEW_ALL: &str = "containers.view_all";
+pub const CONTAINERS_CREATE: &str = "containers.create";
+pub const CONTAINERS_UPDATE: &str = "containers.update";
+pub const CONTAINERS_EDIT_RESOURCES: &str = "containers.edit_resources";
+pub const CONTAINERS_DELETE: &str = "containers.delete";
+pub const CONTAINERS_MANAGE: &str = "containers.manage";
+pub const CONTAINERS_MANAGE_OWN: &str = "containers.manage_own";
+pub const CONTAINERS_CONSOLE_EXEC: &str = "containers.console_exec";
+
+pub const ALLOCATIONS_VIEW: &str = "allocations.view";
+pub const ALLOCATIONS_CREATE: &str = "allocations.create";
+pub const ALLOCATIONS_DELETE: &str = "allocations.delete";
+
+pub const FLAKES_VIEW: &str = "flakes.view";
+pub const FLAKES_CREATE: &str = "flakes.create";
+pub const FLAKES_UPDATE: &str = "flakes.update";
+pub const FLAKES_DELETE: &str = "flakes.delete";
+
+/// What an owner can grant a sub-user on one container (`container_users.permissions`).
+pub const SUB_CONSOLE: &str = "console";
+pub const SUB_POWER: &str = "power";
+pub const SUB_FILES_READ: &str = "files-read";
+pub const SUB_FILES_WRITE: &str = "files-write";
+pub const SUB_STARTUP_EDIT: &str = "startup-edit";
+pub const SUB_ALLOCATIONS: &str = "allocations";
+
+pub const SUB_USER_PERMISSIONS: &[&str] = &[
+    SUB_CONSOLE,
+    SUB_POWER,
+    SUB_FILES_READ,
+    SUB_FILES_WRITE,
+    SUB_STARTUP_EDIT,
+    SUB_ALLOCATIONS,
+];
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Permission {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub group: &'static str,
+}
+
+const fn permission(name: &'static str, description: &'static str, group: &'static str) -> Permission {
+    Permission { name, description, group }
+}
+
+pub const CATALOG: &[Permission] = &[
+    permission(ALL, "Full access to all resources", "general"),
+    permission(ADMIN_ACCESS, "Access to admin panel", "general"),
+    permission(USERS_VIEW, "View users", "users"),
+    permission(USERS_CREATE, "Create users", "users"),
+    permission(USERS_UPDATE, "Update users", "users"),
+    permission(USERS_DELETE, "Delete users", "users"),
+    permission(ROLES_VIEW, "View roles", "roles"),
+    permission(ROLES_CREATE, "Create roles", "roles"),
+    permission(ROLES_UPDATE, "Update roles", "roles"),
+    permission(ROLES_DELETE, "Delete roles", "roles"),
+    permission(DAEMONS_VIEW, "View daemons", "daemons"),
+    permission(DAEMONS_CREATE, "Create daemons", "daemons"),
+    permission(DAEMONS_UPDATE, "Update daemons", "daemons"),
+    permission(DAEMONS_DELETE, "Delete daemons", "daemons"),
+    permission(CONTAINERS_VIEW_OWN, "View own containers", "containers"),
+    permission(CONTAINERS_VIEW_ALL, "View all containers", "containers"),
+    permission(CONTAINERS_CREATE, "Create containers", "containers"),
+    permission(CONTAINERS_UPDATE, "Update containers", "containers"),
+    permission(CONTAINERS_EDIT_RESOURCES, "Change container resource limits", "containers"),
+    permission(CONTAINERS_DELETE, "Delete containers", "containers"),
+    permission(CONTAINERS_MANAGE, "Manage containers (start/stop/restart)", "containers"),
+    permission(CONTAINERS_MANAGE_OWN, "Manage own containers", "containers"),
+    permission(CONTAINERS_CONSOLE_EXEC, "Open a shell inside containers they can manage", "containers"),
+    permission(ALLOCATIONS_VIEW, "View allocations", "allocations"),
+    permission(ALLOCATIONS_CREATE, "Create allocations", "allocations"),
+    permission(ALLOCATIONS_DELETE, "Delete allocations", "allocations"),
+    permission(FLAKES_VIEW, "View flakes (server templates)", "flakes"),
+    permission(FLAKES_CREATE, "Create flakes", "flakes"),
+    permission(FLAKES_UPDATE, "Update flakes", "flakes"),
+    permission(FLAKES_DELETE, "Delete flakes", "flakes"),
+];
+
+pub fn is_known(name: &str) -> bool {
+    CATALOG.iter().any(|p| p.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn every_routed_permission_is_in_the_catalog() {
+        // `require_permission` panics on a permission missing from CATALOG while routes are built
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let _ = crate::router(crate::test_db::app_state(pool));
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown permission")]
+    fn requiring_an_unknown_permission_fails_when_routes_are_built() {
+        let _ = crate::middleware::require_permission("containers.teleport");
+    }
+
+    #[test]
+    fn catalog_names_are_unique() {
+        for (i, permission) in CATALOG.iter().enumerate() {
+            assert!(!CATALOG[i + 1..].iter().any(|p| p.name == permission.name), "'{}' is listed twice", permission.name);
+            assert!(is_known(permission.name));
+        }
+    }
+}
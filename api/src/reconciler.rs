@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use uuid::Uuid;
+
+use crate::handlers::containers::daemon_client;
+use crate::models::{AppState, Daemon};
+
+const TRANSITIONAL_STATUSES: [&str; 3] = ["starting", "stopping", "restarting"];
+
+#[derive(Debug, sqlx::FromRow)]
+struct StuckContainer {
+    id: Uuid,
+    name: String,
+    status: String,
+    daemon_id: Uuid,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct DaemonContainerStatus {
+    pub status: String,
+    pub running: bool,
+    /// Stopped by the daemon after repeated crashes or OOM kills.
+    #[serde(default, rename = "crashLooping")]
+    pub crash_looping: bool,
+}
+
+impl DaemonContainerStatus {
+    /// The panel status this daemon state maps to.
+    pub fn resolved_status(&self) -> &'static str {
+        if self.running {
+            "running"
+        } else if self.crash_looping {
+            "crash_looping"
+        } else {
+            "stopped"
+        }
+    }
+}
+
+pub fn spawn(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(state.config.reconcile_interval_secs.max(1)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = reconcile_stuck_containers(&state).await {
+                tracing::error!("Status reconciler failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn reconcile_stuck_containers(state: &AppState) -> anyhow::Result<()> {
+    // Most recently acted-on containers first, capped per daemon so one pass stays bounded
+    let stuck: Vec<StuckContainer> = sqlx::query_as(
+        r#"
+        SELECT id, name, status, daemon_id FROM (
+            SELECT id, name, status, daemon_id, updated_at,
+                ROW_NUMBER() OVER (PARTITION BY daemon_id ORDER BY updated_at DESC) AS rn
+            FROM containers
+            WHERE status = ANY($1)
+            AND updated_at < NOW() - make_interval(secs => $2)
+        ) ranked
+        WHERE rn <= $3
+        ORDER BY daemon_id, updated_at DESC
+        "#
+    )
+    .bind(&TRANSITIONAL_STATUSES[..])
+    .bind(state.config.status_stuck_threshold_secs as f64)
+    .bind(state.config.reconcile_batch_size.max(1))
+    .fetch_all(&state.db)
+    .await?;
+
+    if stuck.is_empty() {
+        return Ok(());
+    }
+
+    let mut by_daemon: HashMap<Uuid, Vec<StuckContainer>> = HashMap::new();
+    for container in stuck {
+        by_daemon.entry(container.daemon_id).or_default().push(container);
+    }
+
+    let daemon_ids: Vec<Uuid> = by_daemon.keys().cloned().collect();
+    let daemons: Vec<Daemon> = sqlx::query_as("SELECT * FROM daemons WHERE id = ANY($1)")
+        .bind(&daemon_ids)
+        .fetch_all(&state.db)
+        .await?;
+
+    let batches: Vec<(Daemon, Vec<StuckContainer>)> = daemons
+        .into_iter()
+        .filter_map(|daemon| by_daemon.remove(&daemon.id).map(|containers| (daemon, containers)))
+        .collect();
+
+    futures_util::stream::iter(batches)
+        .for_each_concurrent(state.config.reconcile_daemon_concurrency.max(1), |(daemon, containers)| async move {
+            reconcile_daemon(state, &daemon, containers).await;
+        })
+        .await;
+
+    Ok(())
+}
+
+async fn reconcile_daemon(state: &AppState, daemon: &Daemon, containers: Vec<StuckContainer>) {
+    let total = containers.len();
+
+    for (checked, container) in containers.into_iter().enumerate() {
+        let actual = match fetch_daemon_status(daemon, container.id).await {
+            Some(s) => s,
+            None => {
+                // Back off from a struggling daemon instead of queueing more requests on it
+                tracing::warn!(
+                    "Daemon {} did not report a status for container {} ({}), skipping {} remaining containers this pass",
+                    daemon.id, container.name, container.id, total - checked - 1
+                );
+                return;
+            }
+        };
+
+        // Docker itself is mid-restart; give it another interval before deciding
+        if actual.status.eq_ignore_ascii_case("restarting") {
+            continue;
+        }
+
+        let resolved = actual.resolved_status();
+
+        let result = sqlx::query(
+            "UPDATE containers SET status = $1, updated_at = NOW() WHERE id = $2 AND status = $3"
+        )
+        .bind(resolved)
+        .bind(container.id)
+        .bind(&container.status)
+        .execute(&state.db)
+        .await;
+
+        match result {
+            Ok(r) if r.rows_affected() > 0 => {
+                tracing::info!(
+                    "Reconciled container {} ({}): '{}' -> '{}' (daemon reports '{}')",
+                    container.name, container.id, container.status, resolved, actual.status
+                );
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to reconcile container {}: {}", container.id, e),
+        }
+    }
+}
+
+pub async fn fetch_daemon_status(daemon: &Daemon, container_id: Uuid) -> Option<DaemonContainerStatus> {
+    let url = format!("{}/containers/{}/status", daemon.base_url(), container_id);
+
+    let res = daemon_client()
+        .get(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .send()
+        .await
+        .ok()?;
+
+    if !res.status().is_success() {
+        return None;
+    }
+
+    res.json::<DaemonContainerStatus>().await.ok()
+}
+
+/// All container statuses the daemon knows about, keyed by container id, in a single request.
+pub async fn fetch_daemon_statuses(daemon: &Daemon) -> Option<HashMap<Uuid, DaemonContainerStatus>> {
+    let url = format!("{}/containers/statuses", daemon.base_url());
+
+    let res = daemon_client()
+        .get(&url)
+        .header("X-API-Key", &daemon.api_key)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .ok()?;
+
+    if !res.status().is_success() {
+        return None;
+    }
+
+    let statuses: HashMap<String, DaemonContainerStatus> = res.json().await.ok()?;
+    Some(
+        statuses
+            .into_iter()
+            .filter_map(|(name, status)| Uuid::parse_str(&name).ok().map(|id| (id, status)))
+            .collect(),
+    )
+}
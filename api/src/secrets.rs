@@ -0,0 +1,9 @@
+pub use raptor_common::secrets::{decrypt, encrypt};
+
+/// Shown instead of a secret value, and accepted back as "leave unchanged".
+pub const MASK: &str = "****";
+
+/// Key for secret variable values, derived from APP_KEY.
+pub fn derive_key(app_key: &str) -> [u8; 32] {
+    raptor_common::secrets::derive_key("raptor-secret-variables", app_key)
+}
@@ -4,6 +4,7 @@ use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::config::Config;
+use crate::permissions;
 
 fn generate_password(length: usize) -> String {
     const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
@@ -17,10 +18,8 @@ fn generate_password(length: usize) -> String {
 }
 
 pub async fn run(pool: &PgPool, config: &Config) -> anyhow::Result<()> {
-    if !was_executed(pool, "permissions").await? {
-        seed_permissions(pool).await?;
-        mark_executed(pool, "permissions").await?;
-    }
+    // Idempotent, so permissions added to the catalog later reach existing installs too
+    seed_permissions(pool).await?;
 
     if !was_executed(pool, "roles").await? {
         seed_roles(pool).await?;
@@ -68,44 +67,14 @@ async fn mark_executed(pool: &PgPool, name: &str) -> anyhow::Result<()> {
 }
 
 async fn seed_permissions(pool: &PgPool) -> anyhow::Result<()> {
-    let permissions = [
-        ("*", "Full access to all resources"),
-        ("admin.access", "Access to admin panel"),
-        ("users.view", "View users"),
-        ("users.create", "Create users"),
-        ("users.update", "Update users"),
-        ("users.delete", "Delete users"),
-        ("roles.view", "View roles"),
-        ("roles.create", "Create roles"),
-        ("roles.update", "Update roles"),
-        ("roles.delete", "Delete roles"),
-        ("daemons.view", "View daemons"),
-        ("daemons.create", "Create daemons"),
-        ("daemons.update", "Update daemons"),
-        ("daemons.delete", "Delete daemons"),
-        ("containers.view_own", "View own containers"),
-        ("containers.view_all", "View all containers"),
-        ("containers.create", "Create containers"),
-        ("containers.update", "Update containers"),
-        ("containers.delete", "Delete containers"),
-        ("containers.manage", "Manage containers (start/stop/restart)"),
-        ("containers.manage_own", "Manage own containers"),
-        ("allocations.view", "View allocations"),
-        ("allocations.create", "Create allocations"),
-        ("allocations.delete", "Delete allocations"),
-        ("flakes.view", "View flakes (server templates)"),
-        ("flakes.create", "Create flakes"),
-        ("flakes.update", "Update flakes"),
-        ("flakes.delete", "Delete flakes"),
-    ];
 
-    for (name, description) in permissions {
+    for permission in permissions::CATALOG {
         sqlx::query(
             "INSERT INTO permissions (id, name, description) VALUES ($1, $2, $3) ON CONFLICT (name) DO NOTHING"
         )
         .bind(Uuid::new_v4())
-        .bind(name)
-        .bind(description)
+        .bind(permission.name)
+        .bind(permission.description)
         .execute(pool)
         .await?;
     }
@@ -0,0 +1,195 @@
+//! Throwaway Postgres databases for tests that need the real thing: constraints, locking and
+//! concurrent requests. They are created through `TEST_DATABASE_URL`, so those tests are
+//! `#[ignore]`d and run with `cargo test -- --ignored`.
+
+use crate::config::{AdminConfig, Config, DbPoolConfig};
+use crate::models::AppState;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{ConnectOptions, PgPool};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// A migrated database that is dropped again with the value, whether the test passed or not.
+pub struct TestDb {
+    pub pool: PgPool,
+    server: PgConnectOptions,
+    name: String,
+}
+
+/// A freshly migrated database of its own. A database rather than a schema, since older
+/// migrations look columns up by table name only.
+pub async fn test_db() -> TestDb {
+    let url = std::env::var("TEST_DATABASE_URL")
+        .expect("database tests need TEST_DATABASE_URL pointing at a Postgres server");
+
+    let server = PgConnectOptions::from_str(&url).expect("parse TEST_DATABASE_URL");
+    let name = format!("raptor_test_{}", Uuid::new_v4().simple());
+
+    let mut admin = server.connect().await.expect("connect to TEST_DATABASE_URL");
+    sqlx::query(&format!("CREATE DATABASE {}", name))
+        .execute(&mut admin)
+        .await
+        .expect("create test database");
+
+    // From here on the guard owns the database, so a failed migration still cleans up
+    let db = TestDb {
+        pool: PgPoolOptions::new()
+            .max_connections(8)
+            .connect_lazy_with(server.clone().database(&name)),
+        server,
+        name,
+    };
+
+    crate::MIGRATOR.run(&db.pool).await.expect("run migrations");
+
+    db
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let server = self.server.clone();
+        let name = self.name.clone();
+
+        // Drop can't await and may run inside the test's runtime, so use a runtime of its own.
+        // Connections the pool still holds are cut off by FORCE.
+        let result = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+            runtime.block_on(async {
+                let mut admin = server.connect().await?;
+                sqlx::query(&format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", name))
+                    .execute(&mut admin)
+                    .await?;
+                anyhow::Ok(())
+            })
+        })
+        .join();
+
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => eprintln!("failed to drop test database {}: {}", self.name, e),
+            Err(_) => eprintln!("failed to drop test database {}", self.name),
+        }
+    }
+}
+
+impl TestDb {
+    /// Application state over this database, for calling handlers directly.
+    pub fn state(&self) -> AppState {
+        app_state(self.pool.clone())
+    }
+
+    pub async fn user(&self) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO users (id, username, password_hash) VALUES ($1, $2, 'x')")
+            .bind(id)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .expect("insert user");
+        id
+    }
+
+    pub async fn daemon(&self) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO daemons (id, name, host, port, api_key) VALUES ($1, $2, 'localhost', 8080, 'key')")
+            .bind(id)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .expect("insert daemon");
+        id
+    }
+
+    pub async fn allocation(&self, daemon_id: Uuid, ip: &str, port: i32) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO allocations (id, daemon_id, ip, port) VALUES ($1, $2, $3, $4)")
+            .bind(id)
+            .bind(daemon_id)
+            .bind(ip)
+            .bind(port)
+            .execute(&self.pool)
+            .await
+            .expect("insert allocation");
+        id
+    }
+
+    pub async fn ip_pool(&self, daemon_id: Uuid, ip: &str, cidr: i32) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO ip_pools (id, daemon_id, ip_address, cidr) VALUES ($1, $2, $3, $4)")
+            .bind(id)
+            .bind(daemon_id)
+            .bind(ip)
+            .bind(cidr)
+            .execute(&self.pool)
+            .await
+            .expect("insert ip pool");
+        id
+    }
+
+    /// Gives `container_id` the allocation, as the allocation handlers do, and returns the
+    /// `container_allocations` row.
+    pub async fn assign(&self, container_id: Uuid, allocation_id: Uuid, is_primary: bool) -> Uuid {
+        let (id,): (Uuid,) = sqlx::query_as(
+            "INSERT INTO container_allocations (container_id, allocation_id, ip, port, internal_port, protocol, is_primary)
+             SELECT $1, id, ip, port, port, protocol, $3 FROM allocations WHERE id = $2
+             RETURNING id"
+        )
+            .bind(container_id)
+            .bind(allocation_id)
+            .bind(is_primary)
+            .fetch_one(&self.pool)
+            .await
+            .expect("assign allocation");
+        id
+    }
+
+    pub async fn container(&self, user_id: Uuid, daemon_id: Uuid) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO containers (id, user_id, daemon_id, name, image) VALUES ($1, $2, $3, $4, 'test')")
+            .bind(id)
+            .bind(user_id)
+            .bind(daemon_id)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .expect("insert container");
+        id
+    }
+}
+
+/// Application state over `pool` with a fixed test configuration.
+pub fn app_state(pool: PgPool) -> AppState {
+    AppState {
+        db: pool,
+        config: test_config(),
+        metrics: Default::default(),
+        daemon_stats: Default::default(),
+    }
+}
+
+fn test_config() -> Config {
+    Config {
+        database_url: String::new(),
+        db_pool: DbPoolConfig { max_connections: 8, min_connections: 0, acquire_timeout_secs: 10, idle_timeout_secs: 0 },
+        jwt_secret: "test".into(),
+        secret_key: [7; 32],
+        jwt_expiry_days: 7,
+        api_addr: "127.0.0.1:0".into(),
+        app_url: "http://localhost".into(),
+        cors_allowed_origins: Vec::new(),
+        cors_allow_all: false,
+        bcrypt_cost: 4,
+        reconcile_interval_secs: 60,
+        status_stuck_threshold_secs: 300,
+        reconcile_daemon_concurrency: 4,
+        reconcile_batch_size: 50,
+        billing_webhook_url: None,
+        outbox_poll_interval_secs: 10,
+        metrics_token: None,
+        container_trash_retention_hours: 72,
+        ws_idle_timeout_secs: 3600,
+        upload_chunk_size: raptor_common::uploads::chunk_size_from_env(),
+        smtp: None,
+        admin: AdminConfig { username: "admin".into(), email: "admin@localhost".into(), password: None },
+    }
+}
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use crate::handlers::containers::purge_container;
+use crate::models::{AppState, Container, Daemon};
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const PURGE_BATCH_SIZE: i64 = 20;
+
+/// Permanently removes containers whose trash retention has run out.
+pub fn spawn(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = purge_expired(&state).await {
+                tracing::error!("Trash purge failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn purge_expired(state: &AppState) -> anyhow::Result<()> {
+    let expired: Vec<Container> = sqlx::query_as(
+        r#"
+        SELECT * FROM containers
+        WHERE deleted_at IS NOT NULL
+        AND deleted_at < NOW() - make_interval(hours => $1)
+        ORDER BY deleted_at
+        LIMIT $2
+        "#
+    )
+    .bind(state.config.container_trash_retention_hours.max(0) as i32)
+    .bind(PURGE_BATCH_SIZE)
+    .fetch_all(&state.db)
+    .await?;
+
+    for container in expired {
+        let daemon: Option<Daemon> = sqlx::query_as("SELECT * FROM daemons WHERE id = $1")
+            .bind(container.daemon_id)
+            .fetch_optional(&state.db)
+            .await?;
+
+        let Some(daemon) = daemon else {
+            continue;
+        };
+
+        // An unreachable daemon keeps the container in the trash until a later pass gets through
+        match purge_container(state, &container, &daemon).await {
+            Ok(()) => tracing::info!("Purged container {} ({}) from the trash", container.name, container.id),
+            Err(e) => tracing::warn!("Failed to purge container {} from the trash: {}", container.id, e),
+        }
+    }
+
+    Ok(())
+}
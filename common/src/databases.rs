@@ -0,0 +1,52 @@
+/// Longest database or user name, in bytes; Postgres truncates anything past 63.
+pub const MAX_IDENTIFIER_LEN: usize = 63;
+
+/// Checks a database or user name before it is interpolated into provisioning SQL.
+///
+/// Letters (any script, since default names are derived from usernames), digits and
+/// underscores only, so nothing can close the surrounding quotes or start a new statement.
+pub fn validate_identifier(value: &str) -> Result<(), String> {
+    let valid = !value.is_empty()
+        && value.len() <= MAX_IDENTIFIER_LEN
+        && value.chars().all(|c| c.is_alphanumeric() || c == '_');
+
+    if !valid {
+        return Err(format!(
+            "Invalid identifier '{}': only letters, digits and underscores are allowed (max {} bytes)",
+            value, MAX_IDENTIFIER_LEN
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_generated_names() {
+        assert!(validate_identifier("db_alice_1a2b3c4d").is_ok());
+        assert!(validate_identifier("u_0123456789ab").is_ok());
+    }
+
+    #[test]
+    fn accepts_names_derived_from_unicode_usernames() {
+        assert!(validate_identifier("db_zoë_1a2b3c4d").is_ok());
+        assert!(validate_identifier("db_日本語_1a2b3c4d").is_ok());
+    }
+
+    #[test]
+    fn rejects_names_that_could_break_out_of_the_sql() {
+        for name in ["my db", "db;DROP DATABASE x", "db`x", "db'x", "db\"x", "db-x", "db\nx", ""] {
+            assert!(validate_identifier(name).is_err(), "{:?} should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn rejects_names_postgres_would_truncate() {
+        assert!(validate_identifier(&"a".repeat(MAX_IDENTIFIER_LEN)).is_ok());
+        assert!(validate_identifier(&"a".repeat(MAX_IDENTIFIER_LEN + 1)).is_err());
+        assert!(validate_identifier(&"ë".repeat(32)).is_err());
+    }
+}
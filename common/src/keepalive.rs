@@ -0,0 +1,112 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often websocket peers are pinged.
+pub const PING_INTERVAL: Duration = Duration::from_secs(30);
+/// A peer that sends nothing, not even a pong, for this long is treated as gone.
+pub const PONG_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// What to do with a socket when its ping timer fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepaliveCheck {
+    Ping,
+    /// Nothing was heard for `PONG_TIMEOUT`.
+    Unresponsive,
+    /// Only pongs were heard for longer than the idle timeout.
+    Idle,
+}
+
+#[derive(Clone, Copy)]
+struct Seen {
+    frame: Instant,
+    input: Instant,
+}
+
+/// When a websocket peer was last heard from. Clones share state, so the receive half
+/// records frames while the send half pings.
+#[derive(Clone)]
+pub struct Liveness {
+    seen: Arc<Mutex<Seen>>,
+    idle_timeout: Option<Duration>,
+}
+
+impl Liveness {
+    /// `idle_timeout` caps how long a socket stays open without input; None keeps it open
+    /// as long as it answers pings.
+    pub fn new(idle_timeout: Option<Duration>) -> Self {
+        let now = Instant::now();
+        Self {
+            seen: Arc::new(Mutex::new(Seen { frame: now, input: now })),
+            idle_timeout,
+        }
+    }
+
+    /// Records a frame from the peer. Any frame shows it is connected; only `input` frames
+    /// (text or binary, not pongs) reset the idle timer.
+    pub fn seen(&self, input: bool) {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.frame = Instant::now();
+        if input {
+            seen.input = seen.frame;
+        }
+    }
+
+    pub fn check(&self) -> KeepaliveCheck {
+        self.check_at(Instant::now())
+    }
+
+    fn check_at(&self, now: Instant) -> KeepaliveCheck {
+        let seen = *self.seen.lock().unwrap_or_else(|e| e.into_inner());
+
+        if now.saturating_duration_since(seen.frame) > PONG_TIMEOUT {
+            KeepaliveCheck::Unresponsive
+        } else if self.idle_timeout.is_some_and(|idle| now.saturating_duration_since(seen.input) > idle) {
+            KeepaliveCheck::Idle
+        } else {
+            KeepaliveCheck::Ping
+        }
+    }
+
+    /// Ticks every `PING_INTERVAL`, starting one interval from now.
+    pub fn interval() -> tokio::time::Interval {
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + PING_INTERVAL, PING_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        interval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_recent_frame_keeps_pinging() {
+        let liveness = Liveness::new(None);
+        assert_eq!(liveness.check_at(Instant::now() + PONG_TIMEOUT / 2), KeepaliveCheck::Ping);
+    }
+
+    #[test]
+    fn silence_past_the_pong_timeout_is_unresponsive() {
+        let liveness = Liveness::new(Some(Duration::from_secs(3600)));
+        let later = Instant::now() + PONG_TIMEOUT + Duration::from_secs(1);
+        assert_eq!(liveness.check_at(later), KeepaliveCheck::Unresponsive);
+    }
+
+    #[test]
+    fn pongs_alone_do_not_reset_the_idle_timer() {
+        let liveness = Liveness::new(Some(Duration::from_secs(60)));
+        liveness.seen(false);
+        let later = Instant::now() + Duration::from_secs(61);
+        assert_eq!(liveness.check_at(later), KeepaliveCheck::Idle);
+
+        liveness.seen(true);
+        assert_eq!(liveness.check_at(Instant::now() + Duration::from_secs(30)), KeepaliveCheck::Ping);
+    }
+
+    #[test]
+    fn without_an_idle_timeout_answered_pings_keep_the_socket() {
+        let liveness = Liveness::new(None);
+        liveness.seen(false);
+        assert_eq!(liveness.check_at(Instant::now() + Duration::from_secs(60)), KeepaliveCheck::Ping);
+    }
+}
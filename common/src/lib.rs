@@ -0,0 +1,14 @@
+//! Logic shared by the API and the daemon, kept here so both sides always agree.
+
+pub mod databases;
+pub mod keepalive;
+pub mod logs;
+pub mod mounts;
+pub mod net;
+pub mod readiness;
+pub mod resources;
+pub mod restart_policy;
+pub mod secrets;
+pub mod startup;
+pub mod uploads;
+pub mod variables_file;
@@ -0,0 +1,42 @@
+/// Lines of console history returned when neither `tail` nor `since` is given.
+pub const DEFAULT_LOG_TAIL: usize = 500;
+
+/// Most lines a single `tail` may ask for.
+pub const MAX_LOG_TAIL: usize = 10_000;
+
+/// Validates a `?tail=` line count against `MAX_LOG_TAIL`. Missing or blank means no tail.
+pub fn parse_tail(value: Option<&str>) -> Result<Option<usize>, String> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+
+    match value.parse::<usize>() {
+        Ok(tail) if (1..=MAX_LOG_TAIL).contains(&tail) => Ok(Some(tail)),
+        _ => Err(format!("tail must be a number between 1 and {}", MAX_LOG_TAIL)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_or_blank_tail_means_none() {
+        assert_eq!(parse_tail(None), Ok(None));
+        assert_eq!(parse_tail(Some("  ")), Ok(None));
+    }
+
+    #[test]
+    fn accepts_counts_up_to_the_cap() {
+        assert_eq!(parse_tail(Some("1")), Ok(Some(1)));
+        assert_eq!(parse_tail(Some(" 200 ")), Ok(Some(200)));
+        assert_eq!(parse_tail(Some("10000")), Ok(Some(MAX_LOG_TAIL)));
+    }
+
+    #[test]
+    fn rejects_zero_negative_and_oversized_counts() {
+        for value in ["0", "-5", "10001", "all", "1.5"] {
+            assert!(parse_tail(Some(value)).is_err(), "{:?} should be rejected", value);
+        }
+    }
+}
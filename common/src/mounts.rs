@@ -0,0 +1,112 @@
+use std::path::{Component, Path};
+
+use serde::{Deserialize, Serialize};
+
+/// Most asset mounts a single container may have.
+pub const MAX_ASSET_MOUNTS: usize = 16;
+
+/// Most tmpfs mounts a single container may have.
+pub const MAX_TMPFS_MOUNTS: usize = 8;
+
+/// Largest tmpfs in MB. Its contents count against the container's memory limit.
+pub const MAX_TMPFS_SIZE_MB: i64 = 16 * 1024;
+
+/// Container paths the daemon already binds itself.
+const RESERVED_TARGETS: &[&str] = &["/home/container", "/etc/machine-id", "/etc/localtime"];
+
+/// A host directory or file shared read-only into containers, e.g. maps or mod packs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetMount {
+    /// Relative to the daemon's asset directory (ASSETS_PATH).
+    pub source: String,
+    /// Absolute path inside the container.
+    pub target: String,
+}
+
+impl AssetMount {
+    pub fn validate(&self) -> Result<(), String> {
+        let source = Path::new(&self.source);
+        let mut components = source.components().peekable();
+        if components.peek().is_none() || !components.all(|c| matches!(c, Component::Normal(_))) {
+            return Err(format!("Invalid asset mount source '{}'", self.source));
+        }
+
+        validate_target("Asset mount", &self.target)?;
+
+        // Docker bind specs are colon separated
+        if [&self.source, &self.target].iter().any(|p| p.contains(':') || p.contains(',')) {
+            return Err("Asset mount paths cannot contain ':' or ','".into());
+        }
+        Ok(())
+    }
+}
+
+/// An in-memory filesystem for scratch files, e.g. a server's temp directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TmpfsMount {
+    /// Absolute path inside the container.
+    pub target: String,
+    pub size_mb: i64,
+}
+
+impl TmpfsMount {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_target("tmpfs", &self.target)?;
+        if self.target.contains(':') || self.target.contains(',') {
+            return Err("tmpfs targets cannot contain ':' or ','".into());
+        }
+        if !(1..=MAX_TMPFS_SIZE_MB).contains(&self.size_mb) {
+            return Err(format!("tmpfs size must be between 1 and {} MB", MAX_TMPFS_SIZE_MB));
+        }
+        Ok(())
+    }
+
+    /// Docker tmpfs options. Left executable, since servers unpack native libraries into temp dirs.
+    pub fn options(&self) -> String {
+        format!("rw,nosuid,size={}m", self.size_mb)
+    }
+}
+
+fn validate_target(kind: &str, target: &str) -> Result<(), String> {
+    let path = Path::new(target);
+    if !path.is_absolute() || path.components().any(|c| matches!(c, Component::ParentDir | Component::CurDir)) {
+        return Err(format!("{} target '{}' must be an absolute path", kind, target));
+    }
+    let normalized = target.trim_end_matches('/');
+    if normalized.is_empty() || RESERVED_TARGETS.contains(&normalized) {
+        return Err(format!("{} target '{}' is reserved", kind, target));
+    }
+    Ok(())
+}
+
+/// Checks every mount and that no two share a target.
+pub fn validate_asset_mounts(mounts: &[AssetMount]) -> Result<(), String> {
+    if mounts.len() > MAX_ASSET_MOUNTS {
+        return Err(format!("At most {} asset mounts are allowed", MAX_ASSET_MOUNTS));
+    }
+    for (i, mount) in mounts.iter().enumerate() {
+        mount.validate()?;
+        let target = mount.target.trim_end_matches('/');
+        if mounts[..i].iter().any(|m| m.target.trim_end_matches('/') == target) {
+            return Err(format!("Asset mount target '{}' is used more than once", mount.target));
+        }
+    }
+    Ok(())
+}
+
+/// Checks every tmpfs and that no two share a target.
+pub fn validate_tmpfs_mounts(mounts: &[TmpfsMount]) -> Result<(), String> {
+    if mounts.len() > MAX_TMPFS_MOUNTS {
+        return Err(format!("At most {} tmpfs mounts are allowed", MAX_TMPFS_MOUNTS));
+    }
+    for (i, mount) in mounts.iter().enumerate() {
+        mount.validate()?;
+        let target = mount.target.trim_end_matches('/');
+        if mounts[..i].iter().any(|m| m.target.trim_end_matches('/') == target) {
+            return Err(format!("tmpfs target '{}' is used more than once", mount.target));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::net::IpAddr;
+
+/// Parses an allocation IP, accepting IPv6 with or without brackets, and returns its
+/// canonical (unbracketed, compressed) spelling as Docker reports it back.
+pub fn normalize_ip(ip: &str) -> Result<String, String> {
+    let trimmed = ip.trim();
+    let bare = trimmed
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .unwrap_or(trimmed);
+
+    bare.parse::<IpAddr>()
+        .map(|addr| addr.to_string())
+        .map_err(|_| format!("'{}' is not a valid IPv4 or IPv6 address", ip.trim()))
+}
+
+/// Whether two host IPs would fight over the same port. `0.0.0.0` covers every IPv4 address
+/// and `::` every IPv6 address, but the two wildcards coexist since Docker binds them separately.
+pub fn ips_overlap(a: &str, b: &str) -> bool {
+    let (Ok(a), Ok(b)) = (a.parse::<IpAddr>(), b.parse::<IpAddr>()) else {
+        return a == b;
+    };
+
+    if a.is_ipv4() != b.is_ipv4() {
+        return false;
+    }
+
+    a == b || a.is_unspecified() || b.is_unspecified()
+}
+
+/// `ip:port`, with IPv6 addresses bracketed.
+pub fn endpoint(ip: &str, port: i32) -> String {
+    if ip.contains(':') {
+        format!("[{}]:{}", ip, port)
+    } else {
+        format!("{}:{}", ip, port)
+    }
+}
+
+/// The largest prefix length an address of this family can have.
+pub fn max_prefix_len(ip: &str) -> Option<i32> {
+    match ip.parse::<IpAddr>().ok()? {
+        IpAddr::V4(_) => Some(32),
+        IpAddr::V6(_) => Some(128),
+    }
+}
+
+/// Whether `ip` lies in `network/prefix_len`. Addresses of different families never match.
+pub fn in_network(ip: &str, network: &str, prefix_len: i32) -> bool {
+    let (Ok(ip), Ok(network)) = (normalize_ip(ip), normalize_ip(network)) else {
+        return false;
+    };
+
+    match (ip.parse::<IpAddr>(), network.parse::<IpAddr>()) {
+        (Ok(IpAddr::V4(ip)), Ok(IpAddr::V4(network))) => {
+            let prefix = prefix_len.clamp(0, 32) as u32;
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (Ok(IpAddr::V6(ip)), Ok(IpAddr::V6(network))) => {
+            let prefix = prefix_len.clamp(0, 128) as u32;
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_bracketed_and_unbracketed_ipv6() {
+        assert_eq!(normalize_ip("[2001:db8::1]").as_deref(), Ok("2001:db8::1"));
+        assert_eq!(normalize_ip("2001:db8:0:0:0:0:0:1").as_deref(), Ok("2001:db8::1"));
+        assert_eq!(normalize_ip(" [::] ").as_deref(), Ok("::"));
+    }
+
+    #[test]
+    fn normalizes_ipv4_and_rejects_garbage() {
+        assert_eq!(normalize_ip(" 10.0.0.5 ").as_deref(), Ok("10.0.0.5"));
+        for ip in ["", "[10.0.0.5", "2001:db8::1]", "[[::1]]", "example.com", "10.0.0.256"] {
+            assert!(normalize_ip(ip).is_err(), "{:?} should be rejected", ip);
+        }
+    }
+
+    #[test]
+    fn wildcards_of_different_families_share_a_port() {
+        assert!(!ips_overlap("0.0.0.0", "::"));
+        assert!(!ips_overlap("10.0.0.5", "2001:db8::1"));
+    }
+
+    #[test]
+    fn a_wildcard_overlaps_its_own_family() {
+        assert!(ips_overlap("0.0.0.0", "10.0.0.5"));
+        assert!(ips_overlap("2001:db8::1", "::"));
+        assert!(ips_overlap("10.0.0.5", "10.0.0.5"));
+        assert!(!ips_overlap("10.0.0.5", "10.0.0.6"));
+    }
+
+    #[test]
+    fn endpoints_bracket_ipv6() {
+        assert_eq!(endpoint("2001:db8::1", 25565), "[2001:db8::1]:25565");
+        assert_eq!(endpoint("10.0.0.5", 25565), "10.0.0.5:25565");
+    }
+
+    #[test]
+    fn matches_ipv6_networks_with_or_without_brackets() {
+        assert!(in_network("2001:db8::42", "2001:db8::", 64));
+        assert!(in_network("[2001:db8::42]", "[2001:db8::]", 64));
+        assert!(!in_network("2001:db9::1", "2001:db8::", 32));
+        assert!(in_network("2001:db9::1", "::", 0));
+    }
+
+    #[test]
+    fn matches_ipv4_networks_and_never_across_families() {
+        assert!(in_network("10.0.1.7", "10.0.0.0", 16));
+        assert!(!in_network("10.1.0.1", "10.0.0.0", 16));
+        assert!(in_network("10.0.0.5", "10.0.0.5", 32));
+        assert!(!in_network("10.0.0.5", "::", 0));
+        assert!(!in_network("::ffff:10.0.0.5", "10.0.0.0", 8));
+    }
+}
@@ -0,0 +1,45 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// How the daemon decides that the server inside a running container has finished starting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ReadinessCheck {
+    /// A TCP connect succeeds; `port` inside the container, defaulting to the primary allocation.
+    Tcp {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        port: Option<u16>,
+    },
+    /// A console line matches the regex, e.g. `Done \(.*\)! For help`.
+    Log { pattern: String },
+    /// A shell command run inside the container exits with 0.
+    Exec { command: String },
+}
+
+impl ReadinessCheck {
+    /// Flakes only had a literal console line to wait for before readiness checks existed.
+    pub fn from_startup_detection(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        Some(Self::Log { pattern: regex::escape(text) })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Tcp { port: Some(0) } => Err("Readiness port must be between 1 and 65535".into()),
+            Self::Tcp { .. } => Ok(()),
+            Self::Log { pattern } => compile_pattern(pattern).map(|_| ()),
+            Self::Exec { command } if command.trim().is_empty() => Err("Readiness command cannot be empty".into()),
+            Self::Exec { .. } => Ok(()),
+        }
+    }
+}
+
+pub fn compile_pattern(pattern: &str) -> Result<Regex, String> {
+    if pattern.is_empty() {
+        return Err("Readiness pattern cannot be empty".into());
+    }
+    Regex::new(pattern).map_err(|e| format!("Invalid readiness pattern: {}", e))
+}
@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+
+/// Sentinel for memory, swap and disk limits (in MB) meaning "no limit". Kept distinct
+/// from 0, which for swap means "no swap".
+pub const UNLIMITED: i64 = -1;
+
+/// Highest CPU index accepted in a cpuset; Docker rejects CPUs the host doesn't have anyway.
+const MAX_CPU_INDEX: u32 = 1023;
+
+/// Checks a Docker `cpuset-cpus` list such as `0-3,8`: comma separated CPUs or ascending ranges.
+pub fn is_valid_cpuset(cpuset: &str) -> bool {
+    if cpuset.is_empty() {
+        return false;
+    }
+
+    cpuset.split(',').all(|part| {
+        let parse = |s: &str| -> Option<u32> {
+            if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            s.parse().ok().filter(|n| *n <= MAX_CPU_INDEX)
+        };
+
+        match part.split_once('-') {
+            Some((start, end)) => matches!((parse(start), parse(end)), (Some(a), Some(b)) if a <= b),
+            None => parse(part).is_some(),
+        }
+    })
+}
+
+/// Share of a memory limit kept free of the server's own memory (e.g. the JVM heap) for
+/// off-heap allocations and everything else in the container, but never less than
+/// `MIN_MEMORY_HEADROOM_MB`, unless that would take more than half of a small limit.
+pub const MEMORY_HEADROOM_PERCENT: i64 = 10;
+pub const MIN_MEMORY_HEADROOM_MB: i64 = 64;
+
+/// Most server memory in MB that fits in `memory_limit`; None for unlimited memory.
+pub fn max_server_memory(memory_limit: i64) -> Option<i64> {
+    if memory_limit <= 0 {
+        return None;
+    }
+    let headroom = (memory_limit * MEMORY_HEADROOM_PERCENT / 100)
+        .max(MIN_MEMORY_HEADROOM_MB)
+        .min(memory_limit / 2);
+    Some(memory_limit - headroom)
+}
+
+/// Server memory used when none is given: as much as fits in the limit, or the limit itself
+/// when memory is unlimited so the daemon sizes the server from the host.
+pub fn default_server_memory(memory_limit: i64) -> i64 {
+    max_server_memory(memory_limit).unwrap_or(memory_limit)
+}
+
+/// Rejects a server memory the container would be OOM-killed for using.
+pub fn validate_server_memory(server_memory: i64, memory_limit: i64) -> Result<(), String> {
+    if server_memory < 1 {
+        return Err("Server memory must be at least 1 MB".into());
+    }
+
+    match max_server_memory(memory_limit) {
+        Some(max) if server_memory > max => Err(format!(
+            "Server memory of {} MB does not fit in the {} MB memory limit; leave headroom for the rest of the container and use at most {} MB",
+            server_memory, memory_limit, max
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Shortest and longest time usage must stay above a threshold before it is reported.
+pub const MIN_ALERT_WINDOW_SECS: u64 = 30;
+pub const MAX_ALERT_WINDOW_SECS: u64 = 24 * 3600;
+
+/// Sustained usage worth reporting: a threshold held for at least `window_secs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceAlerts {
+    /// Percent of the CPU limit, or of all host CPUs when unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<f64>,
+    /// Percent of the memory limit, or of host memory when unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_percent: Option<f64>,
+    #[serde(default = "default_alert_window")]
+    pub window_secs: u64,
+}
+
+fn default_alert_window() -> u64 {
+    300
+}
+
+impl ResourceAlerts {
+    /// Without any threshold there is nothing to watch.
+    pub fn is_empty(&self) -> bool {
+        self.cpu_percent.is_none() && self.memory_percent.is_none()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, threshold) in [("CPU", self.cpu_percent), ("Memory", self.memory_percent)] {
+            if threshold.is_some_and(|t| !(t > 0.0 && t <= 100.0)) {
+                return Err(format!("{} alert threshold must be above 0 and at most 100 percent", name));
+            }
+        }
+        if !(MIN_ALERT_WINDOW_SECS..=MAX_ALERT_WINDOW_SECS).contains(&self.window_secs) {
+            return Err(format!(
+                "Alert window must be between {} and {} seconds",
+                MIN_ALERT_WINDOW_SECS, MAX_ALERT_WINDOW_SECS
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_limits_keep_a_percentage_free() {
+        assert_eq!(max_server_memory(4096), Some(4096 - 409));
+        assert_eq!(max_server_memory(16384), Some(16384 - 1638));
+    }
+
+    #[test]
+    fn mid_sized_limits_keep_the_minimum_headroom() {
+        assert_eq!(max_server_memory(512), Some(512 - MIN_MEMORY_HEADROOM_MB));
+        assert_eq!(max_server_memory(128), Some(64));
+    }
+
+    #[test]
+    fn small_limits_still_leave_room_for_the_server() {
+        assert_eq!(max_server_memory(64), Some(32));
+        assert_eq!(max_server_memory(100), Some(50));
+        assert_eq!(max_server_memory(1), Some(1));
+    }
+
+    #[test]
+    fn unlimited_memory_has_no_maximum() {
+        assert_eq!(max_server_memory(0), None);
+        assert_eq!(max_server_memory(-1), None);
+    }
+
+    #[test]
+    fn default_server_memory_fits_the_limit() {
+        for limit in [1, 64, 100, 128, 512, 1024, 4096, 65536] {
+            let server_memory = default_server_memory(limit);
+            assert!(server_memory >= 1, "no server memory left in {} MB", limit);
+            assert!(validate_server_memory(server_memory, limit).is_ok(), "default does not fit {} MB", limit);
+        }
+        assert_eq!(default_server_memory(-1), -1);
+        assert_eq!(default_server_memory(0), 0);
+    }
+
+    #[test]
+    fn server_memory_above_the_maximum_is_rejected() {
+        assert!(validate_server_memory(1024, 1024).is_err());
+        assert!(validate_server_memory(922, 1024).is_ok());
+        assert!(validate_server_memory(923, 1024).is_err());
+        assert!(validate_server_memory(0, 1024).is_err());
+        assert!(validate_server_memory(1 << 20, -1).is_ok());
+    }
+}
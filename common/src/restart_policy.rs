@@ -0,0 +1,14 @@
+/// Docker restart policies a container can use, in their canonical spelling.
+pub const RESTART_POLICIES: &[&str] = &["no", "always", "on-failure", "unless-stopped"];
+
+/// The canonical name of a restart policy, also accepting the `none` and `onfailure` spellings
+/// older flakes use. `None` for anything Docker doesn't know.
+pub fn normalize(policy: &str) -> Option<&'static str> {
+    match policy.trim().to_lowercase().as_str() {
+        "no" | "none" => Some("no"),
+        "always" => Some("always"),
+        "on-failure" | "onfailure" => Some("on-failure"),
+        "unless-stopped" => Some("unless-stopped"),
+        _ => None,
+    }
+}
@@ -0,0 +1,89 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use sha2::{Digest, Sha256};
+
+/// Marks a value written by `encrypt`; anything without it is plaintext.
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+/// Compares two secrets in time that depends only on their lengths, so a caller probing
+/// an API key or token can't learn how many leading bytes were right.
+pub fn secrets_match(given: &str, expected: &str) -> bool {
+    let (given, expected) = (given.as_bytes(), expected.as_bytes());
+    if given.len() != expected.len() {
+        return false;
+    }
+
+    let diff = given.iter().zip(expected).fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    std::hint::black_box(diff) == 0
+}
+
+/// AES-256 key for one kind of stored secret, derived from a configured secret. The context
+/// keeps keys for different purposes apart even when they come from the same secret.
+pub fn derive_key(context: &str, secret: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(context.as_bytes());
+    hasher.update(b":");
+    hasher.update(secret.as_bytes());
+    hasher.finalize().into()
+}
+
+pub fn encrypt(key: &[u8; 32], plaintext: &str) -> Result<String, String> {
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce: [u8; NONCE_LEN] = rand::random();
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|_| "Failed to encrypt secret value".to_string())?;
+
+    let mut data = nonce.to_vec();
+    data.extend(ciphertext);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, general_purpose::STANDARD.encode(data)))
+}
+
+/// Values stored before encryption was introduced are plaintext and returned as is.
+pub fn decrypt(key: &[u8; 32], stored: &str) -> Result<String, String> {
+    let Some(encoded) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+        return Ok(stored.to_string());
+    };
+
+    let data = general_purpose::STANDARD.decode(encoded).map_err(|e| e.to_string())?;
+    if data.len() < NONCE_LEN {
+        return Err("Encrypted value is truncated".into());
+    }
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    let plaintext = Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt secret value, was the key changed?".to_string())?;
+
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypted_values_round_trip() {
+        let key = derive_key("test", "app-key");
+        let stored = encrypt(&key, "hunter2").unwrap();
+        assert!(stored.starts_with(ENCRYPTED_PREFIX));
+        assert!(!stored.contains("hunter2"));
+        assert_eq!(decrypt(&key, &stored).unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn plaintext_passes_through() {
+        let key = derive_key("test", "app-key");
+        assert_eq!(decrypt(&key, "plain").unwrap(), "plain");
+    }
+
+    #[test]
+    fn the_wrong_key_is_refused() {
+        let stored = encrypt(&derive_key("test", "app-key"), "hunter2").unwrap();
+        assert!(decrypt(&derive_key("test", "other-key"), &stored).is_err());
+        assert!(decrypt(&derive_key("other", "app-key"), &stored).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+/// Resolves `{{VARIABLE}}` placeholders in a startup command from the container environment.
+///
+/// `server_memory` (in MB) fills `{{SERVER_MEMORY}}`; in the `java` command templated on it,
+/// any other `-Xmx` JVM option is also rewritten to match. With `None` the placeholder is left as is.
+pub fn replace_startup_placeholders(
+    script: &str,
+    environment: &HashMap<String, String>,
+    server_memory: Option<i64>,
+) -> String {
+    let mut result = script.to_string();
+
+    for (key, value) in environment {
+        result = result.replace(&format!("{{{{{}}}}}", key), value);
+    }
+
+    let Some(server_memory) = server_memory else {
+        return result;
+    };
+
+    rewrite_heap_options(&result, server_memory).replace("{{SERVER_MEMORY}}", &server_memory.to_string())
+}
+
+/// `java` launcher options that take their value as the next argument.
+const JAVA_OPTIONS_WITH_VALUE: &[&str] = &[
+    "-cp",
+    "-classpath",
+    "--class-path",
+    "-p",
+    "--module-path",
+    "--upgrade-module-path",
+    "--add-modules",
+];
+
+/// Rewrites `-Xmx` in the JVM options of each `java` command that uses `{{SERVER_MEMORY}}`: the
+/// JVM honours the last one, so a leftover flag would win. Other commands, and the application's
+/// own arguments after `-jar` or the main class, are left alone.
+fn rewrite_heap_options(script: &str, server_memory: i64) -> String {
+    let Ok(separators) = regex::Regex::new(r"&&|\|\||[;|\n]") else {
+        return script.to_string();
+    };
+
+    let mut result = String::with_capacity(script.len());
+    let mut start = 0;
+
+    for separator in separators.find_iter(script) {
+        result.push_str(&rewrite_java_command(&script[start..separator.start()], server_memory));
+        result.push_str(separator.as_str());
+        start = separator.end();
+    }
+    result.push_str(&rewrite_java_command(&script[start..], server_memory));
+
+    result
+}
+
+fn rewrite_java_command(command: &str, server_memory: i64) -> String {
+    if !command.contains("{{SERVER_MEMORY}}") {
+        return command.to_string();
+    }
+
+    let (Ok(words), Ok(heap)) = (regex::Regex::new(r"\S+"), regex::Regex::new(r"^-Xmx\d+[kKmMgG]?$")) else {
+        return command.to_string();
+    };
+
+    let words: Vec<regex::Match> = words.find_iter(command).collect();
+    let Some(java) = words.iter().position(|w| w.as_str().rsplit('/').next() == Some("java")) else {
+        return command.to_string();
+    };
+
+    let mut rewrites = Vec::new();
+    let mut takes_value = false;
+
+    for word in &words[java + 1..] {
+        let option = word.as_str();
+        if takes_value {
+            takes_value = false;
+            continue;
+        }
+        if option == "-jar" || option == "-m" || option == "--module" || !option.starts_with('-') {
+            break;
+        }
+        if JAVA_OPTIONS_WITH_VALUE.contains(&option) {
+            takes_value = true;
+        } else if heap.is_match(option) {
+            rewrites.push(word.range());
+        }
+    }
+
+    let mut result = command.to_string();
+    for range in rewrites.into_iter().rev() {
+        result.replace_range(range, &format!("-Xmx{}M", server_memory));
+    }
+
+    result
+}
+
+/// Names of `{{VARIABLE}}` placeholders still left in a resolved command, in order of first use.
+pub fn unresolved_placeholders(script: &str) -> Vec<String> {
+    let Ok(re) = regex::Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}") else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<String> = Vec::new();
+    for captures in re.captures_iter(script) {
+        let name = captures[1].to_string();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_xmx_in_the_jvm_options_only() {
+        let env = HashMap::new();
+        let script = "java -Xms128M -Xmx2G -Xmx{{SERVER_MEMORY}}M -jar server.jar -Xmx2G";
+        assert_eq!(
+            replace_startup_placeholders(script, &env, Some(1024)),
+            "java -Xms128M -Xmx1024M -Xmx1024M -jar server.jar -Xmx2G"
+        );
+    }
+
+    #[test]
+    fn rewrites_only_the_java_command_using_the_server_memory() {
+        let env = HashMap::new();
+        let script = "java -Xmx512M -jar installer.jar && /usr/bin/java -Xmx2G -cp lib/app.jar -Xmx{{SERVER_MEMORY}}M net.example.Main -Xmx1G";
+        assert_eq!(
+            replace_startup_placeholders(script, &env, Some(1024)),
+            "java -Xmx512M -jar installer.jar && /usr/bin/java -Xmx1024M -cp lib/app.jar -Xmx1024M net.example.Main -Xmx1G"
+        );
+    }
+
+    #[test]
+    fn only_substitutes_the_server_memory_outside_java() {
+        let env = HashMap::new();
+        assert_eq!(replace_startup_placeholders("./srv --mem {{SERVER_MEMORY}}", &env, Some(1024)), "./srv --mem 1024");
+        assert_eq!(
+            replace_startup_placeholders("./srv -Xmx2G --mem {{SERVER_MEMORY}}", &env, Some(1024)),
+            "./srv -Xmx2G --mem 1024"
+        );
+    }
+
+    #[test]
+    fn leaves_xmx_alone_without_the_server_memory_placeholder() {
+        let env = HashMap::new();
+        let script = "java -Xmx2G -jar server.jar";
+        assert_eq!(replace_startup_placeholders(script, &env, Some(1024)), script);
+    }
+
+    #[test]
+    fn leaves_the_placeholder_without_a_server_memory() {
+        let env = HashMap::from([("SERVER_JARFILE".to_string(), "server.jar".to_string())]);
+        let script = "java -Xmx{{SERVER_MEMORY}}M -jar {{SERVER_JARFILE}}";
+        assert_eq!(
+            replace_startup_placeholders(script, &env, None),
+            "java -Xmx{{SERVER_MEMORY}}M -jar server.jar"
+        );
+    }
+
+    #[test]
+    fn lists_unresolved_placeholders_once_in_order() {
+        assert_eq!(
+            unresolved_placeholders("{{ A }} {{B}} {{A}}"),
+            vec!["A".to_string(), "B".to_string()]
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+const MB: usize = 1024 * 1024;
+
+pub const DEFAULT_CHUNK_SIZE_MB: usize = 55;
+pub const MAX_CHUNK_SIZE_MB: usize = 512;
+
+/// The largest file chunk clients may send, from `UPLOAD_CHUNK_SIZE_MB`. The API and the
+/// daemon both read it here so the two sides agree unless configured differently on purpose.
+pub fn chunk_size_from_env() -> usize {
+    std::env::var("UPLOAD_CHUNK_SIZE_MB")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_CHUNK_SIZE_MB)
+        .clamp(1, MAX_CHUNK_SIZE_MB)
+        * MB
+}
+
+/// Request body limit for a multipart chunk upload, leaving room for the form fields.
+pub fn multipart_body_limit(chunk_size: usize) -> usize {
+    chunk_size + 10 * MB
+}
+
+/// Request body limit for a chunk forwarded as base64 inside JSON.
+pub fn base64_body_limit(chunk_size: usize) -> usize {
+    chunk_size.div_ceil(3) * 4 + 8 * MB
+}
+
+/// What `GET /uploads/limits` reports, so clients chunk at a size the server accepts.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadLimits {
+    pub chunk_size: usize,
+    pub max_body_size: usize,
+}
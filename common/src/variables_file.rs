@@ -0,0 +1,100 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::startup::{replace_startup_placeholders, unresolved_placeholders};
+
+/// A file the daemon writes into the volume from the container's variables before each start,
+/// for servers that read their settings from a `.env` or JSON file instead of the environment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariablesFile {
+    /// Relative to the volume root, e.g. `.env` or `config/server.json`.
+    pub path: String,
+    pub format: VariablesFileFormat,
+    /// File contents with `{{VARIABLE}}` placeholders; without one every variable is written.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VariablesFileFormat {
+    Dotenv,
+    Json,
+}
+
+impl VariablesFile {
+    pub fn validate(&self) -> Result<(), String> {
+        let path = Path::new(self.path.trim_start_matches('/'));
+        let mut components = path.components().peekable();
+        if components.peek().is_none() || !components.all(|c| matches!(c, Component::Normal(_))) {
+            return Err(format!("Invalid variables file path '{}'", self.path));
+        }
+        if self.path.ends_with('/') {
+            return Err("Variables file path must name a file".into());
+        }
+        if self.template.as_deref().is_some_and(|t| t.trim().is_empty()) {
+            return Err("Variables file template cannot be empty".into());
+        }
+        Ok(())
+    }
+
+    /// Renders the file from the container environment. Values are escaped for the format, and
+    /// a JSON result must still parse.
+    pub fn render(&self, environment: &HashMap<String, String>) -> Result<String, String> {
+        let Some(template) = &self.template else {
+            return Ok(self.render_all(environment));
+        };
+
+        let escaped: HashMap<String, String> = environment
+            .iter()
+            .map(|(key, value)| (key.clone(), self.escape(value)))
+            .collect();
+
+        let rendered = replace_startup_placeholders(template, &escaped, None);
+        let missing = unresolved_placeholders(&rendered);
+        if !missing.is_empty() {
+            return Err(format!("Variables file references undefined variables: {}", missing.join(", ")));
+        }
+
+        if self.format == VariablesFileFormat::Json {
+            serde_json::from_str::<serde_json::Value>(&rendered)
+                .map_err(|e| format!("Rendered variables file is not valid JSON: {}", e))?;
+        }
+
+        Ok(rendered)
+    }
+
+    fn render_all(&self, environment: &HashMap<String, String>) -> String {
+        let sorted: BTreeMap<&String, &String> = environment.iter().collect();
+
+        match self.format {
+            VariablesFileFormat::Dotenv => sorted
+                .into_iter()
+                .map(|(key, value)| format!("{}=\"{}\"\n", key, self.escape(value)))
+                .collect(),
+            VariablesFileFormat::Json => {
+                let mut json = serde_json::to_string_pretty(&sorted).unwrap_or_default();
+                json.push('\n');
+                json
+            }
+        }
+    }
+
+    /// A value as it goes between double quotes in the target format.
+    fn escape(&self, value: &str) -> String {
+        match self.format {
+            VariablesFileFormat::Dotenv => value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('$', "\\$"),
+            VariablesFileFormat::Json => {
+                let quoted = serde_json::to_string(value).unwrap_or_default();
+                quoted.strip_prefix('"').and_then(|q| q.strip_suffix('"')).unwrap_or_default().to_string()
+            }
+        }
+    }
+}
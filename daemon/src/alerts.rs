@@ -0,0 +1,100 @@
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use raptor_common::resources::ResourceAlerts;
+
+use crate::callback;
+use crate::models::AppState;
+
+/// How often usage of containers with alert thresholds is sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(15);
+const STATS_CONCURRENCY: usize = 8;
+const STATS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A threshold that is currently exceeded; reported once per breach.
+struct Breach {
+    since: Instant,
+    reported: bool,
+}
+
+/// Watches running containers against their resource alert thresholds and reports usage that
+/// stays above one for the whole window to the panel.
+pub fn spawn(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut breaches: HashMap<(String, &'static str), Breach> = HashMap::new();
+
+        loop {
+            interval.tick().await;
+
+            if !state.docker.is_available() {
+                continue;
+            }
+
+            evaluate(&state, &mut breaches).await;
+        }
+    });
+}
+
+async fn evaluate(state: &AppState, breaches: &mut HashMap<(String, &'static str), Breach>) {
+    let watched: Vec<(String, String, f64, ResourceAlerts)> = state
+        .containers
+        .iter()
+        .filter(|c| c.status.map(|s| s.is_running()).unwrap_or(false))
+        .filter_map(|c| {
+            let alerts = c.resource_alerts.clone().filter(|a| !a.is_empty())?;
+            Some((c.key().clone(), c.docker_id.clone(), c.resources.cpu_limit, alerts))
+        })
+        .collect();
+
+    breaches.retain(|(name, _), _| watched.iter().any(|(watched, ..)| watched == name));
+
+    let host_cpus = std::thread::available_parallelism().map(|n| n.get() as f64).unwrap_or(1.0);
+
+    let samples: Vec<_> = futures_util::stream::iter(watched)
+        .map(|(name, docker_id, cpu_limit, alerts)| async move {
+            match tokio::time::timeout(STATS_TIMEOUT, state.docker.get_container_stats(&docker_id)).await {
+                Ok(Ok(stats)) => Some((name, cpu_limit, alerts, stats)),
+                _ => None,
+            }
+        })
+        .buffer_unordered(STATS_CONCURRENCY)
+        .filter_map(|s| async move { s })
+        .collect()
+        .await;
+
+    let now = Instant::now();
+
+    for (name, cpu_limit, alerts, stats) in samples {
+        let cores = if cpu_limit > 0.0 { cpu_limit } else { host_cpus };
+        let usage = [
+            ("cpu", stats.cpu_percent / cores, alerts.cpu_percent),
+            ("memory", stats.memory_percent, alerts.memory_percent),
+        ];
+
+        for (metric, percent, threshold) in usage {
+            let key = (name.clone(), metric);
+            let Some(threshold) = threshold.filter(|t| percent >= *t) else {
+                breaches.remove(&key);
+                continue;
+            };
+
+            let breach = breaches.entry(key).or_insert(Breach { since: now, reported: false });
+            if breach.reported || now.duration_since(breach.since) < Duration::from_secs(alerts.window_secs) {
+                continue;
+            }
+            breach.reported = true;
+
+            tracing::info!("{} {} usage at {:.1}% has been above {}% for {}s", name, metric, percent, threshold, alerts.window_secs);
+            state.panel.send(&name, callback::RESOURCE_ALERT, serde_json::json!({
+                "metric": metric,
+                "percent": (percent * 10.0).round() / 10.0,
+                "threshold": threshold,
+                "windowSecs": alerts.window_secs,
+            }));
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub name: String,
+    pub size: u64,
+    pub created_at: i64,
+}
+
+fn backups_dir(container_name: &str) -> PathBuf {
+    crate::config::base_path().join("backups").join(container_name)
+}
+
+/// Backup names are generated by the daemon; anything else could escape the backups directory.
+pub fn is_valid_backup_name(name: &str) -> bool {
+    name.ends_with(".tar.gz")
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Archives the container volume into `<prefix>-<timestamp>.tar.gz`.
+pub async fn create_backup(container_name: &str, prefix: &str) -> anyhow::Result<BackupInfo> {
+    if !prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') || prefix.is_empty() {
+        anyhow::bail!("Invalid backup prefix '{}'", prefix);
+    }
+
+    let volume_path = crate::config::base_path().join("volumes").join(container_name);
+    if tokio::fs::metadata(&volume_path).await.is_err() {
+        anyhow::bail!("Volume for {} does not exist", container_name);
+    }
+
+    let dir = backups_dir(container_name);
+    tokio::fs::create_dir_all(&dir).await?;
+
+    let name = format!("{}-{}.tar.gz", prefix, chrono::Utc::now().format("%Y%m%d-%H%M%S"));
+    let path = dir.join(&name);
+
+    tracing::info!("Creating backup {} for {}", name, container_name);
+
+    let output = tokio::process::Command::new("tar")
+        .arg("-czf")
+        .arg(&path)
+        .arg("-C")
+        .arg(&volume_path)
+        .arg(".")
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let _ = tokio::fs::remove_file(&path).await;
+        anyhow::bail!("tar failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    let size = tokio::fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+
+    Ok(BackupInfo {
+        name,
+        size,
+        created_at: chrono::Utc::now().timestamp(),
+    })
+}
+
+/// Lists a container's backups, newest first.
+pub async fn list_backups(container_name: &str) -> Vec<BackupInfo> {
+    let mut backups = Vec::new();
+
+    let mut entries = match tokio::fs::read_dir(backups_dir(container_name)).await {
+        Ok(e) => e,
+        Err(_) => return backups,
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_valid_backup_name(&name) {
+            continue;
+        }
+
+        if let Ok(metadata) = entry.metadata().await {
+            let created_at = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0);
+
+            backups.push(BackupInfo {
+                name,
+                size: metadata.len(),
+                created_at,
+            });
+        }
+    }
+
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| b.name.cmp(&a.name)));
+    backups
+}
+
+pub async fn delete_backup(container_name: &str, backup_name: &str) -> anyhow::Result<()> {
+    if !is_valid_backup_name(backup_name) {
+        anyhow::bail!("Invalid backup name");
+    }
+
+    tokio::fs::remove_file(backups_dir(container_name).join(backup_name)).await?;
+    tracing::info!("Deleted backup {} for {}", backup_name, container_name);
+    Ok(())
+}
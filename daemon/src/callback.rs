@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+pub const CONTAINER_CRASHED: &str = "container.crashed";
+pub const INSTALL_COMPLETED: &str = "install.completed";
+/// Details carry `transition`: one of "started", "ready", "stopped", "died", "oom" or
+/// "start_timeout" (with `timeoutSecs`).
+pub const CONTAINER_STATE: &str = "container.state";
+/// Details carry a human readable `reason` and `crashLooping`.
+pub const CONTAINER_SUSPENDED: &str = "container.suspended";
+/// Usage stayed above an alert threshold for its window. Details carry `metric` ("cpu" or
+/// "memory"), `percent`, `threshold` and `windowSecs`.
+pub const RESOURCE_ALERT: &str = "container.resource_alert";
+
+/// Events waiting for the panel; past this the oldest are dropped so an unreachable panel
+/// can't grow the daemon's memory without bound.
+const MAX_QUEUED_EVENTS: usize = 1000;
+
+struct PanelEvent {
+    container_name: String,
+    event: String,
+    details: serde_json::Value,
+    occurred_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Default)]
+struct EventQueue {
+    events: Mutex<VecDeque<PanelEvent>>,
+    notify: Notify,
+}
+
+/// Reports container events back to the panel API, authenticated with this daemon's API key.
+/// Disabled when API_CALLBACK_URL is not set.
+pub struct PanelCallback {
+    queue: Option<Arc<EventQueue>>,
+}
+
+impl PanelCallback {
+    pub fn new(base_url: Option<String>, api_key: String) -> Self {
+        let Some(base_url) = base_url else {
+            return Self { queue: None };
+        };
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+        let url = format!("{}/daemon/events", base_url.trim_end_matches('/'));
+
+        // A single sender keeps events in order, so a quick stop/start never lands reversed
+        let queue = Arc::new(EventQueue::default());
+        let worker = queue.clone();
+        tokio::spawn(async move {
+            loop {
+                let next = worker.events.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+                let Some(event) = next else {
+                    worker.notify.notified().await;
+                    continue;
+                };
+
+                let result = client
+                    .post(&url)
+                    .header("X-API-Key", &api_key)
+                    .json(&serde_json::json!({
+                        "containerId": event.container_name,
+                        "event": event.event,
+                        "details": event.details,
+                        "occurredAt": event.occurred_at,
+                    }))
+                    .send()
+                    .await;
+
+                match result {
+                    Ok(res) if res.status().is_success() => {
+                        tracing::debug!("Reported {} for {} to the panel", event.event, event.container_name);
+                    }
+                    Ok(res) => tracing::warn!("Panel rejected {} for {}: {}", event.event, event.container_name, res.status()),
+                    Err(e) => tracing::warn!("Failed to report {} for {} to the panel: {}", event.event, event.container_name, e),
+                }
+            }
+        });
+
+        Self { queue: Some(queue) }
+    }
+
+    /// Fire-and-forget; a panel that is down only loses the notification, never blocks the daemon.
+    pub fn send(&self, container_name: &str, event: &str, details: serde_json::Value) {
+        let Some(queue) = &self.queue else {
+            return;
+        };
+
+        {
+            let mut events = queue.events.lock().unwrap_or_else(|e| e.into_inner());
+            if events.len() >= MAX_QUEUED_EVENTS {
+                if let Some(dropped) = events.pop_front() {
+                    tracing::warn!("Panel event queue is full, dropping {} for {}", dropped.event, dropped.container_name);
+                }
+            }
+            events.push_back(PanelEvent {
+                container_name: container_name.to_string(),
+                event: event.to_string(),
+                details,
+                occurred_at: chrono::Utc::now(),
+            });
+        }
+        queue.notify.notify_one();
+    }
+}
@@ -1,9 +1,58 @@
 use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Keys a reload (SIGHUP or `POST /reload`) picks up from `.env` without a restart. Everything
+/// else (listen address, API key, TLS, FTP, base paths, log history, callback URL, CORS and
+/// create concurrency) is bound at startup and needs a restart.
+pub const RELOADABLE_ENV: &[&str] = &[
+    "INSTALL_TIMEOUT_MAX_SECS",
+    "START_TIMEOUT_MAX_SECS",
+    "LOG_STREAM_BUFFER",
+    "LOG_STREAM_COALESCE",
+    "CRASH_LOOP_THRESHOLD",
+    "CRASH_LOOP_OOM_THRESHOLD",
+    "CRASH_LOOP_WINDOW_SECS",
+    "AVAILABLE_IPS",
+    "ALLOCATION_PORTS",
+    "FIX_PERMISSIONS_EXCLUDE",
+    "FIX_PERMISSIONS_MAX_DIR_ENTRIES",
+    "CONTAINER_UID",
+    "CONTAINER_GID",
+    "REGISTRY_AUTH_FILE",
+    "REGISTRY_AUTH_KEY",
+];
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub daemon_addr: String,
     pub daemon_api_key: String,
+    pub install_timeout_max_secs: u64,
+    /// Upper bound for per-flake start timeouts.
+    pub start_timeout_max_secs: u64,
+    pub log_stream_buffer: usize,
+    pub log_stream_coalesce: bool,
+    pub api_callback_url: Option<String>,
+    /// Unexpected exits within the window that flag a container as crash looping; 0 disables.
+    pub crash_loop_threshold: usize,
+    /// OOM kills within the window that flag a container as crash looping; 0 disables.
+    pub crash_loop_oom_threshold: usize,
+    pub crash_loop_window_secs: u64,
+    /// Container creates (including their image pulls) running at once.
+    pub create_concurrency: usize,
+    /// Browser origins allowed to call the daemon; defaults to APP_URL when set, otherwise none.
+    pub cors_allowed_origins: Vec<String>,
+    /// Any origin, without credentials. Only for development.
+    pub cors_allow_all: bool,
+    /// Bearer token accepted by `/metrics` in addition to the API key.
+    pub metrics_token: Option<String>,
+    /// Directory holding container volumes, backups and volume archives (FTP_BASE_PATH, or
+    /// SFTP_BASE_PATH). Required.
+    pub base_path: Option<PathBuf>,
+    /// Host directory that asset mounts are resolved in (ASSETS_PATH); without it they are refused.
+    pub assets_path: Option<PathBuf>,
+    /// Largest accepted upload chunk in bytes (UPLOAD_CHUNK_SIZE_MB), shared with the API.
+    pub upload_chunk_size: usize,
 }
 
 impl Config {
@@ -13,6 +62,139 @@ impl Config {
                 .unwrap_or_else(|_| "0.0.0.0:8080".into()),
             daemon_api_key: std::env::var("DAEMON_API_KEY")
                 .unwrap_or_else(|_| "daemon-secret".into()),
+            install_timeout_max_secs: std::env::var("INSTALL_TIMEOUT_MAX_SECS")
+                .unwrap_or_else(|_| "3600".into())
+                .parse()
+                .unwrap_or(3600),
+            start_timeout_max_secs: std::env::var("START_TIMEOUT_MAX_SECS")
+                .unwrap_or_else(|_| "3600".into())
+                .parse()
+                .unwrap_or(3600),
+            log_stream_buffer: std::env::var("LOG_STREAM_BUFFER")
+                .unwrap_or_else(|_| "1000".into())
+                .parse()
+                .unwrap_or(1000),
+            log_stream_coalesce: std::env::var("LOG_STREAM_COALESCE")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            api_callback_url: std::env::var("API_CALLBACK_URL")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            crash_loop_threshold: std::env::var("CRASH_LOOP_THRESHOLD")
+                .unwrap_or_else(|_| "5".into())
+                .parse()
+                .unwrap_or(5),
+            crash_loop_oom_threshold: std::env::var("CRASH_LOOP_OOM_THRESHOLD")
+                .unwrap_or_else(|_| "3".into())
+                .parse()
+                .unwrap_or(3),
+            crash_loop_window_secs: std::env::var("CRASH_LOOP_WINDOW_SECS")
+                .unwrap_or_else(|_| "300".into())
+                .parse()
+                .unwrap_or(300),
+            create_concurrency: std::env::var("CONTAINER_CREATE_CONCURRENCY")
+                .unwrap_or_else(|_| "4".into())
+                .parse()
+                .unwrap_or(4),
+            cors_allowed_origins: std::env::var("CORS_ALLOWED_ORIGINS")
+                .or_else(|_| std::env::var("APP_URL"))
+                .unwrap_or_default()
+                .split(',')
+                .map(|o| o.trim().trim_end_matches('/').to_string())
+                .filter(|o| !o.is_empty())
+                .collect(),
+            cors_allow_all: std::env::var("CORS_ALLOW_ALL")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            metrics_token: std::env::var("METRICS_TOKEN")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            base_path: std::env::var("FTP_BASE_PATH")
+                .or_else(|_| std::env::var("SFTP_BASE_PATH"))
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .map(PathBuf::from),
+            assets_path: std::env::var("ASSETS_PATH")
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .map(PathBuf::from),
+            upload_chunk_size: raptor_common::uploads::chunk_size_from_env(),
         }
     }
+
+    /// Copies changed reloadable keys from `.env` into the process environment and returns
+    /// their names. Keys removed from the file keep their current value.
+    pub fn reload_env() -> anyhow::Result<Vec<String>> {
+        let mut changed = Vec::new();
+
+        let iter = match dotenvy::dotenv_iter() {
+            Ok(iter) => iter,
+            Err(e) if e.not_found() => return Ok(changed),
+            Err(e) => return Err(e.into()),
+        };
+
+        for item in iter {
+            let (key, value) = item?;
+            if !RELOADABLE_ENV.contains(&key.as_str()) || std::env::var(&key).ok().as_deref() == Some(value.as_str()) {
+                continue;
+            }
+
+            std::env::set_var(&key, &value);
+            changed.push(key);
+        }
+
+        Ok(changed)
+    }
+}
+
+static BASE_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// The daemon's data directory, set once at startup by `init_base_path`.
+pub fn base_path() -> &'static Path {
+    BASE_PATH.get().expect("base path is initialized at startup")
+}
+
+/// Checks that the configured base path is an existing, writable directory and makes it
+/// available through `base_path`.
+pub fn init_base_path(config: &Config) -> anyhow::Result<&'static Path> {
+    let path = config.base_path.clone().ok_or_else(|| {
+        anyhow::anyhow!("FTP_BASE_PATH (or SFTP_BASE_PATH) must be set to the directory that holds container volumes")
+    })?;
+
+    let metadata = std::fs::metadata(&path)
+        .map_err(|e| anyhow::anyhow!("Base path {} is not accessible: {}", path.display(), e))?;
+    if !metadata.is_dir() {
+        anyhow::bail!("Base path {} is not a directory", path.display());
+    }
+
+    let probe = path.join(format!(".raptor-write-test-{}", std::process::id()));
+    std::fs::write(&probe, b"")
+        .map_err(|e| anyhow::anyhow!("Base path {} is not writable: {}", path.display(), e))?;
+    let _ = std::fs::remove_file(&probe);
+
+    Ok(BASE_PATH.get_or_init(|| path).as_path())
+}
+
+static ASSETS_PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// The canonical asset directory, if one is configured and exists.
+pub fn assets_path() -> Option<&'static Path> {
+    ASSETS_PATH.get().and_then(|p| p.as_deref())
+}
+
+/// Canonicalizes the configured asset directory so mounts can be checked against it.
+pub fn init_assets_path(config: &Config) -> Option<&'static Path> {
+    let path = config.assets_path.as_ref().and_then(|path| match std::fs::canonicalize(path) {
+        Ok(path) if path.is_dir() => Some(path),
+        Ok(_) => {
+            tracing::warn!("Assets path {} is not a directory; asset mounts are disabled", path.display());
+            None
+        }
+        Err(e) => {
+            tracing::warn!("Assets path {} is not accessible: {}; asset mounts are disabled", path.display(), e);
+            None
+        }
+    });
+
+    ASSETS_PATH.get_or_init(|| path).as_deref()
 }
@@ -256,7 +256,7 @@ pub async fn create_and_start_database_container(
             ..Default::default()
         }),
         None,
-        None,
+        crate::registry::credentials_for(image, None).await,
     );
 
     while let Some(result) = pull_stream.next().await {
@@ -1,26 +1,316 @@
 use bollard::container::{
     AttachContainerOptions, AttachContainerResults,
-    Config, CreateContainerOptions, ListContainersOptions, LogOutput, LogsOptions,
+    Config, CreateContainerOptions, KillContainerOptions, ListContainersOptions, LogOutput, LogsOptions,
     RemoveContainerOptions, StartContainerOptions, StatsOptions, StopContainerOptions,
 };
 use bollard::image::CreateImageOptions;
 use bollard::network::CreateNetworkOptions;
+use bollard::system::EventsOptions;
 use bollard::Docker;
+use dashmap::DashMap;
 use futures_util::StreamExt;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::broadcast;
+use raptor_common::mounts::AssetMount;
+use raptor_common::resources::UNLIMITED;
 
-use crate::models::{ContainerInfo, ContainerResources, ContainerStats};
+use crate::models::{AppliedResources, ContainerInfo, ContainerResources, ContainerRuntime, ContainerStats, ImageVersion, StopStage};
+use crate::log_format::{LogLine, LogStream};
 
 pub const RAPTOR_NETWORK: &str = "raptord_internal";
 
+/// Private bridge an isolated container joins instead of `RAPTOR_NETWORK`.
+pub fn isolated_network_name(container_name: &str) -> String {
+    format!("raptor_{}", container_name)
+}
+
+pub const DEFAULT_INSTALL_TIMEOUT_SECS: u64 = 300;
+
+const DOCKER_PING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Set by the daemon itself and never taken from the container environment.
+pub const RESERVED_ENV_KEYS: [&str; 3] = ["HOME", "USER", "STARTUP"];
+
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+
+/// Labels under this prefix are set by the daemon and cannot be overridden by flakes.
+pub const RESERVED_LABEL_PREFIX: &str = "raptor.";
+
+const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+/// `/tmp` for read-only containers that don't configure their own tmpfs there.
+const READ_ONLY_TMP_OPTIONS: &str = "rw,nosuid,size=256m";
+
+/// How long a SIGKILLed container gets to be reaped before the stop counts as failed.
+const SIGKILL_WAIT_SECS: u64 = 10;
+
+/// `{base}/volumes/{container_name}`, refusing names that would point anywhere else.
+fn volume_dir(container_name: &str) -> anyhow::Result<std::path::PathBuf> {
+    let volumes_dir = crate::config::base_path().join("volumes");
+    let volume_path = volumes_dir.join(container_name);
+
+    let mut components = std::path::Path::new(container_name).components();
+    let single_segment = matches!(
+        (components.next(), components.next()),
+        (Some(std::path::Component::Normal(_)), None)
+    );
+    if !single_segment || !volume_path.starts_with(&volumes_dir) {
+        anyhow::bail!("Invalid container name");
+    }
+
+    Ok(volume_path)
+}
+
+/// Copies one file, directory tree or symlink without following links. Sockets and FIFOs are skipped.
+fn copy_entry(source: &std::path::Path, target: &std::path::Path) -> std::io::Result<u64> {
+    use std::os::unix::fs::{lchown, MetadataExt};
+
+    let metadata = std::fs::symlink_metadata(source)?;
+    let file_type = metadata.file_type();
+    let mut copied = 1;
+
+    if file_type.is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(source)?, target)?;
+    } else if file_type.is_dir() {
+        std::fs::create_dir(target)?;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            copied += copy_entry(&entry.path(), &target.join(entry.file_name()))?;
+        }
+        std::fs::set_permissions(target, metadata.permissions())?;
+    } else if file_type.is_file() {
+        std::fs::copy(source, target)?;
+    } else {
+        return Ok(0);
+    }
+
+    lchown(target, Some(metadata.uid()), Some(metadata.gid()))?;
+    Ok(copied)
+}
+
+/// Merges flake labels, with `{{VARIABLE}}` placeholders filled from the container
+/// environment, under the daemon's own `raptor.*` labels.
+fn container_labels(
+    name: &str,
+    labels: &HashMap<String, String>,
+    environment: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+
+    for (key, value) in labels {
+        if key.starts_with(RESERVED_LABEL_PREFIX) {
+            tracing::warn!("Ignoring reserved label {} on {}", key, name);
+            continue;
+        }
+
+        let mut value = value.clone();
+        for (var, replacement) in environment {
+            value = value.replace(&format!("{{{{{}}}}}", var), replacement);
+        }
+        result.insert(key.clone(), value);
+    }
+
+    result.insert("raptor.managed".to_string(), "true".to_string());
+    result.insert("raptor.container".to_string(), name.to_string());
+    // The daemon names containers after their panel id; kept separate so integrations
+    // can correlate without relying on the naming scheme
+    result.insert("raptor.container_id".to_string(), name.to_string());
+    result
+}
+
+/// Label holding the fingerprint of the spec a container was created from.
+/// Docker's form of a restart policy name; anything unknown means `unless-stopped`.
+fn docker_restart_policy(name: &str) -> bollard::service::RestartPolicy {
+    use bollard::service::{RestartPolicy, RestartPolicyNameEnum};
+
+    let (name, maximum_retry_count) = match raptor_common::restart_policy::normalize(name) {
+        Some("no") => (RestartPolicyNameEnum::NO, None),
+        Some("always") => (RestartPolicyNameEnum::ALWAYS, None),
+        Some("on-failure") => (RestartPolicyNameEnum::ON_FAILURE, Some(5)),
+        _ => (RestartPolicyNameEnum::UNLESS_STOPPED, None),
+    };
+
+    RestartPolicy { name: Some(name), maximum_retry_count }
+}
+
+const SPEC_LABEL: &str = "raptor.spec";
+
+/// Digest of everything a container is created from, stored as a label so a start can tell
+/// whether the existing container is still current or has to be recreated.
+pub fn spec_fingerprint(
+    image: &str,
+    startup_script: Option<&str>,
+    port_bindings: Option<&HashMap<String, Vec<bollard::service::PortBinding>>>,
+    resources: &ContainerResources,
+    restart_policy: &str,
+    tty: bool,
+    environment: &HashMap<String, String>,
+    labels: &HashMap<String, String>,
+    isolated: bool,
+    asset_mounts: &[AssetMount],
+) -> String {
+    use std::hash::{Hash, Hasher};
+
+    // Object keys serialize sorted, so map ordering doesn't change the digest
+    let spec = serde_json::json!({
+        "image": image,
+        "startupScript": startup_script,
+        "ports": port_bindings,
+        "resources": resources,
+        "restartPolicy": restart_policy,
+        "tty": tty,
+        "environment": environment,
+        "labels": labels,
+        "isolated": isolated,
+        "assetMounts": asset_mounts,
+        "user": crate::permissions::container_owner(),
+    });
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    spec.to_string().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+type NormalizedBindings = std::collections::BTreeMap<String, std::collections::BTreeSet<(String, String)>>;
+
+fn normalize_bindings(bindings: impl IntoIterator<Item = (String, Vec<bollard::service::PortBinding>)>) -> NormalizedBindings {
+    bindings
+        .into_iter()
+        .filter(|(_, targets)| !targets.is_empty())
+        .map(|(port, targets)| {
+            let targets = targets
+                .into_iter()
+                .map(|b| (b.host_ip.unwrap_or_default(), b.host_port.unwrap_or_default()))
+                .collect();
+            (port, targets)
+        })
+        .collect()
+}
+
+/// Parses a relative `since` such as "10m" or "1h" into a Unix timestamp.
+pub fn parse_since(since: &str) -> Option<i64> {
+    let since = since.trim();
+    let seconds = if let Some(mins) = since.strip_suffix('m') {
+        mins.parse::<i64>().ok()? * 60
+    } else if let Some(hours) = since.strip_suffix('h') {
+        hours.parse::<i64>().ok()? * 3600
+    } else {
+        return None;
+    };
+
+    Some(chrono::Utc::now().timestamp() - seconds)
+}
+
+/// Without `tail`, a `since` window returns everything in it and otherwise the last `DEFAULT_LOG_TAIL` lines.
+fn history_options(since: Option<&str>, tail: Option<usize>) -> LogsOptions<String> {
+    let since_timestamp = since.and_then(parse_since);
+    let tail = match (tail, since_timestamp) {
+        (Some(tail), _) => tail.to_string(),
+        (None, Some(_)) => "all".to_string(),
+        (None, None) => raptor_common::logs::DEFAULT_LOG_TAIL.to_string(),
+    };
+
+    LogsOptions::<String> {
+        follow: false,
+        stdout: true,
+        stderr: true,
+        tail,
+        timestamps: true,
+        since: since_timestamp.unwrap_or(0),
+        ..Default::default()
+    }
+}
+
+/// Resolves a tz database name to the host's zoneinfo file, rejecting anything outside it.
+fn zoneinfo_path(timezone: &str) -> Option<String> {
+    let valid = !timezone.is_empty()
+        && !timezone.starts_with('/')
+        && timezone.split('/').all(|part| !part.is_empty() && part != "." && part != "..");
+
+    if !valid {
+        return None;
+    }
+
+    let path = format!("{}/{}", ZONEINFO_DIR, timezone);
+    std::path::Path::new(&path).is_file().then_some(path)
+}
+
+/// Resolves asset mounts to read-only bind specs, refusing any source that is missing or
+/// resolves outside the asset directory.
+pub fn asset_binds(mounts: &[AssetMount]) -> anyhow::Result<Vec<String>> {
+    if mounts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    raptor_common::mounts::validate_asset_mounts(mounts).map_err(|e| anyhow::anyhow!(e))?;
+
+    let assets = crate::config::assets_path()
+        .ok_or_else(|| anyhow::anyhow!("Asset mounts are not enabled on this daemon (ASSETS_PATH is not set)"))?;
+
+    mounts
+        .iter()
+        .map(|mount| {
+            let source = std::fs::canonicalize(assets.join(&mount.source))
+                .map_err(|_| anyhow::anyhow!("Asset '{}' does not exist", mount.source))?;
+            if !source.starts_with(assets) {
+                anyhow::bail!("Asset '{}' is outside the asset directory", mount.source);
+            }
+            let source = source
+                .to_str()
+                .filter(|s| !s.contains(':') && !s.contains(','))
+                .ok_or_else(|| anyhow::anyhow!("Asset '{}' has an unsupported path", mount.source))?;
+            Ok(format!("{}:{}:ro", source, mount.target.trim_end_matches('/')))
+        })
+        .collect()
+}
+
+pub type ConsoleInput = std::pin::Pin<Box<dyn tokio::io::AsyncWrite + Send>>;
+
+pub async fn write_console_line(input: &mut ConsoleInput, line: &str) -> anyhow::Result<()> {
+    let line_with_newline = format!("{}\n", line);
+    input.write_all(line_with_newline.as_bytes()).await?;
+    input.flush().await?;
+    Ok(())
+}
+
+/// An interactive TTY exec session; `output` carries the raw terminal bytes.
+pub struct ShellSession {
+    pub exec_id: String,
+    pub output: std::pin::Pin<Box<dyn futures_util::Stream<Item = Result<LogOutput, bollard::errors::Error>> + Send>>,
+    pub input: ConsoleInput,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContainerEvent {
+    pub docker_id: String,
+    pub action: String,
+    /// Set on "die" events.
+    pub exit_code: Option<i64>,
+}
+
+/// A Docker container as seen by startup reconciliation.
+#[derive(Debug, Clone)]
+pub struct DockerEntry {
+    pub id: String,
+    pub name: String,
+    pub state: String,
+    /// The `raptor.container` label, set only on containers the daemon created.
+    pub managed_as: Option<String>,
+}
+
 pub struct DockerManager {
     docker: Docker,
+    available: std::sync::atomic::AtomicBool,
+    /// Per image, when its last successful pull finished.
+    pull_locks: DashMap<String, Arc<tokio::sync::Mutex<Option<std::time::Instant>>>>,
+    /// Bounds concurrent container creates (and therefore pulls) during bulk provisioning.
+    create_permits: tokio::sync::Semaphore,
 }
 
 impl DockerManager {
-    pub async fn new() -> anyhow::Result<Self> {
+    pub async fn new(create_concurrency: usize) -> anyhow::Result<Self> {
         let docker = if let Ok(host) = std::env::var("DOCKER_HOST") {
             if host.starts_with("unix://") {
                 Docker::connect_with_socket(&host[7..], 120, bollard::API_DEFAULT_VERSION)?
@@ -30,16 +320,62 @@ impl DockerManager {
         } else {
             Docker::connect_with_local_defaults()?
         };
-        docker.ping().await?;
-        tracing::info!("Connected to Docker daemon");
 
-        let manager = Self { docker };
+        let manager = Self {
+            docker,
+            available: std::sync::atomic::AtomicBool::new(false),
+            pull_locks: DashMap::new(),
+            create_permits: tokio::sync::Semaphore::new(create_concurrency.max(1)),
+        };
 
-        manager.ensure_network().await?;
+        // Docker often comes up after the daemon on boot; keep serving and retry in the background
+        if let Err(e) = manager.connect().await {
+            tracing::warn!("Docker is not reachable, starting in degraded mode: {}", e);
+        }
 
         Ok(manager)
     }
 
+    async fn connect(&self) -> anyhow::Result<()> {
+        self.docker.ping().await?;
+        self.ensure_network().await?;
+        self.available.store(true, std::sync::atomic::Ordering::SeqCst);
+        tracing::info!("Connected to Docker daemon");
+        Ok(())
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.available.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Retries the Docker connection with backoff until it succeeds.
+    pub async fn wait_until_available(&self) {
+        let mut delay = std::time::Duration::from_secs(2);
+
+        while !self.is_available() {
+            tokio::time::sleep(delay).await;
+
+            if let Err(e) = self.connect().await {
+                delay = (delay * 2).min(std::time::Duration::from_secs(30));
+                tracing::warn!("Docker still unreachable, retrying in {:?}: {}", delay, e);
+            }
+        }
+    }
+
+    /// Pings Docker periodically so `is_available` turns false once it stops answering,
+    /// then reconnects with backoff. Runs forever.
+    pub async fn monitor_connection(&self) {
+        loop {
+            self.wait_until_available().await;
+            tokio::time::sleep(DOCKER_PING_INTERVAL).await;
+
+            if let Err(e) = self.docker.ping().await {
+                tracing::warn!("Lost connection to Docker, reconnecting: {}", e);
+                self.available.store(false, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+    }
+
     async fn ensure_network(&self) -> anyhow::Result<()> {
 
         match self.docker.inspect_network::<String>(RAPTOR_NETWORK, None).await {
@@ -63,6 +399,41 @@ impl DockerManager {
         Ok(())
     }
 
+    /// Creates the container's private network if needed. Other containers, including
+    /// database servers on `RAPTOR_NETWORK`, are only reachable through published ports.
+    async fn ensure_isolated_network(&self, container_name: &str) -> anyhow::Result<String> {
+        let network = isolated_network_name(container_name);
+
+        if self.docker.inspect_network::<String>(&network, None).await.is_ok() {
+            return Ok(network);
+        }
+
+        let config = CreateNetworkOptions {
+            name: network.as_str(),
+            driver: "bridge",
+            labels: HashMap::from([
+                ("raptor.managed", "true"),
+                ("raptor.container", container_name),
+            ]),
+            ..Default::default()
+        };
+
+        self.docker.create_network(config).await?;
+        tracing::info!("Created isolated network {} for {}", network, container_name);
+        Ok(network)
+    }
+
+    /// Removes an isolated container's network; a no-op for containers that never had one.
+    pub async fn remove_isolated_network(&self, container_name: &str) {
+        let network = isolated_network_name(container_name);
+
+        match self.docker.remove_network(&network).await {
+            Ok(()) => tracing::info!("Removed isolated network {}", network),
+            Err(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) => {}
+            Err(e) => tracing::warn!("Failed to remove network {}: {}", network, e),
+        }
+    }
+
     pub async fn create_container_with_resources(
         &self,
         name: &str,
@@ -72,21 +443,19 @@ impl DockerManager {
         resources: &ContainerResources,
         restart_policy_name: &str,
         tty: bool,
+        environment: &HashMap<String, String>,
+        labels: &HashMap<String, String>,
+        isolated: bool,
+        asset_mounts: &[AssetMount],
+        registry_credentials: Option<&str>,
     ) -> anyhow::Result<String> {
-        let mut stream = self.docker.create_image(
-            Some(CreateImageOptions {
-                from_image: image,
-                ..Default::default()
-            }),
-            None,
-            None,
-        );
+        let extra_binds = asset_binds(asset_mounts)?;
 
-        while let Some(result) = stream.next().await {
-            match result {
-                Ok(info) => tracing::debug!("Pulling image: {:?}", info),
-                Err(e) => tracing::warn!("Image pull warning: {}", e),
-            }
+        let _permit = self.create_permits.acquire().await?;
+
+        // A failed pull falls back to the cached image, if there is one
+        if let Err(e) = self.pull_image(image, registry_credentials).await {
+            tracing::warn!("Image pull warning: {}", e);
         }
 
         let mut env_vars: Vec<String> = vec![
@@ -94,6 +463,17 @@ impl DockerManager {
             "USER=container".to_string(),
         ];
 
+        for (key, value) in environment {
+            if !RESERVED_ENV_KEYS.contains(&key.as_str()) {
+                env_vars.push(format!("{}={}", key, value));
+            }
+        }
+
+        let timezone = environment.get("TZ").map(String::as_str).unwrap_or(DEFAULT_TIMEZONE);
+        if !environment.contains_key("TZ") {
+            env_vars.push(format!("TZ={}", DEFAULT_TIMEZONE));
+        }
+
         let (entrypoint, cmd) = if let Some(s) = startup_script {
 
             env_vars.push(format!("STARTUP={}", s));
@@ -106,63 +486,26 @@ impl DockerManager {
             (None, None)
         };
 
-        let memory_bytes = resources.memory_limit * 1024 * 1024;
-        let swap_bytes = resources.swap_limit * 1024 * 1024;
         let cpu_period = 100000i64;
         let cpu_quota = (resources.cpu_limit * cpu_period as f64) as i64;
 
-        let restart_policy = match restart_policy_name.to_lowercase().as_str() {
-            "no" | "none" => bollard::service::RestartPolicy {
-                name: Some(bollard::service::RestartPolicyNameEnum::NO),
-                maximum_retry_count: None,
-            },
-            "always" => bollard::service::RestartPolicy {
-                name: Some(bollard::service::RestartPolicyNameEnum::ALWAYS),
-                maximum_retry_count: None,
-            },
-            "on-failure" | "onfailure" => bollard::service::RestartPolicy {
-                name: Some(bollard::service::RestartPolicyNameEnum::ON_FAILURE),
-                maximum_retry_count: Some(5),
-            },
-            _ => bollard::service::RestartPolicy {
-
-                name: Some(bollard::service::RestartPolicyNameEnum::UNLESS_STOPPED),
-                maximum_retry_count: None,
-            },
-        };
+        let restart_policy = docker_restart_policy(restart_policy_name);
         tracing::debug!("Using restart policy: {} for container {}", restart_policy_name, name);
 
-        let base_path = std::env::var("FTP_BASE_PATH")
-            .unwrap_or_else(|_| std::env::var("SFTP_BASE_PATH")
-                .unwrap_or_else(|_| "/data/raptor".into()));
+        let base_path = crate::config::base_path().display();
         let volume_path = format!("{}/volumes/{}", base_path, name);
 
         if let Err(e) = tokio::fs::create_dir_all(&volume_path).await {
             tracing::warn!("Failed to create volume directory {}: {}", volume_path, e);
         }
 
-        #[cfg(unix)]
-        {
-            let chmod_result = tokio::process::Command::new("chmod")
-                .args(["-R", "777", &volume_path])
-                .output()
-                .await;
-            match chmod_result {
-                Ok(output) if output.status.success() => {
-                    tracing::debug!("Set permissions 777 on {}", volume_path);
-                }
-                Ok(output) => {
-                    tracing::warn!(
-                        "Failed to chmod volume directory {}: {}",
-                        volume_path,
-                        String::from_utf8_lossy(&output.stderr)
-                    );
-                }
-                Err(e) => {
-                    tracing::warn!("Failed to run chmod on volume directory {}: {}", volume_path, e);
-                }
-            }
-        }
+        let user_spec = crate::permissions::prepare_volume(&volume_path).await?;
+
+        let network = if isolated {
+            self.ensure_isolated_network(name).await?
+        } else {
+            RAPTOR_NETWORK.to_string()
+        };
 
         let machine_id_path = format!("{}/.machine-id", volume_path);
         if !std::path::Path::new(&machine_id_path).exists() {
@@ -178,39 +521,39 @@ impl DockerManager {
             }
         }
 
-        let binds = vec![
-            format!("{}:/home/container:rw", volume_path),
+        let volume_mode = if resources.read_only { "ro" } else { "rw" };
+        let mut binds = vec![
+            format!("{}:/home/container:{}", volume_path, volume_mode),
             format!("{}:/etc/machine-id:ro", machine_id_path),
         ];
 
-        #[cfg(unix)]
-        let user_spec = {
-            use std::os::unix::fs::MetadataExt;
+        match zoneinfo_path(timezone) {
+            Some(zoneinfo) => binds.push(format!("{}:/etc/localtime:ro", zoneinfo)),
+            None => tracing::warn!("Timezone '{}' not found in {}, only TZ is set for {}", timezone, ZONEINFO_DIR, name),
+        }
 
-            if let Ok(metadata) = std::fs::metadata(&volume_path) {
-                let uid = metadata.uid();
-                let gid = metadata.gid();
-                Some(format!("{}:{}", uid, gid))
-            } else {
+        binds.extend(extra_binds);
 
-                let uid = unsafe { libc::getuid() };
-                let gid = unsafe { libc::getgid() };
-                Some(format!("{}:{}", uid, gid))
-            }
-        };
-        #[cfg(not(unix))]
-        let user_spec: Option<String> = None;
+        let mut tmpfs: HashMap<String, String> =
+            resources.tmpfs.iter().map(|m| (m.target.clone(), m.options())).collect();
+        if resources.read_only {
+            // Most servers still need somewhere to write scratch files
+            tmpfs.entry("/tmp".to_string()).or_insert_with(|| READ_ONLY_TMP_OPTIONS.to_string());
+        }
 
         let host_config = bollard::service::HostConfig {
             port_bindings: port_bindings.as_ref().map(|pb| pb.iter().map(|(k, v)| (k.clone(), Some(v.clone()))).collect()),
-            memory: Some(memory_bytes),
-            memory_swap: Some(memory_bytes + swap_bytes),
+            memory: Some(resources.docker_memory()),
+            memory_swap: Some(resources.docker_memory_swap()),
             cpu_period: Some(cpu_period),
             cpu_quota: Some(cpu_quota),
             blkio_weight: Some(resources.io_weight as u16),
+            cpuset_cpus: resources.cpuset_cpus.clone(),
             restart_policy: Some(restart_policy),
             binds: Some(binds),
-            network_mode: Some(RAPTOR_NETWORK.to_string()),
+            tmpfs: (!tmpfs.is_empty()).then_some(tmpfs),
+            readonly_rootfs: Some(resources.read_only),
+            network_mode: Some(network),
             ..Default::default()
         };
 
@@ -219,6 +562,21 @@ impl DockerManager {
             .map(|pb| pb.keys().cloned().collect())
             .unwrap_or_default();
 
+        let fingerprint = spec_fingerprint(
+            image,
+            startup_script,
+            port_bindings.as_ref(),
+            resources,
+            restart_policy_name,
+            tty,
+            environment,
+            labels,
+            isolated,
+            asset_mounts,
+        );
+        let mut labels = container_labels(name, labels, environment);
+        labels.insert(SPEC_LABEL.to_string(), fingerprint);
+
         let config = Config {
             image: Some(image),
 
@@ -228,13 +586,14 @@ impl DockerManager {
             host_config: Some(host_config),
             working_dir: Some("/home/container"),
 
-            user: user_spec.as_deref(),
+            user: Some(user_spec.as_str()),
             exposed_ports: if exposed_port_keys.is_empty() {
                 None
             } else {
                 Some(exposed_port_keys.iter().map(|k| (k.as_str(), HashMap::new())).collect())
             },
 
+            labels: Some(labels.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect()),
             tty: Some(tty),
             open_stdin: Some(true),
             attach_stdin: Some(true),
@@ -256,6 +615,34 @@ impl DockerManager {
         Ok(container.id)
     }
 
+    /// Whether a stopped container can be started as is: it was created from `fingerprint` and
+    /// Docker still has exactly the wanted port bindings.
+    pub async fn is_reusable(
+        &self,
+        id: &str,
+        fingerprint: &str,
+        port_bindings: &HashMap<String, Vec<bollard::service::PortBinding>>,
+    ) -> bool {
+        let Ok(info) = self.docker.inspect_container(id, None).await else {
+            return false;
+        };
+
+        let running = info.state.as_ref().and_then(|s| s.running).unwrap_or(false);
+        let current_fingerprint = info.config.as_ref().and_then(|c| c.labels.as_ref()).and_then(|l| l.get(SPEC_LABEL));
+        if running || current_fingerprint.map(String::as_str) != Some(fingerprint) {
+            return false;
+        }
+
+        let current = info
+            .host_config
+            .and_then(|h| h.port_bindings)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(port, targets)| (port, targets.unwrap_or_default()));
+
+        normalize_bindings(current) == normalize_bindings(port_bindings.clone())
+    }
+
     pub async fn start_container(&self, id: &str) -> anyhow::Result<()> {
         self.docker
             .start_container(id, None::<StartContainerOptions<String>>)
@@ -284,20 +671,40 @@ impl DockerManager {
         Ok(())
     }
 
+    /// Changes the restart policy in place, without recreating the container.
+    pub async fn set_restart_policy(&self, id: &str, policy: &str) -> anyhow::Result<()> {
+        use bollard::container::UpdateContainerOptions;
+
+        let options = UpdateContainerOptions::<String> {
+            restart_policy: Some(docker_restart_policy(policy)),
+            ..Default::default()
+        };
+        self.docker.update_container(id, options).await?;
+        Ok(())
+    }
+
     pub async fn update_container_resources(&self, id: &str, resources: &ContainerResources) -> anyhow::Result<()> {
         use bollard::container::UpdateContainerOptions;
 
-        let memory_bytes = resources.memory_limit * 1024 * 1024;
-        let swap_bytes = resources.swap_limit * 1024 * 1024;
         let cpu_period = 100000i64;
         let cpu_quota = (resources.cpu_limit * cpu_period as f64) as i64;
 
+        // Docker ignores an empty cpuset on update, so unpinning means allowing every host CPU again
+        let cpuset_cpus = match &resources.cpuset_cpus {
+            Some(cpuset) => cpuset.clone(),
+            None => {
+                let ncpu = self.docker.info().await?.ncpu.unwrap_or(1).max(1);
+                format!("0-{}", ncpu - 1)
+            }
+        };
+
         let update_options = UpdateContainerOptions::<String> {
-            memory: Some(memory_bytes),
-            memory_swap: Some(memory_bytes + swap_bytes),
+            memory: Some(resources.docker_memory()),
+            memory_swap: Some(resources.docker_memory_swap()),
             cpu_period: Some(cpu_period),
             cpu_quota: Some(cpu_quota),
             blkio_weight: Some(resources.io_weight as u16),
+            cpuset_cpus: Some(cpuset_cpus),
             ..Default::default()
         };
 
@@ -309,6 +716,16 @@ impl DockerManager {
     pub async fn send_command(&self, id: &str, command: &str) -> anyhow::Result<()> {
         tracing::info!("Sending command to container {}: {}", id, command);
 
+        let mut input = self.attach_stdin(id).await?;
+        write_console_line(&mut input, command).await?;
+
+        drop(input);
+
+        tracing::info!("Command sent to container {}", id);
+        Ok(())
+    }
+
+    pub async fn attach_stdin(&self, id: &str) -> anyhow::Result<ConsoleInput> {
         let options = AttachContainerOptions::<String> {
             stdin: Some(true),
             stdout: Some(false),
@@ -317,19 +734,12 @@ impl DockerManager {
             ..Default::default()
         };
 
-        let AttachContainerResults { mut input, .. } = self
+        let AttachContainerResults { input, .. } = self
             .docker
             .attach_container(id, Some(options))
             .await?;
 
-        let cmd_with_newline = format!("{}\n", command);
-        input.write_all(cmd_with_newline.as_bytes()).await?;
-        input.flush().await?;
-
-        drop(input);
-
-        tracing::info!("Command sent to container {}", id);
-        Ok(())
+        Ok(input)
     }
 
     pub async fn run_install_script(&self, id: &str, script: &str, env: &std::collections::HashMap<String, String>) -> anyhow::Result<()> {
@@ -431,8 +841,10 @@ impl DockerManager {
         image: &str,
         script: &str,
         env: &std::collections::HashMap<String, String>,
+        timeout_secs: Option<u64>,
     ) -> anyhow::Result<()> {
-        self.run_install_in_temp_container_with_logs(container_name, image, script, env, None).await
+        let timeout_secs = timeout_secs.unwrap_or(DEFAULT_INSTALL_TIMEOUT_SECS);
+        self.run_install_in_temp_container_with_logs(container_name, image, script, env, timeout_secs, None).await
     }
 
     pub async fn run_install_in_temp_container_with_logs(
@@ -441,7 +853,8 @@ impl DockerManager {
         image: &str,
         script: &str,
         env: &std::collections::HashMap<String, String>,
-        log_tx: Option<broadcast::Sender<String>>,
+        timeout_secs: u64,
+        log_tx: Option<broadcast::Sender<LogLine>>,
     ) -> anyhow::Result<()> {
         use bollard::container::{CreateContainerOptions, Config, LogsOptions, RemoveContainerOptions, WaitContainerOptions};
         use futures_util::StreamExt;
@@ -461,9 +874,7 @@ impl DockerManager {
             tracing::info!("  {}={}", k, v);
         }
 
-        let base_path = std::env::var("FTP_BASE_PATH")
-            .unwrap_or_else(|_| std::env::var("SFTP_BASE_PATH")
-                .unwrap_or_else(|_| "/data/raptor".into()));
+        let base_path = crate::config::base_path().display();
         let volume_path = format!("{}/volumes/{}", base_path, container_name);
         tracing::info!("Volume path: {}", volume_path);
 
@@ -471,50 +882,10 @@ impl DockerManager {
             tracing::warn!("Failed to create volume directory {}: {}", volume_path, e);
         }
 
-        #[cfg(unix)]
-        {
-            let chmod_result = tokio::process::Command::new("chmod")
-                .args(["-R", "777", &volume_path])
-                .output()
-                .await;
-            match chmod_result {
-                Ok(output) if output.status.success() => {
-                    tracing::debug!("Set permissions 777 on {}", volume_path);
-                }
-                Ok(output) => {
-                    tracing::warn!(
-                        "Failed to chmod volume directory {}: {}",
-                        volume_path,
-                        String::from_utf8_lossy(&output.stderr)
-                    );
-                }
-                Err(e) => {
-                    tracing::warn!("Failed to run chmod on volume directory {}: {}", volume_path, e);
-                }
-            }
-        }
+        let install_user_spec = crate::permissions::prepare_volume(&volume_path).await?;
 
         let binds = vec![format!("{}:/home/container:rw", volume_path)];
 
-        #[cfg(unix)]
-        let user_spec = {
-            use std::os::unix::fs::MetadataExt;
-
-            if let Ok(metadata) = std::fs::metadata(&volume_path) {
-                let uid = metadata.uid();
-                let gid = metadata.gid();
-                Some(format!("{}:{}", uid, gid))
-            } else {
-
-                let uid = unsafe { libc::getuid() };
-                let gid = unsafe { libc::getgid() };
-                Some(format!("{}:{}", uid, gid))
-            }
-        };
-        #[cfg(not(unix))]
-        let install_user_spec: Option<String> = None;
-        #[cfg(unix)]
-        let install_user_spec = user_spec;
 
         let script_preview = if script.len() > 500 {
             format!("{}...(truncated)", &script[..500])
@@ -557,7 +928,7 @@ impl DockerManager {
             cmd: Some(vec![&full_script]),
             host_config: Some(host_config),
             working_dir: Some("/home/container"),
-            user: install_user_spec.as_deref(),
+            user: Some(install_user_spec.as_str()),
             env: Some(env_vars.iter().map(|s| s.as_str()).collect()),
             tty: Some(false),
             attach_stdout: Some(true),
@@ -613,14 +984,17 @@ impl DockerManager {
         let wait_options = WaitContainerOptions { condition: "not-running" };
         let mut wait_stream = self.docker.wait_container(&create_result.id, Some(wait_options));
 
-        let timeout = tokio::time::Duration::from_secs(300);
+        let timeout = tokio::time::Duration::from_secs(timeout_secs);
         let start_time = std::time::Instant::now();
 
         loop {
             if start_time.elapsed() > timeout {
-                tracing::error!("Install script timed out after 5 minutes");
+                tracing::error!("Install script timed out after the configured limit of {}s", timeout_secs);
                 if let Some(ref tx) = log_tx {
-                    let _ = tx.send("\x1b[31m[Install] Installation timed out after 5 minutes\x1b[0m".to_string());
+                    let _ = tx.send(LogLine::system(format!(
+                        "\x1b[31m[Install] Installation timed out after the configured limit of {}s\x1b[0m",
+                        timeout_secs
+                    )));
                 }
 
                 let _ = self.docker.kill_container::<String>(&create_result.id, None).await;
@@ -637,7 +1011,7 @@ impl DockerManager {
                                     tracing::info!("[install] {}", line.trim());
 
                                     if let Some(ref tx) = log_tx {
-                                        let _ = tx.send(format!("\x1b[36m[Install]\x1b[0m {}", line.trim()));
+                                        let _ = tx.send(LogLine::new(LogStream::Stdout, format!("\x1b[36m[Install]\x1b[0m {}", line.trim())));
                                     }
                                 }
                             }
@@ -658,12 +1032,12 @@ impl DockerManager {
                             if exit.status_code != 0 {
                                 tracing::error!("=== Install script FAILED with exit code {} ===", exit.status_code);
                                 if let Some(ref tx) = log_tx {
-                                    let _ = tx.send(format!("\x1b[31m[Install] Installation FAILED with exit code {}\x1b[0m", exit.status_code));
+                                    let _ = tx.send(LogLine::system(format!("\x1b[31m[Install] Installation FAILED with exit code {}\x1b[0m", exit.status_code)));
                                 }
                             } else {
                                 tracing::info!("=== Install script completed successfully ===");
                                 if let Some(ref tx) = log_tx {
-                                    let _ = tx.send("\x1b[32m[Install] Installation completed successfully!\x1b[0m".to_string());
+                                    let _ = tx.send(LogLine::system("\x1b[32m[Install] Installation completed successfully!\x1b[0m"));
                                 }
                             }
                             break;
@@ -697,6 +1071,118 @@ impl DockerManager {
         Ok(())
     }
 
+    /// Stops the container one step at a time: the console `stop_command` with `timeout_secs` to
+    /// exit, then SIGTERM with `term_timeout_secs`, then SIGKILL. Without a stop command SIGTERM
+    /// gets `timeout_secs`. Returns the step the container exited at, counted as forced when
+    /// Docker brought it back and it had to be stopped again.
+    pub async fn stop_escalating(
+        &self,
+        id: &str,
+        stop_command: Option<&str>,
+        timeout_secs: u64,
+        term_timeout_secs: u64,
+    ) -> anyhow::Result<StopStage> {
+        if !self.is_running(id).await? {
+            return Ok(StopStage::AlreadyStopped);
+        }
+
+        // A server that exits on its own is restarted by Docker under most policies; park the
+        // policy at "no" until the next start puts it back (see `set_restart_policy`)
+        if let Err(e) = self.set_restart_policy(id, "no").await {
+            tracing::warn!("Failed to clear the restart policy of {} before stopping it: {}", id, e);
+        }
+
+        let stage = self.run_stop_stages(id, stop_command, timeout_secs, term_timeout_secs).await?;
+
+        // Every stage ends with a stop through Docker: if it revived the server anyway, or has a
+        // restart queued, this cancels it and records the stop as manual
+        if self.is_stopped(id).await? {
+            return Ok(stage);
+        }
+        tracing::warn!("Container {} was restarted by Docker after stopping at {:?}, stopping it again", id, stage);
+        let timeout: i64 = term_timeout_secs.try_into().unwrap_or(10);
+        self.docker
+            .stop_container(id, Some(StopContainerOptions { t: timeout }))
+            .await?;
+
+        Ok(StopStage::Sigkill)
+    }
+
+    async fn run_stop_stages(
+        &self,
+        id: &str,
+        stop_command: Option<&str>,
+        timeout_secs: u64,
+        term_timeout_secs: u64,
+    ) -> anyhow::Result<StopStage> {
+        let mut term_timeout_secs = term_timeout_secs;
+
+        match stop_command {
+            Some(command) => match self.send_command(id, command).await {
+                Ok(()) => {
+                    if self.wait_for_exit(id, timeout_secs).await? {
+                        tracing::info!("Container {} exited after stop command", id);
+                        return Ok(StopStage::Command);
+                    }
+                    tracing::warn!("Container {} still running {}s after stop command, sending SIGTERM", id, timeout_secs);
+                }
+                Err(e) => tracing::warn!("Failed to send stop command to {}, sending SIGTERM: {}", id, e),
+            },
+            None => term_timeout_secs = timeout_secs,
+        }
+
+        if self.signal(id, "SIGTERM").await? && self.wait_for_exit(id, term_timeout_secs).await? {
+            tracing::info!("Container {} exited after SIGTERM", id);
+            return Ok(StopStage::Sigterm);
+        }
+
+        tracing::warn!("Container {} still running {}s after SIGTERM, sending SIGKILL", id, term_timeout_secs);
+        self.signal(id, "SIGKILL").await?;
+
+        if !self.wait_for_exit(id, SIGKILL_WAIT_SECS).await? {
+            anyhow::bail!("Container {} is still running after SIGKILL", id);
+        }
+
+        Ok(StopStage::Sigkill)
+    }
+
+    pub async fn is_running(&self, id: &str) -> anyhow::Result<bool> {
+        Ok(self.get_container(id).await?.state.eq_ignore_ascii_case("running"))
+    }
+
+    /// Exited for good, as opposed to running or waiting to be restarted.
+    async fn is_stopped(&self, id: &str) -> anyhow::Result<bool> {
+        let state = self.get_container(id).await?.state.to_ascii_lowercase();
+        Ok(matches!(state.as_str(), "exited" | "dead" | "created"))
+    }
+
+    /// Polls until the container is no longer running; `false` if it still is after `timeout_secs`.
+    async fn wait_for_exit(&self, id: &str, timeout_secs: u64) -> anyhow::Result<bool> {
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(timeout_secs);
+
+        loop {
+            if !self.is_running(id).await? {
+                return Ok(true);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Ok(false);
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Returns `false` when the container exited before the signal could be delivered.
+    async fn signal(&self, id: &str, signal: &str) -> anyhow::Result<bool> {
+        if let Err(e) = self.docker.kill_container(id, Some(KillContainerOptions { signal })).await {
+            if self.is_running(id).await? {
+                return Err(e.into());
+            }
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
     pub async fn remove_container(&self, id: &str) -> anyhow::Result<()> {
         self.docker
             .remove_container(
@@ -711,6 +1197,103 @@ impl DockerManager {
         Ok(())
     }
 
+    /// Moves a container's volume aside so the next create starts from an empty directory.
+    /// Returns the archive location; the old data is kept, never deleted.
+    pub async fn archive_volume(&self, container_name: &str) -> anyhow::Result<Option<String>> {
+        let base_path = crate::config::base_path().display();
+        let volume_path = format!("{}/volumes/{}", base_path, container_name);
+
+        if tokio::fs::metadata(&volume_path).await.is_err() {
+            return Ok(None);
+        }
+
+        let archive_dir = format!("{}/volume_archives", base_path);
+        tokio::fs::create_dir_all(&archive_dir).await?;
+
+        let archive_path = format!(
+            "{}/{}-{}",
+            archive_dir,
+            container_name,
+            chrono::Utc::now().format("%Y%m%d%H%M%S")
+        );
+        tokio::fs::rename(&volume_path, &archive_path).await?;
+
+        tracing::info!("Archived volume {} to {}", volume_path, archive_path);
+        Ok(Some(archive_path))
+    }
+
+    /// Removes the container volume directory entirely; a missing volume is not an error.
+    pub async fn remove_volume(&self, container_name: &str) -> anyhow::Result<()> {
+        let volume_path = volume_dir(container_name)?;
+
+        match tokio::fs::remove_dir_all(&volume_path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Deletes everything inside the container volume except `.machine-id`, keeping the directory itself.
+    /// Returns how many top-level entries were removed.
+    pub async fn wipe_volume(&self, container_name: &str) -> anyhow::Result<u64> {
+        let volume_path = volume_dir(container_name)?;
+
+        if tokio::fs::symlink_metadata(&volume_path).await.is_err() {
+            return Ok(0);
+        }
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<u64> {
+            let mut removed = 0;
+
+            for entry in std::fs::read_dir(&volume_path)? {
+                let entry = entry?;
+                if entry.file_name() == ".machine-id" {
+                    continue;
+                }
+
+                // Symlinks are removed as links, never followed out of the volume
+                let path = entry.path();
+                if entry.file_type()?.is_dir() {
+                    std::fs::remove_dir_all(&path)?;
+                } else {
+                    std::fs::remove_file(&path)?;
+                }
+                removed += 1;
+            }
+
+            Ok(removed)
+        })
+        .await?
+    }
+
+    /// Copies the contents of `source`'s volume into `target`'s, keeping modes and ownership.
+    /// `target` must be empty apart from its `.machine-id`. Returns how many entries were copied.
+    pub async fn copy_volume(&self, source: &str, target: &str) -> anyhow::Result<u64> {
+        let source_path = volume_dir(source)?;
+        let target_path = volume_dir(target)?;
+
+        tokio::task::spawn_blocking(move || -> anyhow::Result<u64> {
+            std::fs::create_dir_all(&target_path)?;
+            for entry in std::fs::read_dir(&target_path)? {
+                if entry?.file_name() != ".machine-id" {
+                    anyhow::bail!("Target volume is not empty");
+                }
+            }
+
+            let mut copied = 0;
+            for entry in std::fs::read_dir(&source_path)? {
+                let entry = entry?;
+                if entry.file_name() == ".machine-id" {
+                    continue;
+                }
+                copied += copy_entry(&entry.path(), &target_path.join(entry.file_name()))?;
+            }
+
+            Ok(copied)
+        })
+        .await?
+    }
+
     pub async fn cleanup_containers_by_name(&self, name: &str) -> anyhow::Result<u32> {
         let options = ListContainersOptions {
             all: true,
@@ -780,6 +1363,40 @@ impl DockerManager {
             .collect())
     }
 
+    pub async fn list_entries(&self) -> anyhow::Result<Vec<DockerEntry>> {
+        let options = ListContainersOptions::<String> {
+            all: true,
+            ..Default::default()
+        };
+
+        let containers = self.docker.list_containers(Some(options)).await?;
+
+        Ok(containers
+            .into_iter()
+            .map(|c| {
+                let labels = c.labels.unwrap_or_default();
+                let managed_as = match labels.get("raptor.managed").map(String::as_str) {
+                    Some("true") => labels.get("raptor.container").cloned(),
+                    _ => None,
+                };
+
+                DockerEntry {
+                    id: c.id.unwrap_or_default(),
+                    name: c
+                        .names
+                        .unwrap_or_default()
+                        .first()
+                        .cloned()
+                        .unwrap_or_default()
+                        .trim_start_matches('/')
+                        .to_string(),
+                    state: c.state.unwrap_or_default(),
+                    managed_as,
+                }
+            })
+            .collect())
+    }
+
     pub async fn get_container(&self, id: &str) -> anyhow::Result<ContainerInfo> {
         let info = self.docker.inspect_container(id, None).await?;
 
@@ -805,66 +1422,338 @@ impl DockerManager {
         })
     }
 
-    pub fn stream_logs(&self, id: &str, tx: broadcast::Sender<String>, since: Option<String>) {
+    /// Resource limits from the container's HostConfig, converted back to the units the
+    /// daemon configures them in.
+    pub async fn applied_resources(&self, id: &str) -> anyhow::Result<AppliedResources> {
+        const MB: i64 = 1024 * 1024;
+
+        let info = self.docker.inspect_container(id, None).await?;
+        let host = info.host_config.unwrap_or_default();
+
+        let memory = host.memory.unwrap_or(0);
+        let memory_swap = host.memory_swap.unwrap_or(0);
+        let swap_limit = if memory == 0 || memory_swap < 0 {
+            UNLIMITED
+        } else {
+            (memory_swap - memory).max(0) / MB
+        };
+
+        let cpu_limit = match (host.nano_cpus, host.cpu_quota, host.cpu_period) {
+            (Some(nano), _, _) if nano > 0 => nano as f64 / 1e9,
+            (_, Some(quota), Some(period)) if quota > 0 && period > 0 => quota as f64 / period as f64,
+            _ => 0.0,
+        };
+
+        Ok(AppliedResources {
+            memory_limit: if memory == 0 { UNLIMITED } else { memory / MB },
+            swap_limit,
+            cpu_limit,
+            io_weight: host.blkio_weight.map(i32::from).unwrap_or(0),
+            cpuset_cpus: host.cpuset_cpus.filter(|c| !c.is_empty()),
+        })
+    }
+
+    /// Image the container was created from, resolved to its ID and registry digest.
+    pub async fn runtime(&self, id: &str) -> anyhow::Result<ContainerRuntime> {
+        let info = self.docker.inspect_container(id, None).await?;
+        let state = info.state.unwrap_or_default();
+
+        // Docker reports the zero time for containers that never started or stopped
+        let timestamp = |t: Option<String>| t.filter(|t| !t.starts_with("0001-"));
+
+        Ok(ContainerRuntime {
+            started_at: timestamp(state.started_at),
+            finished_at: timestamp(state.finished_at),
+            exit_code: state.exit_code,
+            restart_count: info.restart_count.unwrap_or(0),
+        })
+    }
+
+    pub async fn container_image_version(&self, id: &str) -> anyhow::Result<ImageVersion> {
+        let info = self.docker.inspect_container(id, None).await?;
+        let image_id = info.image.unwrap_or_default();
+        let tag = info.config.and_then(|c| c.image).unwrap_or_default();
+
+        self.image_version(&image_id, &tag).await
+    }
+
+    /// Address of the container on its first Docker network with one.
+    pub async fn container_ip(&self, id: &str) -> anyhow::Result<Option<String>> {
+        let info = self.docker.inspect_container(id, None).await?;
+
+        Ok(info
+            .network_settings
+            .and_then(|n| n.networks)
+            .and_then(|networks| {
+                networks
+                    .into_values()
+                    .filter_map(|endpoint| endpoint.ip_address)
+                    .find(|ip| !ip.is_empty())
+            }))
+    }
+
+    /// Runs `command` with `sh -c` in the server's directory and reports whether it exited with 0.
+    pub async fn exec_succeeds(&self, id: &str, command: &str) -> anyhow::Result<bool> {
+        use bollard::exec::{CreateExecOptions, StartExecResults};
+
+        let exec = self.docker.create_exec(
+            id,
+            CreateExecOptions {
+                attach_stdout: Some(true),
+                attach_stderr: Some(true),
+                cmd: Some(vec!["sh", "-c", command]),
+                working_dir: Some("/home/container"),
+                ..Default::default()
+            }
+        ).await?;
+
+        if let StartExecResults::Attached { mut output, .. } = self.docker.start_exec(&exec.id, None).await? {
+            while output.next().await.is_some() {}
+        }
+
+        let inspect = self.docker.inspect_exec(&exec.id).await?;
+        Ok(inspect.exit_code == Some(0))
+    }
+
+    /// Starts bash (or sh where the image has no bash) on a TTY in the server's directory.
+    pub async fn open_shell(&self, id: &str, cols: u16, rows: u16) -> anyhow::Result<ShellSession> {
+        use bollard::exec::{CreateExecOptions, StartExecResults};
+
+        let exec = self.docker.create_exec(
+            id,
+            CreateExecOptions {
+                attach_stdin: Some(true),
+                attach_stdout: Some(true),
+                attach_stderr: Some(true),
+                tty: Some(true),
+                env: Some(vec!["TERM=xterm-256color"]),
+                cmd: Some(vec!["/bin/sh", "-c", "if [ -x /bin/bash ]; then exec /bin/bash; else exec /bin/sh; fi"]),
+                working_dir: Some("/home/container"),
+                ..Default::default()
+            }
+        ).await?;
+
+        let StartExecResults::Attached { output, input } = self.docker.start_exec(&exec.id, None).await? else {
+            anyhow::bail!("exec session started detached");
+        };
+
+        // The exec only has a TTY once started, so the size is applied afterwards
+        self.resize_shell(&exec.id, cols, rows).await?;
+
+        Ok(ShellSession { exec_id: exec.id, output, input })
+    }
+
+    pub async fn resize_shell(&self, exec_id: &str, cols: u16, rows: u16) -> anyhow::Result<()> {
+        use bollard::exec::ResizeExecOptions;
+
+        self.docker.resize_exec(exec_id, ResizeExecOptions { width: cols, height: rows }).await?;
+        Ok(())
+    }
+
+    /// `reference` may be an image ID or tag; `tag` picks the matching repository digest.
+    pub async fn image_version(&self, reference: &str, tag: &str) -> anyhow::Result<ImageVersion> {
+        let image = self.docker.inspect_image(reference).await?;
+        let digests = image.repo_digests.unwrap_or_default();
+
+        let repository = tag.split('@').next().unwrap_or(tag);
+        let repository = match repository.rsplit_once(':') {
+            Some((repo, tag)) if !tag.contains('/') => repo,
+            _ => repository,
+        };
+
+        let digest = digests
+            .iter()
+            .find(|d| d.split('@').next() == Some(repository))
+            .or_else(|| digests.first())
+            .cloned();
+
+        Ok(ImageVersion {
+            image_id: image.id.unwrap_or_else(|| reference.to_string()),
+            digest,
+        })
+    }
+
+    /// Pulls `image` from its registry. Callers asking for the same image while a pull is running
+    /// wait for it and reuse its result instead of starting their own.
+    pub async fn pull_image(&self, image: &str, registry_credentials: Option<&str>) -> anyhow::Result<()> {
+        let requested_at = std::time::Instant::now();
+        let lock = self.pull_locks.entry(image.to_string()).or_default().clone();
+        let mut last_pulled = lock.lock().await;
+
+        if last_pulled.is_some_and(|at| at >= requested_at) {
+            tracing::debug!("Image {} was pulled while waiting, skipping", image);
+            return Ok(());
+        }
+
+        let mut stream = self.docker.create_image(
+            Some(CreateImageOptions {
+                from_image: image,
+                ..Default::default()
+            }),
+            None,
+            crate::registry::credentials_for(image, registry_credentials).await,
+        );
+
+        while let Some(result) = stream.next().await {
+            let info = result?;
+            tracing::debug!("Pulling image: {:?}", info);
+        }
+
+        *last_pulled = Some(std::time::Instant::now());
+        Ok(())
+    }
+
+    /// Streams container lifecycle events until the Docker connection drops or `tx` is closed.
+    pub fn watch_events(&self, tx: tokio::sync::mpsc::UnboundedSender<ContainerEvent>) {
         let docker = self.docker.clone();
-        let id = id.to_string();
 
         tokio::spawn(async move {
-            tracing::info!("Starting log stream for container: {} (since: {:?})", id, since);
-
-            // Parse since parameter (e.g., "10m" for 10 minutes, "1h" for 1 hour)
-            let since_timestamp = since.and_then(|s| {
-                let s = s.trim();
-                if s.ends_with('m') {
-                    s[..s.len()-1].parse::<i64>().ok().map(|mins| {
-                        std::time::SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs() as i64 - (mins * 60)
-                    })
-                } else if s.ends_with('h') {
-                    s[..s.len()-1].parse::<i64>().ok().map(|hours| {
-                        std::time::SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs() as i64 - (hours * 3600)
-                    })
-                } else {
-                    None
+            let mut filters = HashMap::new();
+            filters.insert("type".to_string(), vec!["container".to_string()]);
+
+            let options = EventsOptions::<String> {
+                filters,
+                ..Default::default()
+            };
+
+            let mut stream = docker.events(Some(options));
+
+            while let Some(result) = stream.next().await {
+                let event = match result {
+                    Ok(e) => e,
+                    Err(e) => {
+                        tracing::warn!("Docker event stream ended: {}", e);
+                        break;
+                    }
+                };
+
+                let Some(actor) = event.actor else {
+                    continue;
+                };
+                let (Some(action), Some(docker_id)) = (event.action, actor.id) else {
+                    continue;
+                };
+                let exit_code = actor
+                    .attributes
+                    .as_ref()
+                    .and_then(|attrs| attrs.get("exitCode"))
+                    .and_then(|code| code.parse().ok());
+
+                if tx.send(ContainerEvent { docker_id, action, exit_code }).is_err() {
+                    break;
                 }
-            });
+            }
+        });
+    }
+
+    pub fn follow_logs(&self, id: &str, tx: tokio::sync::mpsc::UnboundedSender<String>) {
+        let docker = self.docker.clone();
+        let id = id.to_string();
 
-            let historical_options = LogsOptions::<String> {
-                follow: false,
+        tokio::spawn(async move {
+            let options = LogsOptions::<String> {
+                follow: true,
                 stdout: true,
                 stderr: true,
-                tail: if since_timestamp.is_some() { "all".to_string() } else { "500".to_string() },
+                tail: "0".to_string(),
                 timestamps: false,
-                since: since_timestamp.unwrap_or(0),
                 ..Default::default()
             };
 
+            let mut stream = docker.logs(&id, Some(options));
+
+            loop {
+                // Stop as soon as the receiver goes away, even if the container is quiet
+                let result = tokio::select! {
+                    _ = tx.closed() => break,
+                    next = stream.next() => match next {
+                        Some(result) => result,
+                        None => break,
+                    },
+                };
+
+                let text = match result {
+                    Ok(LogOutput::StdOut { message }) | Ok(LogOutput::Console { message }) => {
+                        String::from_utf8_lossy(&message).trim_end().to_string()
+                    }
+                    Ok(LogOutput::StdErr { message }) => {
+                        format!("\x1b[31m{}\x1b[0m", String::from_utf8_lossy(&message).trim_end())
+                    }
+                    Ok(_) => continue,
+                    Err(e) => {
+                        tracing::debug!("Log follow for {} ended: {}", id, e);
+                        break;
+                    }
+                };
+
+                if text.is_empty() {
+                    continue;
+                }
+
+                if tx.send(text).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Everything Docker kept for the container, or everything since `since`, as plain text lines.
+    pub fn export_logs(&self, id: &str, since: Option<&str>) -> tokio::sync::mpsc::Receiver<String> {
+        let docker = self.docker.clone();
+        let id = id.to_string();
+        let options = LogsOptions::<String> {
+            tail: "all".to_string(),
+            ..history_options(since, None)
+        };
+        let (tx, rx) = tokio::sync::mpsc::channel(256);
+
+        tokio::spawn(async move {
+            let mut stream = docker.logs(&id, Some(options));
+
+            while let Some(result) = stream.next().await {
+                let (stream, message) = match result {
+                    Ok(LogOutput::StdOut { message }) | Ok(LogOutput::Console { message }) => (LogStream::Stdout, message),
+                    Ok(LogOutput::StdErr { message }) => (LogStream::Stderr, message),
+                    Ok(_) => continue,
+                    Err(e) => {
+                        tracing::warn!("Log export for {} ended early: {}", id, e);
+                        break;
+                    }
+                };
+
+                let line = LogLine::from_docker(stream, String::from_utf8_lossy(&message).trim_end());
+                if tx.send(line.to_plain_text()).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        rx
+    }
+
+    pub fn stream_logs(&self, id: &str, tx: broadcast::Sender<LogLine>, since: Option<String>, tail: Option<usize>) {
+        let docker = self.docker.clone();
+        let id = id.to_string();
+
+        tokio::spawn(async move {
+            tracing::info!("Starting log stream for container: {} (since: {:?}, tail: {:?})", id, since, tail);
+
+            let historical_options = history_options(since.as_deref(), tail);
+
             let mut historical_stream = docker.logs(&id, Some(historical_options));
             let mut log_count = 0;
 
             while let Some(result) = historical_stream.next().await {
                 match result {
                     Ok(log) => {
-                        let text = match log {
-                            LogOutput::StdOut { message } => {
-                                String::from_utf8_lossy(&message).trim_end().to_string()
-                            }
-                            LogOutput::StdErr { message } => {
-                                format!("\x1b[31m{}\x1b[0m", String::from_utf8_lossy(&message).trim_end())
-                            }
-
-                            LogOutput::Console { message } => {
-                                String::from_utf8_lossy(&message).trim_end().to_string()
-                            }
+                        let (stream, message) = match log {
+                            LogOutput::StdOut { message } | LogOutput::Console { message } => (LogStream::Stdout, message),
+                            LogOutput::StdErr { message } => (LogStream::Stderr, message),
                             _ => continue,
                         };
 
-                        if text.is_empty() {
+                        let text = LogLine::from_docker(stream, String::from_utf8_lossy(&message).trim_end());
+                        if text.line.is_empty() {
                             continue;
                         }
 
@@ -874,7 +1763,7 @@ impl DockerManager {
                         }
                     }
                     Err(e) => {
-                        let _ = tx.send(format!("\x1b[31m[Error] Failed to get logs: {}\x1b[0m", e));
+                        let _ = tx.send(LogLine::system(format!("\x1b[31m[Error] Failed to get logs: {}\x1b[0m", e)));
                         return;
                     }
                 }
@@ -887,7 +1776,7 @@ impl DockerManager {
                 stdout: true,
                 stderr: true,
                 tail: "0".to_string(),
-                timestamps: false,
+                timestamps: true,
                 ..Default::default()
             };
 
@@ -896,21 +1785,14 @@ impl DockerManager {
             while let Some(result) = stream.next().await {
                 match result {
                     Ok(log) => {
-                        let text = match log {
-                            LogOutput::StdOut { message } => {
-                                String::from_utf8_lossy(&message).trim_end().to_string()
-                            }
-                            LogOutput::StdErr { message } => {
-                                format!("\x1b[31m{}\x1b[0m", String::from_utf8_lossy(&message).trim_end())
-                            }
-
-                            LogOutput::Console { message } => {
-                                String::from_utf8_lossy(&message).trim_end().to_string()
-                            }
+                        let (stream, message) = match log {
+                            LogOutput::StdOut { message } | LogOutput::Console { message } => (LogStream::Stdout, message),
+                            LogOutput::StdErr { message } => (LogStream::Stderr, message),
                             _ => continue,
                         };
 
-                        if text.is_empty() {
+                        let text = LogLine::from_docker(stream, String::from_utf8_lossy(&message).trim_end());
+                        if text.line.is_empty() {
                             continue;
                         }
 
@@ -920,7 +1802,7 @@ impl DockerManager {
                     }
                     Err(e) => {
                         tracing::error!("Log stream error: {}", e);
-                        let _ = tx.send(format!("\x1b[33m[System] Container stopped or log stream ended\x1b[0m"));
+                        let _ = tx.send(LogLine::system("\x1b[33m[System] Container stopped or log stream ended\x1b[0m"));
                         break;
                     }
                 }
@@ -928,6 +1810,27 @@ impl DockerManager {
         });
     }
 
+    /// Past output of the container without following it.
+    pub async fn recent_logs(&self, id: &str, since: Option<&str>, tail: Option<usize>) -> anyhow::Result<Vec<LogLine>> {
+        let mut stream = self.docker.logs(id, Some(history_options(since, tail)));
+        let mut lines = Vec::new();
+
+        while let Some(result) = stream.next().await {
+            let (stream, message) = match result? {
+                LogOutput::StdOut { message } | LogOutput::Console { message } => (LogStream::Stdout, message),
+                LogOutput::StdErr { message } => (LogStream::Stderr, message),
+                _ => continue,
+            };
+
+            let line = LogLine::from_docker(stream, String::from_utf8_lossy(&message).trim_end());
+            if !line.line.is_empty() {
+                lines.push(line);
+            }
+        }
+
+        Ok(lines)
+    }
+
     pub async fn get_container_stats(&self, id: &str) -> anyhow::Result<ContainerStats> {
         let options = StatsOptions {
             stream: false,
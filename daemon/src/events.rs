@@ -0,0 +1,223 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::callback;
+use crate::docker::ContainerEvent;
+use crate::models::{AppState, ContainerStatus};
+use crate::readiness;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// A "die" this soon after a "kill" was requested (stop, kill, restart, recreate), not a crash.
+const KILL_GRACE: Duration = Duration::from_secs(60);
+
+/// Keeps the cached status of managed containers in sync with Docker events.
+pub fn spawn(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        loop {
+            let (tx, mut rx) = mpsc::unbounded_channel::<ContainerEvent>();
+            state.docker.watch_events(tx);
+
+            // Events may have been missed while disconnected, so refresh from inspect first
+            resync_statuses(&state).await;
+
+            let mut recent_kills: HashMap<String, Instant> = HashMap::new();
+            let mut failures: HashMap<String, FailureHistory> = HashMap::new();
+
+            while let Some(event) = rx.recv().await {
+                apply_event(&state, &event);
+                report_transition(&state, &event, &mut recent_kills, &mut failures);
+            }
+
+            tracing::warn!("Docker event watcher disconnected, reconnecting in {:?}", RECONNECT_DELAY);
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+fn apply_event(state: &AppState, event: &ContainerEvent) {
+    let destroyed = event.action == "destroy";
+    let status = ContainerStatus::from_event_action(&event.action);
+
+    if !destroyed && status.is_none() {
+        return;
+    }
+
+    for mut entry in state.containers.iter_mut() {
+        if entry.docker_id != event.docker_id {
+            continue;
+        }
+
+        entry.status = if destroyed { None } else { status };
+        tracing::debug!(
+            "Container {} status updated from event '{}': {:?}",
+            entry.name, event.action, entry.status
+        );
+    }
+}
+
+/// Recent unexpected exits and OOM kills of one Docker container.
+#[derive(Default)]
+struct FailureHistory {
+    crashes: VecDeque<Instant>,
+    ooms: VecDeque<Instant>,
+}
+
+/// Reports start/stop/die/oom to the panel, starts readiness checks, and flags a "die"
+/// nobody asked for as a crash.
+fn report_transition(
+    state: &Arc<AppState>,
+    event: &ContainerEvent,
+    recent_kills: &mut HashMap<String, Instant>,
+    failures: &mut HashMap<String, FailureHistory>,
+) {
+    if event.action == "kill" {
+        recent_kills.retain(|_, at| at.elapsed() < KILL_GRACE);
+        recent_kills.insert(event.docker_id.clone(), Instant::now());
+        return;
+    }
+
+    if !matches!(event.action.as_str(), "start" | "die" | "oom") {
+        return;
+    }
+
+    let name = state
+        .containers
+        .iter()
+        .find(|entry| entry.docker_id == event.docker_id)
+        .map(|entry| entry.name.clone());
+
+    let Some(name) = name else {
+        return;
+    };
+
+    match event.action.as_str() {
+        "start" => {
+            // Covers starts the daemon didn't drive itself, e.g. Docker's restart policy
+            state.log_store.ensure_persisting(&state.docker, &name, &event.docker_id);
+            state.panel.send(&name, callback::CONTAINER_STATE, serde_json::json!({ "transition": "started" }));
+            readiness::spawn(state, &name);
+        }
+        "oom" => {
+            tracing::warn!("Container {} ran out of memory", name);
+            state.panel.send(&name, callback::CONTAINER_STATE, serde_json::json!({ "transition": "oom" }));
+            record_failure(state, &name, &event.docker_id, failures, true);
+        }
+        _ => {
+            readiness::cancel(state, &name);
+
+            let killed = recent_kills
+                .remove(&event.docker_id)
+                .map(|at| at.elapsed() < KILL_GRACE)
+                .unwrap_or(false);
+
+            let exit_code = event.exit_code.unwrap_or(0);
+            let crashed = !killed && exit_code != 0;
+
+            state.panel.send(&name, callback::CONTAINER_STATE, serde_json::json!({
+                "transition": if crashed { "died" } else { "stopped" },
+                "exitCode": exit_code,
+            }));
+
+            if crashed {
+                tracing::warn!("Container {} exited unexpectedly with code {}", name, exit_code);
+                state.panel.send(&name, callback::CONTAINER_CRASHED, serde_json::json!({ "exitCode": exit_code }));
+                record_failure(state, &name, &event.docker_id, failures, false);
+            }
+        }
+    }
+}
+
+/// Stops a container that keeps crashing or running out of memory, so the restart policy
+/// can't thrash the node, and reports it to the panel as suspended.
+fn record_failure(
+    state: &Arc<AppState>,
+    name: &str,
+    docker_id: &str,
+    failures: &mut HashMap<String, FailureHistory>,
+    oom: bool,
+) {
+    let config = state.runtime().crash_loop;
+    let threshold = if oom { config.oom_threshold } else { config.threshold };
+    if threshold == 0 {
+        return;
+    }
+
+    failures.retain(|_, history| history.crashes.iter().chain(&history.ooms).any(|at| at.elapsed() < config.window));
+
+    let history = failures.entry(docker_id.to_string()).or_default();
+    let recent = if oom { &mut history.ooms } else { &mut history.crashes };
+
+    recent.push_back(Instant::now());
+    while recent.front().map(|at| at.elapsed() >= config.window).unwrap_or(false) {
+        recent.pop_front();
+    }
+
+    let count = recent.len();
+    if count < threshold {
+        return;
+    }
+
+    failures.remove(docker_id);
+
+    let newly_flagged = state
+        .containers
+        .get_mut(name)
+        .map(|mut entry| !std::mem::replace(&mut entry.crash_looping, true))
+        .unwrap_or(false);
+    if !newly_flagged {
+        return;
+    }
+
+    let reason = format!(
+        "it {} {} times within {} seconds and was stopped",
+        if oom { "ran out of memory" } else { "crashed" },
+        count,
+        config.window.as_secs()
+    );
+    tracing::warn!("Container {} is crash looping: {}", name, reason);
+
+    let state = state.clone();
+    let name = name.to_string();
+    let docker_id = docker_id.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = state.docker.stop_container(&docker_id).await {
+            tracing::error!("Failed to stop crash looping container {}: {}", name, e);
+        }
+
+        crate::handlers::save_container_state(&state).await;
+        state.panel.send(&name, callback::CONTAINER_SUSPENDED, serde_json::json!({
+            "reason": reason,
+            "crashLooping": true,
+        }));
+    });
+}
+
+async fn resync_statuses(state: &AppState) {
+    let managed: Vec<(String, String)> = state
+        .containers
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.docker_id.clone()))
+        .collect();
+
+    for (name, docker_id) in managed {
+        let status = match state.docker.get_container(&docker_id).await {
+            Ok(info) => ContainerStatus::from_docker_state(&info.state),
+            Err(_) => None,
+        };
+
+        let pending = readiness::is_pending(state, &name);
+
+        if let Some(mut entry) = state.containers.get_mut(&name) {
+            if entry.docker_id == docker_id {
+                entry.status = status;
+                // Servers already up when the daemon (re)connects started long enough ago
+                if !pending {
+                    entry.ready = status.is_some_and(|s| s.is_running());
+                }
+            }
+        }
+    }
+}
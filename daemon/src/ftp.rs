@@ -4,6 +4,7 @@ use std::time::SystemTime;
 use std::collections::HashMap;
 use async_trait::async_trait;
 use libunftp::auth::{AuthenticationError, Authenticator, Credentials, UserDetail};
+use libunftp::options::PassiveHost;
 use libunftp::storage::{StorageBackend, Fileinfo, Metadata, Result as StorageResult, Error as StorageError, ErrorKind as StorageErrorKind};
 use tokio::io::AsyncSeekExt;
 
@@ -22,6 +23,7 @@ pub struct FtpCredentialsStore {
 pub struct RaptorUser {
     pub username: String,
     pub home_path: PathBuf,
+    pub read_only: bool,
 }
 
 impl UserDetail for RaptorUser {
@@ -55,13 +57,16 @@ fn get_ftp_credentials_path() -> PathBuf {
 pub struct FtpServerState {
     pub users: Arc<dashmap::DashMap<String, FtpUser>>,
     pub base_path: PathBuf,
+    /// Containers whose volume must not be written to.
+    pub read_only: dashmap::DashSet<String>,
 }
 
 impl FtpServerState {
-    pub fn new(base_path: &str) -> Self {
+    pub fn new(base_path: &std::path::Path) -> Self {
         let state = Self {
             users: Arc::new(dashmap::DashMap::new()),
-            base_path: PathBuf::from(base_path),
+            base_path: base_path.to_path_buf(),
+            read_only: dashmap::DashSet::new(),
         };
 
         state.load_all_credentials();
@@ -241,6 +246,15 @@ impl FtpServerState {
         }
     }
 
+    /// Takes effect for FTP sessions opened afterwards.
+    pub fn set_read_only(&self, container_id: &str, read_only: bool) {
+        if read_only {
+            self.read_only.insert(container_id.to_string());
+        } else {
+            self.read_only.remove(container_id);
+        }
+    }
+
     pub fn remove_container_users(&self, container_id: &str) {
         let users_to_remove: Vec<String> = self.users
             .iter()
@@ -299,6 +313,7 @@ impl Authenticator<RaptorUser> for RaptorAuthenticator {
             tracing::info!("FTP auth success for user: {} (home: {:?})",
                 username, user.home_path);
             return Ok(RaptorUser {
+                read_only: self.state.read_only.contains(&user.container_id),
                 username: user.username,
                 home_path: user.home_path,
             });
@@ -387,6 +402,13 @@ impl JailedFilesystem {
 
         user.home_path.join(clean_path)
     }
+
+    fn ensure_writable(user: &RaptorUser) -> StorageResult<()> {
+        if user.read_only {
+            return Err(StorageError::from(StorageErrorKind::PermissionDenied));
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -480,6 +502,7 @@ impl StorageBackend<RaptorUser> for JailedFilesystem {
         path: P,
         start_pos: u64,
     ) -> StorageResult<u64> {
+        Self::ensure_writable(user)?;
         let full_path = self.resolve_path(user, path.as_ref());
         tracing::debug!("FTP put: {:?} -> {:?}", path.as_ref(), full_path);
 
@@ -517,6 +540,7 @@ impl StorageBackend<RaptorUser> for JailedFilesystem {
         user: &RaptorUser,
         path: P,
     ) -> StorageResult<()> {
+        Self::ensure_writable(user)?;
         let full_path = self.resolve_path(user, path.as_ref());
         tracing::debug!("FTP del: {:?} -> {:?}", path.as_ref(), full_path);
 
@@ -530,6 +554,7 @@ impl StorageBackend<RaptorUser> for JailedFilesystem {
         user: &RaptorUser,
         path: P,
     ) -> StorageResult<()> {
+        Self::ensure_writable(user)?;
         let full_path = self.resolve_path(user, path.as_ref());
         tracing::debug!("FTP mkd: {:?} -> {:?}", path.as_ref(), full_path);
 
@@ -544,6 +569,7 @@ impl StorageBackend<RaptorUser> for JailedFilesystem {
         from: P,
         to: P,
     ) -> StorageResult<()> {
+        Self::ensure_writable(user)?;
         let from_path = self.resolve_path(user, from.as_ref());
         let to_path = self.resolve_path(user, to.as_ref());
         tracing::debug!("FTP rename: {:?} -> {:?}", from_path, to_path);
@@ -558,6 +584,7 @@ impl StorageBackend<RaptorUser> for JailedFilesystem {
         user: &RaptorUser,
         path: P,
     ) -> StorageResult<()> {
+        Self::ensure_writable(user)?;
         let full_path = self.resolve_path(user, path.as_ref());
         tracing::debug!("FTP rmd: {:?} -> {:?}", path.as_ref(), full_path);
 
@@ -589,9 +616,71 @@ impl StorageBackend<RaptorUser> for JailedFilesystem {
     }
 }
 
-pub async fn start_ftp_server(state: Arc<FtpServerState>, addr: &str, port: u16) -> anyhow::Result<()> {
+const DEFAULT_PASSIVE_PORTS: std::ops::RangeInclusive<u16> = 50000..=50100;
+
+#[derive(Debug, Clone)]
+pub struct FtpPassiveConfig {
+    pub ports: std::ops::RangeInclusive<u16>,
+    /// Address advertised in PASV replies; needed when the daemon sits behind NAT.
+    pub host: Option<String>,
+}
+
+impl FtpPassiveConfig {
+    pub fn from_env() -> Self {
+        let ports = std::env::var("FTP_PASSIVE_PORTS")
+            .ok()
+            .map(|v| match parse_port_range(&v) {
+                Some(range) => range,
+                None => {
+                    tracing::warn!("Invalid FTP_PASSIVE_PORTS '{}', using {:?}", v, DEFAULT_PASSIVE_PORTS);
+                    DEFAULT_PASSIVE_PORTS
+                }
+            })
+            .unwrap_or(DEFAULT_PASSIVE_PORTS);
+
+        Self {
+            ports,
+            host: std::env::var("FTP_PASSIVE_HOST").ok().filter(|h| !h.trim().is_empty()),
+        }
+    }
+
+    fn passive_host(&self) -> PassiveHost {
+        match self.host.as_deref().map(str::trim) {
+            Some(host) => match host.parse::<std::net::Ipv4Addr>() {
+                Ok(ip) => PassiveHost::Ip(ip),
+                Err(_) => PassiveHost::Dns(host.to_string()),
+            },
+            None => PassiveHost::FromConnection,
+        }
+    }
+}
+
+fn parse_port_range(value: &str) -> Option<std::ops::RangeInclusive<u16>> {
+    let (start, end) = value.split_once('-')?;
+    let start: u16 = start.trim().parse().ok()?;
+    let end: u16 = end.trim().parse().ok()?;
+
+    if start == 0 || start > end {
+        return None;
+    }
+
+    Some(start..=end)
+}
+
+pub async fn start_ftp_server(
+    state: Arc<FtpServerState>,
+    addr: &str,
+    port: u16,
+    passive: FtpPassiveConfig,
+) -> anyhow::Result<()> {
     let bind_addr = format!("{}:{}", addr, port);
     tracing::info!("Starting FTP server on {} (with per-user jailing)", bind_addr);
+    tracing::info!(
+        "FTP passive ports {}-{}, advertised host: {}",
+        passive.ports.start(),
+        passive.ports.end(),
+        passive.host.as_deref().unwrap_or("connection address")
+    );
 
     let volumes_path = state.base_path.join("volumes");
     std::fs::create_dir_all(&volumes_path)?;
@@ -604,7 +693,8 @@ pub async fn start_ftp_server(state: Arc<FtpServerState>, addr: &str, port: u16)
         authenticator,
     )
     .greeting("Welcome to Raptor FTP Server")
-    .passive_ports(50000..=50100)
+    .passive_ports(passive.ports.clone())
+    .passive_host(passive.passive_host())
     .build()
     .unwrap();
 
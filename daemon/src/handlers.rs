@@ -12,12 +12,20 @@ use futures_util::{SinkExt, StreamExt};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
 use tokio::sync::broadcast;
 use serde::{Deserialize, Serialize};
 
+use crate::backups::{self, BackupInfo};
+use crate::callback;
+use crate::log_format::{LogFormat, LogLine};
+use crate::permissions;
+use crate::docker::{write_console_line, ConsoleInput, DEFAULT_INSTALL_TIMEOUT_SECS};
+use crate::keepalive::Keepalive;
+use crate::system_stats::SystemStatsHub;
 use crate::models::{
-    AppState, AssignAllocationRequest, AvailableAllocation, CreateContainerRequest,
-    ManagedContainer,
+    AppState, AssignAllocationRequest, AvailableAllocation, ContainerResourcesResponse, ContainerRuntime,
+    ContainerStatus, CreateContainerRequest, ImageVersion, ManagedContainer, StopStage,
 };
 use crate::ftp::{create_ftp_access, FtpCredentials};
 use crate::database_manager::{
@@ -127,9 +135,22 @@ fn get_docker_id(state: &AppState, id: &str) -> String {
         .unwrap_or_else(|| id.to_string())
 }
 
+/// A host binding for an allocation. Docker wants IPv6 host IPs unbracketed and reports them
+/// back in canonical form, which `is_reusable` compares against.
+fn host_binding(ip: &str, port: i32) -> PortBinding {
+    let host_ip = raptor_common::net::normalize_ip(ip).unwrap_or_else(|_| ip.trim().to_string());
+
+    PortBinding {
+        host_ip: Some(host_ip),
+        host_port: Some(port.to_string()),
+    }
+}
+
 fn update_container_docker_id(state: &AppState, name: &str, new_docker_id: String) {
     if let Some(mut entry) = state.containers.get_mut(name) {
         entry.docker_id = new_docker_id;
+        entry.status = None;
+        entry.crash_looping = false;
     }
 
 }
@@ -148,38 +169,194 @@ fn replace_startup_placeholders(
     environment: &std::collections::HashMap<String, String>,
     resources: &crate::models::ContainerResources,
 ) -> String {
-    let mut result = script.to_string();
-
-    for (key, value) in environment {
-        result = result.replace(&format!("{{{{{}}}}}", key), value);
-    }
-
     let server_memory = if resources.server_memory > 0 {
         resources.server_memory
-    } else {
+    } else if resources.memory_limit > 0 {
         resources.memory_limit
+    } else {
+        // Unlimited memory: size the server for the whole host
+        let mut sys = sysinfo::System::new();
+        sys.refresh_memory();
+        (sys.total_memory() / 1024 / 1024) as i64
     };
 
-    result = result.replace("{{SERVER_MEMORY}}", &server_memory.to_string());
+    raptor_common::startup::replace_startup_placeholders(script, environment, Some(server_memory))
+}
 
-    let memory_str = server_memory.to_string();
+/// Resolves the startup command, refusing one that would hand bash literal `{{VARIABLE}}` placeholders.
+fn resolve_startup_script(
+    script: Option<&str>,
+    environment: &std::collections::HashMap<String, String>,
+    resources: &crate::models::ContainerResources,
+) -> Result<Option<String>, (StatusCode, String)> {
+    let Some(script) = script else {
+        return Ok(None);
+    };
 
-    let xmx_pattern = regex::Regex::new(r"-Xmx\d+M").ok();
+    let resolved = replace_startup_placeholders(script, environment, resources);
+    let missing = raptor_common::startup::unresolved_placeholders(&resolved);
+    if !missing.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Startup command references undefined variables: {}", missing.join(", ")),
+        ));
+    }
 
-    if let Some(re) = xmx_pattern {
-        if !result.contains(&format!("-Xmx{}M", memory_str)) {
-            result = re.replace(&result, format!("-Xmx{}M", memory_str).as_str()).to_string();
-        }
+    Ok(Some(resolved))
+}
+
+/// Writes the flake's variables file into the volume. The file is replaced by a rename so a
+/// symlink left at its path is never followed, and its directory must resolve inside the volume.
+async fn write_variables_file(container: &ManagedContainer) -> Result<(), (StatusCode, String)> {
+    let Some(file) = &container.variables_file else {
+        return Ok(());
+    };
+
+    let contents = file.render(&container.environment).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let internal = |e: std::io::Error| {
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write variables file {}: {}", file.path, e))
+    };
+
+    let volume_path = crate::config::base_path().join("volumes").join(&container.name);
+    let full_path = volume_path.join(file.path.trim_start_matches('/'));
+    let (Some(parent), Some(file_name)) = (full_path.parent(), full_path.file_name()) else {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid variables file path '{}'", file.path)));
+    };
+
+    tokio::fs::create_dir_all(parent).await.map_err(internal)?;
+    let real_volume = tokio::fs::canonicalize(&volume_path).await.map_err(internal)?;
+    let real_parent = tokio::fs::canonicalize(parent).await.map_err(internal)?;
+    if !real_parent.starts_with(&real_volume) {
+        return Err((StatusCode::FORBIDDEN, format!("Variables file path '{}' leaves the volume", file.path)));
+    }
+
+    let target = real_parent.join(file_name);
+    let temp = real_parent.join(format!(".{}.raptor-tmp", file_name.to_string_lossy()));
+    let _ = tokio::fs::remove_file(&temp).await;
+
+    let mut out = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp)
+        .await
+        .map_err(internal)?;
+    out.write_all(contents.as_bytes()).await.map_err(internal)?;
+    out.flush().await.map_err(internal)?;
+    drop(out);
+
+    let (uid, gid) = permissions::container_owner();
+    if let Err(e) = std::os::unix::fs::lchown(&temp, Some(uid), Some(gid)) {
+        tracing::warn!("Failed to chown variables file of {}: {}", container.name, e);
+    }
+
+    tokio::fs::rename(&temp, &target).await.map_err(internal)?;
+
+    tracing::info!("Wrote variables file {} for {}", file.path, container.name);
+    Ok(())
+}
+
+pub async fn require_docker(
+    State(state): State<Arc<AppState>>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    if !state.docker.is_available() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Docker is not reachable on this node yet, retrying in the background",
+        ).into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Runs each request in a span carrying the caller's `X-Request-Id` (the panel forwards its own,
+/// otherwise a fresh one), so daemon logs can be matched to the API request that caused them.
+pub async fn request_id(
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    use tracing::Instrument;
+
+    let id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|h| h.to_str().ok())
+        .filter(|h| !h.is_empty() && h.len() <= 128)
+        .map(|h| h.to_string())
+        .unwrap_or_else(|| format!("{:032x}", rand::random::<u128>()));
+
+    let span = tracing::info_span!("request", request_id = %id, method = %req.method(), path = %req.uri().path());
+    let started = std::time::Instant::now();
+
+    let mut response = next.run(req).instrument(span.clone()).await;
+
+    span.in_scope(|| {
+        tracing::info!(
+            status = response.status().as_u16(),
+            duration_ms = started.elapsed().as_millis() as u64,
+            "request finished"
+        )
+    });
+
+    if let Ok(value) = axum::http::HeaderValue::from_str(&id) {
+        response.headers_mut().insert("x-request-id", value);
+    }
+
+    response
+}
+
+pub async fn health(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let docker = state.docker.is_available();
+
+    Json(serde_json::json!({
+        "status": if docker { "ok" } else { "degraded" },
+        "docker": docker,
+    }))
+}
+
+/// POST /reload - same as SIGHUP: re-reads the hot-reloadable settings from `.env`.
+pub async fn reload_config(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    let changed = state
+        .reload_config()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to reload config: {}", e)))?;
+    tracing::info!("Reloaded config via API, changed: {:?}", changed);
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "changed": changed,
+        "reloadable": crate::config::RELOADABLE_ENV,
+    })))
+}
+
+pub async fn reconcile_state(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<crate::reconcile::ReconcileReport>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
     }
 
-    result
+    let report = crate::reconcile::reconcile(&state)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to reconcile: {}", e)))?;
+
+    Ok(Json(report))
 }
 
 fn verify_api_key(headers: &HeaderMap, state: &AppState) -> bool {
     headers
         .get("X-API-Key")
         .and_then(|v| v.to_str().ok())
-        .map(|k| k == state.api_key)
+        .map(|k| raptor_common::secrets::secrets_match(k, &state.api_key))
         .unwrap_or(false)
 }
 
@@ -209,6 +386,31 @@ pub async fn create_container(
         return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
     }
 
+    if let Some(cpuset) = req.cpuset_cpus.as_deref() {
+        if !raptor_common::resources::is_valid_cpuset(cpuset) {
+            return Err((StatusCode::BAD_REQUEST, format!("Invalid cpuset '{}'", cpuset)));
+        }
+    }
+
+    if let Some(readiness) = &req.readiness {
+        readiness.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    if let Some(file) = &req.variables_file {
+        file.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    if let Some(alerts) = &req.resource_alerts {
+        alerts.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    crate::docker::asset_binds(&req.asset_mounts).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    raptor_common::mounts::validate_tmpfs_mounts(&req.tmpfs).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    for ip in req.allocations.iter().map(|a| &a.ip).chain(req.allocation.iter().map(|a| &a.ip)) {
+        raptor_common::net::normalize_ip(ip).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
     tracing::info!("Creating container {} with {} allocations", req.name, req.allocations.len());
 
     let mut port_bindings: HashMap<String, Vec<PortBinding>> = HashMap::new();
@@ -218,10 +420,7 @@ pub async fn create_container(
         bindings
             .entry(key)
             .or_insert_with(Vec::new)
-            .push(PortBinding {
-                host_ip: Some(ip.to_string()),
-                host_port: Some(port.to_string()),
-            });
+            .push(host_binding(ip, port));
     };
 
     for alloc in &req.allocations {
@@ -236,10 +435,7 @@ pub async fn create_container(
             port_bindings
                 .entry(key)
                 .or_insert_with(Vec::new)
-                .push(PortBinding {
-                    host_ip: Some(alloc.ip.clone()),
-                    host_port: Some(alloc.port.to_string()),
-                });
+                .push(host_binding(&alloc.ip, alloc.port));
         }
     }
 
@@ -250,10 +446,7 @@ pub async fn create_container(
             port_bindings
                 .entry(key)
                 .or_insert_with(Vec::new)
-                .push(PortBinding {
-                    host_ip: Some(alloc.ip.clone()),
-                    host_port: Some(alloc.port.to_string()),
-                });
+                .push(host_binding(&alloc.ip, alloc.port));
         }
     }
 
@@ -270,7 +463,12 @@ pub async fn create_container(
 
     tracing::info!("Total port bindings: {:?}", port_bindings);
 
-    let server_memory = req.server_memory.unwrap_or(req.memory_limit);
+    if let Some(server_memory) = req.server_memory {
+        raptor_common::resources::validate_server_memory(server_memory, req.memory_limit)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    let server_memory = req.server_memory
+        .unwrap_or_else(|| raptor_common::resources::default_server_memory(req.memory_limit));
 
     let resources = crate::models::ContainerResources {
         memory_limit: req.memory_limit,
@@ -279,27 +477,38 @@ pub async fn create_container(
         disk_limit: req.disk_limit,
         swap_limit: req.swap_limit,
         io_weight: req.io_weight,
+        cpuset_cpus: req.cpuset_cpus.clone(),
+        disable_swap: req.disable_swap,
+        tmpfs: req.tmpfs.clone(),
+        read_only: req.read_only,
     };
 
+    let mut environment = req.environment.clone();
+    environment.insert("SERVER_MEMORY".to_string(), server_memory.to_string());
+
+    let startup_script = resolve_startup_script(req.startup_script.as_deref(), &environment, &resources)?;
+
     let docker_id = state
         .docker
         .create_container_with_resources(
             &req.name,
             &req.image,
-            req.startup_script.as_deref(),
+            startup_script.as_deref(),
             if port_bindings.is_empty() { None } else { Some(port_bindings) },
             &resources,
             &req.restart_policy,
             req.tty,
+            &environment,
+            &req.labels,
+            req.isolated,
+            &req.asset_mounts,
+            req.registry_credentials.as_deref(),
         )
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     let has_install_script = req.install_script.is_some();
 
-    let mut environment = req.environment.clone();
-    environment.insert("SERVER_MEMORY".to_string(), server_memory.to_string());
-
     let managed = ManagedContainer {
         name: req.name.clone(),
         docker_id: docker_id.clone(),
@@ -310,12 +519,25 @@ pub async fn create_container(
         allocations: req.allocations.clone(),
         resources: resources.clone(),
         install_script: req.install_script.clone(),
+        install_timeout_secs: req.install_timeout_secs,
         installed: !has_install_script,
         environment,
         restart_policy: req.restart_policy.clone(),
         tty: req.tty,
+        labels: req.labels.clone(),
+        isolated: req.isolated,
+        readiness: req.readiness.clone(),
+        start_timeout_secs: req.start_timeout_secs,
+        variables_file: req.variables_file.clone(),
+        resource_alerts: req.resource_alerts.clone().filter(|a| !a.is_empty()),
+        asset_mounts: req.asset_mounts.clone(),
+        registry_credentials: req.registry_credentials.clone(),
+        crash_looping: false,
+        status: Some(ContainerStatus::Created),
+        ready: false,
     };
 
+    state.ftp_state.set_read_only(&req.name, req.read_only);
     state.containers.insert(req.name.clone(), managed.clone());
 
     save_container_state(&state).await;
@@ -327,11 +549,20 @@ pub async fn create_container(
     Ok(Json(managed))
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerDetails {
+    #[serde(flatten)]
+    pub container: ManagedContainer,
+    pub image_version: Option<ImageVersion>,
+    pub runtime: Option<ContainerRuntime>,
+}
+
 pub async fn get_container(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(id): Path<String>,
-) -> Result<Json<ManagedContainer>, StatusCode> {
+) -> Result<Json<ContainerDetails>, StatusCode> {
     if !verify_api_key(&headers, &state) {
         return Err(StatusCode::UNAUTHORIZED);
     }
@@ -343,13 +574,67 @@ pub async fn get_container(
         tracing::debug!("Docker container {} status: {}", id, docker_info.status);
     }
 
-    Ok(Json(container))
+    let image_version = match state.docker.container_image_version(&container.docker_id).await {
+        Ok(version) => Some(version),
+        Err(e) => {
+            tracing::debug!("Failed to resolve image of container {}: {}", id, e);
+            None
+        }
+    };
+
+    let runtime = state.docker.runtime(&container.docker_id).await.ok();
+
+    Ok(Json(ContainerDetails { container, image_version, runtime }))
+}
+
+/// Pulls the newest build of the container's image tag. The container keeps running the old
+/// build until it is recreated.
+pub async fn pull_container_image(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    let container = get_container_clone(&state, &id)
+        .ok_or((StatusCode::NOT_FOUND, "Container not found in managed state".into()))?;
+
+    let current = state.docker.container_image_version(&container.docker_id).await.ok();
+
+    state.docker
+        .pull_image(&container.image, container.registry_credentials.as_deref())
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Failed to pull {}: {}", container.image, e)))?;
+
+    let latest = state.docker
+        .image_version(&container.image, &container.image)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let updated = current.as_ref().map(|c| c.image_id != latest.image_id).unwrap_or(true);
+    tracing::info!("Pulled {} for container {} (new build: {})", container.image, id, updated);
+
+    Ok(Json(serde_json::json!({
+        "previous": current,
+        "latest": latest,
+        "updated": updated,
+    })))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteContainerQuery {
+    /// Also delete the container's files; otherwise the volume is left on disk.
+    #[serde(default)]
+    pub remove_volume: bool,
 }
 
 pub async fn delete_container(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(id): Path<String>,
+    Query(query): Query<DeleteContainerQuery>,
 ) -> Result<Json<()>, (StatusCode, String)> {
     if !verify_api_key(&headers, &state) {
         return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
@@ -362,7 +647,19 @@ pub async fn delete_container(
         tracing::warn!("Failed to remove Docker container (may not exist): {}", e);
     }
 
+    // Also catches a network left behind by a container that was isolated earlier
+    state.docker.remove_isolated_network(&container.name).await;
+
+    if query.remove_volume {
+        if let Err(e) = state.docker.remove_volume(&container.name).await {
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to remove volume: {}", e)));
+        }
+    }
+
     state.containers.remove(&id);
+    state.log_store.remove(&id).await;
+    state.ftp_state.remove_container_users(&id);
+    state.ftp_state.set_read_only(&id, false);
 
     save_container_state(&state).await;
 
@@ -382,13 +679,34 @@ pub async fn update_container(
     let mut container = get_container_clone(&state, &id)
         .ok_or((StatusCode::NOT_FOUND, "Container not found".into()))?;
 
+    let previous_memory = container.resources.memory_limit;
     if let Some(memory) = req.memory_limit {
         container.resources.memory_limit = memory;
     }
     if let Some(server_memory) = req.server_memory {
+        raptor_common::resources::validate_server_memory(server_memory, container.resources.memory_limit)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
         container.resources.server_memory = server_memory;
 
         container.environment.insert("SERVER_MEMORY".to_string(), server_memory.to_string());
+    } else if container.resources.memory_limit != previous_memory {
+        // A server memory that was defaulted from the limit follows it; an explicit one that
+        // no longer fits is cut down rather than left to get the server OOM-killed. Older
+        // containers defaulted to the whole limit, so that counts as defaulted too.
+        let resources = &mut container.resources;
+        let defaulted = resources.server_memory == previous_memory
+            || resources.server_memory == raptor_common::resources::default_server_memory(previous_memory);
+        let server_memory = if defaulted {
+            Some(raptor_common::resources::default_server_memory(resources.memory_limit))
+        } else {
+            raptor_common::resources::max_server_memory(resources.memory_limit)
+                .filter(|max| resources.server_memory > *max)
+        };
+        if let Some(server_memory) = server_memory {
+            tracing::info!("Server memory of {} follows its memory limit to {} MB", id, server_memory);
+            resources.server_memory = server_memory;
+            container.environment.insert("SERVER_MEMORY".to_string(), server_memory.to_string());
+        }
     }
     if let Some(cpu) = req.cpu_limit {
         container.resources.cpu_limit = cpu;
@@ -402,6 +720,26 @@ pub async fn update_container(
     if let Some(io) = req.io_weight {
         container.resources.io_weight = io;
     }
+    if let Some(cpuset) = req.cpuset_cpus {
+        if cpuset.is_empty() {
+            container.resources.cpuset_cpus = None;
+        } else if raptor_common::resources::is_valid_cpuset(&cpuset) {
+            container.resources.cpuset_cpus = Some(cpuset);
+        } else {
+            return Err((StatusCode::BAD_REQUEST, format!("Invalid cpuset '{}'", cpuset)));
+        }
+    }
+    if let Some(disable_swap) = req.disable_swap {
+        container.resources.disable_swap = disable_swap;
+    }
+    if let Some(tmpfs) = req.tmpfs {
+        raptor_common::mounts::validate_tmpfs_mounts(&tmpfs).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        container.resources.tmpfs = tmpfs;
+    }
+    if let Some(read_only) = req.read_only {
+        container.resources.read_only = read_only;
+        state.ftp_state.set_read_only(&id, read_only);
+    }
 
     if let Some(alloc) = req.allocation {
         container.allocation = Some(alloc);
@@ -417,10 +755,31 @@ pub async fn update_container(
         }
     }
 
+    if let Some(keys) = req.remove_environment {
+        for key in keys {
+            container.environment.remove(&key);
+        }
+    }
+
     if let Some(startup) = req.startup_script {
         container.startup_script = Some(startup);
     }
 
+    if let Some(isolated) = req.isolated {
+        container.isolated = isolated;
+    }
+
+    if let Some(policy) = req.restart_policy {
+        let policy = raptor_common::restart_policy::normalize(&policy)
+            .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Invalid restart policy '{}'", policy)))?;
+        container.restart_policy = policy.to_string();
+    }
+
+    if let Some(alerts) = req.resource_alerts {
+        alerts.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        container.resource_alerts = (!alerts.is_empty()).then_some(alerts);
+    }
+
     if let Err(e) = state.docker.update_container_resources(
         &container.docker_id,
         &container.resources,
@@ -435,6 +794,13 @@ pub async fn update_container(
     Ok(Json(container))
 }
 
+/// Graceful stops park the restart policy at "no"; a container being run again gets its own back.
+async fn restore_restart_policy(state: &AppState, docker_id: &str, policy: &str) {
+    if let Err(e) = state.docker.set_restart_policy(docker_id, policy).await {
+        tracing::warn!("Failed to restore restart policy {} on {}: {}", policy, docker_id, e);
+    }
+}
+
 async fn container_action(
     state: &AppState,
     id: &str,
@@ -468,11 +834,7 @@ pub async fn start_container(
     let container = get_container_clone(&state, &id);
 
     if let Some(container) = container {
-        tracing::info!(
-            "Starting container {} with {} allocations - will recreate to ensure port bindings",
-            id,
-            container.allocations.len()
-        );
+        tracing::info!("Starting container {} with {} allocations", id, container.allocations.len());
 
         let mut port_bindings: HashMap<String, Vec<PortBinding>> = HashMap::new();
 
@@ -485,10 +847,7 @@ pub async fn start_container(
                     port_bindings
                         .entry(key)
                         .or_insert_with(Vec::new)
-                        .push(PortBinding {
-                            host_ip: Some(alloc.ip.clone()),
-                            host_port: Some(alloc.port.to_string()),
-                        });
+                        .push(host_binding(&alloc.ip, alloc.port));
                 }
             } else {
                 let key = format!("{}/{}", alloc.internal_port, alloc.protocol);
@@ -496,10 +855,7 @@ pub async fn start_container(
                 port_bindings
                     .entry(key)
                     .or_insert_with(Vec::new)
-                    .push(PortBinding {
-                        host_ip: Some(alloc.ip.clone()),
-                        host_port: Some(alloc.port.to_string()),
-                    });
+                    .push(host_binding(&alloc.ip, alloc.port));
             }
         }
 
@@ -509,30 +865,67 @@ pub async fn start_container(
                 port_bindings
                     .entry(key)
                     .or_insert_with(Vec::new)
-                    .push(PortBinding {
-                        host_ip: Some(alloc.ip.clone()),
-                        host_port: Some(alloc.port.to_string()),
-                    });
+                    .push(host_binding(&alloc.ip, alloc.port));
             }
         }
 
         tracing::info!("Port bindings: {:?}", port_bindings);
 
+        let startup_script = resolve_startup_script(
+            container.startup_script.as_deref(),
+            &container.environment,
+            &container.resources,
+        )?;
+        if let Some(ref replaced) = startup_script {
+            tracing::info!("Original startup script: {:?}", container.startup_script);
+            tracing::info!("Memory limit from resources: {}", container.resources.memory_limit);
+            tracing::info!("SERVER_MEMORY from env: {:?}", container.environment.get("SERVER_MEMORY"));
+            tracing::info!("Replaced startup script: {}", replaced);
+        }
+
+        // An unchanged, stopped container keeps its Docker id and logs instead of being recreated
+        let fingerprint = crate::docker::spec_fingerprint(
+            &container.image,
+            startup_script.as_deref(),
+            (!port_bindings.is_empty()).then_some(&port_bindings),
+            &container.resources,
+            &container.restart_policy,
+            container.tty,
+            &container.environment,
+            &container.labels,
+            container.isolated,
+            &container.asset_mounts,
+        );
+        let needs_install = !container.installed && container.install_script.is_some();
+
+        if !needs_install && state.docker.is_reusable(&container.docker_id, &fingerprint, &port_bindings).await {
+            tracing::info!("Container {} is up to date, starting it without recreating", container.name);
+
+            write_variables_file(&container).await?;
+            restore_restart_policy(&state, &container.docker_id, &container.restart_policy).await;
+
+            state.docker
+                .start_container(&container.docker_id)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+            update_container_docker_id(&state, &container.name, container.docker_id.clone());
+            save_container_state(&state).await;
+            state.log_store.ensure_persisting(&state.docker, &container.name, &container.docker_id);
+
+            return Ok(Json(serde_json::json!({
+                "success": true,
+                "recreated": false,
+                "dockerId": container.docker_id
+            })));
+        }
+
         let _ = state.docker.graceful_stop(&container.docker_id, 30).await;
 
         if let Err(e) = state.docker.cleanup_containers_by_name(&container.name).await {
             tracing::warn!("Failed to cleanup old containers: {}", e);
         }
 
-        let startup_script = container.startup_script.as_ref().map(|s| {
-            let replaced = replace_startup_placeholders(s, &container.environment, &container.resources);
-            tracing::info!("Original startup script: {}", s);
-            tracing::info!("Memory limit from resources: {}", container.resources.memory_limit);
-            tracing::info!("SERVER_MEMORY from env: {:?}", container.environment.get("SERVER_MEMORY"));
-            tracing::info!("Replaced startup script: {}", replaced);
-            replaced
-        });
-
         let docker_id = state
             .docker
             .create_container_with_resources(
@@ -543,6 +936,11 @@ pub async fn start_container(
                 &container.resources,
                 &container.restart_policy,
                 container.tty,
+                &container.environment,
+                &container.labels,
+                container.isolated,
+                &container.asset_mounts,
+                container.registry_credentials.as_deref(),
             )
             .await
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -561,11 +959,15 @@ pub async fn start_container(
             })));
         }
 
+        write_variables_file(&container).await?;
+
         state.docker
             .start_container(&docker_id)
             .await
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+        state.log_store.ensure_persisting(&state.docker, &container.name, &docker_id);
+
         return Ok(Json(serde_json::json!({
             "success": true,
             "recreated": true,
@@ -618,23 +1020,52 @@ pub async fn restart_container(
 
     tracing::info!("Restarting container {} (docker_id: {})", id, docker_id);
 
+    if let Some(container) = get_container_clone(&state, &id) {
+        write_variables_file(&container).await?;
+        restore_restart_policy(&state, &docker_id, &container.restart_policy).await;
+    }
+
     state.docker
         .restart_container(&docker_id)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    let was_crash_looping = state
+        .containers
+        .get_mut(&id)
+        .map(|mut entry| std::mem::take(&mut entry.crash_looping))
+        .unwrap_or(false);
+    if was_crash_looping {
+        save_container_state(&state).await;
+    }
+
+    state.log_store.ensure_persisting(&state.docker, &id, &docker_id);
+
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecreateContainerRequest {
+    /// Archive the current volume and start from an empty one.
+    #[serde(default)]
+    pub fresh: bool,
+    /// Install script to run again on the fresh volume.
+    pub install_script: Option<String>,
+}
+
 pub async fn recreate_container(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(id): Path<String>,
+    body: Option<Json<RecreateContainerRequest>>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
     if !verify_api_key(&headers, &state) {
         return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
     }
 
+    let req = body.map(|Json(r)| r).unwrap_or_default();
+
     let container = get_container_clone(&state, &id)
         .ok_or((StatusCode::NOT_FOUND, "Container not found in managed state".into()))?;
 
@@ -651,10 +1082,7 @@ pub async fn recreate_container(
                 port_bindings
                     .entry(key)
                     .or_insert_with(Vec::new)
-                    .push(PortBinding {
-                        host_ip: Some(alloc.ip.clone()),
-                        host_port: Some(alloc.port.to_string()),
-                    });
+                    .push(host_binding(&alloc.ip, alloc.port));
             }
         } else {
             let key = format!("{}/{}", alloc.internal_port, alloc.protocol);
@@ -663,10 +1091,7 @@ pub async fn recreate_container(
             port_bindings
                 .entry(key)
                 .or_insert_with(Vec::new)
-                .push(PortBinding {
-                    host_ip: Some(alloc.ip.clone()),
-                    host_port: Some(alloc.port.to_string()),
-                });
+                .push(host_binding(&alloc.ip, alloc.port));
         }
     }
 
@@ -676,31 +1101,55 @@ pub async fn recreate_container(
             port_bindings
                 .entry(key)
                 .or_insert_with(Vec::new)
-                .push(PortBinding {
-                    host_ip: Some(alloc.ip.clone()),
-                    host_port: Some(alloc.port.to_string()),
-                });
+                .push(host_binding(&alloc.ip, alloc.port));
         }
     }
 
     tracing::info!("Total port bindings: {:?}", port_bindings);
 
+    let startup_script = resolve_startup_script(
+        container.startup_script.as_deref(),
+        &container.environment,
+        &container.resources,
+    )?;
+
+    // A stopped server stays stopped on its new container
+    let was_running = state.docker.is_running(&container.docker_id).await.unwrap_or(false);
+
     let _ = state.docker.graceful_stop(&container.docker_id, 30).await;
 
     if let Err(e) = state.docker.cleanup_containers_by_name(&container.name).await {
         tracing::warn!("Failed to cleanup old containers: {}", e);
     }
 
+    let mut archived_volume = None;
+    if req.fresh {
+        archived_volume = state.docker
+            .archive_volume(&container.name)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to archive volume: {}", e)))?;
+
+        if let Some(mut entry) = state.containers.get_mut(&container.name) {
+            entry.installed = req.install_script.is_none();
+            entry.install_script = req.install_script.clone();
+        }
+    }
+
     let docker_id = state
         .docker
         .create_container_with_resources(
             &container.name,
             &container.image,
-            container.startup_script.as_deref(),
+            startup_script.as_deref(),
             if port_bindings.is_empty() { None } else { Some(port_bindings) },
             &container.resources,
             &container.restart_policy,
             container.tty,
+            &container.environment,
+            &container.labels,
+            container.isolated,
+            &container.asset_mounts,
+            container.registry_credentials.as_deref(),
         )
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -709,75 +1158,236 @@ pub async fn recreate_container(
 
     save_container_state(&state).await;
 
+    if req.fresh && req.install_script.is_some() {
+        tracing::info!("Container {} was reset - installation will be triggered via WebSocket", container.name);
+        return Ok(Json(serde_json::json!({
+            "success": true,
+            "recreated": true,
+            "dockerId": docker_id,
+            "archivedVolume": archived_volume,
+            "needsInstall": true
+        })));
+    }
+
+    if !was_running {
+        return Ok(Json(serde_json::json!({
+            "success": true,
+            "recreated": true,
+            "dockerId": docker_id,
+            "archivedVolume": archived_volume,
+            "started": false
+        })));
+    }
+
+    write_variables_file(&container).await?;
+
     state.docker
         .start_container(&docker_id)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    state.log_store.ensure_persisting(&state.docker, &container.name, &docker_id);
+
     Ok(Json(serde_json::json!({
         "success": true,
         "recreated": true,
-        "dockerId": docker_id
+        "dockerId": docker_id,
+        "archivedVolume": archived_volume,
+        "started": true
     })))
 }
 
-pub async fn kill_container(
+#[derive(Debug, Deserialize)]
+pub struct WipeContainerRequest {
+    /// Must repeat the container name.
+    pub confirm: String,
+}
+
+/// Stops the container and deletes all files in its volume, keeping `.machine-id`.
+pub async fn wipe_container(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(id): Path<String>,
+    Json(req): Json<WipeContainerRequest>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
     if !verify_api_key(&headers, &state) {
         return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
     }
 
-    let docker_id = get_docker_id(&state, &id);
+    let container = get_container_clone(&state, &id)
+        .ok_or((StatusCode::NOT_FOUND, "Container not found in managed state".into()))?;
 
-    state.docker
-        .kill_container(&docker_id)
+    if req.confirm != container.name {
+        return Err((StatusCode::BAD_REQUEST, "Confirmation does not match the container name".into()));
+    }
+
+    // Never wipe underneath a running install
+    let lock = state.container_locks.get_lock(&container.name);
+    let _guard = lock.lock().await;
+
+    // 304/404: already stopped or gone. Anything else could leave it writing to the volume
+    if let Err(e) = state.docker.graceful_stop(&container.docker_id, 30).await {
+        let already_stopped = matches!(
+            e.downcast_ref::<bollard::errors::Error>(),
+            Some(bollard::errors::Error::DockerResponseServerError { status_code: 304 | 404, .. })
+        );
+        if !already_stopped {
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to stop container: {}", e)));
+        }
+    }
+
+    let removed = state.docker
+        .wipe_volume(&container.name)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to wipe volume: {}", e)))?;
 
-    Ok(Json(serde_json::json!({ "success": true })))
+    tracing::info!("Wiped volume of container {} ({} entries removed)", container.name, removed);
+
+    Ok(Json(serde_json::json!({ "success": true, "removed": removed })))
 }
 
 #[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SendCommandRequest {
-    pub command: String,
+pub struct CopyVolumeRequest {
+    /// Container whose files are copied into this one.
+    pub source: String,
 }
 
-pub async fn send_command(
+/// Fills the (stopped, empty) volume of a freshly cloned container from another container's volume.
+pub async fn copy_container_volume(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(id): Path<String>,
-    Json(req): Json<SendCommandRequest>,
+    Json(req): Json<CopyVolumeRequest>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
     if !verify_api_key(&headers, &state) {
         return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
     }
 
-    let docker_id = get_docker_id(&state, &id);
+    let target = get_container_clone(&state, &id)
+        .ok_or((StatusCode::NOT_FOUND, "Container not found in managed state".into()))?;
+    let source = get_container_clone(&state, &req.source)
+        .ok_or((StatusCode::NOT_FOUND, "Source container not found in managed state".into()))?;
 
-    state
-        .docker
-        .send_command(&docker_id, &req.command)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if source.name == target.name {
+        return Err((StatusCode::BAD_REQUEST, "Cannot copy a volume onto itself".into()));
+    }
+
+    let lock = state.container_locks.get_lock(&target.name);
+    let _guard = lock.lock().await;
+
+    if target.status.is_some_and(|s| s.is_running()) {
+        return Err((StatusCode::CONFLICT, "Stop the container before copying files into it".into()));
+    }
+
+    let copied = state.docker
+        .copy_volume(&source.name, &target.name)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to copy volume: {}", e)))?;
+
+    tracing::info!("Copied {} entries from volume {} into {}", copied, source.name, target.name);
+
+    Ok(Json(serde_json::json!({ "success": true, "copied": copied })))
+}
+
+pub async fn kill_container(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    let docker_id = get_docker_id(&state, &id);
+
+    state.docker
+        .kill_container(&docker_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendCommandRequest {
+    pub command: String,
+    #[serde(default)]
+    pub capture_output_ms: Option<u64>,
+}
+
+const MAX_COMMAND_CAPTURE_MS: u64 = 5000;
+const MAX_COMMAND_CAPTURE_LINES: usize = 200;
+
+pub async fn send_command(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(req): Json<SendCommandRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    let docker_id = get_docker_id(&state, &id);
+
+    let capture = req.capture_output_ms.map(|ms| {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        state.docker.follow_logs(&docker_id, tx);
+        (ms.min(MAX_COMMAND_CAPTURE_MS), rx)
+    });
+
+    if capture.is_some() {
+        // Let the log follower attach before the command produces output
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    state
+        .docker
+        .send_command(&docker_id, &req.command)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let Some((window_ms, mut rx)) = capture else {
+        return Ok(Json(serde_json::json!({ "success": true })));
+    };
+
+    let mut output: Vec<String> = Vec::new();
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_millis(window_ms);
+    while output.len() < MAX_COMMAND_CAPTURE_LINES {
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(line)) => output.push(line),
+            _ => break,
+        }
+    }
+    // Closing the receiver ends the log follower
+    drop(rx);
+
+    Ok(Json(serde_json::json!({ "success": true, "output": output })))
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GracefulStopRequest {
+    /// Time to exit after the stop command, or after SIGTERM when there is none.
     #[serde(default = "default_timeout")]
     pub timeout_secs: u64,
+    /// Time to exit after SIGTERM when a stop command was tried first.
+    #[serde(default = "default_term_timeout")]
+    pub term_timeout_secs: u64,
+    /// Console command asking the server to save and quit, e.g. "stop".
+    pub stop_command: Option<String>,
 }
 
 fn default_timeout() -> u64 {
     30
 }
 
+fn default_term_timeout() -> u64 {
+    10
+}
+
+/// Stop command, then SIGTERM, then SIGKILL; `stage` tells which one the server exited at.
 pub async fn graceful_stop_container(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
@@ -789,14 +1399,19 @@ pub async fn graceful_stop_container(
     }
 
     let docker_id = get_docker_id(&state, &id);
+    let stop_command = req.stop_command.as_deref().map(str::trim).filter(|c| !c.is_empty());
 
-    state
+    let stage = state
         .docker
-        .graceful_stop(&docker_id, req.timeout_secs)
+        .stop_escalating(&docker_id, stop_command, req.timeout_secs, req.term_timeout_secs)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok(Json(serde_json::json!({ "success": true })))
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "stage": stage,
+        "forced": stage == StopStage::Sigkill,
+    })))
 }
 
 #[derive(Debug, Deserialize)]
@@ -820,6 +1435,102 @@ pub async fn create_ftp(
     Ok(Json(creds))
 }
 
+pub async fn delete_ftp(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    state.ftp_state.remove_container_users(&id);
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateBackupRequest {
+    /// Name prefix so scheduled and manual backups can be pruned separately.
+    pub prefix: Option<String>,
+}
+
+pub async fn list_backups(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<BackupInfo>>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    if !state.containers.contains_key(&id) {
+        return Err((StatusCode::NOT_FOUND, "Container not found in managed state".into()));
+    }
+
+    Ok(Json(backups::list_backups(&id).await))
+}
+
+pub async fn create_backup(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Option<Json<CreateBackupRequest>>,
+) -> Result<Json<BackupInfo>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    if !state.containers.contains_key(&id) {
+        return Err((StatusCode::NOT_FOUND, "Container not found in managed state".into()));
+    }
+
+    let req = body.map(|Json(r)| r).unwrap_or_default();
+    let prefix = req.prefix.as_deref().unwrap_or("manual");
+
+    let backup = backups::create_backup(&id, prefix)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(backup))
+}
+
+pub async fn delete_backup(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((id, backup)): Path<(String, String)>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    if !state.containers.contains_key(&id) {
+        return Err((StatusCode::NOT_FOUND, "Container not found in managed state".into()));
+    }
+
+    if !backups::is_valid_backup_name(&backup) {
+        return Err((StatusCode::BAD_REQUEST, "Invalid backup name".into()));
+    }
+
+    backups::delete_backup(&id, &backup)
+        .await
+        .map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))?;
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// ALLOCATION_PORTS as `start-end`, defaulting to 25565-25600.
+fn allocation_ports() -> std::ops::RangeInclusive<i32> {
+    std::env::var("ALLOCATION_PORTS")
+        .ok()
+        .and_then(|v| {
+            let (start, end) = v.split_once('-')?;
+            Some(start.trim().parse().ok()?..=end.trim().parse().ok()?)
+        })
+        .filter(|range| !range.is_empty())
+        .unwrap_or(25565..=25600)
+}
+
 pub async fn list_allocations(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
@@ -834,7 +1545,7 @@ pub async fn list_allocations(
         .map(|s| s.trim().to_string())
         .collect();
 
-    let port_range: Vec<i32> = (25565..=25600).collect();
+    let port_range: Vec<i32> = allocation_ports().collect();
 
     let used_ports: std::collections::HashSet<i32> = {
         state.containers
@@ -884,6 +1595,67 @@ pub async fn assign_allocation(
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
+/// Marks the container installed whether or not the script succeeded, so it can still be started, and tells the panel.
+async fn finish_install(state: &AppState, container_name: &str, result: &anyhow::Result<()>) {
+    match result {
+        Ok(_) => tracing::info!("Install completed for {}", container_name),
+        Err(e) => tracing::error!("Install failed for {}: {}", container_name, e),
+    }
+
+    mark_container_installed(state, container_name);
+    save_container_state(state).await;
+
+    let details = match result {
+        Ok(_) => serde_json::json!({ "success": true }),
+        Err(e) => serde_json::json!({ "success": false, "error": e.to_string() }),
+    };
+    state.panel.send(container_name, callback::INSTALL_COMPLETED, details);
+}
+
+/// POST /containers/:id/install - runs a pending install script without a console attached.
+/// Interactive clients get the same install through the log websocket instead.
+pub async fn install_container(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    let install_lock = state.container_locks.get_lock(&id);
+    let _install_guard = install_lock.lock().await;
+
+    let container = get_container_clone(&state, &id)
+        .ok_or((StatusCode::NOT_FOUND, "Container not found".into()))?;
+
+    let script = match (&container.install_script, container.installed) {
+        (Some(script), false) => script.clone(),
+        _ => return Ok(Json(serde_json::json!({ "success": true, "installed": false }))),
+    };
+
+    let timeout_secs = container.install_timeout_secs
+        .unwrap_or(DEFAULT_INSTALL_TIMEOUT_SECS)
+        .min(state.runtime().install_timeout_max_secs);
+
+    tracing::info!("Running headless install for {} (timeout {}s)", container.name, timeout_secs);
+
+    let result = state.docker.run_install_in_temp_container_with_logs(
+        &container.name,
+        &container.image,
+        &script,
+        &container.environment,
+        timeout_secs,
+        None,
+    ).await;
+
+    finish_install(&state, &container.name, &result).await;
+
+    result.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Installation failed: {}", e)))?;
+
+    Ok(Json(serde_json::json!({ "success": true, "installed": true })))
+}
+
 pub async fn ws_logs(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -892,18 +1664,121 @@ pub async fn ws_logs(
 ) -> impl IntoResponse {
     let api_key = params.get("api_key").cloned().unwrap_or_default();
 
-    if api_key != state.api_key {
+    if !raptor_common::secrets::secrets_match(&api_key, &state.api_key) {
         return (StatusCode::UNAUTHORIZED, "Invalid API key").into_response();
     }
 
     // Parse since parameter (e.g., "10m" for 10 minutes)
     let since = params.get("since").cloned();
+    let format = LogFormat::parse(params.get("format").map(String::as_str));
+    let tail = match raptor_common::logs::parse_tail(params.get("tail").map(String::as_str)) {
+        Ok(tail) => tail,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    ws.on_upgrade(move |socket| handle_logs_websocket(socket, state, id, since, tail, format)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogsQuery {
+    pub tail: Option<String>,
+    pub since: Option<String>,
+}
+
+/// Past output of the container as JSON, for clients that do not want a websocket.
+pub async fn get_logs(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
 
-    ws.on_upgrade(move |socket| handle_logs_websocket(socket, state, id, since)).into_response()
+    let tail = raptor_common::logs::parse_tail(query.tail.as_deref()).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if let Some(since) = query.since.as_deref() {
+        if crate::docker::parse_since(since).is_none() {
+            return Err((StatusCode::BAD_REQUEST, "since must look like 10m or 2h".into()));
+        }
+    }
+
+    let container = get_container_clone(&state, &id)
+        .ok_or((StatusCode::NOT_FOUND, "Container not found".into()))?;
+
+    // A since window is still bounded, so the response never grows past the cap
+    let tail = tail.unwrap_or(if query.since.is_some() { raptor_common::logs::MAX_LOG_TAIL } else { raptor_common::logs::DEFAULT_LOG_TAIL });
+
+    let mut lines = state.docker
+        .recent_logs(&container.docker_id, query.since.as_deref(), Some(tail))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read logs: {}", e)))?;
+
+    for line in &mut lines {
+        line.line = crate::log_format::strip_ansi(&line.line);
+    }
+
+    Ok(Json(serde_json::json!({ "lines": lines })))
 }
 
-async fn handle_logs_websocket(socket: WebSocket, state: Arc<AppState>, container_name: String, since: Option<String>) {
+#[derive(Debug, Deserialize)]
+pub struct LogDownloadQuery {
+    pub since: Option<String>,
+}
+
+/// GET /containers/:id/logs/download - the persisted console history as plain text, or Docker's
+/// own log when nothing was persisted. `x-log-source` says which one it is.
+pub async fn download_logs(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(query): Query<LogDownloadQuery>,
+) -> Result<axum::response::Response, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    let since = query.since.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let since_timestamp = match since {
+        Some(since) => Some(
+            crate::docker::parse_since(since)
+                .ok_or((StatusCode::BAD_REQUEST, "since must look like 10m or 2h".to_string()))?,
+        ),
+        None => None,
+    };
+
+    let container = get_container_clone(&state, &id)
+        .ok_or((StatusCode::NOT_FOUND, "Container not found".into()))?;
+
+    let (rx, source) = match state.log_store.export(&container.name, since_timestamp) {
+        Some(rx) => (rx, "history"),
+        None => (state.docker.export_logs(&container.docker_id, since), "docker"),
+    };
+
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        let line = rx.recv().await?;
+        Some((Ok::<_, std::convert::Infallible>(line), rx))
+    });
+
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (axum::http::HeaderName::from_static("x-log-source"), source),
+        ],
+        axum::body::Body::from_stream(stream),
+    ).into_response())
+}
+
+async fn handle_logs_websocket(
+    socket: WebSocket,
+    state: Arc<AppState>,
+    container_name: String,
+    since: Option<String>,
+    tail: Option<usize>,
+    format: LogFormat,
+) {
     let (mut sender, mut receiver) = socket.split();
+    let system = |text: String| Message::Text(format.render(&LogLine::system(text)));
 
     let container_info = {
         state.containers.get(&container_name)
@@ -911,12 +1786,13 @@ async fn handle_logs_websocket(socket: WebSocket, state: Arc<AppState>, containe
                 entry.value().docker_id.clone(),
                 entry.value().installed,
                 entry.value().install_script.clone(),
+                entry.value().install_timeout_secs,
                 entry.value().image.clone(),
                 entry.value().environment.clone(),
             ))
     };
 
-    let (docker_id, installed, install_script, image, environment) = match container_info {
+    let (docker_id, installed, install_script, install_timeout_secs, image, environment) = match container_info {
         Some(info) => info,
         None => {
 
@@ -926,41 +1802,52 @@ async fn handle_logs_websocket(socket: WebSocket, state: Arc<AppState>, containe
                     entry.value().docker_id.clone(),
                     entry.value().installed,
                     entry.value().install_script.clone(),
+                    entry.value().install_timeout_secs,
                     entry.value().image.clone(),
                     entry.value().environment.clone(),
                 ));
             match found {
                 Some(info) => info,
                 None => {
-                    let _ = sender.send(Message::Text("\x1b[31m● Container not found\x1b[0m".to_string())).await;
+                    let _ = sender.send(system("\x1b[31m● Container not found\x1b[0m".to_string())).await;
                     return;
                 }
             }
         }
     };
 
-    if sender.send(Message::Text(format!("\x1b[32m● Connected to container: {}\x1b[0m", container_name))).await.is_err() {
+    if sender.send(system(format!("\x1b[32m● Connected to container: {}\x1b[0m", container_name))).await.is_err() {
         return;
     }
 
-    let (tx, mut rx) = broadcast::channel::<String>(1000);
+    let (tx, mut rx) = broadcast::channel::<LogLine>(state.runtime().log_stream_buffer);
+
+    let install_lock = state.container_locks.get_lock(&container_name);
+    let install_guard = if installed { None } else { Some(install_lock.lock().await) };
+    // A headless install may have finished while this socket waited for the lock
+    let installed = installed || get_container_clone(&state, &container_name).map(|c| c.installed).unwrap_or(false);
 
     if !installed {
         if let Some(script) = install_script {
 
-            let _ = sender.send(Message::Text("\x1b[33m● Starting installation...\x1b[0m".to_string())).await;
+            let _ = sender.send(system("\x1b[33m● Starting installation...\x1b[0m".to_string())).await;
 
             let tx_for_install = tx.clone();
             let container_name_clone = container_name.clone();
             let state_clone = state.clone();
 
-            tracing::info!("Running install script for {} via WebSocket", container_name);
+            let timeout_secs = install_timeout_secs
+                .unwrap_or(DEFAULT_INSTALL_TIMEOUT_SECS)
+                .min(state.runtime().install_timeout_max_secs);
+
+            tracing::info!("Running install script for {} via WebSocket (timeout {}s)", container_name, timeout_secs);
 
             let install_fut = state.docker.run_install_in_temp_container_with_logs(
                 &container_name,
                 &image,
                 &script,
                 &environment,
+                timeout_secs,
                 Some(tx_for_install),
             );
 
@@ -973,14 +1860,14 @@ async fn handle_logs_websocket(socket: WebSocket, state: Arc<AppState>, containe
                     result = &mut install_fut => {
 
                         while let Ok(log) = rx.try_recv() {
-                            let _ = sender.send(Message::Text(log)).await;
+                            let _ = sender.send(Message::Text(format.render(&log))).await;
                         }
                         break result;
                     }
                     log_result = rx.recv() => {
                         match log_result {
                             Ok(log) => {
-                                if sender.send(Message::Text(log)).await.is_err() {
+                                if sender.send(Message::Text(format.render(&log))).await.is_err() {
                                     return;
                                 }
                             }
@@ -991,84 +1878,283 @@ async fn handle_logs_websocket(socket: WebSocket, state: Arc<AppState>, containe
                 }
             };
 
-            match install_result {
-                Ok(_) => {
-                    tracing::info!("Install completed for {}", container_name);
-                    mark_container_installed(&state_clone, &container_name_clone);
-                    save_container_state(&state_clone).await;
+            finish_install(&state_clone, &container_name_clone, &install_result).await;
+
+            match install_result {
+                Ok(_) => {
+                    let _ = sender.send(system("\x1b[32m● Installation complete! Click Start to launch the server.\x1b[0m".to_string())).await;
+
+                }
+                Err(e) => {
+                    let _ = sender.send(system(format!("\x1b[31m● Installation failed: {}\x1b[0m", e))).await;
+                    return;
+                }
+            }
+        }
+    }
+
+    drop(install_guard);
+
+    let docker_id = get_docker_id(&state, &container_name);
+
+    state.docker.stream_logs(&docker_id, tx, since, tail);
+
+    let docker_id_for_cmd = docker_id.clone();
+    let state_for_cmd = state.clone();
+    let container_name_for_cmd = container_name.clone();
+
+    let coalesce_on_lag = state.runtime().log_stream_coalesce;
+    let keepalive = Keepalive::new();
+
+    // A client that falls more than the buffer behind loses lines; it is told how many
+    // instead of silently skipping. With coalescing enabled a lagging client then gets
+    // all queued lines in one frame (newline separated, so NDJSON in json format),
+    // trading per-line messages for keeping up.
+    let send_task = async {
+        let mut coalescing = false;
+        let mut ping = Keepalive::interval();
+
+        loop {
+            let received = tokio::select! {
+                received = rx.recv() => received,
+                _ = ping.tick() => {
+                    if !keepalive.ping(&mut sender).await {
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            match received {
+                Ok(log) => {
+                    let message = if coalescing {
+                        let mut batch = format.render(&log);
+                        loop {
+                            match rx.try_recv() {
+                                Ok(next) => {
+                                    batch.push('\n');
+                                    batch.push_str(&format.render(&next));
+                                }
+                                Err(broadcast::error::TryRecvError::Lagged(n)) => {
+                                    batch.push('\n');
+                                    batch.push_str(&format.render(&skipped_lines_marker(n)));
+                                }
+                                Err(_) => break,
+                            }
+                        }
+                        batch
+                    } else {
+                        format.render(&log)
+                    };
+
+                    if sender.send(Message::Text(message)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!("Log receiver for {} lagged by {} messages", container_name, n);
+
+                    if sender.send(Message::Text(format.render(&skipped_lines_marker(n)))).await.is_err() {
+                        break;
+                    }
+
+                    if coalesce_on_lag && !coalescing {
+                        tracing::debug!("Switching log stream for {} to coalesced mode", container_name);
+                        coalescing = true;
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+
+                    let _ = sender.send(Message::Close(None)).await;
+                    break;
+                }
+            }
+        }
+    };
+
+    let recv_task = async {
+        let mut console_input: Option<ConsoleInput> = None;
+
+        while let Some(msg) = receiver.next().await {
+            keepalive.seen();
+            match msg {
+                Ok(Message::Text(text)) => {
+
+                    let text = text.trim();
+                    if text.is_empty() {
+                        continue;
+                    }
+
+                    tracing::info!("Received command for container {}: {}", docker_id_for_cmd, text);
+
+                    if let Some(input) = console_input.as_mut() {
+                        match write_console_line(input, text).await {
+                            Ok(_) => continue,
+                            Err(e) => {
+                                tracing::warn!("Console attach for {} broke, reattaching: {}", docker_id_for_cmd, e);
+                                console_input = None;
+                            }
+                        }
+                    }
+
+                    let current_docker_id = get_docker_id(&state_for_cmd, &container_name_for_cmd);
+                    match state_for_cmd.docker.attach_stdin(&current_docker_id).await {
+                        Ok(mut input) => {
+                            if let Err(e) = write_console_line(&mut input, text).await {
+                                tracing::error!("Failed to send command: {}", e);
+                            } else {
+                                console_input = Some(input);
+                            }
+                        }
+                        Err(e) => tracing::error!("Failed to attach to container {}: {}", current_docker_id, e),
+                    }
+                }
+                Ok(Message::Close(_)) => break,
+                Err(_) => break,
+                _ => {}
+            }
+        }
+
+        if let Some(mut input) = console_input.take() {
+            let _ = input.shutdown().await;
+        }
+    };
+
+    tokio::select! {
+        _ = send_task => {
+            tracing::debug!("Log send task completed for {}", container_name);
+        },
+        _ = recv_task => {
+            tracing::debug!("Log receive task completed for {}", container_name);
+        },
+    }
+
+    tracing::debug!("WebSocket logs handler completed for {}", container_name);
+}
+
+/// Interactive shell inside the running container. Binary frames are raw terminal I/O both
+/// ways; text frames from the client are `{"type":"input","data":..}` or
+/// `{"type":"resize","cols":..,"rows":..}`.
+pub async fn ws_exec(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let api_key = params.get("api_key").cloned().unwrap_or_default();
 
-                    let _ = sender.send(Message::Text("\x1b[32m● Installation complete! Click Start to launch the server.\x1b[0m".to_string())).await;
+    if !raptor_common::secrets::secrets_match(&api_key, &state.api_key) {
+        return (StatusCode::UNAUTHORIZED, "Invalid API key").into_response();
+    }
 
-                }
-                Err(e) => {
-                    tracing::error!("Install failed for {}: {}", container_name, e);
-                    mark_container_installed(&state_clone, &container_name_clone);
-                    save_container_state(&state_clone).await;
-                    let _ = sender.send(Message::Text(format!("\x1b[31m● Installation failed: {}\x1b[0m", e))).await;
-                    return;
-                }
-            }
-        }
+    let Some(container) = get_container_clone(&state, &id) else {
+        return (StatusCode::NOT_FOUND, "Container not found").into_response();
+    };
+
+    if !state.docker.is_running(&container.docker_id).await.unwrap_or(false) {
+        return (StatusCode::CONFLICT, "Container is not running").into_response();
     }
 
-    let docker_id = get_docker_id(&state, &container_name);
+    let size = |key: &str, default: u16| params.get(key).and_then(|v| v.parse().ok()).filter(|v| *v > 0).unwrap_or(default);
+    let (cols, rows) = (size("cols", 80), size("rows", 24));
 
-    state.docker.stream_logs(&docker_id, tx, since);
+    ws.on_upgrade(move |socket| handle_exec_websocket(socket, state, container.docker_id, id, cols, rows)).into_response()
+}
 
-    let docker_id_for_cmd = docker_id.clone();
-    let state_for_cmd = state.clone();
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ExecMessage {
+    Input { data: String },
+    Resize { cols: u16, rows: u16 },
+}
+
+async fn handle_exec_websocket(socket: WebSocket, state: Arc<AppState>, docker_id: String, container_name: String, cols: u16, rows: u16) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let session = match state.docker.open_shell(&docker_id, cols, rows).await {
+        Ok(session) => session,
+        Err(e) => {
+            tracing::warn!("Failed to open shell in {}: {}", container_name, e);
+            let _ = sender.send(Message::Text(format!("\x1b[31m● Failed to open shell: {}\x1b[0m", e))).await;
+            return;
+        }
+    };
+
+    tracing::info!("Opened shell session {} in {}", session.exec_id, container_name);
+
+    let crate::docker::ShellSession { exec_id, mut output, mut input } = session;
+    let keepalive = Keepalive::new();
 
     let send_task = async {
+        let mut ping = Keepalive::interval();
+
         loop {
-            match rx.recv().await {
-                Ok(log) => {
-                    if sender.send(Message::Text(log)).await.is_err() {
+            let chunk = tokio::select! {
+                chunk = output.next() => match chunk {
+                    Some(chunk) => chunk,
+                    None => break,
+                },
+                _ = ping.tick() => {
+                    if !keepalive.ping(&mut sender).await {
                         break;
                     }
+                    continue;
                 }
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    tracing::warn!("Log receiver lagged by {} messages", n);
+            };
 
+            match chunk {
+                Ok(chunk) => {
+                    if sender.send(Message::Binary(chunk.into_bytes().to_vec())).await.is_err() {
+                        break;
+                    }
                 }
-                Err(broadcast::error::RecvError::Closed) => {
-
-                    let _ = sender.send(Message::Close(None)).await;
+                Err(e) => {
+                    tracing::debug!("Shell output for {} ended: {}", container_name, e);
                     break;
                 }
             }
         }
+
+        let _ = sender.send(Message::Close(None)).await;
     };
 
     let recv_task = async {
         while let Some(msg) = receiver.next().await {
-            match msg {
-                Ok(Message::Text(text)) => {
-
-                    let text = text.trim();
-                    if !text.is_empty() {
-                        tracing::info!("Received command for container {}: {}", docker_id_for_cmd, text);
-                        if let Err(e) = state_for_cmd.docker.send_command(&docker_id_for_cmd, text).await {
-                            tracing::error!("Failed to send command: {}", e);
+            keepalive.seen();
+            let data = match msg {
+                Ok(Message::Binary(data)) => data,
+                Ok(Message::Text(text)) => match serde_json::from_str::<ExecMessage>(&text) {
+                    Ok(ExecMessage::Input { data }) => data.into_bytes(),
+                    Ok(ExecMessage::Resize { cols, rows }) => {
+                        if let Err(e) = state.docker.resize_shell(&exec_id, cols, rows).await {
+                            tracing::debug!("Failed to resize shell {}: {}", exec_id, e);
                         }
+                        continue;
                     }
-                }
-                Ok(Message::Close(_)) => break,
-                Err(_) => break,
-                _ => {}
+                    Err(_) => text.into_bytes(),
+                },
+                Ok(Message::Close(_)) | Err(_) => break,
+                _ => continue,
+            };
+
+            if input.write_all(&data).await.is_err() || input.flush().await.is_err() {
+                break;
             }
         }
+
+        let _ = input.shutdown().await;
     };
 
     tokio::select! {
-        _ = send_task => {
-            tracing::debug!("Log send task completed for {}", container_name);
-        },
-        _ = recv_task => {
-            tracing::debug!("Log receive task completed for {}", container_name);
-        },
+        _ = send_task => {},
+        _ = recv_task => {},
     }
 
-    tracing::debug!("WebSocket logs handler completed for {}", container_name);
+    tracing::info!("Closed shell session {} in {}", exec_id, container_name);
+}
+
+fn skipped_lines_marker(count: u64) -> LogLine {
+    LogLine::system(format!("\x1b[33m[{} lines skipped]\x1b[0m", count))
 }
 
 pub async fn get_system_resources(
@@ -1079,7 +2165,16 @@ pub async fn get_system_resources(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    let result = tokio::task::spawn_blocking(|| {
+    collect_system_resources(&state)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn collect_system_resources(state: &AppState) -> Result<crate::models::SystemResources, tokio::task::JoinError> {
+    let aggregates = crate::models::ContainerAggregates::collect(&state.containers);
+
+    tokio::task::spawn_blocking(move || {
         let mut sys = sysinfo::System::new_all();
         sys.refresh_all();
         sys.refresh_memory();
@@ -1106,12 +2201,35 @@ pub async fn get_system_resources(
             total_disk,
             available_disk,
             hostname,
+            load_average: crate::system_stats::load_average(),
+            running_containers: aggregates.running,
+            allocated_memory: aggregates.memory_bytes,
+            allocated_cpu: aggregates.cpu,
         }
     })
     .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+}
+
+/// GET /metrics - Prometheus scrape endpoint. Accepts the API key or `Authorization: Bearer
+/// <METRICS_TOKEN>`, so a scraper never needs the key that controls containers.
+pub async fn metrics(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    let bearer = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    let token_ok = matches!((bearer, state.metrics_token.as_deref()), (Some(given), Some(token)) if raptor_common::secrets::secrets_match(given, token));
+
+    if !token_ok && !verify_api_key(&headers, &state) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
 
-    Ok(Json(result))
+    let body = crate::metrics::render(&state).await;
+
+    Ok(([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], body))
 }
 
 pub async fn ws_system_stats(
@@ -1121,84 +2239,50 @@ pub async fn ws_system_stats(
 ) -> impl IntoResponse {
     let api_key = params.get("api_key").cloned().unwrap_or_default();
 
-    if api_key != state.api_key {
+    if !raptor_common::secrets::secrets_match(&api_key, &state.api_key) {
         return (StatusCode::UNAUTHORIZED, "Invalid API key").into_response();
     }
 
-    ws.on_upgrade(|socket| handle_system_stats_socket(socket))
+    ws.on_upgrade(|socket| handle_system_stats_socket(socket, state))
 }
 
-async fn handle_system_stats_socket(socket: WebSocket) {
+async fn handle_system_stats_socket(socket: WebSocket, state: Arc<AppState>) {
     let (mut sender, mut receiver) = socket.split();
+    let keepalive = Keepalive::new();
+    let (latest, mut rx) = SystemStatsHub::subscribe(&state);
 
     let send_task = async {
-        let mut interval = tokio::time::interval(std::time::Duration::from_secs(2));
-
-        let sys = tokio::task::spawn_blocking(|| {
-            let mut sys = sysinfo::System::new_all();
-            sys.refresh_all();
-            sys
-        })
-        .await;
+        if let Some(latest) = latest {
+            if sender.send(Message::Text(latest)).await.is_err() {
+                return;
+            }
+        }
 
-        let mut sys = match sys {
-            Ok(s) => s,
-            Err(_) => return,
-        };
+        let mut ping = Keepalive::interval();
 
         loop {
-            interval.tick().await;
-
-            let stats = {
-                let stats_result = tokio::task::spawn_blocking(move || {
-                    sys.refresh_memory();
-                    sys.refresh_cpu_all();
-
-                    let total_memory = sys.total_memory();
-                    let used_memory = sys.used_memory();
-                    let available_memory = total_memory.saturating_sub(used_memory);
-                    let cpu_cores = sys.cpus().len();
-                    let cpu_usage = sys.global_cpu_usage() as f64;
-
-                    let disks = sysinfo::Disks::new_with_refreshed_list();
-                    let (total_disk, available_disk) = disks.iter().fold((0u64, 0u64), |(total, avail), disk| {
-                        (total + disk.total_space(), avail + disk.available_space())
-                    });
-
-                    let hostname = sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string());
-
-                    let stats = crate::models::SystemResources {
-                        total_memory,
-                        available_memory,
-                        cpu_cores,
-                        cpu_usage,
-                        total_disk,
-                        available_disk,
-                        hostname,
-                    };
-
-                    (sys, stats)
-                })
-                .await;
-
-                match stats_result {
-                    Ok((returned_sys, stats)) => {
-                        sys = returned_sys;
-                        stats
+            tokio::select! {
+                stats = rx.recv() => match stats {
+                    Ok(stats) => {
+                        if sender.send(Message::Text(stats)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = ping.tick() => {
+                    if !keepalive.ping(&mut sender).await {
+                        break;
                     }
-                    Err(_) => return,
                 }
-            };
-
-            let json = serde_json::to_string(&stats).unwrap_or_default();
-            if sender.send(Message::Text(json)).await.is_err() {
-                break;
             }
         }
     };
 
     let recv_task = async {
         while let Some(Ok(msg)) = receiver.next().await {
+            keepalive.seen();
             if matches!(msg, Message::Close(_)) {
                 break;
             }
@@ -1238,6 +2322,11 @@ pub struct ContainerStatusResponse {
     pub exit_code: Option<i64>,
     pub started_at: Option<String>,
     pub finished_at: Option<String>,
+    /// `None` in bulk listings, which don't inspect every container.
+    pub restart_count: Option<i64>,
+    pub crash_looping: bool,
+    /// Running and past the flake's readiness check ("Online" rather than "Starting").
+    pub ready: bool,
 }
 
 pub async fn get_container_status(
@@ -1249,22 +2338,157 @@ pub async fn get_container_status(
         return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
     }
 
+    let (crash_looping, ready) = state.containers.get(&id).map(|c| (c.crash_looping, c.ready)).unwrap_or_default();
     let docker_id = get_docker_id(&state, &id);
+    let runtime = state.docker.runtime(&docker_id).await.ok();
+
+    if let Some(status) = state.containers.get(&id).and_then(|c| c.status) {
+        let runtime = runtime.unwrap_or_default();
+        return Ok(Json(ContainerStatusResponse {
+            status: status.as_str().to_string(),
+            running: status.is_running(),
+            exit_code: runtime.exit_code,
+            started_at: runtime.started_at,
+            finished_at: runtime.finished_at,
+            restart_count: Some(runtime.restart_count),
+            crash_looping,
+            ready: ready && status.is_running(),
+        }));
+    }
 
     let info = state.docker.get_container(&docker_id).await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     let running = info.state.to_lowercase() == "running";
 
+    if let Some(status) = ContainerStatus::from_docker_state(&info.state) {
+        if let Some(mut entry) = state.containers.get_mut(&id) {
+            if entry.docker_id == docker_id {
+                entry.status = Some(status);
+            }
+        }
+    }
+
+    let runtime = runtime.unwrap_or_default();
+
     Ok(Json(ContainerStatusResponse {
-        status: info.state.clone(),
+        status: info.state.to_lowercase(),
         running,
-        exit_code: None,
-        started_at: None,
-        finished_at: None,
+        exit_code: runtime.exit_code,
+        started_at: runtime.started_at,
+        finished_at: runtime.finished_at,
+        restart_count: Some(runtime.restart_count),
+        crash_looping,
+        ready: ready && running,
     }))
 }
 
+/// Configured limits next to the ones Docker enforces, to spot drift between the two.
+pub async fn get_container_resources(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<ContainerResourcesResponse>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    let (docker_id, configured) = state
+        .containers
+        .get(&id)
+        .map(|c| (c.docker_id.clone(), c.resources.clone()))
+        .ok_or((StatusCode::NOT_FOUND, "Container not found".to_string()))?;
+
+    let applied = state.docker.applied_resources(&docker_id).await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(ContainerResourcesResponse { configured, applied }))
+}
+
+/// Statuses of all managed containers keyed by name, so the API can refresh a list in one request.
+pub async fn list_container_statuses(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<HashMap<String, ContainerStatusResponse>>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    let managed: Vec<(String, String, Option<ContainerStatus>, bool, bool)> = state
+        .containers
+        .iter()
+        .map(|entry| (entry.name.clone(), entry.docker_id.clone(), entry.status, entry.crash_looping, entry.ready))
+        .collect();
+
+    let mut statuses = HashMap::new();
+
+    for (name, docker_id, cached, crash_looping, ready) in managed {
+        // The events watcher keeps most statuses cached; only inspect the ones it hasn't seen
+        let status = match cached {
+            Some(status) => Some(status),
+            None => match state.docker.get_container(&docker_id).await {
+                Ok(info) => ContainerStatus::from_docker_state(&info.state),
+                Err(e) => {
+                    tracing::debug!("Failed to inspect {} for status listing: {}", name, e);
+                    None
+                }
+            },
+        };
+
+        if let Some(status) = status {
+            statuses.insert(name, ContainerStatusResponse {
+                status: status.as_str().to_string(),
+                running: status.is_running(),
+                exit_code: None,
+                started_at: None,
+                finished_at: None,
+                restart_count: None,
+                crash_looping,
+                ready: ready && status.is_running(),
+            });
+        }
+    }
+
+    Ok(Json(statuses))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogHistoryQuery {
+    pub search: Option<String>,
+    #[serde(default = "default_history_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub include_current: bool,
+}
+
+fn default_history_limit() -> usize {
+    500
+}
+
+pub async fn get_log_history(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(query): Query<LogHistoryQuery>,
+) -> Result<Json<Vec<crate::log_store::LogEntry>>, (StatusCode, String)> {
+    if !verify_api_key(&headers, &state) {
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
+    }
+
+    let container = get_container_clone(&state, &id)
+        .ok_or((StatusCode::NOT_FOUND, "Container not found".into()))?;
+
+    // Lines of the running container are already served by the live log stream
+    let exclude = if query.include_current { None } else { Some(container.docker_id.as_str()) };
+
+    let entries = state.log_store
+        .read_history(&container.name, exclude, query.search.as_deref(), query.limit.clamp(1, 5000))
+        .await;
+
+    Ok(Json(entries))
+}
+
 pub async fn ws_container_stats(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -1273,7 +2497,7 @@ pub async fn ws_container_stats(
 ) -> impl IntoResponse {
     let api_key = params.get("api_key").cloned().unwrap_or_default();
 
-    if api_key != state.api_key {
+    if !raptor_common::secrets::secrets_match(&api_key, &state.api_key) {
         return (StatusCode::UNAUTHORIZED, "Invalid API key").into_response();
     }
 
@@ -1294,16 +2518,31 @@ async fn handle_container_stats_socket(socket: WebSocket, state: Arc<AppState>,
 
     state.docker.stream_container_stats(&docker_id, tx);
 
+    let keepalive = Keepalive::new();
+
     let send_task = async {
-        while let Ok(stats) = rx.recv().await {
-            if sender.send(Message::Text(stats)).await.is_err() {
-                break;
+        let mut ping = Keepalive::interval();
+
+        loop {
+            tokio::select! {
+                stats = rx.recv() => {
+                    let Ok(stats) = stats else { break };
+                    if sender.send(Message::Text(stats)).await.is_err() {
+                        break;
+                    }
+                }
+                _ = ping.tick() => {
+                    if !keepalive.ping(&mut sender).await {
+                        break;
+                    }
+                }
             }
         }
     };
 
     let recv_task = async {
         while let Some(msg) = receiver.next().await {
+            keepalive.seen();
             match msg {
                 Ok(Message::Close(_)) => break,
                 Err(_) => break,
@@ -1349,14 +2588,12 @@ pub async fn list_files(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    let base_path = std::env::var("FTP_BASE_PATH")
-        .unwrap_or_else(|_| std::env::var("SFTP_BASE_PATH")
-            .unwrap_or_else(|_| "/data/raptor".into()));
-    let container_path = std::path::Path::new(&base_path).join("volumes").join(&container_name);
+    let base_path = crate::config::base_path();
+    let container_path = base_path.join("volumes").join(&container_name);
     let rel_path = query.path.unwrap_or_else(|| "/".into());
     let full_path = container_path.join(rel_path.trim_start_matches('/'));
 
-    tracing::info!("list_files: base_path={}, container_path={:?}, full_path={:?}", base_path, container_path, full_path);
+    tracing::info!("list_files: base_path={:?}, container_path={:?}, full_path={:?}", base_path, container_path, full_path);
 
     if !full_path.starts_with(&container_path) {
         return Err(StatusCode::FORBIDDEN);
@@ -1402,29 +2639,143 @@ pub async fn list_files(
 #[serde(rename_all = "camelCase")]
 pub struct ReadFileQuery {
     pub path: String,
+    /// Only the last this many bytes.
+    #[serde(default)]
+    pub tail: Option<u64>,
+    /// Start of a byte range of `length` bytes (up to the read limit by default).
+    #[serde(default)]
+    pub offset: Option<u64>,
+    #[serde(default)]
+    pub length: Option<u64>,
 }
 
+/// Largest read served at once; bigger files are only readable with `tail` or a range.
+const MAX_FILE_READ_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Reads in flight at once. More get 429, so a burst of big reads can't exhaust memory.
+static FILE_READS: Lazy<tokio::sync::Semaphore> = Lazy::new(|| tokio::sync::Semaphore::new(8));
+
+/// Text comes back as UTF-8; anything else base64 encoded with `X-File-Encoding: base64` and
+/// a guessed `X-File-Content-Type`. `X-File-Size` and `X-File-Offset` locate partial reads.
 pub async fn read_file(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(container_name): Path<String>,
     Query(query): Query<ReadFileQuery>,
-) -> Result<String, StatusCode> {
+) -> Result<axum::response::Response, (StatusCode, String)> {
+    use axum::response::IntoResponse;
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
     if !verify_api_key(&headers, &state) {
-        return Err(StatusCode::UNAUTHORIZED);
+        return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
     }
 
-    let base_path = std::env::var("FTP_BASE_PATH")
-        .unwrap_or_else(|_| std::env::var("SFTP_BASE_PATH")
-            .unwrap_or_else(|_| "/data/raptor".into()));
-    let container_path = std::path::Path::new(&base_path).join("volumes").join(&container_name);
+    let base_path = crate::config::base_path();
+    let container_path = base_path.join("volumes").join(&container_name);
     let full_path = container_path.join(query.path.trim_start_matches('/'));
 
     if !full_path.starts_with(&container_path) {
-        return Err(StatusCode::FORBIDDEN);
+        return Err((StatusCode::FORBIDDEN, "Path is outside the container volume".into()));
+    }
+
+    let _permit = FILE_READS
+        .try_acquire()
+        .map_err(|_| (StatusCode::TOO_MANY_REQUESTS, "Too many file reads in progress, try again shortly".into()))?;
+
+    let not_found = |_| (StatusCode::NOT_FOUND, "File not found".to_string());
+    let metadata = tokio::fs::metadata(&full_path).await.map_err(not_found)?;
+    if !metadata.is_file() {
+        return Err((StatusCode::BAD_REQUEST, "Not a file".into()));
+    }
+
+    let size = metadata.len();
+    let (start, length) = match (query.tail, query.offset) {
+        (Some(tail), _) => {
+            let length = tail.min(MAX_FILE_READ_BYTES).min(size);
+            (size - length, length)
+        }
+        (None, Some(offset)) => {
+            let offset = offset.min(size);
+            let length = query.length.unwrap_or(MAX_FILE_READ_BYTES).min(MAX_FILE_READ_BYTES).min(size - offset);
+            (offset, length)
+        }
+        (None, None) if size > MAX_FILE_READ_BYTES => {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!(
+                    "File is {:.1} MB; files over {} MB can only be read partially with ?tail= or ?offset=",
+                    size as f64 / (1024.0 * 1024.0),
+                    MAX_FILE_READ_BYTES / (1024 * 1024)
+                ),
+            ));
+        }
+        (None, None) => (0, size),
+    };
+
+    let mut file = tokio::fs::File::open(&full_path).await.map_err(not_found)?;
+    let mut data = Vec::with_capacity(length as usize);
+    let read = async {
+        file.seek(std::io::SeekFrom::Start(start)).await?;
+        file.take(length).read_to_end(&mut data).await
+    };
+    read.await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read file: {}", e)))?;
+
+    let ranged = length < size;
+    let text = if data.contains(&0) {
+        Err(data)
+    } else if ranged {
+        // A range may cut a multi-byte character at either end
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    } else {
+        String::from_utf8(data).map_err(|e| e.into_bytes())
+    };
+
+    let location = [
+        ("x-file-size", size.to_string()),
+        ("x-file-offset", start.to_string()),
+    ];
+
+    Ok(match text {
+        Ok(text) => (
+            [(axum::http::header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string())],
+            location,
+            text,
+        ).into_response(),
+        Err(data) => {
+            use base64::Engine;
+            (
+                [
+                    (axum::http::header::CONTENT_TYPE, "text/plain".to_string()),
+                    (axum::http::HeaderName::from_static("x-file-encoding"), "base64".to_string()),
+                    (axum::http::HeaderName::from_static("x-file-content-type"), content_type_hint(&full_path).to_string()),
+                ],
+                location,
+                base64::engine::general_purpose::STANDARD.encode(&data),
+            ).into_response()
+        }
+    })
+}
+
+fn content_type_hint(path: &std::path::Path) -> &'static str {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "zip" => "application/zip",
+        "jar" => "application/java-archive",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
     }
+}
 
-    tokio::fs::read_to_string(&full_path).await.map_err(|_| StatusCode::NOT_FOUND)
+/// Read-only containers keep their volume as installed; the file manager may only read it.
+fn is_read_only(state: &AppState, container_name: &str) -> bool {
+    state.containers.get(container_name).is_some_and(|c| c.resources.read_only)
 }
 
 #[derive(Debug, Deserialize)]
@@ -1446,10 +2797,12 @@ pub async fn write_file(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    let base_path = std::env::var("FTP_BASE_PATH")
-        .unwrap_or_else(|_| std::env::var("SFTP_BASE_PATH")
-            .unwrap_or_else(|_| "/data/raptor".into()));
-    let container_path = std::path::Path::new(&base_path).join("volumes").join(&container_name);
+    if is_read_only(&state, &container_name) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let base_path = crate::config::base_path();
+    let container_path = base_path.join("volumes").join(&container_name);
     let full_path = container_path.join(req.path.trim_start_matches('/'));
 
     tracing::info!("write_file: container={}, path={}, full_path={:?}", container_name, req.path, full_path);
@@ -1503,10 +2856,12 @@ pub async fn create_folder(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    let base_path = std::env::var("FTP_BASE_PATH")
-        .unwrap_or_else(|_| std::env::var("SFTP_BASE_PATH")
-            .unwrap_or_else(|_| "/data/raptor".into()));
-    let container_path = std::path::Path::new(&base_path).join("volumes").join(&container_name);
+    if is_read_only(&state, &container_name) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let base_path = crate::config::base_path();
+    let container_path = base_path.join("volumes").join(&container_name);
     let full_path = container_path.join(req.path.trim_start_matches('/'));
 
     if !full_path.starts_with(&container_path) {
@@ -1534,10 +2889,12 @@ pub async fn delete_file(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    let base_path = std::env::var("FTP_BASE_PATH")
-        .unwrap_or_else(|_| std::env::var("SFTP_BASE_PATH")
-            .unwrap_or_else(|_| "/data/raptor".into()));
-    let container_path = std::path::Path::new(&base_path).join("volumes").join(&container_name);
+    if is_read_only(&state, &container_name) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let base_path = crate::config::base_path();
+    let container_path = base_path.join("volumes").join(&container_name);
     let full_path = container_path.join(query.path.trim_start_matches('/'));
 
     if !full_path.starts_with(&container_path) {
@@ -1563,10 +2920,8 @@ pub async fn download_file(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    let base_path = std::env::var("FTP_BASE_PATH")
-        .unwrap_or_else(|_| std::env::var("SFTP_BASE_PATH")
-            .unwrap_or_else(|_| "/data/raptor".into()));
-    let container_path = std::path::Path::new(&base_path).join("volumes").join(&container_name);
+    let base_path = crate::config::base_path();
+    let container_path = base_path.join("volumes").join(&container_name);
     let full_path = container_path.join(query.path.trim_start_matches('/'));
 
     if !full_path.starts_with(&container_path) {
@@ -1588,50 +2943,70 @@ pub async fn download_file(
     ).into_response())
 }
 
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixPermissionsRequest {
+    /// Directory inside the volume to fix; the whole volume when omitted.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// `.dockerignore`-style patterns skipped in addition to FIX_PERMISSIONS_EXCLUDE.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// Chowns the volume to the container user, streaming progress as NDJSON lines.
 pub async fn fix_permissions(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(container_name): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    body: Option<Json<FixPermissionsRequest>>,
+) -> Result<axum::response::Response, (StatusCode, String)> {
     if !verify_api_key(&headers, &state) {
         return Err((StatusCode::UNAUTHORIZED, "Unauthorized".into()));
     }
 
-    let base_path = std::env::var("FTP_BASE_PATH")
-        .unwrap_or_else(|_| std::env::var("SFTP_BASE_PATH")
-            .unwrap_or_else(|_| "/data/raptor".into()));
-    let container_path = std::path::Path::new(&base_path).join("volumes").join(&container_name);
+    let req = body.map(|Json(b)| b).unwrap_or_default();
+
+    let base_path = crate::config::base_path();
+    let container_path = base_path.join("volumes").join(&container_name);
 
     if !container_path.exists() {
         return Err((StatusCode::NOT_FOUND, "Container volume not found".into()));
     }
 
-    // chown -R to the container user (UID/GID 1000, matching the container's internal user)
-    let chown_output = tokio::process::Command::new("chown")
-        .args(["-R", "1000:1000", container_path.to_str().unwrap_or("")])
-        .output()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to run chown: {}", e)))?;
+    let (container_path, scope) = match permissions::resolve_scope(&container_path, req.path.as_deref()) {
+        Ok(resolved) => resolved,
+        Err(permissions::ScopeError::Invalid) => return Err((StatusCode::BAD_REQUEST, "Invalid path".into())),
+        Err(permissions::ScopeError::NotFound) => return Err((StatusCode::NOT_FOUND, "Path not found".into())),
+    };
 
-    if !chown_output.status.success() {
-        let stderr = String::from_utf8_lossy(&chown_output.stderr);
-        tracing::warn!("chown stderr: {}", stderr);
-    }
+    let mut patterns = permissions::default_excludes();
+    patterns.extend(req.exclude);
+    let excludes = permissions::Excludes::new(&patterns);
+    let max_entries = permissions::max_dir_entries();
 
-    // chmod -R 755 for dirs, 644 for files
-    let chmod_output = tokio::process::Command::new("chmod")
-        .args(["-R", "u+rwX,g+rX,o+rX", container_path.to_str().unwrap_or("")])
-        .output()
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to run chmod: {}", e)))?;
+    let (tx, rx) = tokio::sync::mpsc::channel::<permissions::FixProgress>(64);
 
-    if !chmod_output.status.success() {
-        let stderr = String::from_utf8_lossy(&chmod_output.stderr);
-        tracing::warn!("chmod stderr: {}", stderr);
-    }
+    tokio::task::spawn_blocking(move || {
+        let summary = permissions::fix_tree(&container_path, &scope, &excludes, max_entries, &tx);
+        tracing::info!(
+            "Fixed permissions for container volume {}: {} entries, {} changed, {} skipped, {} failed",
+            container_name, summary.processed, summary.changed, summary.skipped, summary.failed
+        );
+        let _ = tx.blocking_send(permissions::FixProgress::Done(summary));
+    });
 
-    tracing::info!("Fixed permissions for container volume: {}", container_name);
-    Ok(Json(serde_json::json!({"message": "Permissions fixed successfully"})))
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        let event = rx.recv().await?;
+        let mut line = serde_json::to_vec(&event).unwrap_or_default();
+        line.push(b'\n');
+        Some((Ok::<_, std::convert::Infallible>(line), rx))
+    });
+
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "application/x-ndjson")],
+        axum::body::Body::from_stream(stream),
+    ).into_response())
 }
 
 use std::collections::HashMap as StdHashMap;
@@ -1659,6 +3034,21 @@ pub struct WriteChunkRequest {
     pub content: String,
 }
 
+/// GET /uploads/limits - the chunk size `write-chunk` accepts
+pub async fn upload_limits(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<raptor_common::uploads::UploadLimits>, StatusCode> {
+    if !verify_api_key(&headers, &state) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(Json(raptor_common::uploads::UploadLimits {
+        chunk_size: state.upload_chunk_size,
+        max_body_size: raptor_common::uploads::base64_body_limit(state.upload_chunk_size),
+    }))
+}
+
 pub async fn write_file_chunk(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
@@ -1669,10 +3059,12 @@ pub async fn write_file_chunk(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    let base_path = std::env::var("FTP_BASE_PATH")
-        .unwrap_or_else(|_| std::env::var("SFTP_BASE_PATH")
-            .unwrap_or_else(|_| "/data/raptor".into()));
-    let container_path = std::path::Path::new(&base_path).join("volumes").join(&container_name);
+    if is_read_only(&state, &container_name) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let base_path = crate::config::base_path();
+    let container_path = base_path.join("volumes").join(&container_name);
     let final_path = container_path.join(req.path.trim_start_matches('/'));
 
     if !final_path.starts_with(&container_path) {
@@ -1688,6 +3080,14 @@ pub async fn write_file_chunk(
             StatusCode::BAD_REQUEST
         })?;
 
+    if chunk_data.len() > state.upload_chunk_size {
+        tracing::warn!(
+            "write_file_chunk: chunk of {} bytes exceeds the {} byte limit",
+            chunk_data.len(), state.upload_chunk_size
+        );
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
     let storage_key = format!("{}:{}", container_name, req.upload_id);
     let mut storage = DAEMON_CHUNK_STORAGE.lock().await;
 
@@ -1763,7 +3163,6 @@ pub async fn write_file_chunk(
             }
         };
 
-        use tokio::io::AsyncWriteExt;
         let mut total_bytes = 0u64;
         for i in 0..total {
             let chunk_file = temp_dir.join(format!("chunk_{:06}", i));
@@ -1940,6 +3339,10 @@ pub async fn create_user_database(
         return Err((StatusCode::BAD_REQUEST, "Database server is not running".to_string()));
     }
 
+    raptor_common::databases::validate_identifier(&req.db_name)
+        .and_then(|_| raptor_common::databases::validate_identifier(&req.db_user))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
     database_manager::create_user_database(&server, &req.db_name, &req.db_user, &req.db_password)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
@@ -1961,6 +3364,10 @@ pub async fn delete_user_database(
         return Err((StatusCode::BAD_REQUEST, "Database server is not running".to_string()));
     }
 
+    raptor_common::databases::validate_identifier(&req.db_name)
+        .and_then(|_| raptor_common::databases::validate_identifier(&req.db_user))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
     database_manager::delete_user_database(&server, &req.db_name, &req.db_user)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
@@ -1982,6 +3389,10 @@ pub async fn reset_user_database_password(
         return Err((StatusCode::BAD_REQUEST, "Database server is not running".to_string()));
     }
 
+    raptor_common::databases::validate_identifier(&req.db_name)
+        .and_then(|_| raptor_common::databases::validate_identifier(&req.db_user))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
     database_manager::reset_user_database_password(&server, &req.db_name, &req.db_user, &req.new_password)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
@@ -1990,3 +3401,33 @@ pub async fn reset_user_database_password(
         "message": "Password reset successfully"
     })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_bindings_unbracket_ipv6() {
+        for ip in ["[2001:db8::1]", "2001:db8::1", "2001:db8:0:0:0:0:0:1"] {
+            let binding = host_binding(ip, 25565);
+            assert_eq!(binding.host_ip.as_deref(), Some("2001:db8::1"), "{}", ip);
+            assert_eq!(binding.host_port.as_deref(), Some("25565"));
+        }
+    }
+
+    #[test]
+    fn host_bindings_keep_ipv4() {
+        let binding = host_binding(" 10.0.0.5 ", 27015);
+        assert_eq!(binding.host_ip.as_deref(), Some("10.0.0.5"));
+        assert_eq!(binding.host_port.as_deref(), Some("27015"));
+    }
+
+    #[test]
+    fn ipv4_and_ipv6_wildcards_bind_the_same_port_separately() {
+        let bindings = [host_binding("0.0.0.0", 25565), host_binding("[::]", 25565)];
+        let hosts: Vec<_> = bindings.iter().map(|b| b.host_ip.as_deref()).collect();
+
+        assert_eq!(hosts, [Some("0.0.0.0"), Some("::")]);
+        assert!(bindings.iter().all(|b| b.host_port.as_deref() == Some("25565")));
+    }
+}
@@ -0,0 +1,39 @@
+use axum::extract::ws::{Message, WebSocket};
+use futures_util::stream::SplitSink;
+use futures_util::SinkExt;
+use raptor_common::keepalive::{KeepaliveCheck, Liveness};
+
+/// When a websocket peer was last heard from. Clones share the timestamp, so the receive half
+/// records frames while the send half pings.
+#[derive(Clone)]
+pub struct Keepalive {
+    liveness: Liveness,
+}
+
+impl Keepalive {
+    pub fn new() -> Self {
+        Self { liveness: Liveness::new(None) }
+    }
+
+    /// Any frame, pongs included, shows the peer is still there.
+    pub fn seen(&self) {
+        self.liveness.seen(false);
+    }
+
+    /// Ticks every `PING_INTERVAL`, starting one interval from now.
+    pub fn interval() -> tokio::time::Interval {
+        Liveness::interval()
+    }
+
+    /// Pings the peer, or closes the socket if it stopped answering. Returns false once the
+    /// socket should be dropped.
+    pub async fn ping(&self, sender: &mut SplitSink<WebSocket, Message>) -> bool {
+        if self.liveness.check() != KeepaliveCheck::Ping {
+            tracing::debug!("Closing websocket that stopped answering pings");
+            let _ = sender.send(Message::Close(None)).await;
+            return false;
+        }
+
+        sender.send(Message::Ping(Vec::new())).await.is_ok()
+    }
+}
@@ -0,0 +1,122 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    /// Messages from the daemon itself: connection, install progress, skipped lines.
+    System,
+}
+
+/// One line of console output on its way to a log websocket.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    pub stream: LogStream,
+    pub line: String,
+    pub ts: DateTime<Utc>,
+}
+
+impl LogLine {
+    pub fn new(stream: LogStream, line: impl Into<String>) -> Self {
+        Self { stream, line: line.into(), ts: Utc::now() }
+    }
+
+    pub fn system(line: impl Into<String>) -> Self {
+        Self::new(LogStream::System, line)
+    }
+
+    /// Splits the RFC 3339 timestamp Docker prefixes lines with when `timestamps` is set.
+    pub fn from_docker(stream: LogStream, text: &str) -> Self {
+        match text.split_once(' ') {
+            Some((ts, rest)) => match DateTime::parse_from_rfc3339(ts) {
+                Ok(ts) => Self { stream, line: rest.to_string(), ts: ts.with_timezone(&Utc) },
+                Err(_) => Self::new(stream, text),
+            },
+            None => Self::new(stream, text),
+        }
+    }
+
+    /// `[timestamp] line` without ANSI codes, as written to downloaded log files.
+    pub fn to_plain_text(&self) -> String {
+        format!("[{}] {}\n", self.ts.to_rfc3339_opts(SecondsFormat::Secs, true), strip_ansi(&self.line))
+    }
+}
+
+/// `?format=` of the log websocket.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Raw output with ANSI colors and stderr in red, for xterm.
+    #[default]
+    Terminal,
+    /// ANSI escape sequences stripped.
+    Plain,
+    /// `{ "stream", "line", "ts" }` objects with plain text lines.
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(value: Option<&str>) -> Self {
+        match value.map(|v| v.to_ascii_lowercase()).as_deref() {
+            Some("plain") | Some("text") => Self::Plain,
+            Some("json") => Self::Json,
+            _ => Self::Terminal,
+        }
+    }
+
+    pub fn render(&self, log: &LogLine) -> String {
+        match self {
+            Self::Terminal => match log.stream {
+                LogStream::Stderr => format!("\x1b[31m{}\x1b[0m", log.line),
+                _ => log.line.clone(),
+            },
+            Self::Plain => strip_ansi(&log.line),
+            Self::Json => serde_json::json!({
+                "stream": log.stream,
+                "line": strip_ansi(&log.line),
+                "ts": log.ts,
+            })
+            .to_string(),
+        }
+    }
+}
+
+/// Removes ANSI escape sequences (CSI such as colors, OSC such as window titles, and two-byte escapes).
+pub fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('[') => {
+                // Parameters and intermediates until a final byte in @..~
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                // Terminated by BEL or ESC \
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
@@ -0,0 +1,241 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use crate::docker::DockerManager;
+use crate::log_format::{LogLine, LogStream};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    pub timestamp: i64,
+    pub docker_id: String,
+    pub line: String,
+}
+
+pub struct LogStore {
+    enabled: bool,
+    dir: PathBuf,
+    max_bytes: u64,
+    active: Arc<DashMap<String, String>>,
+}
+
+impl LogStore {
+    pub fn from_env() -> Self {
+        let data_dir = std::env::var("DAEMON_DATA_DIR")
+            .unwrap_or_else(|_| "/var/lib/raptor-daemon".to_string());
+
+        Self {
+            enabled: std::env::var("LOG_HISTORY_ENABLED")
+                .map(|v| v != "false" && v != "0")
+                .unwrap_or(true),
+            dir: std::env::var("LOG_HISTORY_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from(data_dir).join("logs")),
+            max_bytes: std::env::var("LOG_HISTORY_MAX_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5 * 1024 * 1024),
+            active: Arc::new(DashMap::new()),
+        }
+    }
+
+    fn log_path(&self, container_name: &str) -> PathBuf {
+        self.dir.join(format!("{}.log", container_name))
+    }
+
+    fn rotated_path(&self, container_name: &str) -> PathBuf {
+        self.dir.join(format!("{}.log.1", container_name))
+    }
+
+    /// Starts appending new console output of `docker_id` to the container's history file.
+    /// Only one writer runs per container, so lines are never persisted twice.
+    pub fn ensure_persisting(&self, docker: &DockerManager, container_name: &str, docker_id: &str) {
+        if !self.enabled {
+            return;
+        }
+
+        if let Some(current) = self.active.get(container_name) {
+            if current.value() == docker_id {
+                return;
+            }
+        }
+        self.active.insert(container_name.to_string(), docker_id.to_string());
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        docker.follow_logs(docker_id, tx);
+
+        let active = self.active.clone();
+        let path = self.log_path(container_name);
+        let rotated = self.rotated_path(container_name);
+        let max_bytes = self.max_bytes;
+        let container_name = container_name.to_string();
+        let docker_id = docker_id.to_string();
+
+        tokio::spawn(async move {
+            if let Some(parent) = path.parent() {
+                if let Err(e) = tokio::fs::create_dir_all(parent).await {
+                    tracing::error!("Failed to create log history directory: {}", e);
+                    active.remove_if(&container_name, |_, id| id == &docker_id);
+                    return;
+                }
+            }
+
+            let mut file = match open_append(&path).await {
+                Ok(f) => f,
+                Err(e) => {
+                    tracing::error!("Failed to open log history for {}: {}", container_name, e);
+                    active.remove_if(&container_name, |_, id| id == &docker_id);
+                    return;
+                }
+            };
+            let mut written = file.metadata().await.map(|m| m.len()).unwrap_or(0);
+
+            while let Some(line) = rx.recv().await {
+                let entry = LogEntry {
+                    timestamp: chrono::Utc::now().timestamp(),
+                    docker_id: docker_id.clone(),
+                    line,
+                };
+
+                let mut json = match serde_json::to_string(&entry) {
+                    Ok(j) => j,
+                    Err(_) => continue,
+                };
+                json.push('\n');
+
+                if written + json.len() as u64 > max_bytes {
+                    let _ = file.flush().await;
+                    if let Err(e) = tokio::fs::rename(&path, &rotated).await {
+                        tracing::warn!("Failed to rotate log history for {}: {}", container_name, e);
+                    }
+                    file = match open_append(&path).await {
+                        Ok(f) => f,
+                        Err(e) => {
+                            tracing::error!("Failed to reopen log history for {}: {}", container_name, e);
+                            break;
+                        }
+                    };
+                    written = 0;
+                }
+
+                if let Err(e) = file.write_all(json.as_bytes()).await {
+                    tracing::error!("Failed to write log history for {}: {}", container_name, e);
+                    break;
+                }
+                written += json.len() as u64;
+            }
+
+            active.remove_if(&container_name, |_, id| id == &docker_id);
+            tracing::debug!("Log persistence for {} ({}) finished", container_name, docker_id);
+        });
+    }
+
+    /// Reads persisted history, oldest first, returning at most `limit` of the newest matching entries.
+    pub async fn read_history(
+        &self,
+        container_name: &str,
+        exclude_docker_id: Option<&str>,
+        search: Option<&str>,
+        limit: usize,
+    ) -> Vec<LogEntry> {
+        let search = search.map(|s| s.to_lowercase()).filter(|s| !s.is_empty());
+        let mut entries: VecDeque<LogEntry> = VecDeque::with_capacity(limit.min(1024));
+
+        for path in [self.rotated_path(container_name), self.log_path(container_name)] {
+            let file = match tokio::fs::File::open(&path).await {
+                Ok(f) => f,
+                Err(_) => continue,
+            };
+
+            let mut lines = tokio::io::BufReader::new(file).lines();
+            while let Ok(Some(raw)) = lines.next_line().await {
+                let entry: LogEntry = match serde_json::from_str(&raw) {
+                    Ok(e) => e,
+                    Err(_) => continue,
+                };
+
+                if exclude_docker_id.map(|id| id == entry.docker_id).unwrap_or(false) {
+                    continue;
+                }
+                if let Some(ref needle) = search {
+                    if !entry.line.to_lowercase().contains(needle) {
+                        continue;
+                    }
+                }
+
+                if entries.len() == limit {
+                    entries.pop_front();
+                }
+                entries.push_back(entry);
+            }
+        }
+
+        entries.into_iter().collect()
+    }
+
+    /// Streams the persisted history from `since` (unix seconds) on as plain text, oldest first.
+    /// `None` when history is off or nothing was persisted for the container.
+    pub fn export(&self, container_name: &str, since: Option<i64>) -> Option<mpsc::Receiver<String>> {
+        let paths: Vec<PathBuf> = [self.rotated_path(container_name), self.log_path(container_name)]
+            .into_iter()
+            .filter(|p| p.exists())
+            .collect();
+        if !self.enabled || paths.is_empty() {
+            return None;
+        }
+
+        let (tx, rx) = mpsc::channel(256);
+
+        tokio::spawn(async move {
+            for path in paths {
+                let file = match tokio::fs::File::open(&path).await {
+                    Ok(f) => f,
+                    Err(_) => continue,
+                };
+
+                let mut lines = tokio::io::BufReader::new(file).lines();
+                while let Ok(Some(raw)) = lines.next_line().await {
+                    let entry: LogEntry = match serde_json::from_str(&raw) {
+                        Ok(e) => e,
+                        Err(_) => continue,
+                    };
+                    if since.is_some_and(|since| entry.timestamp < since) {
+                        continue;
+                    }
+
+                    let line = LogLine {
+                        stream: LogStream::Stdout,
+                        line: entry.line,
+                        ts: chrono::TimeZone::timestamp_opt(&chrono::Utc, entry.timestamp, 0)
+                            .single()
+                            .unwrap_or_default(),
+                    };
+                    if tx.send(line.to_plain_text()).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Some(rx)
+    }
+
+    pub async fn remove(&self, container_name: &str) {
+        self.active.remove(container_name);
+        let _ = tokio::fs::remove_file(self.log_path(container_name)).await;
+        let _ = tokio::fs::remove_file(self.rotated_path(container_name)).await;
+    }
+}
+
+async fn open_append(path: &Path) -> std::io::Result<tokio::fs::File> {
+    tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+}
@@ -1,9 +1,22 @@
+mod alerts;
+mod backups;
+mod callback;
 mod config;
 mod database_manager;
 mod docker;
+mod events;
 mod ftp;
 mod handlers;
+mod keepalive;
+mod log_format;
+mod log_store;
+mod metrics;
 mod models;
+mod permissions;
+mod readiness;
+mod reconcile;
+mod registry;
+mod system_stats;
 
 use axum::{
     routing::{get, post, delete, patch},
@@ -11,21 +24,71 @@ use axum::{
     extract::DefaultBodyLimit,
 };
 use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
+use axum::http::HeaderValue;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use crate::callback::PanelCallback;
 use crate::config::Config;
 use crate::database_manager::DatabaseManager;
 use crate::docker::DockerManager;
-use crate::models::{AppState, ContainerLocks};
+use crate::log_store::LogStore;
+use crate::models::{AppState, ContainerLocks, RuntimeConfig};
 use crate::ftp::FtpServerState;
 
-pub const UPLOAD_CHUNK_SIZE: usize = 55 * 1024 * 1024;
+pub const MAX_FILE_WRITE_SIZE: usize = 500 * 1024 * 1024;
 
-pub const UPLOAD_CHUNK_BODY_LIMIT: usize = 80 * 1024 * 1024;
+/// Credentialed CORS for the configured origins; `Any` only with the explicit CORS_ALLOW_ALL opt-in.
+fn cors_layer(config: &Config) -> CorsLayer {
+    if config.cors_allow_all {
+        tracing::warn!("CORS_ALLOW_ALL is set: any origin may call this server");
+        return CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any);
+    }
 
-pub const MAX_FILE_WRITE_SIZE: usize = 500 * 1024 * 1024;
+    let origins: Vec<HeaderValue> = config
+        .cors_allowed_origins
+        .iter()
+        .filter_map(|origin| match origin.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                tracing::warn!("Ignoring invalid CORS origin {}", origin);
+                None
+            }
+        })
+        .collect();
+
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(AllowHeaders::mirror_request())
+        .allow_credentials(true)
+}
+
+/// Applies the hot-reloadable config (see `config::RELOADABLE_ENV`) on SIGHUP.
+fn spawn_reload_on_sighup(state: Arc<AppState>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            tracing::warn!("Failed to install SIGHUP handler, config reload is only available via POST /reload: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match state.reload_config() {
+                Ok(changed) => tracing::info!("Reloaded config on SIGHUP, changed: {:?}", changed),
+                Err(e) => tracing::error!("Failed to reload config on SIGHUP: {}", e),
+            }
+        }
+    });
+}
 
 fn load_tls_config(cert_path: &str, key_path: &str) -> anyhow::Result<axum_server::tls_rustls::RustlsConfig> {
     use std::fs::File;
@@ -62,6 +125,11 @@ fn load_tls_config(cert_path: &str, key_path: &str) -> anyhow::Result<axum_serve
 async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
 
+    // `echo -n "$TOKEN" | raptor-daemon encrypt-registry-secret` prints a value for registries.json
+    if std::env::args().nth(1).as_deref() == Some("encrypt-registry-secret") {
+        return registry::print_encrypted_secret();
+    }
+
     rustls::crypto::ring::default_provider()
         .install_default()
         .expect("Failed to install rustls crypto provider");
@@ -76,16 +144,19 @@ async fn main() -> anyhow::Result<()> {
     let config = Config::from_env();
     tracing::info!("Loaded config: {:?}", config);
 
-    let docker = DockerManager::new().await?;
+    let docker = DockerManager::new(config.create_concurrency).await?;
 
-    let ftp_base_path = std::env::var("FTP_BASE_PATH")
-        .unwrap_or_else(|_| std::env::var("SFTP_BASE_PATH")
-            .unwrap_or_else(|_| "/Users/lubomirstankov/Development/me/raptor/daemon".into()));
-    let ftp_state = Arc::new(FtpServerState::new(&ftp_base_path));
+    let base_path = crate::config::init_base_path(&config)?;
+    tracing::info!("Using base path {}", base_path.display());
+    if let Some(assets_path) = crate::config::init_assets_path(&config) {
+        tracing::info!("Serving asset mounts from {}", assets_path.display());
+    }
+    let ftp_state = Arc::new(FtpServerState::new(base_path));
 
     let saved_containers = handlers::load_container_state().await;
     let containers_map = dashmap::DashMap::new();
     for container in saved_containers {
+        ftp_state.set_read_only(&container.name, container.resources.read_only);
         containers_map.insert(container.name.clone(), container);
     }
     tracing::info!("Loaded {} containers from saved state", containers_map.len());
@@ -97,18 +168,43 @@ async fn main() -> anyhow::Result<()> {
     let app_state = Arc::new(AppState {
         docker,
         api_key: config.daemon_api_key.clone(),
+        metrics_token: config.metrics_token.clone(),
         containers: containers_map,
         ftp_state: ftp_state.clone(),
         container_locks: ContainerLocks::new(),
         database_manager,
+        log_store: LogStore::from_env(),
+        readiness_checks: dashmap::DashMap::new(),
+        runtime: std::sync::RwLock::new(RuntimeConfig::from_config(&config)),
+        panel: PanelCallback::new(config.api_callback_url.clone(), config.daemon_api_key.clone()),
+        system_stats: Default::default(),
+        upload_chunk_size: config.upload_chunk_size,
     });
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    let state_for_docker = app_state.clone();
+    tokio::spawn(async move {
+        state_for_docker.docker.wait_until_available().await;
 
-    let app = Router::new()
+        match reconcile::reconcile(&state_for_docker).await {
+            Ok(report) if report.is_clean() => tracing::info!("Container state matches Docker"),
+            Ok(report) => tracing::warn!("Reconciled container state with Docker: {:?}", report),
+            Err(e) => tracing::error!("Failed to reconcile container state with Docker: {}", e),
+        }
+
+        for entry in state_for_docker.containers.iter() {
+            state_for_docker.log_store.ensure_persisting(&state_for_docker.docker, entry.key(), &entry.value().docker_id);
+        }
+
+        state_for_docker.docker.monitor_connection().await;
+    });
+
+    events::spawn(app_state.clone());
+    alerts::spawn(app_state.clone());
+    spawn_reload_on_sighup(app_state.clone());
+
+    let cors = cors_layer(&config);
+
+    let docker_routes = Router::new()
 
         .route("/containers", get(handlers::list_containers))
         .route("/containers", post(handlers::create_container))
@@ -119,12 +215,22 @@ async fn main() -> anyhow::Result<()> {
         .route("/containers/:id/stop", post(handlers::stop_container))
         .route("/containers/:id/restart", post(handlers::restart_container))
         .route("/containers/:id/recreate", post(handlers::recreate_container))
+        .route("/containers/:id/install", post(handlers::install_container))
         .route("/containers/:id/kill", post(handlers::kill_container))
         .route("/containers/:id/command", post(handlers::send_command))
         .route("/containers/:id/graceful-stop", post(handlers::graceful_stop_container))
         .route("/containers/:id/ftp", post(handlers::create_ftp))
+        .route("/containers/:id/ftp", delete(handlers::delete_ftp))
         .route("/containers/:id/stats", get(handlers::get_container_stats))
         .route("/containers/:id/status", get(handlers::get_container_status))
+        .route("/containers/:id/resources", get(handlers::get_container_resources))
+        .route("/containers/statuses", get(handlers::list_container_statuses))
+        .route("/containers/:id/logs", get(handlers::get_logs))
+        .route("/containers/:id/logs/history", get(handlers::get_log_history))
+        .route("/containers/:id/logs/download", get(handlers::download_logs))
+        .route("/containers/:id/backups", get(handlers::list_backups))
+        .route("/containers/:id/backups", post(handlers::create_backup))
+        .route("/containers/:id/backups/:backup", delete(handlers::delete_backup))
 
         .route("/allocations", get(handlers::list_allocations))
         .route("/allocations/assign", post(handlers::assign_allocation))
@@ -132,19 +238,21 @@ async fn main() -> anyhow::Result<()> {
         .route("/ws/containers/:id/logs", get(handlers::ws_logs))
 
         .route("/ws/containers/:id/stats", get(handlers::ws_container_stats))
-
-        .route("/ws/system", get(handlers::ws_system_stats))
+        .route("/ws/containers/:id/exec", get(handlers::ws_exec))
 
         .route("/containers/:name/files", get(handlers::list_files))
         .route("/containers/:name/files/read", get(handlers::read_file))
         .route("/containers/:name/files/write", post(handlers::write_file)
             .layer(DefaultBodyLimit::max(MAX_FILE_WRITE_SIZE)))
         .route("/containers/:name/files/write-chunk", post(handlers::write_file_chunk)
-            .layer(DefaultBodyLimit::max(UPLOAD_CHUNK_BODY_LIMIT)))
+            .layer(DefaultBodyLimit::max(raptor_common::uploads::base64_body_limit(config.upload_chunk_size))))
         .route("/containers/:name/files/folder", post(handlers::create_folder))
         .route("/containers/:name/files/delete", delete(handlers::delete_file))
         .route("/containers/:name/files/download", get(handlers::download_file))
         .route("/containers/:name/fix-permissions", post(handlers::fix_permissions))
+        .route("/containers/:id/wipe", post(handlers::wipe_container))
+        .route("/containers/:id/copy-volume", post(handlers::copy_container_volume))
+        .route("/containers/:id/pull", post(handlers::pull_container_image))
 
         .route("/database-servers", get(handlers::list_database_servers))
         .route("/database-servers", post(handlers::create_database_server))
@@ -157,10 +265,19 @@ async fn main() -> anyhow::Result<()> {
         .route("/database-servers/:id/databases", post(handlers::create_user_database))
         .route("/database-servers/:id/databases", delete(handlers::delete_user_database))
         .route("/database-servers/:id/databases/reset-password", post(handlers::reset_user_database_password))
+        .route("/reconcile", post(handlers::reconcile_state))
+        .route_layer(axum::middleware::from_fn_with_state(app_state.clone(), handlers::require_docker));
 
-        .route("/health", get(|| async { "OK" }))
+    let app = Router::new()
+        .merge(docker_routes)
+        .route("/health", get(handlers::health))
+        .route("/reload", post(handlers::reload_config))
         .route("/system", get(handlers::get_system_resources))
+        .route("/uploads/limits", get(handlers::upload_limits))
+        .route("/metrics", get(handlers::metrics))
+        .route("/ws/system", get(handlers::ws_system_stats))
         .layer(cors)
+        .layer(axum::middleware::from_fn(handlers::request_id))
         .layer(TraceLayer::new_for_http())
         .with_state(app_state);
 
@@ -170,9 +287,11 @@ async fn main() -> anyhow::Result<()> {
         .parse()
         .unwrap_or(2121);
 
+    let ftp_passive = ftp::FtpPassiveConfig::from_env();
+
     let ftp_state_clone = ftp_state.clone();
     tokio::spawn(async move {
-        if let Err(e) = ftp::start_ftp_server(ftp_state_clone, &ftp_host, ftp_port).await {
+        if let Err(e) = ftp::start_ftp_server(ftp_state_clone, &ftp_host, ftp_port, ftp_passive).await {
             tracing::error!("FTP server error: {}", e);
         }
     });
@@ -0,0 +1,114 @@
+use futures_util::StreamExt;
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::models::{AppState, ContainerStats, SystemResources};
+
+/// Containers whose stats are fetched from Docker at once.
+const STATS_CONCURRENCY: usize = 8;
+const STATS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Prometheus text exposition, written one metric family at a time.
+#[derive(Default)]
+struct Exposition(String);
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {} {}", name, help);
+        let _ = writeln!(self.0, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.0.push_str(name);
+
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")))
+                .collect();
+            let _ = write!(self.0, "{{{}}}", labels.join(","));
+        }
+
+        let _ = writeln!(self.0, " {}", value);
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.family(name, "gauge", help);
+        self.sample(name, &[], value);
+    }
+}
+
+/// Host stats plus live stats of every running container, in the Prometheus text format.
+pub async fn render(state: &AppState) -> String {
+    let mut out = Exposition::default();
+
+    if let Ok(system) = crate::handlers::collect_system_resources(state).await {
+        write_system(&mut out, &system);
+    }
+
+    let running: Vec<(String, String, bool, bool)> = state
+        .containers
+        .iter()
+        .filter(|c| c.status.map(|s| s.is_running()).unwrap_or(false))
+        .map(|c| (c.key().clone(), c.docker_id.clone(), c.ready, c.crash_looping))
+        .collect();
+
+    out.family("raptor_container_ready", "gauge", "1 once a running container passed its readiness check");
+    for (name, _, ready, _) in &running {
+        out.sample("raptor_container_ready", &[("container", name)], f64::from(u8::from(*ready)));
+    }
+
+    out.family("raptor_container_crash_looping", "gauge", "1 while a container is flagged as crash looping");
+    for (name, _, _, crash_looping) in &running {
+        out.sample("raptor_container_crash_looping", &[("container", name)], f64::from(u8::from(*crash_looping)));
+    }
+
+    let stats: Vec<(String, ContainerStats)> = futures_util::stream::iter(running)
+        .map(|(name, docker_id, _, _)| async move {
+            match tokio::time::timeout(STATS_TIMEOUT, state.docker.get_container_stats(&docker_id)).await {
+                Ok(Ok(stats)) => Some((name, stats)),
+                _ => None,
+            }
+        })
+        .buffer_unordered(STATS_CONCURRENCY)
+        .filter_map(|s| async move { s })
+        .collect()
+        .await;
+
+    let families: [(&str, &str, &str, fn(&ContainerStats) -> f64); 7] = [
+        ("raptor_container_cpu_percent", "gauge", "CPU usage, 100 per fully used core", |s| s.cpu_percent),
+        ("raptor_container_memory_bytes", "gauge", "Memory in use", |s| s.memory_usage as f64),
+        ("raptor_container_memory_limit_bytes", "gauge", "Memory limit", |s| s.memory_limit as f64),
+        ("raptor_container_network_receive_bytes_total", "counter", "Bytes received", |s| s.network_rx as f64),
+        ("raptor_container_network_transmit_bytes_total", "counter", "Bytes sent", |s| s.network_tx as f64),
+        ("raptor_container_block_read_bytes_total", "counter", "Bytes read from block devices", |s| s.block_read as f64),
+        ("raptor_container_block_write_bytes_total", "counter", "Bytes written to block devices", |s| s.block_write as f64),
+    ];
+
+    for (metric, kind, help, value) in families {
+        out.family(metric, kind, help);
+        for (name, s) in &stats {
+            out.sample(metric, &[("container", name)], value(s));
+        }
+    }
+
+    out.0
+}
+
+fn write_system(out: &mut Exposition, system: &SystemResources) {
+    out.gauge("raptor_host_memory_total_bytes", "Host memory", system.total_memory as f64);
+    out.gauge("raptor_host_memory_available_bytes", "Host memory not in use", system.available_memory as f64);
+    out.gauge("raptor_host_cpu_cores", "Host CPU cores", system.cpu_cores as f64);
+    out.gauge("raptor_host_cpu_usage_percent", "Host CPU usage across all cores", system.cpu_usage);
+    out.gauge("raptor_host_disk_total_bytes", "Host disk space", system.total_disk as f64);
+    out.gauge("raptor_host_disk_available_bytes", "Host disk space available", system.available_disk as f64);
+
+    out.family("raptor_host_load", "gauge", "Host load average");
+    out.sample("raptor_host_load", &[("period", "1m")], system.load_average.one);
+    out.sample("raptor_host_load", &[("period", "5m")], system.load_average.five);
+    out.sample("raptor_host_load", &[("period", "15m")], system.load_average.fifteen);
+
+    out.gauge("raptor_containers_running", "Running containers", system.running_containers as f64);
+    out.gauge("raptor_allocated_memory_bytes", "Memory limits of running containers combined", system.allocated_memory as f64);
+    out.gauge("raptor_allocated_cpu_cores", "CPU limits of running containers combined", system.allocated_cpu);
+}
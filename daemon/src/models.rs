@@ -1,11 +1,18 @@
 use std::sync::Arc;
 use dashmap::DashMap;
+use raptor_common::mounts::{AssetMount, TmpfsMount};
+use raptor_common::readiness::ReadinessCheck;
+use raptor_common::resources::{ResourceAlerts, UNLIMITED};
+use raptor_common::variables_file::VariablesFile;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
+use crate::callback::PanelCallback;
+use crate::config::Config;
 use crate::database_manager::DatabaseManager;
 use crate::docker::DockerManager;
 use crate::ftp::FtpServerState;
+use crate::log_store::LogStore;
 
 pub struct ContainerLocks {
     locks: DashMap<String, Arc<Mutex<()>>>,
@@ -39,10 +46,67 @@ impl Default for ContainerLocks {
 pub struct AppState {
     pub docker: DockerManager,
     pub api_key: String,
+    pub metrics_token: Option<String>,
     pub containers: DashMap<String, ManagedContainer>,
     pub ftp_state: Arc<FtpServerState>,
     pub container_locks: ContainerLocks,
     pub database_manager: DatabaseManager,
+    pub log_store: LogStore,
+    /// Readiness checks in progress, by container name.
+    pub readiness_checks: DashMap<String, tokio::task::JoinHandle<()>>,
+    pub runtime: std::sync::RwLock<RuntimeConfig>,
+    pub panel: PanelCallback,
+    /// Host stats shared by every `/ws/system` socket.
+    pub system_stats: crate::system_stats::SystemStatsHub,
+    pub upload_chunk_size: usize,
+}
+
+impl AppState {
+    pub fn runtime(&self) -> RuntimeConfig {
+        *self.runtime.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Re-reads `.env` and swaps in the hot-reloadable settings. Running containers, log
+    /// streams and websockets are untouched; new streams pick up the new values.
+    pub fn reload_config(&self) -> anyhow::Result<Vec<String>> {
+        let changed = Config::reload_env()?;
+        let runtime = RuntimeConfig::from_config(&Config::from_env());
+        *self.runtime.write().unwrap_or_else(|e| e.into_inner()) = runtime;
+        Ok(changed)
+    }
+}
+
+/// Settings that a config reload replaces in place.
+#[derive(Debug, Clone, Copy)]
+pub struct RuntimeConfig {
+    pub install_timeout_max_secs: u64,
+    pub start_timeout_max_secs: u64,
+    pub log_stream_buffer: usize,
+    pub log_stream_coalesce: bool,
+    pub crash_loop: CrashLoopConfig,
+}
+
+impl RuntimeConfig {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            install_timeout_max_secs: config.install_timeout_max_secs,
+            start_timeout_max_secs: config.start_timeout_max_secs,
+            log_stream_buffer: config.log_stream_buffer.max(16),
+            log_stream_coalesce: config.log_stream_coalesce,
+            crash_loop: CrashLoopConfig {
+                threshold: config.crash_loop_threshold,
+                oom_threshold: config.crash_loop_oom_threshold,
+                window: std::time::Duration::from_secs(config.crash_loop_window_secs.max(1)),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CrashLoopConfig {
+    pub threshold: usize,
+    pub oom_threshold: usize,
+    pub window: std::time::Duration,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,6 +124,8 @@ pub struct ManagedContainer {
     pub resources: ContainerResources,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub install_script: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install_timeout_secs: Option<u64>,
     #[serde(default)]
     pub installed: bool,
     #[serde(default)]
@@ -68,6 +134,103 @@ pub struct ManagedContainer {
     pub restart_policy: String,
     #[serde(default)]
     pub tty: bool,
+    #[serde(default, skip_serializing_if = "std::collections::HashMap::is_empty")]
+    pub labels: std::collections::HashMap<String, String>,
+    /// Runs on its own bridge network instead of the shared `RAPTOR_NETWORK`.
+    #[serde(default)]
+    pub isolated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readiness: Option<ReadinessCheck>,
+    /// Stopped when not ready this long after a start, capped at START_TIMEOUT_MAX_SECS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_timeout_secs: Option<u64>,
+    /// Rendered into the volume from `environment` before every start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variables_file: Option<VariablesFile>,
+    /// Sustained CPU/memory usage reported to the panel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_alerts: Option<ResourceAlerts>,
+    /// Read-only binds from the daemon's asset directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub asset_mounts: Vec<AssetMount>,
+    /// Named entry of the registry credentials file to pull the image with, from the flake.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry_credentials: Option<String>,
+    /// Stopped by the daemon after crashing repeatedly; cleared when it is started again.
+    #[serde(default)]
+    pub crash_looping: bool,
+    /// Last-known Docker state, kept current by the Docker events watcher.
+    #[serde(skip)]
+    pub status: Option<ContainerStatus>,
+    /// The readiness check passed since the last start.
+    #[serde(skip)]
+    pub ready: bool,
+}
+
+impl ManagedContainer {
+    /// Port the server listens on inside the container, from the primary allocation.
+    pub fn primary_internal_port(&self) -> Option<i32> {
+        self.allocations
+            .iter()
+            .find(|a| a.is_primary)
+            .or_else(|| self.allocations.first())
+            .map(|a| a.internal_port)
+            .or_else(|| self.allocation.as_ref().map(|a| a.port))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerStatus {
+    Created,
+    Running,
+    Paused,
+    Restarting,
+    Removing,
+    Exited,
+    Dead,
+}
+
+impl ContainerStatus {
+    pub fn from_docker_state(state: &str) -> Option<Self> {
+        match state.to_lowercase().as_str() {
+            "created" => Some(Self::Created),
+            "running" => Some(Self::Running),
+            "paused" => Some(Self::Paused),
+            "restarting" => Some(Self::Restarting),
+            "removing" => Some(Self::Removing),
+            "exited" => Some(Self::Exited),
+            "dead" => Some(Self::Dead),
+            _ => None,
+        }
+    }
+
+    /// Maps a Docker container event action to the state it leaves the container in.
+    pub fn from_event_action(action: &str) -> Option<Self> {
+        match action {
+            "create" => Some(Self::Created),
+            "start" | "unpause" | "restart" => Some(Self::Running),
+            "pause" => Some(Self::Paused),
+            "die" | "stop" => Some(Self::Exited),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Running => "running",
+            Self::Paused => "paused",
+            Self::Restarting => "restarting",
+            Self::Removing => "removing",
+            Self::Exited => "exited",
+            Self::Dead => "dead",
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self, Self::Running)
+    }
 }
 
 fn default_restart_policy() -> String {
@@ -96,6 +259,63 @@ pub struct ContainerResources {
     pub disk_limit: i64,
     pub swap_limit: i64,
     pub io_weight: i32,
+    /// Docker cpuset, e.g. "0-3,8"; None lets the container run on any CPU.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpuset_cpus: Option<String>,
+    /// Caps swap at the memory limit, i.e. no swap at all, whatever `swap_limit` says.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disable_swap: bool,
+    /// Only applied when the container is created.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tmpfs: Vec<TmpfsMount>,
+    /// Mounts the volume and root filesystem read-only; applied on the next recreate.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+}
+
+impl ContainerResources {
+    /// Docker `Memory` in bytes; 0 tells Docker not to limit memory.
+    pub fn docker_memory(&self) -> i64 {
+        if self.memory_limit == UNLIMITED {
+            0
+        } else {
+            self.memory_limit * 1024 * 1024
+        }
+    }
+
+    /// Docker `MemorySwap` (memory + swap) in bytes; -1 allows unlimited swap. Swap can't be
+    /// disabled without a memory limit, since Docker needs one to compare against.
+    pub fn docker_memory_swap(&self) -> i64 {
+        if self.memory_limit == UNLIMITED {
+            -1
+        } else if self.disable_swap {
+            self.docker_memory()
+        } else if self.swap_limit == UNLIMITED {
+            -1
+        } else {
+            (self.memory_limit + self.swap_limit) * 1024 * 1024
+        }
+    }
+}
+
+/// Limits Docker actually enforces, read back from the container's HostConfig. Memory and
+/// swap are in MB with `UNLIMITED` for none; a CPU limit of 0 means unlimited.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedResources {
+    pub memory_limit: i64,
+    pub swap_limit: i64,
+    pub cpu_limit: f64,
+    pub io_weight: i32,
+    pub cpuset_cpus: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerResourcesResponse {
+    /// What the daemon will apply on the next create.
+    pub configured: ContainerResources,
+    pub applied: AppliedResources,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -113,6 +333,17 @@ pub struct AllocationInfo {
     pub port: i32,
 }
 
+/// The step of a stop escalation that the container finally exited at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopStage {
+    AlreadyStopped,
+    /// The console stop command, so the server shut down on its own terms.
+    Command,
+    Sigterm,
+    Sigkill,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ContainerInfo {
@@ -123,6 +354,27 @@ pub struct ContainerInfo {
     pub state: String,
 }
 
+/// The exact image build a container runs, independent of its (possibly mutable) tag.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageVersion {
+    pub image_id: String,
+    /// `repo@sha256:...` as reported by the registry; `None` for locally built images.
+    pub digest: Option<String>,
+}
+
+/// When the current Docker container last started and stopped, and how often Docker restarted it.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerRuntime {
+    /// RFC 3339; `None` if it never ran.
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub exit_code: Option<i64>,
+    /// Restarts by Docker's restart policy since the container was created.
+    pub restart_count: i64,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateContainerRequest {
@@ -147,13 +399,40 @@ pub struct CreateContainerRequest {
     pub swap_limit: i64,
     #[serde(default = "default_io")]
     pub io_weight: i32,
+    #[serde(default)]
+    pub cpuset_cpus: Option<String>,
+    #[serde(default)]
+    pub disable_swap: bool,
+    #[serde(default)]
+    pub tmpfs: Vec<TmpfsMount>,
+    #[serde(default)]
+    pub read_only: bool,
     pub install_script: Option<String>,
     #[serde(default)]
+    pub install_timeout_secs: Option<u64>,
+    #[serde(default)]
     pub environment: std::collections::HashMap<String, String>,
     #[serde(default = "default_restart_policy")]
     pub restart_policy: String,
     #[serde(default)]
     pub tty: bool,
+    /// Extra Docker labels from the flake; values may use `{{VARIABLE}}` placeholders.
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    pub isolated: bool,
+    #[serde(default)]
+    pub readiness: Option<ReadinessCheck>,
+    #[serde(default)]
+    pub start_timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub variables_file: Option<VariablesFile>,
+    #[serde(default)]
+    pub resource_alerts: Option<ResourceAlerts>,
+    #[serde(default)]
+    pub asset_mounts: Vec<AssetMount>,
+    #[serde(default)]
+    pub registry_credentials: Option<String>,
 }
 
 fn default_memory() -> i64 { 512 }
@@ -247,6 +526,17 @@ pub struct UpdateContainerRequest {
     pub swap_limit: Option<i64>,
     #[serde(default)]
     pub io_weight: Option<i32>,
+    /// An empty string removes the pinning.
+    #[serde(default)]
+    pub cpuset_cpus: Option<String>,
+    #[serde(default)]
+    pub disable_swap: Option<bool>,
+    /// Replaces the tmpfs mounts; applied on the next recreate.
+    #[serde(default)]
+    pub tmpfs: Option<Vec<TmpfsMount>>,
+    /// Applied on the next recreate.
+    #[serde(default)]
+    pub read_only: Option<bool>,
     #[serde(default)]
     pub allocation: Option<AllocationInfo>,
     #[serde(default)]
@@ -256,7 +546,18 @@ pub struct UpdateContainerRequest {
     #[serde(default)]
     pub environment: Option<std::collections::HashMap<String, String>>,
     #[serde(default)]
+    pub remove_environment: Option<Vec<String>>,
+    #[serde(default)]
     pub startup_script: Option<String>,
+    /// Takes effect when the container is next recreated (every start recreates it).
+    #[serde(default)]
+    pub isolated: Option<bool>,
+    /// Also applied on the next recreate.
+    #[serde(default)]
+    pub restart_policy: Option<String>,
+    /// Thresholds without a CPU or memory percent turn alerts off.
+    #[serde(default)]
+    pub resource_alerts: Option<ResourceAlerts>,
 }
 
 #[derive(Debug, Serialize)]
@@ -276,6 +577,38 @@ pub struct SystemResources {
     pub total_disk: u64,
     pub available_disk: u64,
     pub hostname: String,
+    pub load_average: LoadAverage,
+    pub running_containers: usize,
+    pub allocated_memory: u64,
+    pub allocated_cpu: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
+/// Totals across managed containers, so node overcommit is visible next to host capacity.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContainerAggregates {
+    pub running: usize,
+    pub memory_bytes: u64,
+    pub cpu: f64,
+}
+
+impl ContainerAggregates {
+    pub fn collect(containers: &DashMap<String, ManagedContainer>) -> Self {
+        containers.iter().fold(Self::default(), |mut acc, entry| {
+            if entry.status.map(|s| s.is_running()).unwrap_or(false) {
+                acc.running += 1;
+            }
+            acc.memory_bytes += entry.resources.memory_limit.max(0) as u64 * 1024 * 1024;
+            acc.cpu += entry.resources.cpu_limit;
+            acc
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
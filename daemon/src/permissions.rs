@@ -0,0 +1,363 @@
+use serde::Serialize;
+use std::os::unix::fs::{lchown, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+/// A progress line is emitted every this many entries.
+const PROGRESS_EVERY: u64 = 1000;
+
+/// Error messages kept in the summary; the count covers the rest.
+const MAX_REPORTED_ERRORS: usize = 20;
+
+/// UID/GID that owns container volumes (CONTAINER_UID/CONTAINER_GID, default 1000:1000).
+pub fn container_owner() -> (u32, u32) {
+    let parse = |key: &str| std::env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(1000);
+    (parse("CONTAINER_UID"), parse("CONTAINER_GID"))
+}
+
+/// Patterns always excluded from permission fixes (FIX_PERMISSIONS_EXCLUDE, comma separated).
+pub fn default_excludes() -> Vec<String> {
+    std::env::var("FIX_PERMISSIONS_EXCLUDE")
+        .unwrap_or_default()
+        .split(',')
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Directories with more direct entries than this are skipped (FIX_PERMISSIONS_MAX_DIR_ENTRIES, 0 = no limit).
+pub fn max_dir_entries() -> usize {
+    std::env::var("FIX_PERMISSIONS_MAX_DIR_ENTRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Hands the volume to the container user and returns the `uid:gid` containers run as.
+/// Normally only the root is touched, so this stays cheap on large volumes. A root with another
+/// owner marks a volume from before containers ran unprivileged (or a fresh one), and its whole
+/// tree is handed over once with `fix_tree`.
+pub async fn prepare_volume(volume_path: &str) -> anyhow::Result<String> {
+    let (uid, gid) = container_owner();
+    let path = Path::new(volume_path);
+
+    let migrate = std::fs::symlink_metadata(path).is_ok_and(|m| m.uid() != uid || m.gid() != gid);
+    if migrate {
+        let root = path.to_path_buf();
+        let summary = tokio::task::spawn_blocking(move || {
+            // Nobody follows the progress of a migration; the closed channel drops it
+            let (progress, _) = mpsc::channel(1);
+            fix_tree(&root, &root, &Excludes::new(&default_excludes()), 0, &progress)
+        })
+            .await?;
+
+        tracing::info!(
+            "Handed volume {} to {}:{}: {} entries, {} changed, {} skipped, {} failed",
+            volume_path, uid, gid, summary.processed, summary.changed, summary.skipped, summary.failed
+        );
+        if !summary.errors.is_empty() {
+            tracing::warn!("Could not fix every entry in volume {}: {}", volume_path, summary.errors.join("; "));
+        }
+    }
+
+    lchown(path, Some(uid), Some(gid)).map_err(|e| {
+        anyhow::anyhow!("Failed to chown volume directory {} to {}:{}: {}", volume_path, uid, gid, e)
+    })?;
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        let mode = metadata.permissions().mode();
+        if mode & 0o755 != 0o755 {
+            let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode | 0o755));
+        }
+    }
+
+    Ok(format!("{}:{}", uid, gid))
+}
+
+/// Resolves the part of a volume a permission fix may touch. `relative` must stay inside the
+/// volume after every symlink along it is followed, otherwise a link planted by the tenant
+/// would hand host files to the container user. Returns the canonical root and scope.
+pub fn resolve_scope(volume_path: &Path, relative: Option<&str>) -> Result<(PathBuf, PathBuf), ScopeError> {
+    let root = std::fs::canonicalize(volume_path).map_err(|_| ScopeError::NotFound)?;
+
+    let relative = match relative.map(|p| p.trim_start_matches('/')).filter(|p| !p.is_empty()) {
+        Some(relative) => relative,
+        None => return Ok((root.clone(), root)),
+    };
+
+    let safe = Path::new(relative)
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir));
+    if !safe {
+        return Err(ScopeError::Invalid);
+    }
+
+    let scope = std::fs::canonicalize(root.join(relative)).map_err(|_| ScopeError::NotFound)?;
+    if !scope.starts_with(&root) {
+        return Err(ScopeError::Invalid);
+    }
+
+    Ok((root, scope))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ScopeError {
+    Invalid,
+    NotFound,
+}
+
+/// `.dockerignore`-style patterns relative to the volume root: `*` and `?` match within a
+/// path segment, `**` matches any number of segments. A matching directory is skipped whole.
+pub struct Excludes {
+    patterns: Vec<Vec<String>>,
+}
+
+impl Excludes {
+    pub fn new(patterns: &[String]) -> Self {
+        let patterns = patterns
+            .iter()
+            .map(|p| {
+                p.trim()
+                    .trim_matches('/')
+                    .split('/')
+                    .filter(|s| !s.is_empty() && *s != ".")
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|segments| !segments.is_empty())
+            .collect();
+
+        Self { patterns }
+    }
+
+    pub fn is_excluded(&self, relative: &Path) -> bool {
+        let segments: Vec<&str> = relative.iter().filter_map(|s| s.to_str()).collect();
+        self.patterns.iter().any(|pattern| matches_segments(pattern, &segments))
+    }
+}
+
+fn matches_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => (0..=path.len()).any(|i| matches_segments(rest, &path[i..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, path_rest)) => wildcard_match(first.as_bytes(), segment.as_bytes()) && matches_segments(rest, path_rest),
+            None => false,
+        },
+    }
+}
+
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    match (pattern.split_first(), text.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => wildcard_match(rest, text) || (!text.is_empty() && wildcard_match(pattern, &text[1..])),
+        (Some((b'?', rest)), Some((_, text_rest))) => wildcard_match(rest, text_rest),
+        (Some((p, rest)), Some((t, text_rest))) if p == t => wildcard_match(rest, text_rest),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct FixSummary {
+    pub processed: u64,
+    pub changed: u64,
+    pub skipped: u64,
+    pub failed: u64,
+    pub errors: Vec<String>,
+}
+
+/// One line of the streamed fix-permissions response.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FixProgress {
+    Progress { processed: u64, changed: u64, current: String },
+    Skipped { path: String, reason: String },
+    Done(FixSummary),
+}
+
+/// Recursively chowns `scope` (inside `root`) to the container user and makes it readable,
+/// without ever following symlinks. Blocking; run it on a blocking thread.
+pub fn fix_tree(
+    root: &Path,
+    scope: &Path,
+    excludes: &Excludes,
+    max_entries: usize,
+    progress: &mpsc::Sender<FixProgress>,
+) -> FixSummary {
+    let (uid, gid) = container_owner();
+    let mut summary = FixSummary::default();
+    let mut pending: Vec<PathBuf> = vec![scope.to_path_buf()];
+
+    while let Some(path) = pending.pop() {
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        let display = relative.to_string_lossy().to_string();
+
+        if !relative.as_os_str().is_empty() && excludes.is_excluded(&relative) {
+            summary.skipped += 1;
+            let _ = progress.blocking_send(FixProgress::Skipped { path: display, reason: "excluded".into() });
+            continue;
+        }
+
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(m) => m,
+            Err(e) => {
+                record_error(&mut summary, &display, &e);
+                continue;
+            }
+        };
+
+        summary.processed += 1;
+        match fix_entry(&path, &metadata, uid, gid) {
+            Ok(true) => summary.changed += 1,
+            Ok(false) => {}
+            Err(e) => record_error(&mut summary, &display, &e),
+        }
+
+        if summary.processed % PROGRESS_EVERY == 0 {
+            let _ = progress.blocking_send(FixProgress::Progress {
+                processed: summary.processed,
+                changed: summary.changed,
+                current: display.clone(),
+            });
+        }
+
+        if !metadata.is_dir() {
+            continue;
+        }
+
+        let children: Vec<PathBuf> = match std::fs::read_dir(&path) {
+            Ok(entries) => entries.filter_map(|e| e.ok().map(|e| e.path())).collect(),
+            Err(e) => {
+                record_error(&mut summary, &display, &e);
+                continue;
+            }
+        };
+
+        if max_entries > 0 && children.len() > max_entries {
+            summary.skipped += 1;
+            let _ = progress.blocking_send(FixProgress::Skipped {
+                path: display,
+                reason: format!("{} entries exceeds the limit of {}", children.len(), max_entries),
+            });
+            continue;
+        }
+
+        pending.extend(children);
+    }
+
+    summary
+}
+
+/// Returns whether anything had to change. Symlinks are re-owned but never chmodded.
+fn fix_entry(path: &Path, metadata: &std::fs::Metadata, uid: u32, gid: u32) -> std::io::Result<bool> {
+    let mut changed = false;
+
+    if metadata.uid() != uid || metadata.gid() != gid {
+        lchown(path, Some(uid), Some(gid))?;
+        changed = true;
+    }
+
+    if metadata.file_type().is_symlink() {
+        return Ok(changed);
+    }
+
+    // u+rwX,g+rX,o+rX: executables and directories stay traversable, nothing becomes world-writable
+    let mode = metadata.permissions().mode();
+    let wanted = if metadata.is_dir() || mode & 0o111 != 0 {
+        mode | 0o755
+    } else {
+        mode | 0o644
+    };
+
+    if wanted != mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(wanted))?;
+        changed = true;
+    }
+
+    Ok(changed)
+}
+
+fn record_error(summary: &mut FixSummary, path: &str, error: &std::io::Error) {
+    summary.failed += 1;
+    if summary.errors.len() < MAX_REPORTED_ERRORS {
+        summary.errors.push(format!("{}: {}", path, error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raptor-permissions-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("volume/data")).unwrap();
+        std::fs::create_dir_all(dir.join("host/etc")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn resolves_paths_inside_the_volume() {
+        let dir = volume("inside");
+        let (root, scope) = resolve_scope(&dir.join("volume"), Some("/data")).unwrap();
+        assert_eq!(root, std::fs::canonicalize(dir.join("volume")).unwrap());
+        assert_eq!(scope, root.join("data"));
+
+        let (root, scope) = resolve_scope(&dir.join("volume"), None).unwrap();
+        assert_eq!(scope, root);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_a_symlinked_directory_leading_out_of_the_volume() {
+        let dir = volume("symlink");
+        std::os::unix::fs::symlink(dir.join("host"), dir.join("volume/escape")).unwrap();
+
+        assert_eq!(resolve_scope(&dir.join("volume"), Some("escape/etc")), Err(ScopeError::Invalid));
+        assert_eq!(resolve_scope(&dir.join("volume"), Some("escape")), Err(ScopeError::Invalid));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn follows_symlinks_that_stay_inside_the_volume() {
+        let dir = volume("internal");
+        std::os::unix::fs::symlink(dir.join("volume/data"), dir.join("volume/link")).unwrap();
+
+        let (root, scope) = resolve_scope(&dir.join("volume"), Some("link")).unwrap();
+        assert_eq!(scope, root.join("data"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn hands_a_volume_with_another_owner_over_whole() {
+        // Changing owners needs root
+        if unsafe { libc::geteuid() } != 0 {
+            return;
+        }
+
+        let dir = volume("migrate");
+        let nested = dir.join("volume/data/world.dat");
+        std::fs::write(&nested, b"x").unwrap();
+        std::os::unix::fs::symlink(dir.join("host/etc"), dir.join("volume/data/link")).unwrap();
+        for path in [dir.join("volume"), dir.join("volume/data"), nested.clone(), dir.join("host/etc")] {
+            lchown(&path, Some(0), Some(0)).unwrap();
+        }
+
+        let (uid, gid) = container_owner();
+        assert_eq!(prepare_volume(dir.join("volume").to_str().unwrap()).await.unwrap(), format!("{}:{}", uid, gid));
+
+        let metadata = std::fs::metadata(&nested).unwrap();
+        assert_eq!((metadata.uid(), metadata.gid()), (uid, gid));
+        let host = std::fs::metadata(dir.join("host/etc")).unwrap();
+        assert_eq!((host.uid(), host.gid()), (0, 0), "the symlink target outside the volume must be left alone");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_parent_components_and_missing_paths() {
+        let dir = volume("invalid");
+        assert_eq!(resolve_scope(&dir.join("volume"), Some("../host")), Err(ScopeError::Invalid));
+        assert_eq!(resolve_scope(&dir.join("volume"), Some("missing")), Err(ScopeError::NotFound));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use raptor_common::readiness::{compile_pattern, ReadinessCheck};
+
+use crate::callback;
+use crate::log_format::strip_ansi;
+use crate::models::AppState;
+
+/// A server without a start timeout that hasn't passed its check by then stays "running"
+/// without becoming ready.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Evaluates the container's readiness check after a start, replacing a check still running
+/// from an earlier start. Containers without a check are ready as soon as they run; those
+/// with a start timeout are stopped when they miss it.
+pub fn spawn(state: &Arc<AppState>, name: &str) {
+    cancel(state, name);
+
+    let Some((docker_id, check, port, start_timeout)) = state
+        .containers
+        .get(name)
+        .map(|c| (c.docker_id.clone(), c.readiness.clone(), c.primary_internal_port(), c.start_timeout_secs))
+    else {
+        return;
+    };
+
+    let Some(check) = check else {
+        mark_ready(state, name, &docker_id);
+        return;
+    };
+
+    let start_timeout = start_timeout
+        .map(|secs| Duration::from_secs(secs.min(state.runtime().start_timeout_max_secs).max(1)));
+
+    let handle = tokio::spawn({
+        let state = state.clone();
+        let name = name.to_string();
+
+        async move {
+            let timeout = start_timeout.unwrap_or(READINESS_TIMEOUT);
+            match tokio::time::timeout(timeout, wait_until_ready(&state, &docker_id, &check, port)).await {
+                Ok(Ok(())) => mark_ready(&state, &name, &docker_id),
+                Ok(Err(e)) => tracing::warn!("Readiness check for {} gave up: {}", name, e),
+                Err(_) if start_timeout.is_some() => {
+                    // Detached: the stop's "die" event cancels this task
+                    tokio::spawn(stop_unready(state.clone(), name.clone(), docker_id.clone(), timeout));
+                }
+                Err(_) => tracing::warn!("Container {} did not become ready within {:?}", name, timeout),
+            }
+        }
+    });
+
+    state.readiness_checks.insert(name.to_string(), handle);
+}
+
+/// Stops a running check and clears the ready flag, on stop or before a new start.
+pub fn cancel(state: &AppState, name: &str) {
+    if let Some((_, handle)) = state.readiness_checks.remove(name) {
+        handle.abort();
+    }
+
+    if let Some(mut entry) = state.containers.get_mut(name) {
+        entry.ready = false;
+    }
+}
+
+/// Whether a check is still waiting on the container.
+pub fn is_pending(state: &AppState, name: &str) -> bool {
+    state.readiness_checks.get(name).is_some_and(|handle| !handle.is_finished())
+}
+
+fn mark_ready(state: &AppState, name: &str, docker_id: &str) {
+    let newly_ready = match state.containers.get_mut(name) {
+        Some(mut entry) if entry.docker_id == docker_id => !std::mem::replace(&mut entry.ready, true),
+        _ => false,
+    };
+
+    if newly_ready {
+        tracing::info!("Container {} is ready", name);
+        state.panel.send(name, callback::CONTAINER_STATE, serde_json::json!({ "transition": "ready" }));
+    }
+}
+
+/// Stops a container that missed its start timeout, e.g. stuck on a stalled download, so it
+/// doesn't hold its resources while never coming up.
+async fn stop_unready(state: Arc<AppState>, name: String, docker_id: String, timeout: Duration) {
+    let still_starting = state
+        .containers
+        .get(&name)
+        .is_some_and(|entry| entry.docker_id == docker_id && !entry.ready);
+    if !still_starting {
+        return;
+    }
+
+    tracing::warn!("Container {} did not become ready within {:?}, stopping it", name, timeout);
+
+    if let Err(e) = state.docker.stop_container(&docker_id).await {
+        tracing::error!("Failed to stop container {} after its start timeout: {}", name, e);
+        return;
+    }
+
+    state.panel.send(&name, callback::CONTAINER_STATE, serde_json::json!({
+        "transition": "start_timeout",
+        "timeoutSecs": timeout.as_secs(),
+    }));
+}
+
+async fn wait_until_ready(state: &AppState, docker_id: &str, check: &ReadinessCheck, port: Option<i32>) -> anyhow::Result<()> {
+    match check {
+        ReadinessCheck::Log { pattern } => {
+            let pattern = compile_pattern(pattern).map_err(anyhow::Error::msg)?;
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            state.docker.follow_logs(docker_id, tx);
+
+            while let Some(line) = rx.recv().await {
+                if pattern.is_match(&strip_ansi(&line)) {
+                    return Ok(());
+                }
+            }
+
+            anyhow::bail!("log stream ended before a line matched")
+        }
+        ReadinessCheck::Tcp { port: check_port } => {
+            let port = check_port
+                .map(i32::from)
+                .or(port)
+                .ok_or_else(|| anyhow::anyhow!("no port to check: set one or give the container an allocation"))?;
+
+            loop {
+                // The container's own address: a published port is accepted by Docker's proxy
+                // whether or not the server listens yet
+                if let Ok(Some(ip)) = state.docker.container_ip(docker_id).await {
+                    let connect = tokio::net::TcpStream::connect(format!("{}:{}", ip, port));
+                    if let Ok(Ok(_)) = tokio::time::timeout(CONNECT_TIMEOUT, connect).await {
+                        return Ok(());
+                    }
+                }
+
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+        ReadinessCheck::Exec { command } => loop {
+            match state.docker.exec_succeeds(docker_id, command).await {
+                Ok(true) => return Ok(()),
+                Ok(false) => {}
+                Err(e) => tracing::debug!("Readiness command for {} failed to run: {}", docker_id, e),
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        },
+    }
+}
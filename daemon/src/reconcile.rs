@@ -0,0 +1,96 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::handlers::save_container_state;
+use crate::models::{AppState, ContainerStatus};
+
+const INSTALL_SUFFIX: &str = "-install";
+
+/// Discrepancies found between `containers.json` and Docker.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileReport {
+    /// In state, but no Docker container exists; the next start recreates them.
+    pub missing: Vec<String>,
+    /// In state under a stale Docker ID; relinked to the container found by label or name.
+    pub relinked: Vec<String>,
+    /// Labelled as ours but unknown to the state file. Left untouched for an operator to inspect.
+    pub orphaned: Vec<String>,
+    /// Leftover install containers of known servers.
+    pub stale_installs: Vec<String>,
+}
+
+impl ReconcileReport {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.relinked.is_empty() && self.orphaned.is_empty() && self.stale_installs.is_empty()
+    }
+}
+
+/// Brings the loaded container state in line with what Docker actually has.
+pub async fn reconcile(state: &Arc<AppState>) -> anyhow::Result<ReconcileReport> {
+    let entries = state.docker.list_entries().await?;
+    let mut report = ReconcileReport::default();
+
+    let by_id: HashMap<&str, _> = entries.iter().map(|e| (e.id.as_str(), e)).collect();
+    let known: HashSet<String> = state.containers.iter().map(|e| e.key().clone()).collect();
+
+    for mut entry in state.containers.iter_mut() {
+        if by_id.contains_key(entry.docker_id.as_str()) {
+            continue;
+        }
+
+        let name = entry.key().clone();
+        let found = entries
+            .iter()
+            .find(|e| e.managed_as.as_deref() == Some(name.as_str()))
+            .or_else(|| entries.iter().find(|e| e.name == name));
+
+        match found {
+            Some(docker) => {
+                tracing::warn!(
+                    "Container {} had a stale Docker ID {}, relinking to {}",
+                    name, entry.docker_id, docker.id
+                );
+                entry.docker_id = docker.id.clone();
+                entry.status = ContainerStatus::from_docker_state(&docker.state);
+                entry.crash_looping = false;
+                report.relinked.push(name);
+            }
+            None => {
+                tracing::warn!(
+                    "Container {} has no Docker container (was {}), it will be recreated on next start",
+                    name, entry.docker_id
+                );
+                entry.status = None;
+                report.missing.push(name);
+            }
+        }
+    }
+
+    for docker in &entries {
+        if let Some(managed_as) = &docker.managed_as {
+            if !known.contains(managed_as) {
+                tracing::warn!(
+                    "Docker container {} ({}) is labelled for {} but not in state, leaving it alone",
+                    docker.name, docker.id, managed_as
+                );
+                report.orphaned.push(docker.name.clone());
+            }
+            continue;
+        }
+
+        if let Some(base) = docker.name.strip_suffix(INSTALL_SUFFIX) {
+            if known.contains(base) && !docker.state.eq_ignore_ascii_case("running") {
+                tracing::warn!("Install container {} ({}) was left behind", docker.name, docker.id);
+                report.stale_installs.push(docker.name.clone());
+            }
+        }
+    }
+
+    if !report.relinked.is_empty() {
+        save_container_state(state).await;
+    }
+
+    Ok(report)
+}
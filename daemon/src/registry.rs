@@ -0,0 +1,228 @@
+use base64::Engine;
+use bollard::auth::DockerCredentials;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+const DOCKER_HUB: &str = "docker.io";
+
+/// Registry credentials file, in the same shape as Docker's `config.json`:
+/// `{"auths": {"ghcr.io": {"auth": "<base64 user:pass>"}}}` or with `username`/`password`.
+/// `credentials` holds named entries (with a `registry`) that flakes pick explicitly, for
+/// registries where different images need different accounts. Secret values can be stored
+/// encrypted with `raptor-daemon encrypt-registry-secret`.
+#[derive(Debug, Default, Deserialize)]
+struct RegistryAuthFile {
+    #[serde(default)]
+    auths: HashMap<String, RegistryAuthEntry>,
+    #[serde(default)]
+    credentials: HashMap<String, RegistryAuthEntry>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+struct RegistryAuthEntry {
+    registry: Option<String>,
+    auth: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    identitytoken: Option<String>,
+}
+
+impl RegistryAuthEntry {
+    fn has_plaintext_secret(&self) -> bool {
+        [&self.auth, &self.password, &self.identitytoken]
+            .into_iter()
+            .flatten()
+            .any(|value| !value.starts_with(raptor_common::secrets::ENCRYPTED_PREFIX))
+    }
+}
+
+/// The parsed file, kept until its modification time or size changes.
+struct CachedAuthFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    len: u64,
+    file: Arc<RegistryAuthFile>,
+}
+
+static AUTH_FILE: Lazy<Mutex<Option<CachedAuthFile>>> = Lazy::new(|| Mutex::new(None));
+
+fn auth_file_path() -> PathBuf {
+    std::env::var("REGISTRY_AUTH_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            let data_dir = std::env::var("DAEMON_DATA_DIR")
+                .unwrap_or_else(|_| "/var/lib/raptor-daemon".to_string());
+            PathBuf::from(data_dir).join("registries.json")
+        })
+}
+
+/// Key for encrypted values: REGISTRY_AUTH_KEY, or the daemon API key without one. Rotating
+/// whichever is used means encrypting the stored values again.
+fn secret_key() -> anyhow::Result<[u8; 32]> {
+    let secret = std::env::var("REGISTRY_AUTH_KEY")
+        .or_else(|_| std::env::var("DAEMON_API_KEY"))
+        .ok()
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Registry secrets need REGISTRY_AUTH_KEY or DAEMON_API_KEY to be set"))?;
+    Ok(raptor_common::secrets::derive_key("raptor-registry-credentials", &secret))
+}
+
+/// Reads stdin and prints it encrypted for the registry credentials file.
+pub fn print_encrypted_secret() -> anyhow::Result<()> {
+    let mut value = String::new();
+    std::io::stdin().read_line(&mut value)?;
+    let value = value.trim_end_matches(['\r', '\n']);
+    if value.is_empty() {
+        anyhow::bail!("Pass the secret to encrypt on stdin");
+    }
+
+    let encrypted = raptor_common::secrets::encrypt(&secret_key()?, value).map_err(|e| anyhow::anyhow!(e))?;
+    println!("{}", encrypted);
+    Ok(())
+}
+
+/// The credentials file, re-read only when it changed so edits still apply without a restart.
+async fn load_auth_file() -> Option<Arc<RegistryAuthFile>> {
+    let path = auth_file_path();
+    let metadata = tokio::fs::metadata(&path).await.ok()?;
+    let modified = metadata.modified().ok();
+
+    {
+        let cache = AUTH_FILE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = cache.as_ref().filter(|c| c.path == path && c.modified == modified && c.len == metadata.len()) {
+            return Some(cached.file.clone());
+        }
+    }
+
+    let contents = tokio::fs::read_to_string(&path).await.ok()?;
+    let file: RegistryAuthFile = match serde_json::from_str(&contents) {
+        Ok(f) => f,
+        Err(e) => {
+            tracing::warn!("Ignoring invalid registry auth file {:?}: {}", path, e);
+            return None;
+        }
+    };
+
+    let plaintext: Vec<&String> = file.auths.iter().chain(&file.credentials)
+        .filter(|(_, entry)| entry.has_plaintext_secret())
+        .map(|(key, _)| key)
+        .collect();
+    if !plaintext.is_empty() {
+        tracing::warn!(
+            "Registry credentials for {:?} are stored in plaintext in {:?}; encrypt them with `raptor-daemon encrypt-registry-secret`",
+            plaintext, path
+        );
+    }
+
+    let file = Arc::new(file);
+    *AUTH_FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(CachedAuthFile {
+        path,
+        modified,
+        len: metadata.len(),
+        file: file.clone(),
+    });
+    Some(file)
+}
+
+/// Registry host an image is pulled from, following Docker's reference rules.
+pub fn registry_host(image: &str) -> &str {
+    match image.split_once('/') {
+        Some((first, _)) if first.contains('.') || first.contains(':') || first == "localhost" => first,
+        _ => DOCKER_HUB,
+    }
+}
+
+/// Normalizes a configured registry key (which may be a URL like `https://index.docker.io/v1/`).
+fn normalize_host(key: &str) -> &str {
+    let host = key
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split('/')
+        .next()
+        .unwrap_or(key);
+
+    match host {
+        "index.docker.io" | "registry-1.docker.io" | "registry.hub.docker.com" => DOCKER_HUB,
+        other => other,
+    }
+}
+
+/// Looks up pull credentials for `image`: the named entry when `name` is given (a flake's
+/// choice), otherwise whichever entry is configured for the image's registry.
+pub async fn credentials_for(image: &str, name: Option<&str>) -> Option<DockerCredentials> {
+    let file = load_auth_file().await?;
+    let host = registry_host(image);
+
+    let named = name.and_then(|name| match file.credentials.get(name) {
+        Some(entry) if entry.registry.as_deref().map(normalize_host) == Some(host) => {
+            Some((entry.registry.clone().unwrap_or_default(), entry.clone()))
+        }
+        Some(_) => {
+            tracing::warn!("Registry credentials '{}' are not for {}, which {} is pulled from", name, host, image);
+            None
+        }
+        None => {
+            tracing::warn!("Registry credentials '{}' are not configured", name);
+            None
+        }
+    });
+
+    let (key, entry) = match named {
+        Some(found) => found,
+        None => file.auths.iter()
+            .find(|(key, _)| normalize_host(key) == host)
+            .map(|(key, entry)| (key.clone(), entry.clone()))?,
+    };
+
+    // Plaintext values don't need the key, so a daemon without one can still use them
+    let secret_key = secret_key();
+    let reveal = |value: Option<String>| -> Result<Option<String>, String> {
+        value
+            .map(|v| match &secret_key {
+                Ok(key) => raptor_common::secrets::decrypt(key, &v),
+                Err(_) if !v.starts_with(raptor_common::secrets::ENCRYPTED_PREFIX) => Ok(v),
+                Err(e) => Err(e.to_string()),
+            })
+            .transpose()
+    };
+    let (auth, password, identitytoken) = match (reveal(entry.auth), reveal(entry.password), reveal(entry.identitytoken)) {
+        (Ok(auth), Ok(password), Ok(identitytoken)) => (auth, password, identitytoken),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            tracing::warn!("Registry credentials for {} could not be decrypted: {}", key, e);
+            return None;
+        }
+    };
+
+    let (username, password) = match (entry.username, password, auth) {
+        (Some(u), Some(p), _) => (Some(u), Some(p)),
+        (_, _, Some(auth)) => {
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(auth.trim())
+                .ok()
+                .and_then(|b| String::from_utf8(b).ok());
+
+            match decoded.as_deref().and_then(|d| d.split_once(':')) {
+                Some((u, p)) => (Some(u.to_string()), Some(p.to_string())),
+                None => {
+                    tracing::warn!("Registry auth for {} is not valid base64 user:password", key);
+                    return None;
+                }
+            }
+        }
+        _ => (None, None),
+    };
+
+    tracing::debug!("Using registry credentials for {} to pull {}", host, image);
+
+    Some(DockerCredentials {
+        username,
+        password,
+        identitytoken,
+        serveraddress: Some(key),
+        ..Default::default()
+    })
+}
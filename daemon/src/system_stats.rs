@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+
+use crate::models::{AppState, ContainerAggregates, LoadAverage, SystemResources};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// One host stats sampler shared by every `/ws/system` socket. It runs while anyone is
+/// subscribed and stops with the last subscriber.
+pub struct SystemStatsHub {
+    tx: broadcast::Sender<String>,
+    running: AtomicBool,
+    latest: Mutex<Option<String>>,
+}
+
+impl Default for SystemStatsHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemStatsHub {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(16);
+        Self { tx, running: AtomicBool::new(false), latest: Mutex::new(None) }
+    }
+
+    /// Subscribes to the stats stream, starting the sampler if it is idle. Also returns the
+    /// most recent sample so a new socket doesn't wait for the next one.
+    pub fn subscribe(state: &Arc<AppState>) -> (Option<String>, broadcast::Receiver<String>) {
+        let hub = &state.system_stats;
+        let rx = hub.tx.subscribe();
+
+        if hub.running.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+            tokio::spawn(run(state.clone()));
+        }
+
+        (hub.latest.lock().unwrap_or_else(|e| e.into_inner()).clone(), rx)
+    }
+
+    /// Called by the sampler when nobody is listening; false if a subscriber arrived in the
+    /// meantime and the sampler should keep going.
+    fn try_stop(&self) -> bool {
+        self.running.store(false, Ordering::Release);
+        if self.tx.receiver_count() > 0 && self.running.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+            return false;
+        }
+        *self.latest.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        true
+    }
+}
+
+pub fn load_average() -> LoadAverage {
+    let load = sysinfo::System::load_average();
+    LoadAverage {
+        one: load.one,
+        five: load.five,
+        fifteen: load.fifteen,
+    }
+}
+
+async fn run(state: Arc<AppState>) {
+    let hub = &state.system_stats;
+    tracing::debug!("Starting system stats sampler");
+
+    let sys = tokio::task::spawn_blocking(|| {
+        let mut sys = sysinfo::System::new_all();
+        sys.refresh_all();
+        sys
+    })
+    .await;
+
+    let mut sys = match sys {
+        Ok(sys) => sys,
+        Err(_) => {
+            hub.running.store(false, Ordering::Release);
+            return;
+        }
+    };
+
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if hub.tx.receiver_count() == 0 && hub.try_stop() {
+            break;
+        }
+
+        let aggregates = ContainerAggregates::collect(&state.containers);
+
+        let sampled = tokio::task::spawn_blocking(move || {
+            sys.refresh_memory();
+            sys.refresh_cpu_all();
+
+            let total_memory = sys.total_memory();
+            let used_memory = sys.used_memory();
+            let available_memory = total_memory.saturating_sub(used_memory);
+            let cpu_cores = sys.cpus().len();
+            let cpu_usage = sys.global_cpu_usage() as f64;
+
+            let disks = sysinfo::Disks::new_with_refreshed_list();
+            let (total_disk, available_disk) = disks.iter().fold((0u64, 0u64), |(total, avail), disk| {
+                (total + disk.total_space(), avail + disk.available_space())
+            });
+
+            let hostname = sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string());
+
+            let stats = SystemResources {
+                total_memory,
+                available_memory,
+                cpu_cores,
+                cpu_usage,
+                total_disk,
+                available_disk,
+                hostname,
+                load_average: load_average(),
+                running_containers: aggregates.running,
+                allocated_memory: aggregates.memory_bytes,
+                allocated_cpu: aggregates.cpu,
+            };
+
+            (sys, stats)
+        })
+        .await;
+
+        let stats = match sampled {
+            Ok((returned_sys, stats)) => {
+                sys = returned_sys;
+                stats
+            }
+            Err(e) => {
+                tracing::warn!("System stats sampler failed: {}", e);
+                hub.running.store(false, Ordering::Release);
+                return;
+            }
+        };
+
+        let json = serde_json::to_string(&stats).unwrap_or_default();
+        *hub.latest.lock().unwrap_or_else(|e| e.into_inner()) = Some(json.clone());
+        let _ = hub.tx.send(json);
+    }
+
+    tracing::debug!("Stopped system stats sampler, no subscribers left");
+}
@@ -0,0 +1,4 @@
+-- Token version for JWT revocation
+-- Every issued token carries the version it was signed with; bumping the
+-- column invalidates all tokens issued before the bump
+ALTER TABLE users ADD COLUMN IF NOT EXISTS token_version INTEGER NOT NULL DEFAULT 0;
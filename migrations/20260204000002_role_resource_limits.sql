@@ -0,0 +1,11 @@
+-- Per-role resource ceilings enforced when creating or resizing containers
+-- NULL means unlimited
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS max_memory BIGINT;
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS max_cpu DECIMAL(6,2);
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS max_disk BIGINT;
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS max_containers INTEGER;
+
+COMMENT ON COLUMN roles.max_memory IS 'Maximum memory per container in MB (NULL = unlimited)';
+COMMENT ON COLUMN roles.max_cpu IS 'Maximum CPU cores per container (NULL = unlimited)';
+COMMENT ON COLUMN roles.max_disk IS 'Maximum disk per container in MB (NULL = unlimited)';
+COMMENT ON COLUMN roles.max_containers IS 'Maximum number of containers owned by a user with this role (NULL = unlimited)';
@@ -0,0 +1,12 @@
+-- Console command history per container
+CREATE TABLE IF NOT EXISTS container_commands (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    container_id UUID NOT NULL REFERENCES containers(id) ON DELETE CASCADE,
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    command TEXT NOT NULL,
+    source VARCHAR(20) NOT NULL DEFAULT 'api' CHECK (source IN ('api', 'console')),
+    output TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_container_commands_container_created ON container_commands(container_id, created_at DESC);
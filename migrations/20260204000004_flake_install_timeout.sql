@@ -0,0 +1,5 @@
+-- Per-flake install script timeout, capped by the daemon's INSTALL_TIMEOUT_MAX_SECS
+-- NULL uses the daemon default
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS install_timeout_secs INTEGER;
+
+COMMENT ON COLUMN flakes.install_timeout_secs IS 'Install script timeout in seconds (NULL = daemon default)';
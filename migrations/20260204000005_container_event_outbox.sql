@@ -0,0 +1,14 @@
+-- Durable outbox of container lifecycle events for billing integrations
+-- Rows are written in the same transaction as the container change and drained by the API publisher
+CREATE TABLE IF NOT EXISTS container_event_outbox (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    container_id UUID NOT NULL,
+    event_type VARCHAR(50) NOT NULL,
+    payload JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    published_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_container_event_outbox_pending ON container_event_outbox(created_at) WHERE published_at IS NULL;
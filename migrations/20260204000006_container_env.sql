@@ -0,0 +1,9 @@
+-- Free-form environment variables per container, independent of flake variables
+CREATE TABLE IF NOT EXISTS container_env (
+    container_id UUID NOT NULL REFERENCES containers(id) ON DELETE CASCADE,
+    key VARCHAR(255) NOT NULL,
+    value TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (container_id, key)
+);
@@ -0,0 +1,7 @@
+-- Free-form annotations for operators managing many allocations
+-- e.g. label "reserved for staff", notes about DDoS protection on the IP
+ALTER TABLE allocations ADD COLUMN IF NOT EXISTS label VARCHAR(100);
+ALTER TABLE allocations ADD COLUMN IF NOT EXISTS notes TEXT;
+
+COMMENT ON COLUMN allocations.label IS 'Short operator-facing label for the allocation';
+COMMENT ON COLUMN allocations.notes IS 'Operator notes for the allocation';
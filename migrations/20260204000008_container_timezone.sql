@@ -0,0 +1,4 @@
+-- Per-container timezone (IANA tz database name), exposed to the container as TZ
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS timezone VARCHAR(64) NOT NULL DEFAULT 'UTC';
+
+COMMENT ON COLUMN containers.timezone IS 'IANA timezone applied to the container via TZ and /etc/localtime';
@@ -0,0 +1,16 @@
+-- Recurring daily backups per container, run by the API backup scheduler
+-- hour/minute are interpreted in the container's timezone
+CREATE TABLE IF NOT EXISTS backup_schedules (
+    container_id UUID PRIMARY KEY REFERENCES containers(id) ON DELETE CASCADE,
+    hour SMALLINT NOT NULL CHECK (hour BETWEEN 0 AND 23),
+    minute SMALLINT NOT NULL DEFAULT 0 CHECK (minute BETWEEN 0 AND 59),
+    keep_last INTEGER NOT NULL DEFAULT 7 CHECK (keep_last > 0),
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    last_run_at TIMESTAMPTZ,
+    last_status VARCHAR(20),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_backup_schedules_enabled ON backup_schedules(enabled) WHERE enabled = TRUE;
@@ -0,0 +1,8 @@
+-- Per-user opt-in email notifications for container events reported by daemons
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    container_crashed BOOLEAN NOT NULL DEFAULT FALSE,
+    install_completed BOOLEAN NOT NULL DEFAULT FALSE,
+    container_suspended BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
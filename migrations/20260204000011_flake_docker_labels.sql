@@ -0,0 +1,5 @@
+-- Extra Docker labels applied to containers created from a flake (e.g. Traefik routing)
+-- Values may use {{VARIABLE}} placeholders; raptor.* keys are reserved for the daemon
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS docker_labels JSONB NOT NULL DEFAULT '{}';
+
+COMMENT ON COLUMN flakes.docker_labels IS 'Docker labels as a JSON object of strings; raptor.* keys are reserved';
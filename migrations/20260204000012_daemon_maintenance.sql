@@ -0,0 +1,2 @@
+-- Daemons in maintenance have been drained and accept no new containers
+ALTER TABLE daemons ADD COLUMN IF NOT EXISTS maintenance BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Optional CPU pinning for containers, in Docker cpuset-cpus format (e.g. "0-3,8")
+-- NULL means the container may run on any CPU
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS cpuset_cpus VARCHAR(255);
@@ -0,0 +1,2 @@
+-- Isolated containers get their own bridge network instead of sharing raptord_internal
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS isolated BOOLEAN NOT NULL DEFAULT FALSE;
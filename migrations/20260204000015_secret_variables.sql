@@ -0,0 +1,2 @@
+-- Secret flake variables are write-only through the API and stored encrypted per container
+ALTER TABLE flake_variables ADD COLUMN IF NOT EXISTS secret BOOLEAN NOT NULL DEFAULT FALSE;
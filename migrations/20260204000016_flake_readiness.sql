@@ -0,0 +1,4 @@
+-- How the daemon tells a started server is actually up ("Starting..." vs "Online")
+-- {"type": "tcp", "port"?}, {"type": "log", "pattern"} or {"type": "exec", "command"};
+-- flakes without one fall back to matching startup_detection as a literal console line
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS readiness_check JSONB;
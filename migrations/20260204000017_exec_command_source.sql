@@ -0,0 +1,3 @@
+-- Shell sessions opened over the exec websocket are recorded in the command history
+ALTER TABLE container_commands DROP CONSTRAINT IF EXISTS container_commands_source_check;
+ALTER TABLE container_commands ADD CONSTRAINT container_commands_source_check CHECK (source IN ('api', 'console', 'exec'));
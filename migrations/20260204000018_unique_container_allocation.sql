@@ -0,0 +1,46 @@
+-- An allocation belongs to at most one container. The handlers check this before inserting,
+-- but two concurrent requests could both pass the check; the index turns the loser into an error.
+
+-- Earlier races may have left duplicates. The container using the allocation as its primary
+-- keeps it (the oldest assignment otherwise); every other assignment is removed and logged, and
+-- a container that lost its primary that way gets its oldest remaining allocation promoted.
+DO $$
+DECLARE
+    dup RECORD;
+    affected UUID[] := '{}';
+BEGIN
+    FOR dup IN
+        SELECT ranked.id, ranked.container_id, ranked.allocation_id, ranked.ip, ranked.port, ranked.keeper
+        FROM (
+            SELECT ca.*,
+                   ROW_NUMBER() OVER w AS rank,
+                   FIRST_VALUE(ca.container_id) OVER w AS keeper
+            FROM container_allocations ca
+            WHERE ca.allocation_id IS NOT NULL
+            WINDOW w AS (PARTITION BY ca.allocation_id ORDER BY COALESCE(ca.is_primary, FALSE) DESC, ca.created_at, ca.id)
+        ) ranked
+        WHERE ranked.rank > 1
+    LOOP
+        RAISE WARNING 'Allocation % (%:%) was assigned to containers % and %; removing it from %',
+            dup.allocation_id, dup.ip, dup.port, dup.keeper, dup.container_id, dup.container_id;
+        DELETE FROM container_allocations WHERE id = dup.id;
+        affected := affected || dup.container_id;
+    END LOOP;
+
+    UPDATE container_allocations
+    SET is_primary = TRUE
+    WHERE id IN (
+        SELECT DISTINCT ON (ca.container_id) ca.id
+        FROM container_allocations ca
+        WHERE ca.container_id = ANY(affected)
+          AND NOT EXISTS (
+              SELECT 1 FROM container_allocations p
+              WHERE p.container_id = ca.container_id AND p.is_primary = TRUE
+          )
+        ORDER BY ca.container_id, ca.created_at, ca.id
+    );
+END $$;
+
+DROP INDEX IF EXISTS idx_container_allocations_allocation_id;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_container_allocations_unique_allocation
+    ON container_allocations(allocation_id);
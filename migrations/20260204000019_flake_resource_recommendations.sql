@@ -0,0 +1,6 @@
+-- Resource guidance per flake, in MB. Containers below the minimum are rejected at creation;
+-- the recommendation only pre-fills the create form
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS min_memory BIGINT;
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS recommended_memory BIGINT;
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS min_disk BIGINT;
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS recommended_disk BIGINT;
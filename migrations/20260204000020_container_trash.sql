@@ -0,0 +1,4 @@
+-- Deleted containers go to the trash first: stopped, volume kept, restorable until the
+-- retention window (CONTAINER_TRASH_RETENTION_HOURS) passes and the purge job removes them
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+CREATE INDEX IF NOT EXISTS idx_containers_deleted_at ON containers(deleted_at) WHERE deleted_at IS NOT NULL;
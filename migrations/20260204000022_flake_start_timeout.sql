@@ -0,0 +1,3 @@
+-- Seconds a container of this flake gets to pass its readiness check after a start before
+-- the daemon stops it. NULL leaves it running however long startup takes
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS start_timeout_secs INTEGER;
@@ -0,0 +1,7 @@
+-- Restart policy of each container, editable after creation; the daemon applies a change
+-- when it next recreates the container. Existing containers got theirs from the flake
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS restart_policy VARCHAR(50) NOT NULL DEFAULT 'unless-stopped';
+
+UPDATE containers c SET restart_policy = f.restart_policy
+FROM flakes f
+WHERE c.flake_id = f.id AND f.restart_policy IS NOT NULL;
@@ -0,0 +1,5 @@
+-- A container started before this one and stopped after it, e.g. a voice proxy next to a
+-- game server. Only one level deep: a dependency has no dependency of its own
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS depends_on UUID REFERENCES containers(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_containers_depends_on ON containers(depends_on) WHERE depends_on IS NOT NULL;
@@ -0,0 +1,11 @@
+-- Who did what on a container: power actions, commands, file changes and settings edits
+CREATE TABLE IF NOT EXISTS container_activity (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    container_id UUID NOT NULL REFERENCES containers(id) ON DELETE CASCADE,
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    action VARCHAR(50) NOT NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_container_activity_container_created ON container_activity(container_id, created_at DESC);
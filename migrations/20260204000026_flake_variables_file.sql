@@ -0,0 +1,3 @@
+-- {"path", "format": "dotenv" | "json", "template"?}: a file the daemon renders from the
+-- container's variables before each start, for servers that don't read the environment
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS variables_file JSONB;
@@ -0,0 +1,16 @@
+-- {"cpuPercent"?, "memoryPercent"?, "windowSecs"}: usage the daemon reports once it has been
+-- sustained for the window
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS resource_alerts JSONB;
+
+CREATE TABLE IF NOT EXISTS container_alerts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    container_id UUID NOT NULL REFERENCES containers(id) ON DELETE CASCADE,
+    metric VARCHAR(20) NOT NULL,
+    percent DOUBLE PRECISION NOT NULL,
+    threshold DOUBLE PRECISION NOT NULL,
+    window_secs INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_container_alerts_created ON container_alerts(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_container_alerts_container_created ON container_alerts(container_id, created_at DESC);
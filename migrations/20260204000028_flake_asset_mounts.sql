@@ -0,0 +1,2 @@
+-- [{"source", "target"}]: read-only binds from the daemon's asset directory (ASSETS_PATH)
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS asset_mounts JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,2 @@
+-- Set when settings that only apply on the next start change while the container runs
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS pending_changes BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- [{"target", "sizeMb"}]: size-capped in-memory mounts, applied when the container is recreated
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS tmpfs JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS disable_swap BOOLEAN NOT NULL DEFAULT FALSE;
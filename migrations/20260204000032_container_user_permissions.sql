@@ -0,0 +1,9 @@
+-- Capabilities the owner granted a sub-user: console, power, files-read, files-write,
+-- startup-edit, allocations
+ALTER TABLE container_users ADD COLUMN IF NOT EXISTS permissions TEXT[] NOT NULL DEFAULT '{}';
+
+-- Keep what each level allowed so far
+UPDATE container_users SET permissions = ARRAY['files-read'] WHERE permission_level = 'user' AND permissions = '{}';
+UPDATE container_users SET permissions = ARRAY['files-read', 'files-write'] WHERE permission_level = 'admin' AND permissions = '{}';
+UPDATE container_users SET permissions = ARRAY['console', 'power', 'files-read', 'files-write', 'startup-edit', 'allocations']
+WHERE permission_level = 'owner' AND permissions = '{}';
@@ -0,0 +1,2 @@
+-- {"key": "value"}: Docker labels set at creation, layered over the flake's docker_labels
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS docker_labels JSONB NOT NULL DEFAULT '{}';
@@ -0,0 +1,3 @@
+-- Read-only containers get their volume mounted :ro and a read-only root filesystem
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS read_only BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS read_only BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Console command that stops the server; containers created from the flake default to it
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS stop_command TEXT;
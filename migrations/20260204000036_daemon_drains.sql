@@ -0,0 +1,14 @@
+-- Drains run in the background; their per-container results are polled from here
+CREATE TABLE IF NOT EXISTS daemon_drains (
+    id UUID PRIMARY KEY,
+    daemon_id UUID NOT NULL REFERENCES daemons(id) ON DELETE CASCADE,
+    -- running, finished or interrupted (the API restarted mid-drain)
+    status VARCHAR(16) NOT NULL DEFAULT 'running',
+    timeout_secs INTEGER NOT NULL,
+    total INTEGER NOT NULL DEFAULT 0,
+    results JSONB NOT NULL DEFAULT '[]',
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_daemon_drains_daemon ON daemon_drains(daemon_id, started_at DESC);
@@ -0,0 +1,2 @@
+-- Publishers claim a batch for a while instead of holding row locks during the webhook posts
+ALTER TABLE container_event_outbox ADD COLUMN IF NOT EXISTS claimed_until TIMESTAMPTZ;
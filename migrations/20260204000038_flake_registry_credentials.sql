@@ -0,0 +1,2 @@
+-- Named entry of the daemon's registry credentials file that containers from the flake pull with
+ALTER TABLE flakes ADD COLUMN IF NOT EXISTS registry_credentials VARCHAR(255);
@@ -0,0 +1,2 @@
+-- When the daemon event behind the current status happened, so a late delivery can't overwrite a newer one
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS status_event_at TIMESTAMPTZ;
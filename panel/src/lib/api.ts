@@ -11,6 +11,7 @@ function getApiUrl(): string {
 
 const API_URL = getApiUrl();
 
+// Used when the API cannot report the negotiated limit
 export const UPLOAD_CHUNK_SIZE = 55 * 1024 * 1024;
 
 async function request<T>(path: string, options: RequestInit = {}): Promise<T> {
@@ -183,6 +184,7 @@ export const api = {
         }),
 
     listDaemons: () => request<Daemon[]>('/daemons'),
+    getDaemonCapacity: () => request<import('./types').DaemonCapacity[]>('/daemons/capacity'),
     getDaemon: (id: string) => request<Daemon>(`/daemons/${id}`),
     getDaemonStatus: (id: string) => request<{ id: string; status: string; system?: { totalMemory: number; availableMemory: number; cpuCores: number; cpuUsage: number; totalDisk: number; availableDisk: number; hostname: string } }>(`/daemons/${id}/status`),
     createDaemon: (data: { name: string; host: string; port: number; location?: string; secure?: boolean; totalMemory?: number; totalCpu?: number; totalDisk?: number }) =>
@@ -272,11 +274,16 @@ export const api = {
         request<{ message: string }>(`/containers/${containerId}/files/folder`, { method: 'POST', body: JSON.stringify({ path }) }),
     deleteContainerFile: (containerId: string, path: string) =>
         request<{ message: string }>(`/containers/${containerId}/files/delete?path=${encodeURIComponent(path)}`, { method: 'DELETE' }),
+    getUploadLimits: (containerId: string) =>
+        request<{ chunkSize: number; maxBodySize: number }>(`/containers/${containerId}/files/upload-limits`),
     uploadContainerFile: async (containerId: string, path: string, file: File, onProgress?: (progress: number) => void) => {
         const t = get(token);
         const apiUrl = getApiUrl();
+        const chunkSize = await api.getUploadLimits(containerId)
+            .then((limits) => limits.chunkSize)
+            .catch(() => UPLOAD_CHUNK_SIZE);
 
-        if (file.size <= UPLOAD_CHUNK_SIZE) {
+        if (file.size <= chunkSize) {
             const formData = new FormData();
             formData.append('file', file);
             formData.append('path', path);
@@ -297,12 +304,12 @@ export const api = {
             return response.json();
         }
         
-        const totalChunks = Math.ceil(file.size / UPLOAD_CHUNK_SIZE);
+        const totalChunks = Math.ceil(file.size / chunkSize);
         const uploadId = crypto.randomUUID();
         
         for (let chunkIndex = 0; chunkIndex < totalChunks; chunkIndex++) {
-            const start = chunkIndex * UPLOAD_CHUNK_SIZE;
-            const end = Math.min(start + UPLOAD_CHUNK_SIZE, file.size);
+            const start = chunkIndex * chunkSize;
+            const end = Math.min(start + chunkSize, file.size);
             const chunk = file.slice(start, end);
             
             const formData = new FormData();
@@ -340,11 +347,12 @@ export const api = {
     createFlake: (data: any) => request<import('./types').FlakeWithVariables>('/flakes', { method: 'POST', body: JSON.stringify(data) }),
     importFlake: (flakeJson: any) => request<import('./types').FlakeWithVariables>('/flakes/import', { method: 'POST', body: JSON.stringify({ flakeJson }) }),
     deleteFlake: (id: string) => request<void>(`/flakes/${id}`, { method: 'DELETE' }),
-    exportFlake: (id: string) => request<any>(`/flakes/${id}/export`),
+    exportFlake: (id: string, format: 'raptor' | 'pterodactyl' = 'raptor') =>
+        request<any>(`/flakes/${id}/export?format=${format}`),
 
     getContainerStartup: (id: string) => request<import('./types').ContainerStartupInfo>(`/containers/${id}/startup`),
     updateContainerStartup: (id: string, data: { startupScript?: string; variables?: Record<string, string> }) =>
-        request<import('./types').ContainerStartupInfo>(`/containers/${id}/startup`, { method: 'PUT', body: JSON.stringify(data) }),
+        request<import('./types').ContainerStartupUpdate>(`/containers/${id}/startup`, { method: 'PUT', body: JSON.stringify(data) }),
 
     downloadFile: (containerId: string, path: string) => {
         const t = get(token);
@@ -370,6 +378,26 @@ export const api = {
         });
     },
 
+    downloadLogs: (containerId: string, since?: string) => {
+        const t = get(token);
+        const query = since ? `?since=${encodeURIComponent(since)}` : '';
+        return fetch(`${getApiUrl()}/containers/${containerId}/logs/download${query}`, {
+            headers: { 'Authorization': `Bearer ${t}` }
+        }).then(async resp => {
+            if (!resp.ok) throw new Error('Log download failed');
+            const disposition = resp.headers.get('content-disposition') || '';
+            const name = disposition.match(/filename="([^"]+)"/)?.[1] || 'console.log';
+            const blobUrl = URL.createObjectURL(await resp.blob());
+            const a = document.createElement('a');
+            a.href = blobUrl;
+            a.download = name;
+            document.body.appendChild(a);
+            a.click();
+            document.body.removeChild(a);
+            URL.revokeObjectURL(blobUrl);
+        });
+    },
+
     fixPermissions: (containerId: string) =>
         request<{ message: string }>(`/containers/${containerId}/fix-permissions`, { method: 'POST' }),
 
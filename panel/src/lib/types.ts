@@ -50,6 +50,10 @@ export interface Container {
     diskLimit?: number;
     swapLimit?: number;
     ioWeight?: number;
+    pendingChanges?: boolean;
+    startedAt?: string;
+    uptimeSecs?: number;
+    restartCount?: number;
     createdAt: string;
     updatedAt: string;
 }
@@ -72,6 +76,24 @@ export interface Daemon {
     updatedAt: string;
 }
 
+export interface DaemonCapacity {
+    daemonId: string;
+    name: string;
+    location?: string;
+    status: string;
+    maintenance: boolean;
+    containers: number;
+    totalMemory?: number;
+    totalCpu?: number;
+    totalDisk?: number;
+    allocatedMemory: number;
+    allocatedCpu: number;
+    allocatedDisk: number;
+    availableMemory?: number;
+    availableCpu?: number;
+    availableDisk?: number;
+}
+
 export interface Allocation {
     id: string;
     daemonId: string;
@@ -119,6 +141,7 @@ export interface Flake {
     description: string | null;
     dockerImage: string;
     startupCommand: string;
+    stopCommand: string | null;
     configFiles: Record<string, unknown>;
     startupDetection: string | null;
     installScript: string | null;
@@ -164,3 +187,11 @@ export interface ContainerStartupInfo {
     startupScript: string | null;
     variables: ContainerVariableInfo[];
 }
+
+export interface ContainerStartupUpdate extends ContainerStartupInfo {
+    variableChanges: {
+        applied: string[];
+        ignored: string[];
+        rejected: string[];
+    };
+}